@@ -0,0 +1,59 @@
+//! 逐个导入与批量导入区块的对比基准
+//!
+//! 运行方式：`cargo bench --bench block_import`
+
+use blockchain_demo::block::{Block, Transaction, TxInput, TxOutput, COINBASE_TX_ID};
+use blockchain_demo::blockchain::Blockchain;
+use std::fs;
+use std::time::{Duration, Instant};
+
+const BLOCKS: usize = 100;
+
+// 构造包含100个区块的链，每个区块的Coinbase支付给不同地址，避免交易ID重复
+fn source_chain() -> Blockchain {
+    let mut blockchain = Blockchain::new(1);
+    for i in 0..BLOCKS {
+        let coinbase = Transaction::new(
+            vec![TxInput {
+                prev_tx: String::from(COINBASE_TX_ID),
+                prev_index: 0,
+                script_sig: format!("区块{}", i),
+            }],
+            vec![TxOutput { value: 50, script_pubkey: format!("{:040x}", i) }],
+        );
+        blockchain.add_block(vec![coinbase]);
+    }
+    blockchain
+}
+
+fn time<F: FnOnce()>(f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn main() {
+    let source = source_chain();
+    let blocks: Vec<Block> = source.as_slice()[1..].to_vec();
+
+    let single_time = time(|| {
+        let mut blockchain = source.fresh_copy();
+        for block in blocks.clone() {
+            assert!(blockchain.validate_block(&block));
+            blockchain.add_received_block(block);
+        }
+    });
+    let batch_time = time(|| {
+        let mut blockchain = source.fresh_copy();
+        assert_eq!(blockchain.add_block_batch(blocks.clone()), Ok(BLOCKS));
+    });
+
+    println!("导入 {} 个区块", BLOCKS);
+    println!("逐个导入: {:?}", single_time);
+    println!("批量导入: {:?}", batch_time);
+    println!("加速比: {:.2}", single_time.as_secs_f64() / batch_time.as_secs_f64());
+
+    let _ = fs::remove_file("blockchain.json");
+    let _ = fs::remove_file("blockchain.undo.json");
+    let _ = fs::remove_file("blockchain.utxo.json");
+}
@@ -0,0 +1,57 @@
+//! JSON与二进制（bincode）区块编码的对比基准
+//!
+//! 运行方式：`cargo bench --bench serialization`
+
+use blockchain_demo::block::{Block, Transaction, TxInput, TxOutput, COINBASE_TX_ID};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const TRANSACTIONS: usize = 100;
+const ITERATIONS: u32 = 1000;
+
+// 构造包含100笔交易的区块，字段长度与真实签名交易相近
+fn sample_block() -> Block {
+    let mut block = Block::new(String::from(COINBASE_TX_ID), 2);
+    for i in 0..TRANSACTIONS {
+        let input = TxInput {
+            prev_tx: format!("{:064x}", i),
+            prev_index: i as u32,
+            script_sig: format!("{}:{}", "02".repeat(33), "ab".repeat(64)),
+        };
+        let outputs = vec![
+            TxOutput { value: 50 + i as u64, script_pubkey: format!("{:040x}", i) },
+            TxOutput { value: 10, script_pubkey: format!("{:040x}", i + 1) },
+        ];
+        block.transactions.push(Transaction::with_fee(vec![input], outputs, 1));
+    }
+    block
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let block = sample_block();
+
+    let json = serde_json::to_vec(&block).unwrap();
+    let binary = block.to_bytes();
+
+    let json_time = time(|| {
+        let data = serde_json::to_vec(black_box(&block)).unwrap();
+        black_box(serde_json::from_slice::<Block>(&data).unwrap());
+    });
+    let binary_time = time(|| {
+        let data = black_box(&block).to_bytes();
+        black_box(Block::from_bytes(&data).unwrap());
+    });
+
+    println!("区块包含 {} 笔交易，往返 {} 次取平均", TRANSACTIONS, ITERATIONS);
+    println!("JSON:    {:>8} 字节, 往返 {:?}", json.len(), json_time);
+    println!("bincode: {:>8} 字节, 往返 {:?}", binary.len(), binary_time);
+    println!("体积比: {:.2}", binary.len() as f64 / json.len() as f64);
+}
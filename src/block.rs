@@ -8,6 +8,72 @@ use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use hex;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Coinbase交易输入引用的占位交易ID（全零）
+pub const COINBASE_TX_ID: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 数据输出锁定脚本的前缀，后接十六进制编码的数据，类似比特币的OP_RETURN
+pub const OP_RETURN_PREFIX: &str = "OP_RETURN:";
+
+/// 数据输出最多携带的字节数
+pub const MAX_OP_RETURN_DATA: usize = 80;
+
+/// 默认哈希算法的名称
+pub const DEFAULT_HASH_ALGORITHM: &str = "sha256";
+
+/// 区块和交易哈希使用的哈希算法
+///
+/// 区块链通过该trait计算区块哈希、默克尔根、交易哈希和工作量证明，便于试验其他算法（例如测试中更快的哈希）。
+/// 地址和签名使用的哈希不受影响
+pub trait Hasher: Send + Sync {
+    /// 算法名称，同一网络中的节点必须使用相同的算法
+    fn name(&self) -> &str;
+
+    /// 计算数据的32字节摘要
+    fn digest(&self, data: &[u8]) -> [u8; 32];
+}
+
+/// 默认的SHA256哈希算法
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hasher;
+
+impl Hasher for Sha256Hasher {
+    fn name(&self) -> &str {
+        DEFAULT_HASH_ALGORITHM
+    }
+
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
+
+/// 按名称查找内置的哈希算法
+///
+/// 目前只内置SHA256；其他算法由调用方实现`Hasher`后直接传入
+///
+/// # 参数
+///
+/// * `name` - 算法名称，与[`Hasher::name`]相同
+///
+/// # 返回值
+///
+/// 返回对应的算法实例；不是内置算法时返回None
+pub fn hasher_by_name(name: &str) -> Option<Arc<dyn Hasher>> {
+    (name == DEFAULT_HASH_ALGORITHM).then(|| Arc::new(Sha256Hasher) as Arc<dyn Hasher>)
+}
+
+/// 二进制区块解码错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DecodeError {
+    /// 数据不是有效的bincode编码区块
+    #[error("区块解码失败: {0}")]
+    Bincode(String),
+}
 
 /// 区块结构，包含区块头和交易列表
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +107,9 @@ pub struct Transaction {
     pub inputs: Vec<TxInput>,
     /// 交易输出列表，表示创建的新UTXO
     pub outputs: Vec<TxOutput>,
+    /// 交易手续费，等于输入总额减去输出总额，由打包区块的矿工获得
+    #[serde(default)]
+    pub fee: u64,
 }
 
 /// 交易输入结构，引用之前交易的输出
@@ -63,6 +132,24 @@ pub struct TxOutput {
     pub script_pubkey: String,
 }
 
+/// 交易输出的引用，由交易ID和输出索引唯一确定
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OutPoint {
+    /// 输出所在交易的ID
+    pub tx_id: String,
+    /// 输出在交易中的索引
+    pub index: u32,
+}
+
+/// 未花费输出的详细信息
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UtxoEntry {
+    /// 输出金额
+    pub value: u64,
+    /// 锁定脚本，即输出所有者的地址
+    pub script_pubkey: String,
+}
+
 impl Block {
     /// 创建新的区块
     ///
@@ -75,9 +162,26 @@ impl Block {
     ///
     /// 返回一个新创建的区块实例
     pub fn new(prev_hash: String, difficulty: u64) -> Self {
+        Self::with_timestamp(prev_hash, difficulty, Utc::now().timestamp())
+    }
+
+    /// 使用指定时间戳创建新的区块
+    ///
+    /// 时间戳固定时区块哈希只取决于nonce，测试可以断言挖出的确切哈希和nonce
+    ///
+    /// # 参数
+    ///
+    /// * `prev_hash` - 前一个区块的哈希值
+    /// * `difficulty` - 挖矿难度
+    /// * `timestamp` - 区块时间戳（Unix秒）
+    ///
+    /// # 返回值
+    ///
+    /// 返回一个新创建的区块实例
+    pub fn with_timestamp(prev_hash: String, difficulty: u64, timestamp: i64) -> Self {
         Block {
             header: BlockHeader {
-                timestamp: Utc::now().timestamp(),
+                timestamp,
                 prev_hash,
                 merkle_root: String::new(),
                 nonce: 0,
@@ -93,33 +197,98 @@ impl Block {
     ///
     /// 返回计算得到的区块哈希值（16进制字符串）
     pub fn calculate_hash(&self) -> String {
-        let mut hasher = sha2::Sha256::new();
+        self.calculate_hash_with(&Sha256Hasher)
+    }
+
+    /// 使用指定的哈希算法计算区块的哈希值
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法
+    ///
+    /// # 返回值
+    ///
+    /// 返回计算得到的区块哈希值（16进制字符串）
+    pub fn calculate_hash_with(&self, hasher: &dyn Hasher) -> String {
         let serialized = serde_json::to_string(&self).unwrap();
-        hasher.update(serialized.as_bytes());
-        hex::encode(hasher.finalize())
+        hex::encode(hasher.digest(serialized.as_bytes()))
+    }
+
+    /// 将区块编码为紧凑的二进制格式（bincode）
+    ///
+    /// # 返回值
+    ///
+    /// 返回编码后的字节
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("区块可以被bincode编码")
+    }
+
+    /// 区块在线路上占用的字节数
+    ///
+    /// 使用与[`Block::to_bytes`]相同的bincode编码计算，不实际分配编码缓冲区
+    pub fn size(&self) -> usize {
+        bincode::serialized_size(self).expect("区块可以被bincode编码") as usize
+    }
+
+    /// 从二进制格式解码区块
+    ///
+    /// # 参数
+    ///
+    /// * `data` - 由[`Block::to_bytes`]编码的字节
+    ///
+    /// # 返回值
+    ///
+    /// 成功时返回解码的区块，数据无效时返回`DecodeError`
+    pub fn from_bytes(data: &[u8]) -> Result<Block, DecodeError> {
+        bincode::deserialize(data).map_err(|e| DecodeError::Bincode(e.to_string()))
     }
 
     /// 挖掘区块，尝试找到满足难度要求的哈希值
     ///
-    /// 此方法会调整nonce值，直到找到满足难度要求的哈希值
+    /// 此方法会从当前nonce开始调整nonce值，直到找到满足难度要求的哈希值
     pub fn mine(&mut self) {
+        self.mine_from(self.header.nonce);
+    }
+
+    /// 从指定的nonce开始挖掘区块
+    ///
+    /// 依次尝试`start_nonce`、`start_nonce + 1`……，区块内容和时间戳相同时结果是确定的
+    ///
+    /// # 参数
+    ///
+    /// * `start_nonce` - 第一个尝试的nonce
+    pub fn mine_from(&mut self, start_nonce: u64) {
+        self.mine_from_with(start_nonce, &Sha256Hasher);
+    }
+
+    /// 使用指定的哈希算法从指定的nonce开始挖掘区块
+    ///
+    /// 挖矿前先按当前交易写入默克尔根，挖出的区块哈希包含正确的默克尔根
+    ///
+    /// # 参数
+    ///
+    /// * `start_nonce` - 第一个尝试的nonce
+    /// * `hasher` - 区块链使用的哈希算法
+    pub fn mine_from_with(&mut self, start_nonce: u64, hasher: &dyn Hasher) {
         let max_iterations = 1000000; // 设置一个合理的最大迭代次数
         let mut iterations = 0;
+        self.header.merkle_root = self.compute_merkle_root_with(hasher);
+        self.header.nonce = start_nonce;
         
-        while !self.is_valid() && iterations < max_iterations {
+        while !self.is_valid_with(hasher) && iterations < max_iterations {
             self.header.nonce += 1;
             iterations += 1;
             
             // 每10000次迭代打印一次进度
             if iterations % 10000 == 0 {
-                println!("Mining... iterations: {}, nonce: {}", iterations, self.header.nonce);
+                debug!(iterations, nonce = self.header.nonce, "挖矿中");
             }
         }
         
         if iterations >= max_iterations {
-            println!("挖矿达到最大迭代次数限制，未找到满足条件的哈希");
+            warn!(iterations, "挖矿达到最大迭代次数限制，未找到满足条件的哈希");
         } else {
-            println!("成功挖到区块，迭代次数: {}, nonce: {}", iterations, self.header.nonce);
+            info!(iterations, nonce = self.header.nonce, "成功挖到区块");
         }
     }
 
@@ -129,18 +298,153 @@ impl Block {
     ///
     /// 如果区块哈希满足难度要求，返回true；否则返回false
     pub fn is_valid(&self) -> bool {
-        let hash = self.calculate_hash();
+        self.is_valid_with(&Sha256Hasher)
+    }
+
+    /// 使用指定的哈希算法验证区块是否满足难度要求
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法
+    ///
+    /// # 返回值
+    ///
+    /// 如果区块哈希满足难度要求，返回true；否则返回false
+    pub fn is_valid_with(&self, hasher: &dyn Hasher) -> bool {
         // 检查哈希值前缀是否有足够的0
         // 简单高效的方法：检查哈希值的前n个字符是否都是0
         let prefix_zeros = self.header.difficulty as usize;
         if prefix_zeros == 0 {
-            return true; // 如果难度为0，任何哈希值都有效
+            return true; // 如果难度为0，任何哈希值都有效，无需计算哈希
         }
         
         // 检查哈希值前缀是否有足够的0
+        let hash = self.calculate_hash_with(hasher);
         let required_prefix = "0".repeat(prefix_zeros);
         hash.starts_with(&required_prefix)
     }
+
+    /// 按手续费率（每字节手续费）从高到低排序交易
+    ///
+    /// Coinbase交易始终排在最前面，手续费率相同的交易保持原有顺序
+    ///
+    /// # 参数
+    ///
+    /// * `txs` - 待排序的交易列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回排序后的交易引用列表
+    pub fn transactions_sorted_by_fee(txs: &[Transaction]) -> Vec<&Transaction> {
+        let mut sorted: Vec<&Transaction> = txs.iter().collect();
+        sorted.sort_by(|a, b| {
+            b.is_coinbase()
+                .cmp(&a.is_coinbase())
+                .then_with(|| b.fee_per_byte().total_cmp(&a.fee_per_byte()))
+        });
+        sorted
+    }
+
+    /// 根据区块中的交易计算默克尔根
+    ///
+    /// 叶子为各交易哈希，逐层对相邻两个哈希拼接后求SHA256，奇数个时复制最后一个。
+    /// 没有交易时返回全零哈希
+    ///
+    /// # 返回值
+    ///
+    /// 返回默克尔根（16进制字符串）
+    pub fn compute_merkle_root(&self) -> String {
+        self.compute_merkle_root_with(&Sha256Hasher)
+    }
+
+    /// 使用指定的哈希算法计算默克尔根，叶子和各层拼接都用该算法求摘要，其余规则见[`Block::compute_merkle_root`]
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法
+    ///
+    /// # 返回值
+    ///
+    /// 返回默克尔根（16进制字符串）
+    pub fn compute_merkle_root_with(&self, hasher: &dyn Hasher) -> String {
+        let mut level: Vec<[u8; 32]> = self.transactions.iter()
+            .map(|tx| hasher.digest(serde_json::to_string(tx).unwrap().as_bytes()))
+            .collect();
+        if level.is_empty() {
+            return String::from(COINBASE_TX_ID);
+        }
+        
+        while level.len() > 1 {
+            level = level.chunks(2)
+                .map(|pair| {
+                    let mut data = [0u8; 64];
+                    data[..32].copy_from_slice(&pair[0]);
+                    data[32..].copy_from_slice(pair.get(1).unwrap_or(&pair[0]));
+                    hasher.digest(&data)
+                })
+                .collect();
+        }
+        hex::encode(level[0])
+    }
+
+    /// 计算区块中所有非Coinbase交易的手续费总额
+    pub fn total_fees(&self) -> u64 {
+        self.transactions.iter()
+            .filter(|tx| !tx.is_coinbase())
+            .map(|tx| tx.fee)
+            .sum()
+    }
+}
+
+/// 区块摘要：区块哈希、前一区块哈希、默克尔根、时间戳、随机数、难度，以及交易数、输出总额和手续费总额
+///
+/// 区块不记录自己的高度，需要高度时由调用方另行输出。使用`{:#}`时每笔交易再单独占一行
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output_total: u64 = self.transactions.iter().flat_map(|tx| &tx.outputs).map(|output| output.value).sum();
+        let fees = self.total_fees();
+        writeln!(f, "Block {}", self.calculate_hash())?;
+        writeln!(f, "  Previous hash: {}", self.header.prev_hash)?;
+        writeln!(f, "  Merkle root: {}", self.header.merkle_root)?;
+        writeln!(f, "  Timestamp: {}  Nonce: {}  Difficulty: {}", self.header.timestamp, self.header.nonce, self.header.difficulty)?;
+        write!(f, "  Transactions: {}  Output total: {}  Fees: {}", self.transactions.len(), output_total, fees)?;
+        if f.alternate() {
+            for tx in &self.transactions {
+                write!(f, "\n    {}", tx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TxOutput {
+    /// 创建携带数据的零金额输出
+    ///
+    /// 数据输出不能被花费，不会进入UTXO集合，用于在链上记录哈希等少量数据
+    ///
+    /// # 参数
+    ///
+    /// * `data` - 要记录的数据，超过`MAX_OP_RETURN_DATA`字节的输出不能通过验证
+    pub fn op_return(data: &[u8]) -> Self {
+        TxOutput {
+            value: 0,
+            script_pubkey: format!("{}{}", OP_RETURN_PREFIX, hex::encode(data)),
+        }
+    }
+
+    /// 输出是否不可花费（数据输出）
+    pub fn is_unspendable(&self) -> bool {
+        self.script_pubkey.starts_with(OP_RETURN_PREFIX)
+    }
+
+    /// 获取数据输出携带的数据
+    ///
+    /// # 返回值
+    ///
+    /// 返回解码后的数据；不是数据输出或数据不是有效的十六进制时返回None
+    pub fn embedded_data(&self) -> Option<Vec<u8>> {
+        hex::decode(self.script_pubkey.strip_prefix(OP_RETURN_PREFIX)?).ok()
+    }
 }
 
 impl Transaction {
@@ -155,18 +459,204 @@ impl Transaction {
     ///
     /// 返回一个新创建的交易实例
     pub fn new(inputs: Vec<TxInput>, outputs: Vec<TxOutput>) -> Self {
-        Transaction { inputs, outputs }
+        Transaction { inputs, outputs, fee: 0 }
+    }
+
+    /// 创建带手续费的交易
+    ///
+    /// # 参数
+    ///
+    /// * `inputs` - 交易输入列表
+    /// * `outputs` - 交易输出列表
+    /// * `fee` - 交易手续费
+    ///
+    /// # 返回值
+    ///
+    /// 返回一个新创建的交易实例
+    pub fn with_fee(inputs: Vec<TxInput>, outputs: Vec<TxOutput>, fee: u64) -> Self {
+        Transaction { inputs, outputs, fee }
+    }
+
+    /// 判断是否为Coinbase交易（输入引用全零交易ID）
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.iter().any(|input| input.prev_tx == COINBASE_TX_ID)
+    }
+
+    /// 判断交易的所有非Coinbase输入是否都已签名
+    ///
+    /// 钱包构建的未签名交易中，输入的`script_sig`是所花费输出的地址；
+    /// 签名后为`公钥十六进制:签名十六进制`
+    pub fn is_signed(&self) -> bool {
+        self.inputs.iter()
+            .filter(|input| input.prev_tx != COINBASE_TX_ID)
+            .all(|input| input.script_sig.contains(':'))
+    }
+
+    /// 查找花费同一输出的两笔交易
+    ///
+    /// 逐笔交易检查各自验证时发现不了同一区块内的双重花费，
+    /// 这里记录每个非Coinbase输入引用的`(prev_tx, prev_index)`第一次出现在哪笔交易中
+    ///
+    /// # 参数
+    ///
+    /// * `txs` - 按区块中顺序排列的交易
+    ///
+    /// # 返回值
+    ///
+    /// 返回第一对共享输入的交易的索引`(i, j)`，`i <= j`；同一交易重复花费同一输出时`i == j`
+    pub fn find_double_spend(txs: &[Transaction]) -> Option<(usize, usize)> {
+        let mut spent: HashMap<(&str, u32), usize> = HashMap::new();
+        for (j, tx) in txs.iter().enumerate() {
+            for input in tx.inputs.iter().filter(|input| input.prev_tx != COINBASE_TX_ID) {
+                if let Some(&i) = spent.get(&(input.prev_tx.as_str(), input.prev_index)) {
+                    return Some((i, j));
+                }
+                spent.insert((input.prev_tx.as_str(), input.prev_index), j);
+            }
+        }
+        None
+    }
+
+    /// 估算交易在网络上占用的字节数
+    ///
+    /// 采用比特币常用的紧凑估算：10字节基础开销，每个输入148字节（含压缩ECDSA签名），
+    /// 每个输出34字节。估算只依赖输入和输出数量，签名前后结果一致
+    pub fn size_bytes(&self) -> usize {
+        10 + self.inputs.len() * 148 + self.outputs.len() * 34
+    }
+
+    /// 交易在线路上占用的字节数
+    ///
+    /// 使用与二进制区块编码（[`Block::to_bytes`]）相同的bincode编码计算，
+    /// 与`size_bytes`的估算不同，结果取决于签名、地址等字段的实际长度
+    pub fn size(&self) -> usize {
+        bincode::serialized_size(self).expect("交易可以被bincode编码") as usize
+    }
+
+    /// 计算每字节手续费（手续费除以[`Transaction::size`]得到的线路字节数）
+    ///
+    /// 与交易池按`size`装填区块时使用的字节数一致
+    pub fn fee_per_byte(&self) -> f64 {
+        self.fee as f64 / self.size() as f64
+    }
+    
+    /// 计算某个输入的签名摘要
+    ///
+    /// 摘要覆盖除签名外的全部交易内容：所有输入的`script_sig`被清空，
+    /// 被签名的输入填入其引用输出的`script_pubkey`，因此签名写入后摘要保持不变，
+    /// 且每个输入都承诺了自己所花费输出的所有者。
+    /// 摘要还包含网络标识，在一个网络上签名的交易无法在其他网络上重放
+    ///
+    /// # 参数
+    ///
+    /// * `input_index` - 被签名的输入索引
+    /// * `script_pubkey` - 该输入引用的输出的锁定脚本
+    /// * `network_id` - 交易所属网络的标识
+    ///
+    /// # 返回值
+    ///
+    /// 返回32字节的SHA256摘要
+    pub fn signature_hash(&self, input_index: usize, script_pubkey: &str, network_id: &str) -> [u8; 32] {
+        let mut unsigned = self.clone();
+        for (index, input) in unsigned.inputs.iter_mut().enumerate() {
+            input.script_sig = if index == input_index {
+                script_pubkey.to_string()
+            } else {
+                String::new()
+            };
+        }
+        
+        let mut hasher = Sha256::new();
+        hasher.update(network_id.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(serde_json::to_vec(&unsigned).unwrap());
+        hasher.finalize().into()
     }
     
-    /// 计算交易的哈希值
+    /// 计算交易ID，UTXO集合和交易输入用它引用交易
+    ///
+    /// 只哈希交易的不可变部分：输入引用的`(prev_tx, prev_index)`和输出的`(value, script_pubkey)`，
+    /// 不包含`script_sig`，因此签名前后、或同一交易的不同有效签名得到相同的ID。
+    /// Coinbase交易没有签名，它的`script_sig`是区分不同Coinbase的数据，仍然计入
+    ///
+    /// # 返回值
+    ///
+    /// 返回交易ID（16进制字符串）
+    pub fn txid(&self) -> String {
+        self.txid_with(&Sha256Hasher)
+    }
+
+    /// 使用指定的哈希算法计算交易ID，规则见[`Transaction::txid`]
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法
+    ///
+    /// # 返回值
+    ///
+    /// 返回交易ID（16进制字符串）
+    pub fn txid_with(&self, hasher: &dyn Hasher) -> String {
+        let coinbase = self.is_coinbase();
+        let inputs: Vec<(&str, u32, &str)> = self.inputs.iter()
+            .map(|input| {
+                let data = if coinbase { input.script_sig.as_str() } else { "" };
+                (input.prev_tx.as_str(), input.prev_index, data)
+            })
+            .collect();
+        let outputs: Vec<(u64, &str)> = self.outputs.iter()
+            .map(|output| (output.value, output.script_pubkey.as_str()))
+            .collect();
+        let serialized = serde_json::to_vec(&(inputs, outputs)).unwrap();
+        hex::encode(hasher.digest(&serialized))
+    }
+
+    /// 计算交易的内容哈希，包含`script_sig`在内的全部字段
+    ///
+    /// 用于按内容识别交易（例如交易池去重和消息去重），引用交易输出时应使用[`Transaction::txid`]
     ///
     /// # 返回值
     ///
     /// 返回计算得到的交易哈希值（16进制字符串）
     pub fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
+        self.calculate_hash_with(&Sha256Hasher)
+    }
+
+    /// 使用指定的哈希算法计算交易的内容哈希，规则见[`Transaction::calculate_hash`]
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法
+    ///
+    /// # 返回值
+    ///
+    /// 返回计算得到的交易哈希值（16进制字符串）
+    pub fn calculate_hash_with(&self, hasher: &dyn Hasher) -> String {
         let serialized = serde_json::to_string(&self).unwrap();
-        hasher.update(serialized.as_bytes());
-        hex::encode(hasher.finalize())
+        hex::encode(hasher.digest(serialized.as_bytes()))
     }
-} 
\ No newline at end of file
+}
+
+/// 交易摘要：交易ID、是否为Coinbase、输入和输出数量、输出总额、手续费和字节数
+///
+/// 输入总额需要查询UTXO集合，这里不输出；非Coinbase交易的输入总额等于输出总额加手续费。
+/// 使用`{:#}`时每个输入和输出再单独占一行
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output_total: u64 = self.outputs.iter().map(|output| output.value).sum();
+        write!(f, "Transaction {}", self.txid())?;
+        if self.is_coinbase() {
+            write!(f, " (coinbase)")?;
+        }
+        write!(f, ": {} inputs, {} outputs, output total {}, fee {}, {} bytes",
+            self.inputs.len(), self.outputs.len(), output_total, self.fee, self.size())?;
+        if f.alternate() {
+            for input in &self.inputs {
+                write!(f, "\n  in:  {}:{}", input.prev_tx, input.prev_index)?;
+            }
+            for output in &self.outputs {
+                write!(f, "\n  out: {} -> {}", output.value, output.script_pubkey)?;
+            }
+        }
+        Ok(())
+    }
+}
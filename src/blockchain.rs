@@ -4,340 +4,2687 @@
 //! 
 //! 该模块负责管理区块链的状态，包括维护区块列表和未花费交易输出(UTXO)集合。
 
-use std::collections::HashMap;
-use crate::block::{Block, Transaction};
+use std::borrow::Cow;
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::block::{hasher_by_name, Block, Hasher, OutPoint, Sha256Hasher, Transaction, UtxoEntry, DEFAULT_HASH_ALGORITHM};
+use crate::metrics::Metrics;
+use crate::validation::{TxError, TxValidator};
+use crate::wallet::Wallet;
 use std::fs;
+use std::io::{self, Write};
 use std::path::Path;
-use sha2::{Sha256, Digest};
+use std::sync::Arc;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+/// 默认每个区块允许的最大交易数量（包含Coinbase交易）
+pub const DEFAULT_MAX_TRANSACTIONS: usize = 100;
+
+/// 默认的区块大小上限（字节），按`Block::size`计算
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 1_000_000;
+
+/// 每个区块的挖矿奖励，Coinbase交易可以领取奖励加上区块中交易的手续费
+pub const BLOCK_REWARD: u64 = 50;
+
+/// 挖矿难度上限：难度是区块哈希要求的前导0个数，不能超过SHA-256哈希的十六进制长度
+pub const MAX_DIFFICULTY: u64 = 64;
+
+/// 默认的奖励减半间隔（区块数）
+pub const DEFAULT_HALVING_INTERVAL: usize = 210_000;
+
+/// 默认的目标出块间隔（秒）
+pub const DEFAULT_TARGET_BLOCK_TIME_SECS: u64 = 600;
+
+/// 默认的难度调整间隔（区块数）
+pub const DEFAULT_RETARGET_INTERVAL: usize = 2016;
+
+/// 计算过去中位时间（MPT）使用的最近区块数量
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// 区块时间戳最多可以超前本地时间的秒数
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 2 * 60 * 60;
+
+/// 默认的Coinbase成熟深度，为0时挖出的奖励立即可以花费
+pub const DEFAULT_COINBASE_MATURITY: usize = 0;
+
+/// 手续费估算考察的最近区块数量
+pub const FEE_ESTIMATE_WINDOW: usize = 50;
+
+/// 手续费估算缓存的有效区块数，链增长这么多区块后重新估算
+pub const FEE_ESTIMATE_CACHE_BLOCKS: usize = 6;
+
+/// 最低手续费率（每字节），历史数据不足时使用
+pub const MIN_FEE_RATE: u64 = 1;
+
+/// 默认网络的网络标识，见[`GenesisConfig::default`]
+pub const DEFAULT_NETWORK_ID: &str = "demo";
+
+/// 回归测试网络的网络标识，见[`Blockchain::regtest`]
+pub const REGTEST_NETWORK_ID: &str = "regtest";
+
+/// 区块链默认保存到的文件，见[`Blockchain::set_data_file`]
+pub const DEFAULT_CHAIN_FILE: &str = "blockchain.json";
+
+/// 区块验证错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BlockError {
+    /// 区块中没有Coinbase交易
+    #[error("区块缺少Coinbase交易")]
+    MissingCoinbase,
+    /// Coinbase交易不在区块的第一个位置
+    #[error("Coinbase交易位于第{index}笔，必须是区块的第一笔交易")]
+    MisplacedCoinbase { index: usize },
+    /// 区块包含多个Coinbase交易
+    #[error("区块包含多个Coinbase交易，第{index}笔是多余的Coinbase")]
+    MultipleCoinbase { index: usize },
+    /// 区块交易数量超过上限
+    #[error("区块包含{count}笔交易，超过上限{max}")]
+    TooManyTransactions { count: usize, max: usize },
+    /// 区块编码后的大小超过上限
+    #[error("区块大小为{size}字节，超过上限{max}字节")]
+    BlockTooLarge { size: usize, max: usize },
+    /// 区块中的两笔交易花费了同一个输出
+    #[error("第{first}笔和第{second}笔交易花费了同一个输出")]
+    DoubleSpend { first: usize, second: usize },
+    /// 区块哈希不满足难度要求
+    #[error("区块哈希不满足难度要求")]
+    InvalidProofOfWork,
+    /// 区块时间戳不晚于最近区块的过去中位时间
+    #[error("区块时间戳{timestamp}不晚于过去中位时间{median_past_time}")]
+    TimestampTooOld { timestamp: i64, median_past_time: i64 },
+    /// 区块时间戳超前本地时间太多
+    #[error("区块时间戳{timestamp}超过了允许的最晚时间{max}")]
+    TimestampTooFarInFuture { timestamp: i64, max: i64 },
+    /// 区块的前一个哈希与本地链顶不一致
+    #[error("区块前一个哈希为{found}，本地链顶哈希为{expected}")]
+    PrevHashMismatch { expected: String, found: String },
+    /// 区块中的交易未通过验证
+    #[error("第{index}笔交易无效: {error}")]
+    InvalidTransaction { index: usize, error: TxError },
+    /// Coinbase交易领取的金额超过了区块奖励加手续费
+    #[error("Coinbase领取了{claimed}，最多可以领取{allowed}")]
+    ExcessiveCoinbase { claimed: u64, allowed: u64 },
+    /// 区块的输出总额超过了输入总额加区块奖励
+    #[error("区块输出总额{outputs}超过了输入总额{inputs}加区块奖励{reward}")]
+    ValueNotConserved { inputs: u64, outputs: u64, reward: u64 },
+}
+
+/// 区块链操作错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BlockchainError {
+    /// 回滚的区块数超过了创世区块之后的区块数
+    #[error("无法回滚{requested}个区块，当前高度只有{height}")]
+    RollbackPastGenesis { requested: usize, height: usize },
+    /// 增量同步的区块与本地链不衔接（起始高度超过本地链长度，或为创世区块）
+    #[error("从高度{start_height}开始的区块无法接在长度为{len}的本地链之后")]
+    NonContiguousBlocks { start_height: usize, len: usize },
+    /// 增量同步的区块与本地同一高度的区块不同，需要完整同步
+    #[error("高度{height}的区块与本地链不同")]
+    ForkDetected { height: usize },
+    /// 增量同步的区块未通过验证
+    #[error("高度{height}的区块验证失败")]
+    InvalidBlock { height: usize },
+    /// 从高度`height`分叉的链累计工作量不高于本地链，保留本地链
+    #[error("从高度{height}分叉的链累计工作量不高于本地链")]
+    InsufficientForkWork { height: usize },
+}
+
+/// 模拟交易时发现的问题
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum SimulationError {
+    /// 交易没有输入或没有输出
+    #[error("交易没有输入或没有输出")]
+    EmptyTransaction,
+    /// 输入引用的输出不存在
+    #[error("输入{index}引用的输出{prev_tx}:{prev_index}不存在")]
+    UtxoNotFound { index: usize, prev_tx: String, prev_index: u32 },
+    /// 输入引用的输出已被同一交易的前一个输入花费
+    #[error("输入{index}重复花费了输出{prev_tx}:{prev_index}")]
+    DoubleSpend { index: usize, prev_tx: String, prev_index: u32 },
+    /// 输入引用的Coinbase输出尚未达到成熟高度，暂时不能花费
+    #[error("输入{index}引用的Coinbase输出{prev_tx}尚未成熟")]
+    LockTimeNotReached { index: usize, prev_tx: String },
+    /// 输入的签名无效或签名公钥不属于被花费输出的所有者
+    #[error("输入{index}的签名无效")]
+    InvalidSignature { index: usize },
+    /// 输出总额加手续费超过了输入总额
+    #[error("输出{outputs}加手续费{fee}超过了输入总额{inputs}")]
+    InsufficientFunds { inputs: u64, outputs: u64, fee: u64 },
+}
+
+/// 交易模拟结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationResult {
+    /// 交易提交后是否会被接受
+    pub would_succeed: bool,
+    /// 交易消耗的输入总额，只统计能找到的输出
+    pub consumed: u64,
+    /// 交易支付的手续费
+    pub fee: u64,
+    /// 发现的全部问题，交易会被接受时为空
+    pub errors: Vec<SimulationError>,
+}
+
+/// 区块链完整性检查发现的问题
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum IntegrityError {
+    /// 区块的`prev_hash`与前一个区块的哈希不一致
+    #[error("高度{height}的区块prev_hash为{found}，前一个区块的哈希为{expected}")]
+    BrokenLink { height: usize, expected: String, found: String },
+    /// 区块哈希不满足区块头中的难度要求
+    #[error("高度{height}的区块哈希{hash}不满足难度{difficulty}")]
+    InsufficientWork { height: usize, hash: String, difficulty: u64 },
+    /// 区块头中的默克尔根与交易计算出的不一致
+    #[error("高度{height}的区块默克尔根为{found}，根据交易计算为{expected}")]
+    MerkleRootMismatch { height: usize, expected: String, found: String },
+    /// 交易输入引用的输出不存在或已被花费
+    #[error("高度{height}的交易{tx_id}引用了不存在或已花费的输出{prev_tx}:{prev_index}")]
+    MissingInput { height: usize, tx_id: String, prev_tx: String, prev_index: u32 },
+    /// 交易的输入总额不等于输出总额加手续费
+    #[error("高度{height}的交易{tx_id}不平衡：输入{inputs}，输出{outputs}，手续费{fee}")]
+    Unbalanced { height: usize, tx_id: String, inputs: u64, outputs: u64, fee: u64 },
+}
+
+impl IntegrityError {
+    /// 出现问题的区块高度
+    pub fn height(&self) -> usize {
+        match self {
+            IntegrityError::BrokenLink { height, .. }
+            | IntegrityError::InsufficientWork { height, .. }
+            | IntegrityError::MerkleRootMismatch { height, .. }
+            | IntegrityError::MissingInput { height, .. }
+            | IntegrityError::Unbalanced { height, .. } => *height,
+        }
+    }
+}
+
+/// 从文件加载区块链时完整性检查的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadOutcome {
+    /// 所有区块都通过了完整性检查
+    Verified,
+    /// 文件中有无效区块，已截断到第一个无效区块之前
+    Truncated {
+        /// 保留的区块数
+        kept: usize,
+        /// 丢弃的区块数
+        discarded: usize,
+        /// 完整性检查发现的问题
+        errors: Vec<IntegrityError>,
+    },
+    /// 区块通过了完整性检查，但重放验证（包括签名）在该高度失败
+    Invalid {
+        /// 第一个无效区块的高度
+        height: usize,
+    },
+}
+
+/// 从JSON导入区块链时的错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ImportError {
+    /// 输入不是有效的区块列表JSON
+    #[error("无法解析区块链JSON: {0}")]
+    InvalidJson(String),
+    /// 区块列表为空，没有创世区块
+    #[error("区块链JSON中没有区块")]
+    Empty,
+    /// 完整性检查发现问题
+    #[error("区块链未通过完整性检查，发现{}个问题", .0.len())]
+    Integrity(Vec<IntegrityError>),
+    /// 从创世区块重放验证时发现无效区块
+    #[error("区块链未通过交易验证")]
+    InvalidChain,
+    /// 区块链记录的哈希算法与指定的算法不同
+    #[error("区块链使用哈希算法{found}，而不是{expected}")]
+    HashAlgorithmMismatch { expected: String, found: String },
+    /// 区块链记录的哈希算法不是内置算法，需要调用方提供
+    #[error("未知的哈希算法: {0}")]
+    UnknownHashAlgorithm(String),
+}
+
+/// 重新扫描区块数据、重建UTXO集合后的修正报告
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RescanReport {
+    /// 重建后才出现的输出（原UTXO集合中缺失或金额、所有者不符）
+    pub added: Vec<(OutPoint, UtxoEntry)>,
+    /// 重建后被移除的输出（原UTXO集合中多出或金额、所有者不符）
+    pub removed: Vec<(OutPoint, UtxoEntry)>,
+    /// 余额发生变化的地址，每项为(地址, 修正前余额, 修正后余额)，按地址排序
+    pub balance_changes: Vec<(String, u64, u64)>,
+    /// 重建后的UTXO集合是否与按修改记录重放的结果一致
+    pub consistent: bool,
+}
+
+impl RescanReport {
+    /// 重建前的UTXO集合是否已经正确，无需任何修正
+    pub fn is_clean(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// 区块对UTXO集合的修改记录，用于回滚区块
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct BlockUndo {
+    /// 区块花费的输出，每项为(交易ID, 输出索引, 金额, 所有者地址)
+    pub spent_outputs: Vec<(String, u32, u64, String)>,
+    /// 区块创建的输出，每项为(交易ID, 输出索引, 金额, 所有者地址)
+    pub created_outputs: Vec<(String, u32, u64, String)>,
+}
+
+/// 区块链文件的内容：区块哈希使用的算法和按高度排列的区块
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredChain<'a> {
+    /// 哈希算法名称，见[`Hasher::name`]
+    hash_algorithm: Cow<'a, str>,
+    /// 按高度排列的区块
+    blocks: Cow<'a, [Block]>,
+}
+
+/// 读取区块链文件时接受的格式
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredFormat {
+    /// 记录了哈希算法的区块链
+    Tagged(StoredChain<'static>),
+    /// 旧版本只保存区块数组，使用默认的SHA256
+    Legacy(Vec<Block>),
+}
+
+impl From<StoredFormat> for StoredChain<'static> {
+    fn from(format: StoredFormat) -> Self {
+        match format {
+            StoredFormat::Tagged(stored) => stored,
+            StoredFormat::Legacy(blocks) => StoredChain {
+                hash_algorithm: Cow::Borrowed(DEFAULT_HASH_ALGORITHM),
+                blocks: Cow::Owned(blocks),
+            },
+        }
+    }
+}
+
+impl StoredChain<'_> {
+    /// 确定区块链的哈希算法
+    ///
+    /// 指定了`expected`时文件记录的算法必须与之相同，否则按名称查找内置算法
+    fn hasher(&self, expected: Option<Arc<dyn Hasher>>) -> Result<Arc<dyn Hasher>, ImportError> {
+        let found = self.hash_algorithm.as_ref();
+        match expected {
+            Some(hasher) if hasher.name() == found => Ok(hasher),
+            Some(hasher) => Err(ImportError::HashAlgorithmMismatch {
+                expected: hasher.name().to_string(),
+                found: found.to_string(),
+            }),
+            None => hasher_by_name(found).ok_or_else(|| ImportError::UnknownHashAlgorithm(found.to_string())),
+        }
+    }
+}
+
+/// UTXO集合快照，记录生成快照时的链顶，用于重启时跳过重放区块
+#[derive(Debug, Serialize, Deserialize)]
+struct UtxoSnapshot {
+    /// 快照对应的链顶高度
+    height: usize,
+    /// 快照对应的链顶区块哈希
+    tip_hash: String,
+    /// 该链顶处的UTXO集合
+    utxo_set: HashMap<String, Vec<(u32, u64, String)>>,
+}
+
+/// 先写入同目录下的临时文件再重命名，写入中途失败不会破坏原有的文件
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, data)
+        .and_then(|()| fs::rename(&temp, path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })
+}
+
+/// 链数据导出的内容类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    /// 每个区块一行
+    Blocks,
+    /// 每笔交易一行
+    Transactions,
+}
+
+/// 区块导出行
+#[derive(Serialize)]
+struct BlockRow {
+    height: usize,
+    hash: String,
+    prev_hash: String,
+    timestamp: i64,
+    nonce: u64,
+    difficulty: u64,
+    tx_count: usize,
+}
+
+/// 交易导出行
+#[derive(Serialize)]
+struct TransactionRow {
+    txid: String,
+    block_height: usize,
+    input_count: usize,
+    output_count: usize,
+    total_output_value: u64,
+    fee: u64,
+}
+
+/// 区块链汇总统计，用于区块浏览器等展示场景
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChainStatistics {
+    /// 区块总数（包含创世区块）
+    pub total_blocks: usize,
+    /// 交易总数（包含Coinbase交易）
+    pub total_transactions: usize,
+    /// 所有Coinbase交易（包含创世区块）的输出总额
+    pub total_coinbase_amount: u64,
+    /// 所有普通交易的手续费总额
+    pub total_fees: u64,
+    /// 创世区块之后相邻区块的平均出块间隔（秒）
+    pub average_block_time_secs: f64,
+    /// 平均每个区块包含的交易数
+    pub average_txs_per_block: f64,
+    /// 未花费输出的数量
+    pub utxo_count: usize,
+    /// 流通总量，即Coinbase发行总额扣除其中包含的手续费
+    pub circulating_supply: u64,
+}
+
+/// 区块接入回调，参数为新接入的区块及其高度
+pub type BlockCallback = Arc<dyn Fn(&Block, usize) + Send + Sync>;
+
+/// 交易接受回调，参数为被接受的交易
+pub type TransactionCallback = Arc<dyn Fn(&Transaction) + Send + Sync>;
+
+/// 按CSV规则转义字段：包含逗号、引号或换行时用双引号包裹，并将引号加倍
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
 
 /// 区块链结构，包含区块列表、UTXO集合和挖矿难度
 #[derive(Clone)]
 pub struct Blockchain {
-    /// 区块列表，存储链中所有区块
-    pub blocks: Vec<Block>,
+    /// 区块列表，存储链中所有区块（通过`iter`、`tip`等访问器读取）
+    blocks: Vec<Block>,
     /// UTXO集合，存储未花费的交易输出
-    /// 键为交易ID，值为(输出索引, 金额)元组的列表
-    pub utxo_set: HashMap<String, Vec<(u32, u64)>>, // tx_id -> [(output_index, amount)]
+    /// 键为交易ID，值为(输出索引, 金额, 锁定脚本)元组的列表，查询所有者时无需回查原交易
+    pub utxo_set: HashMap<String, Vec<(u32, u64, String)>>, // tx_id -> [(output_index, amount, script_pubkey)]
     /// 挖矿难度，影响新区块的哈希要求
+    difficulty: u64,
+    /// 每个区块允许的最大交易数量（包含Coinbase交易）
+    max_transactions: usize,
+    /// 每个区块允许的最大字节数
+    max_block_size: usize,
+    /// 初始挖矿奖励，每经过`halving_interval`个区块减半
+    block_reward: u64,
+    /// 奖励减半间隔（区块数）
+    halving_interval: usize,
+    /// 目标出块间隔（秒）
+    target_block_time_secs: u64,
+    /// 难度调整间隔（区块数）
+    retarget_interval: usize,
+    /// Coinbase输出至少需要的确认数，达到之前不能花费
+    coinbase_maturity: usize,
+    /// 统计信息缓存，区块变化时失效
+    stats_cache: OnceCell<ChainStatistics>,
+    /// 累计工作量缓存，区块变化时失效
+    chain_work_cache: OnceCell<Vec<(usize, u128)>>,
+    /// 每个区块的UTXO修改记录，与`blocks`一一对应
+    undo_log: Vec<BlockUndo>,
+    /// 交易哈希到首次见到该交易时的链长度，用于计算确认延迟
+    first_seen: HashMap<String, usize>,
+    /// 手续费率估算缓存，键为目标区块数，值为(估算时的链长度, 费率)
+    fee_estimate_cache: RefCell<HashMap<u32, (usize, u64)>>,
+    /// 节点运行指标，与网络层共享
+    metrics: Arc<Metrics>,
+    /// 区块哈希、交易哈希和工作量证明使用的哈希算法
+    hasher: Arc<dyn Hasher>,
+    /// 区块接入后调用的回调
+    block_callbacks: Vec<BlockCallback>,
+    /// 交易被接受后调用的回调
+    transaction_callbacks: Vec<TransactionCallback>,
+    /// 区块变化后保存到的文件
+    data_file: String,
+}
+
+/// 区块链共识参数
+///
+/// 可以从配置文件读取，未给出的字段使用[`BlockchainConfig::mainnet`]的值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlockchainConfig {
+    /// 挖矿难度（区块哈希要求的前导0个数）
+    pub difficulty: u64,
+    /// 每个区块允许的最大字节数
+    pub max_block_size: usize,
+    /// 每个区块的初始挖矿奖励
+    pub initial_coinbase: u64,
+    /// 奖励减半间隔（区块数）
+    pub halving_interval: usize,
+    /// 目标出块间隔（秒）
+    pub target_block_time_secs: u64,
+    /// 难度调整间隔（区块数）
+    pub retarget_interval: usize,
+}
+
+impl BlockchainConfig {
+    /// 默认网络的参数，与[`GenesisConfig::default`]的难度一致
+    pub fn mainnet() -> Self {
+        BlockchainConfig {
+            difficulty: GenesisConfig::default().difficulty,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            initial_coinbase: BLOCK_REWARD,
+            halving_interval: DEFAULT_HALVING_INTERVAL,
+            target_block_time_secs: DEFAULT_TARGET_BLOCK_TIME_SECS,
+            retarget_interval: DEFAULT_RETARGET_INTERVAL,
+        }
+    }
+
+    /// 测试网络的参数：难度更低，出块、减半和难度调整都更频繁
+    pub fn testnet() -> Self {
+        BlockchainConfig {
+            difficulty: 1,
+            halving_interval: 1_000,
+            target_block_time_secs: 60,
+            retarget_interval: 100,
+            ..Self::mainnet()
+        }
+    }
+}
+
+impl Default for BlockchainConfig {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+/// 创世区块配置
+///
+/// 不同的配置（尤其是`network_id`）会产生不同的创世区块哈希，
+/// 使用不同配置的节点无法互相同步
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenesisConfig {
+    /// 创世区块时间戳
+    pub timestamp: i64,
+    /// 预挖分配，每项为(地址, 金额)
+    pub premine: Vec<(String, u64)>,
+    /// 写入创世Coinbase交易的消息
+    pub message: String,
+    /// 网络标识，区分不同的部署
+    pub network_id: String,
+    /// 挖矿难度
     pub difficulty: u64,
 }
 
-impl Blockchain {
-    /// 创建新的区块链实例
+impl Default for GenesisConfig {
+    fn default() -> Self {
+        GenesisConfig {
+            timestamp: 1748793600, // 固定时间戳：2025-06-01 00:00:00
+            premine: vec![(String::from("genesis_address"), 100)], // 固定的创世地址和奖励
+            message: String::from("Genesis Block - Blockchain Demo"),
+            network_id: String::from(DEFAULT_NETWORK_ID),
+            difficulty: 2,
+        }
+    }
+}
+
+impl GenesisConfig {
+    /// 回归测试网络的创世配置：难度为0，网络标识为[`REGTEST_NETWORK_ID`]
+    ///
+    /// 网络标识不同，回归测试节点不会与默认网络的节点互相同步
+    pub fn regtest() -> Self {
+        GenesisConfig {
+            network_id: String::from(REGTEST_NETWORK_ID),
+            difficulty: 0,
+            ..GenesisConfig::default()
+        }
+    }
+
+    /// 根据配置构建创世区块
+    ///
+    /// 网络标识写入Coinbase输入的脚本签名，因此会影响创世区块哈希，
+    /// 也可以用[`GenesisConfig::network_id_of`]从创世区块中读回
+    pub fn build_block(&self) -> Block {
+        // 创世区块包含一个coinbase交易，为每个预挖地址创建一个输出
+        let genesis_coinbase = crate::block::Transaction::new(
+            vec![crate::block::TxInput {
+                prev_tx: String::from(crate::block::COINBASE_TX_ID),
+                prev_index: 0,
+                script_sig: format!("{} [network: {}]", self.message, self.network_id),
+            }],
+            self.premine.iter()
+                .map(|(address, amount)| crate::block::TxOutput {
+                    value: *amount,
+                    script_pubkey: address.clone(),
+                })
+                .collect(),
+        );
+        
+        let mut genesis = Block {
+            header: crate::block::BlockHeader {
+                prev_hash: String::from("0"),
+                timestamp: self.timestamp,
+                merkle_root: String::new(),
+                nonce: 0,
+                difficulty: self.difficulty,
+            },
+            transactions: vec![genesis_coinbase],
+        };
+        genesis.header.merkle_root = genesis.compute_merkle_root();
+        genesis
+    }
+
+    /// 从创世区块中读取网络标识
+    ///
+    /// # 参数
+    ///
+    /// * `genesis` - 由[`GenesisConfig::build_block`]构建的创世区块
+    ///
+    /// # 返回值
+    ///
+    /// 返回Coinbase脚本签名中记录的网络标识；没有记录时返回[`DEFAULT_NETWORK_ID`]
+    pub fn network_id_of(genesis: &Block) -> &str {
+        genesis.transactions.first()
+            .and_then(|tx| tx.inputs.first())
+            .and_then(|input| input.script_sig.rsplit_once(" [network: "))
+            .and_then(|(_, rest)| rest.strip_suffix(']'))
+            .unwrap_or(DEFAULT_NETWORK_ID)
+    }
+}
+
+impl Blockchain {
+    /// 创建新的区块链实例
+    ///
+    /// 使用默认的创世区块配置，仅替换挖矿难度
+    ///
+    /// # 参数
+    ///
+    /// * `difficulty` - 挖矿难度，影响新区块的哈希要求
+    ///
+    /// # 返回值
+    ///
+    /// 返回初始化的区块链实例，包含创世区块
+    pub fn new(difficulty: u64) -> Self {
+        Self::new_with_genesis(GenesisConfig {
+            difficulty,
+            ..GenesisConfig::default()
+        })
+    }
+
+    /// 使用指定的共识参数创建区块链
+    ///
+    /// 创世区块使用默认配置，难度取自`config`
+    ///
+    /// # 参数
+    ///
+    /// * `config` - 共识参数
+    ///
+    /// # 返回值
+    ///
+    /// 返回初始化的区块链实例，包含创世区块
+    pub fn new_with_config(config: BlockchainConfig) -> Self {
+        let mut blockchain = Self::new(config.difficulty);
+        blockchain.apply_config(&config);
+        blockchain
+    }
+
+    /// 创建回归测试区块链
+    ///
+    /// 难度为0，任何哈希都满足要求，挖矿不需要搜索nonce，区块立即产生，
+    /// 适合需要确定、快速出块的测试
+    ///
+    /// # 返回值
+    ///
+    /// 返回使用[`GenesisConfig::regtest`]创世区块的区块链
+    pub fn regtest() -> Self {
+        Self::new_with_genesis(GenesisConfig::regtest())
+    }
+
+    /// 使用指定的创世区块配置创建区块链
+    ///
+    /// # 参数
+    ///
+    /// * `config` - 创世区块配置
+    ///
+    /// # 返回值
+    ///
+    /// 返回初始化的区块链实例，包含按配置生成的创世区块
+    pub fn new_with_genesis(config: GenesisConfig) -> Self {
+        Self::new_with_hasher(config, Arc::new(Sha256Hasher))
+    }
+
+    /// 使用指定的创世区块配置和哈希算法创建区块链
+    ///
+    /// 创世区块哈希由该算法计算，使用不同算法的节点在创世区块握手时就会互相拒绝
+    ///
+    /// # 参数
+    ///
+    /// * `config` - 创世区块配置
+    /// * `hasher` - 区块哈希、默克尔根、交易哈希和工作量证明使用的哈希算法
+    pub fn new_with_hasher(config: GenesisConfig, hasher: Arc<dyn Hasher>) -> Self {
+        Self::from_genesis_block(config.build_block(), config.difficulty, hasher)
+    }
+
+    /// 以给定的创世区块初始化区块链
+    fn from_genesis_block(genesis: Block, difficulty: u64, hasher: Arc<dyn Hasher>) -> Self {
+        let mut blockchain = Blockchain {
+            blocks: vec![genesis],
+            utxo_set: HashMap::new(),
+            difficulty,
+            max_transactions: DEFAULT_MAX_TRANSACTIONS,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            block_reward: BLOCK_REWARD,
+            halving_interval: DEFAULT_HALVING_INTERVAL,
+            target_block_time_secs: DEFAULT_TARGET_BLOCK_TIME_SECS,
+            retarget_interval: DEFAULT_RETARGET_INTERVAL,
+            coinbase_maturity: DEFAULT_COINBASE_MATURITY,
+            stats_cache: OnceCell::new(),
+            chain_work_cache: OnceCell::new(),
+            undo_log: Vec::new(),
+            first_seen: HashMap::new(),
+            fee_estimate_cache: RefCell::new(HashMap::new()),
+            metrics: Arc::new(Metrics::new()),
+            hasher,
+            block_callbacks: Vec::new(),
+            transaction_callbacks: Vec::new(),
+            data_file: String::from(DEFAULT_CHAIN_FILE),
+        };
+        blockchain.rebuild_undo_log();
+        blockchain.update_utxo_set();
+        blockchain
+    }
+
+    /// 创建只包含相同创世区块的新区块链
+    ///
+    /// 用于在不影响本地链的情况下验证同步收到的区块链
+    pub fn fresh_copy(&self) -> Self {
+        let mut blockchain = Self::from_genesis_block(self.genesis().clone(), self.difficulty, Arc::clone(&self.hasher));
+        blockchain.max_transactions = self.max_transactions;
+        blockchain.apply_config(&self.config());
+        blockchain.coinbase_maturity = self.coinbase_maturity;
+        blockchain.metrics = Arc::clone(&self.metrics);
+        blockchain.data_file = self.data_file.clone();
+        blockchain
+    }
+
+    /// 获取节点运行指标
+    ///
+    /// 返回共享的指标实例，可以克隆后交给网络层，使两者更新同一组计数器
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// 注册区块接入回调
+    ///
+    /// 本地挖出、收到或同步的区块接入链并更新UTXO集后，按高度顺序对每个区块调用一次；
+    /// 切换分叉时在UTXO集按新分叉重建之后，对新分叉上的区块调用。
+    /// 克隆的区块链共享已注册的回调，`fresh_copy`不复制回调
+    ///
+    /// # 参数
+    ///
+    /// * `callback` - 回调函数，参数为区块和它的高度
+    pub fn on_block(&mut self, callback: impl Fn(&Block, usize) + Send + Sync + 'static) {
+        self.block_callbacks.push(Arc::new(callback));
+    }
+
+    /// 注册交易接受回调
+    ///
+    /// 交易通过验证并由`record_seen`首次记录（即进入交易池）时调用一次
+    ///
+    /// # 参数
+    ///
+    /// * `callback` - 回调函数，参数为被接受的交易
+    pub fn on_transaction(&mut self, callback: impl Fn(&Transaction) + Send + Sync + 'static) {
+        self.transaction_callbacks.push(Arc::new(callback));
+    }
+
+    /// 对`from_height`及之后的区块调用区块接入回调
+    fn notify_blocks_from(&self, from_height: usize) {
+        if self.block_callbacks.is_empty() {
+            return;
+        }
+        for (height, block) in self.blocks.iter().enumerate().skip(from_height) {
+            for callback in &self.block_callbacks {
+                callback(block, height);
+            }
+        }
+    }
+
+    /// 向区块链添加新区块
+    ///
+    /// 交易按传入的顺序写入区块，调用方负责将Coinbase交易放在首位并按手续费排序
+    ///
+    /// # 参数
+    ///
+    /// * `transactions` - 要包含在新区块中的交易列表
+    pub fn add_block(&mut self, transactions: Vec<Transaction>) {
+        let new_block = self.mine_block(transactions);
+        
+        self.push_block(new_block);
+        self.persist();
+        self.notify_blocks_from(self.height());
+    }
+
+    /// 在当前链顶之上挖出新区块，但不添加到区块链
+    ///
+    /// 矿工可以先用`check_block`确认区块有效，再调用`add_received_block`添加并广播
+    ///
+    /// # 参数
+    ///
+    /// * `transactions` - 要包含在新区块中的交易列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回满足当前难度的新区块
+    pub fn mine_block(&self, transactions: Vec<Transaction>) -> Block {
+        let prev_hash = self.block_hash(self.tip());
+
+        let mut new_block = Block::with_timestamp(prev_hash, self.difficulty, self.next_block_timestamp());
+        new_block.transactions = transactions;
+        new_block.mine_from_with(0, self.hasher.as_ref());
+        self.metrics.record_block_mined();
+        new_block
+    }
+
+    /// 计算最近区块的过去中位时间（MPT）
+    ///
+    /// 取最近`MEDIAN_TIME_SPAN`个区块（链较短时取全部区块）时间戳的中位数，
+    /// 新区块的时间戳必须晚于它，单个矿工无法通过伪造时间戳把链的时间拨回
+    ///
+    /// # 返回值
+    ///
+    /// 返回中位时间戳（Unix秒）
+    pub fn median_past_time(&self) -> i64 {
+        let start = self.blocks.len().saturating_sub(MEDIAN_TIME_SPAN);
+        let mut timestamps: Vec<i64> = self.blocks[start..].iter().map(|block| block.header.timestamp).collect();
+        timestamps.sort_unstable();
+        timestamps[timestamps.len() / 2]
+    }
+
+    /// 获取下一个区块应使用的时间戳
+    ///
+    /// 通常为当前时间；同一秒内挖出多个区块时，当前时间可能不晚于过去中位时间，此时使用中位时间加1秒
+    pub fn next_block_timestamp(&self) -> i64 {
+        Utc::now().timestamp().max(self.median_past_time() + 1)
+    }
+
+    /// 将区块追加到链尾，增量更新UTXO集合并记录它的UTXO修改，不重放之前的区块
+    fn push_block(&mut self, block: Block) {
+        let undo = self.apply_block_to_utxo_set(&block);
+        self.undo_log.push(undo);
+        self.blocks.push(block);
+        self.stats_cache.take();
+        self.chain_work_cache.take();
+    }
+
+    /// 计算区块对UTXO集合的修改
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 要计算的区块
+    /// * `known` - 交易ID到交易的索引，需要包含该区块及之前所有区块的交易
+    fn block_undo(&self, block: &Block, known: &HashMap<String, &Transaction>) -> BlockUndo {
+        let mut undo = BlockUndo::default();
+        
+        for tx in &block.transactions {
+            let tx_id = self.calculate_tx_hash(tx);
+            for (index, output) in tx.outputs.iter().enumerate().filter(|(_, output)| !output.is_unspendable()) {
+                undo.created_outputs.push((tx_id.clone(), index as u32, output.value, output.script_pubkey.clone()));
+            }
+            
+            for input in tx.inputs.iter().filter(|input| input.prev_tx != crate::block::COINBASE_TX_ID) {
+                let spent = known.get(&input.prev_tx)
+                    .and_then(|prev_tx| prev_tx.outputs.get(input.prev_index as usize));
+                if let Some(output) = spent {
+                    undo.spent_outputs.push((
+                        input.prev_tx.clone(),
+                        input.prev_index,
+                        output.value,
+                        output.script_pubkey.clone(),
+                    ));
+                }
+            }
+        }
+        
+        undo
+    }
+
+    /// 根据当前区块重新计算全部UTXO修改记录
+    fn rebuild_undo_log(&mut self) {
+        let mut known = HashMap::new();
+        let mut undo_log = Vec::with_capacity(self.blocks.len());
+        for block in &self.blocks {
+            for tx in &block.transactions {
+                known.insert(self.calculate_tx_hash(tx), tx);
+            }
+            undo_log.push(self.block_undo(block, &known));
+        }
+        self.undo_log = undo_log;
+    }
+
+    /// 回滚链尾的若干个区块
+    ///
+    /// 按相反顺序应用每个区块的修改记录：重新加入被花费的输出，移除区块创建的输出
+    ///
+    /// # 参数
+    ///
+    /// * `n_blocks` - 要移除的区块数量，不能移除创世区块
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回Ok；如果回滚会移除创世区块，返回错误且链保持不变
+    pub fn rollback(&mut self, n_blocks: usize) -> Result<(), BlockchainError> {
+        if n_blocks > self.height() {
+            return Err(BlockchainError::RollbackPastGenesis {
+                requested: n_blocks,
+                height: self.height(),
+            });
+        }
+        
+        for _ in 0..n_blocks {
+            self.blocks.pop();
+            let undo = self.undo_log.pop().expect("每个区块都有对应的修改记录");
+            
+            // 先恢复被花费的输出，再移除创建的输出，这样同一区块内创建又花费的输出也会被正确移除
+            for (tx_id, index, value, script_pubkey) in undo.spent_outputs {
+                let outputs = self.utxo_set.entry(tx_id).or_default();
+                outputs.push((index, value, script_pubkey));
+                outputs.sort_by_key(|(idx, ..)| *idx);
+            }
+            for (tx_id, index, ..) in undo.created_outputs {
+                if let Some(outputs) = self.utxo_set.get_mut(&tx_id) {
+                    outputs.retain(|(idx, ..)| *idx != index);
+                }
+            }
+            self.utxo_set.retain(|_, outputs| !outputs.is_empty());
+        }
+        
+        self.stats_cache.take();
+        self.chain_work_cache.take();
+        self.persist();
+        Ok(())
+    }
+
+    /// 获取指定高度区块的UTXO修改记录
+    pub fn block_undo_at(&self, height: usize) -> Option<&BlockUndo> {
+        self.undo_log.get(height)
+    }
+
+    /// 返回从创世区块到链顶的区块迭代器
+    pub fn iter(&self) -> std::slice::Iter<'_, Block> {
+        self.blocks.iter()
+    }
+
+    /// 返回从链顶到创世区块的逆序区块迭代器
+    pub fn iter_rev(&self) -> std::iter::Rev<std::slice::Iter<'_, Block>> {
+        self.blocks.iter().rev()
+    }
+
+    /// 获取链顶区块（最新的区块）
+    pub fn tip(&self) -> &Block {
+        self.blocks.last().expect("区块链至少包含创世区块")
+    }
+
+    /// 获取创世区块
+    pub fn genesis(&self) -> &Block {
+        &self.blocks[0]
+    }
+
+    /// 导出创世区块的JSON表示，格式与区块链文件中的区块相同
+    pub fn export_genesis_block(&self) -> serde_json::Value {
+        serde_json::to_value(self.genesis()).expect("区块可以被序列化为JSON")
+    }
+
+    /// 判断给定区块是否与本链的创世区块相同
+    ///
+    /// 创世区块不同的两条链无法互相同步，节点连接时据此拒绝对方
+    ///
+    /// # 参数
+    ///
+    /// * `other` - 对方的创世区块
+    pub fn matches_genesis(&self, other: &Block) -> bool {
+        self.block_hash(self.genesis()) == self.block_hash(other)
+    }
+
+    /// 获取指定高度的区块
+    ///
+    /// # 参数
+    ///
+    /// * `height` - 区块高度，创世区块高度为0
+    pub fn get(&self, height: usize) -> Option<&Block> {
+        self.blocks.get(height)
+    }
+
+    /// 根据区块哈希查找区块，从链顶向前查找
+    ///
+    /// # 参数
+    ///
+    /// * `hash` - 区块哈希
+    pub fn get_block_by_hash(&self, hash: &str) -> Option<&Block> {
+        self.blocks.iter().rev().find(|block| self.block_hash(block) == hash)
+    }
+
+    /// 检查区块的父区块是否缺失
+    ///
+    /// 父区块不在本地链上时（例如错过了一个区块广播），可以只向其他节点请求该父区块，
+    /// 而不必同步整条链
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 收到的区块
+    ///
+    /// # 返回值
+    ///
+    /// 父区块未知时返回父区块的哈希；父区块在本地链上（包括分叉的情况）时返回None
+    pub fn missing_parent<'a>(&self, block: &'a Block) -> Option<&'a str> {
+        let prev_hash = block.header.prev_hash.as_str();
+        (prev_hash != "0" && self.get_block_by_hash(prev_hash).is_none()).then_some(prev_hash)
+    }
+
+    /// 以切片形式获取全部区块
+    pub fn as_slice(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// 获取区块数量（包含创世区块）
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// 区块链是否为空
+    ///
+    /// 正常构造的区块链始终包含创世区块，因此该方法通常返回false
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// 获取链高度
+    ///
+    /// 高度定义为`len() - 1`，即链顶区块的高度；只有创世区块时高度为0
+    pub fn height(&self) -> usize {
+        self.blocks.len().saturating_sub(1)
+    }
+
+    /// 获取挖矿难度
+    pub fn difficulty(&self) -> u64 {
+        self.difficulty
+    }
+
+    /// 设置挖矿难度，之后挖出的区块使用新难度
+    ///
+    /// # 参数
+    ///
+    /// * `difficulty` - 区块哈希要求的前导0个数，不能超过[`MAX_DIFFICULTY`]
+    ///
+    /// # 返回值
+    ///
+    /// 设置成功返回Ok，难度超出范围时返回错误说明，原难度保持不变
+    pub fn set_difficulty(&mut self, difficulty: u64) -> Result<(), &'static str> {
+        if difficulty > MAX_DIFFICULTY {
+            return Err("难度超过区块哈希的十六进制长度，无法挖出区块");
+        }
+        self.difficulty = difficulty;
+        Ok(())
+    }
+
+    /// 获取每个区块允许的最大交易数量
+    pub fn max_transactions(&self) -> usize {
+        self.max_transactions
+    }
+
+    /// 设置每个区块允许的最大交易数量（包含Coinbase交易）
+    pub fn set_max_transactions(&mut self, max: usize) {
+        self.max_transactions = max;
+    }
+
+    /// 获取每个区块允许的最大字节数
+    pub fn max_block_size(&self) -> usize {
+        self.max_block_size
+    }
+
+    /// 设置每个区块允许的最大字节数
+    pub fn set_max_block_size(&mut self, max: usize) {
+        self.max_block_size = max;
+    }
+
+    /// 获取初始挖矿奖励，某一高度的实际奖励见[`Blockchain::block_reward_at_height`]
+    pub fn block_reward(&self) -> u64 {
+        self.block_reward
+    }
+
+    /// 计算指定高度的区块奖励
+    ///
+    /// 与比特币相同，每经过`halving_interval`个区块奖励减半：`初始奖励 >> (高度 / 减半间隔)`，
+    /// 减半次数达到64次之后奖励为0；`halving_interval`为0表示奖励从不减半
+    ///
+    /// # 参数
+    ///
+    /// * `height` - 区块高度，创世区块高度为0
+    ///
+    /// # 返回值
+    ///
+    /// 返回该高度的区块允许领取的奖励（不含手续费）
+    pub fn block_reward_at_height(&self, height: usize) -> u64 {
+        let Some(halvings) = height.checked_div(self.halving_interval) else {
+            return self.block_reward;
+        };
+        u32::try_from(halvings).ok()
+            .and_then(|halvings| self.block_reward.checked_shr(halvings))
+            .unwrap_or(0)
+    }
+
+    /// 下一次奖励减半的区块高度，即链顶之后第一个是减半间隔整数倍的高度
+    ///
+    /// # 返回值
+    ///
+    /// 返回下一次减半的高度；`halving_interval`为0（奖励从不减半）或高度超出`usize`范围时返回None
+    pub fn next_halving_height(&self) -> Option<usize> {
+        let interval = self.halving_interval;
+        (self.height().checked_div(interval)? + 1).checked_mul(interval)
+    }
+
+    /// 获取指定高度区块的Coinbase交易
+    ///
+    /// # 参数
+    ///
+    /// * `height` - 区块高度，创世区块高度为0
+    ///
+    /// # 返回值
+    ///
+    /// 返回区块的第一笔交易；区块不存在或第一笔交易不是Coinbase时返回None
+    pub fn get_coinbase_transaction(&self, height: usize) -> Option<&Transaction> {
+        self.get(height)?.transactions.first().filter(|tx| tx.is_coinbase())
+    }
+
+    /// 获取当前使用的共识参数
+    pub fn config(&self) -> BlockchainConfig {
+        BlockchainConfig {
+            difficulty: self.difficulty,
+            max_block_size: self.max_block_size,
+            initial_coinbase: self.block_reward,
+            halving_interval: self.halving_interval,
+            target_block_time_secs: self.target_block_time_secs,
+            retarget_interval: self.retarget_interval,
+        }
+    }
+
+    /// 应用共识参数，已有的区块不受影响，之后挖出和验证的区块按新参数处理
+    ///
+    /// 参数不做检查，从配置文件读取时由[`crate::config::NodeConfig::load`]检查取值
+    ///
+    /// # 参数
+    ///
+    /// * `config` - 共识参数
+    pub fn apply_config(&mut self, config: &BlockchainConfig) {
+        self.difficulty = config.difficulty;
+        self.max_block_size = config.max_block_size;
+        self.block_reward = config.initial_coinbase;
+        self.halving_interval = config.halving_interval;
+        self.target_block_time_secs = config.target_block_time_secs;
+        self.retarget_interval = config.retarget_interval;
+    }
+
+    /// 获取Coinbase成熟深度
+    pub fn coinbase_maturity(&self) -> usize {
+        self.coinbase_maturity
+    }
+
+    /// 设置Coinbase成熟深度
+    ///
+    /// 高度为h的区块中的Coinbase输出在链高度为H时有`H - h + 1`个确认，
+    /// 确认数达到`maturity`之前不能花费，避免花费可能因链重组而消失的奖励。
+    /// 创世区块的预挖输出不受限制
+    ///
+    /// # 参数
+    ///
+    /// * `maturity` - 花费Coinbase输出需要的最少确认数，0表示立即可以花费
+    pub fn set_coinbase_maturity(&mut self, maturity: usize) {
+        self.coinbase_maturity = maturity;
+    }
+
+    /// 尚未达到成熟深度的Coinbase交易ID
+    fn immature_coinbases(&self) -> HashSet<String> {
+        // 只有最近maturity - 1个区块的Coinbase确认数不足
+        let first = (self.height() + 2).saturating_sub(self.coinbase_maturity).max(1);
+        self.blocks.iter().skip(first)
+            .flat_map(|block| block.transactions.iter().filter(|tx| tx.is_coinbase()))
+            .map(|tx| self.calculate_tx_hash(tx))
+            .collect()
+    }
+
+    /// 获取区块链所属网络的标识，交易签名摘要包含该标识
+    pub fn network_id(&self) -> &str {
+        GenesisConfig::network_id_of(self.genesis())
+    }
+
+    /// 获取全部区块
+    #[deprecated(note = "请使用 iter()、as_slice()、tip() 等访问器")]
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// 更新UTXO集合
+    ///
+    /// 遍历区块链中的所有交易，重新构建UTXO集合
+    fn update_utxo_set(&mut self) {
+        self.stats_cache.take();
+        self.chain_work_cache.take();
+        self.utxo_set.clear();
+        
+        // 首先添加所有交易的输出
+        for block in &self.blocks {
+            for tx in &block.transactions {
+                let tx_id = self.calculate_tx_hash(tx);
+                
+                // 添加所有输出到UTXO集，数据输出不能被花费，不加入
+                for (index, output) in tx.outputs.iter().enumerate().filter(|(_, output)| !output.is_unspendable()) {
+                    let outputs = self.utxo_set.entry(tx_id.clone())
+                        .or_default();
+                    outputs.push((index as u32, output.value, output.script_pubkey.clone()));
+                }
+            }
+        }
+        
+        // 然后移除所有被花费的输出
+        for block in &self.blocks {
+            for tx in &block.transactions {
+                // 处理输入，移除已花费的UTXO
+                for input in &tx.inputs {
+                    // 跳过coinbase交易的输入
+                    if input.prev_tx == "0000000000000000000000000000000000000000000000000000000000000000" {
+                        continue;
+                    }
+                    
+                    // 从UTXO集中移除已花费的输出
+                    if let Some(outputs) = self.utxo_set.get_mut(&input.prev_tx) {
+                        outputs.retain(|(idx, ..)| *idx != input.prev_index);
+                        // 如果这个交易的所有输出都被花费了，移除整个条目
+                        if outputs.is_empty() {
+                            self.utxo_set.remove(&input.prev_tx);
+                        }
+                    }
+                }
+            }
+        }
+        
+        // 清理空的条目
+        self.utxo_set.retain(|_, outputs| !outputs.is_empty());
+    }
+
+    /// 使用本链的哈希算法计算交易ID，作为UTXO集合的键
+    ///
+    /// 交易ID不包含签名，见[`Transaction::txid`]
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 要计算哈希的交易
+    ///
+    /// # 返回值
+    ///
+    /// 返回交易ID（16进制字符串）
+    pub fn calculate_tx_hash(&self, tx: &Transaction) -> String {
+        tx.txid_with(self.hasher.as_ref())
+    }
+
+    /// 使用本链的哈希算法计算区块哈希
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 要计算哈希的区块
+    ///
+    /// # 返回值
+    ///
+    /// 返回区块哈希值（16进制字符串）
+    pub fn block_hash(&self, block: &Block) -> String {
+        block.calculate_hash_with(self.hasher.as_ref())
+    }
+
+    /// 本链使用的哈希算法
+    ///
+    /// 返回共享的算法实例，交易池等需要计算相同交易ID的组件可以克隆后使用
+    pub fn hasher(&self) -> &Arc<dyn Hasher> {
+        &self.hasher
+    }
+
+    /// 本链使用的哈希算法名称
+    pub fn hash_algorithm(&self) -> &str {
+        self.hasher.name()
+    }
+
+    /// 以保存格式描述本链：哈希算法名称和全部区块
+    fn stored(&self) -> StoredChain<'_> {
+        StoredChain {
+            hash_algorithm: Cow::Borrowed(self.hash_algorithm()),
+            blocks: Cow::Borrowed(&self.blocks),
+        }
+    }
+
+    /// 区块变化后保存到的文件
+    pub fn data_file(&self) -> &str {
+        &self.data_file
+    }
+
+    /// 设置区块变化后保存到的文件，默认为[`DEFAULT_CHAIN_FILE`]
+    ///
+    /// 从文件加载的区块链保存回加载时的文件
+    ///
+    /// # 参数
+    ///
+    /// * `filename` - 保存区块链数据的文件名
+    pub fn set_data_file(&mut self, filename: &str) {
+        self.data_file = filename.to_string();
+    }
+
+    /// 将区块链保存到[`Blockchain::data_file`]
+    ///
+    /// # 返回值
+    ///
+    /// 写入失败时返回IO错误
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to_file(&self.data_file)
+    }
+
+    /// 区块变化后保存区块链，失败时只记录错误，内存中的链不受影响
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            error!(filename = %self.data_file, error = %e, "无法保存区块链");
+        }
+    }
+
+    /// 将区块链数据保存到文件
+    ///
+    /// 文件同时记录哈希算法名称，加载时据此使用相同的算法。
+    /// 每个文件都先写入临时文件再重命名，写入中途失败不会破坏原有的文件
+    ///
+    /// # 参数
+    ///
+    /// * `filename` - 保存区块链数据的文件名
+    ///
+    /// # 返回值
+    ///
+    /// 写入失败时返回IO错误
+    pub fn save_to_file(&self, filename: &str) -> io::Result<()> {
+        let serialized = serde_json::to_string_pretty(&self.stored())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        write_atomic(Path::new(filename), serialized.as_bytes())?;
+        
+        // UTXO修改记录保存在旁边的文件中，供回滚使用
+        let serialized_undo = serde_json::to_string(&self.undo_log)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        write_atomic(Path::new(&Self::undo_filename(filename)), serialized_undo.as_bytes())?;
+        
+        // UTXO快照同样保存在旁边，重启时无需重放全部区块
+        self.save_utxo_snapshot(Path::new(&Self::snapshot_filename(filename)))
+    }
+
+    /// 将当前UTXO集合和链顶保存为快照
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 目标文件路径
+    pub fn save_utxo_snapshot(&self, path: &Path) -> io::Result<()> {
+        let snapshot = UtxoSnapshot {
+            height: self.height(),
+            tip_hash: self.block_hash(self.tip()),
+            utxo_set: self.utxo_set.clone(),
+        };
+        let data = serde_json::to_string(&snapshot)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        write_atomic(path, data.as_bytes())
+    }
+
+    /// 从快照加载UTXO集合
+    ///
+    /// 快照记录的链顶必须是本地链上同一高度的区块，否则快照已过期或属于其他链，会被忽略。
+    /// 快照之后追加的区块按修改记录依次应用，无需从创世区块重放
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 由[`Blockchain::save_utxo_snapshot`]写入的文件
+    ///
+    /// # 返回值
+    ///
+    /// 使用了快照返回true；文件不存在、格式错误或链顶不匹配时返回false，UTXO集合保持不变
+    pub fn load_utxo_snapshot(&mut self, path: &Path) -> bool {
+        match self.read_utxo_snapshot(path) {
+            Some(snapshot) => {
+                self.apply_utxo_snapshot(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 读取与本地链匹配的UTXO快照，文件不存在、格式错误或链顶不匹配时返回None
+    fn read_utxo_snapshot(&self, path: &Path) -> Option<UtxoSnapshot> {
+        let snapshot: UtxoSnapshot = fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())?;
+        let matches_chain = self.blocks.get(snapshot.height)
+            .is_some_and(|block| self.block_hash(block) == snapshot.tip_hash);
+        (matches_chain && self.undo_log.len() == self.blocks.len()).then_some(snapshot)
+    }
+
+    /// 以快照作为UTXO集合，再按修改记录应用快照之后的区块
+    fn apply_utxo_snapshot(&mut self, snapshot: UtxoSnapshot) {
+        self.utxo_set = snapshot.utxo_set;
+        for height in snapshot.height + 1..self.blocks.len() {
+            // 先加入区块创建的输出，再移除花费的输出，同一区块内创建又花费的输出会被正确移除
+            let undo = &self.undo_log[height];
+            for (tx_id, index, value, script_pubkey) in &undo.created_outputs {
+                self.utxo_set.entry(tx_id.clone()).or_default().push((*index, *value, script_pubkey.clone()));
+            }
+            for (tx_id, index, ..) in &undo.spent_outputs {
+                if let Some(outputs) = self.utxo_set.get_mut(tx_id) {
+                    outputs.retain(|(idx, ..)| *idx != *index);
+                }
+            }
+            self.utxo_set.retain(|_, outputs| !outputs.is_empty());
+        }
+    }
+
+    /// 将区块链以紧凑的二进制格式（bincode）保存到文件
+    ///
+    /// `blockchain.json`仍是默认的持久化格式，便于阅读；二进制格式用于节省存储空间
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 目标文件路径
+    pub fn save_to_binary(&self, path: &Path) -> io::Result<()> {
+        let data = bincode::serialize(&self.stored())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        write_atomic(path, &data)
+    }
+
+    /// 从二进制格式的文件加载区块链
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 由[`Blockchain::save_to_binary`]写入的文件
+    ///
+    /// # 返回值
+    ///
+    /// 如果文件存在、格式正确并且使用内置的哈希算法，返回加载的区块链；否则返回None
+    pub fn load_from_binary(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        // 旧版本只保存区块数组
+        let stored: StoredChain = bincode::deserialize(&data)
+            .or_else(|_| bincode::deserialize(&data).map(StoredFormat::Legacy).map(StoredChain::from))
+            .ok()?;
+        let hasher = match stored.hasher(None) {
+            Ok(hasher) => hasher,
+            Err(e) => {
+                error!(path = %path.display(), error = %e, "无法加载二进制区块链");
+                return None;
+            }
+        };
+        let blocks = stored.blocks.into_owned();
+        let difficulty = blocks.first()?.header.difficulty;
+        
+        let mut blockchain = Self::from_stored_blocks(blocks, difficulty, hasher);
+        blockchain.rebuild_undo_log();
+        blockchain.update_utxo_set();
+        Some(blockchain)
+    }
+
+    /// 用已保存的区块创建区块链，UTXO集合和修改记录留空，由调用方在检查区块后重新计算
+    fn from_stored_blocks(blocks: Vec<Block>, difficulty: u64, hasher: Arc<dyn Hasher>) -> Self {
+        Blockchain {
+            blocks,
+            utxo_set: HashMap::new(),
+            difficulty,
+            max_transactions: DEFAULT_MAX_TRANSACTIONS,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            block_reward: BLOCK_REWARD,
+            halving_interval: DEFAULT_HALVING_INTERVAL,
+            target_block_time_secs: DEFAULT_TARGET_BLOCK_TIME_SECS,
+            retarget_interval: DEFAULT_RETARGET_INTERVAL,
+            coinbase_maturity: DEFAULT_COINBASE_MATURITY,
+            stats_cache: OnceCell::new(),
+            chain_work_cache: OnceCell::new(),
+            undo_log: Vec::new(),
+            first_seen: HashMap::new(),
+            fee_estimate_cache: RefCell::new(HashMap::new()),
+            metrics: Arc::new(Metrics::new()),
+            hasher,
+            block_callbacks: Vec::new(),
+            transaction_callbacks: Vec::new(),
+            data_file: String::from(DEFAULT_CHAIN_FILE),
+        }
+    }
+
+    /// 将整条链导出为JSON
+    ///
+    /// 格式与`save_to_file`写入的区块链文件相同，可以用[`Blockchain::import_json`]导入
+    ///
+    /// # 返回值
+    ///
+    /// 返回包含哈希算法名称和按高度排列的全部区块的JSON
+    pub fn export_json(&self) -> String {
+        serde_json::to_string_pretty(&self.stored()).unwrap()
+    }
+
+    /// 将指定高度的区块导出为JSON
+    ///
+    /// # 参数
+    ///
+    /// * `height` - 区块高度
+    ///
+    /// # 返回值
+    ///
+    /// 返回该区块的JSON；高度超过链顶时返回None
+    pub fn export_block_json(&self, height: usize) -> Option<String> {
+        self.blocks.get(height).map(|block| serde_json::to_string_pretty(block).unwrap())
+    }
+
+    /// 从JSON导入区块链
+    ///
+    /// 与`load_verified`不同，导入时不截断：任何区块未通过完整性检查或从创世区块重放的验证，
+    /// 整条链都被拒绝
+    ///
+    /// JSON记录的哈希算法必须是内置算法，使用其他算法的链用[`Blockchain::import_json_with_hasher`]导入
+    ///
+    /// # 参数
+    ///
+    /// * `json` - 由[`Blockchain::export_json`]导出的区块链，也接受旧版本导出的区块数组
+    ///
+    /// # 返回值
+    ///
+    /// 验证通过时返回导入的区块链，否则返回遇到的问题
+    pub fn import_json(json: &str) -> Result<Self, ImportError> {
+        Self::import_stored(json, None)
+    }
+
+    /// 使用指定的哈希算法从JSON导入区块链，JSON记录的算法与之不同时拒绝导入
+    ///
+    /// # 参数
+    ///
+    /// * `json` - 由[`Blockchain::export_json`]导出的区块链
+    /// * `hasher` - 区块链使用的哈希算法
+    ///
+    /// # 返回值
+    ///
+    /// 验证通过时返回导入的区块链，否则返回遇到的问题
+    pub fn import_json_with_hasher(json: &str, hasher: Arc<dyn Hasher>) -> Result<Self, ImportError> {
+        Self::import_stored(json, Some(hasher))
+    }
+
+    /// 解析并验证导入的区块链，`expected`见[`StoredChain::hasher`]
+    fn import_stored(json: &str, expected: Option<Arc<dyn Hasher>>) -> Result<Self, ImportError> {
+        let stored: StoredChain = serde_json::from_str::<StoredFormat>(json)
+            .map_err(|e| ImportError::InvalidJson(e.to_string()))?
+            .into();
+        let hasher = stored.hasher(expected)?;
+        let blocks = stored.blocks.into_owned();
+        let difficulty = blocks.first().ok_or(ImportError::Empty)?.header.difficulty;
+        
+        let mut blockchain = Self::from_stored_blocks(blocks, difficulty, hasher);
+        blockchain.verify_integrity().map_err(ImportError::Integrity)?;
+        if !blockchain.validate_chain() {
+            return Err(ImportError::InvalidChain);
+        }
+        blockchain.rebuild_undo_log();
+        blockchain.update_utxo_set();
+        Ok(blockchain)
+    }
+
+    /// 获取与区块链文件对应的修改记录文件名
+    fn undo_filename(filename: &str) -> String {
+        match filename.strip_suffix(".json") {
+            Some(stem) => format!("{}.undo.json", stem),
+            None => format!("{}.undo", filename),
+        }
+    }
+
+    /// 获取与区块链文件对应的UTXO快照文件名
+    fn snapshot_filename(filename: &str) -> String {
+        match filename.strip_suffix(".json") {
+            Some(stem) => format!("{}.utxo.json", stem),
+            None => format!("{}.utxo", filename),
+        }
+    }
+
+    /// 从文件加载区块链数据
+    ///
+    /// 加载时进行完整性检查，无效区块及其之后的区块会被丢弃，见[`Blockchain::load_verified`]
+    ///
+    /// # 参数
+    ///
+    /// * `filename` - 包含区块链数据的文件名
+    ///
+    /// # 返回值
+    ///
+    /// 如果文件存在并且格式正确，返回加载的区块链；否则返回None
+    pub fn load_from_file(filename: &str) -> Option<Self> {
+        let (blockchain, outcome) = Self::load_verified(filename)?;
+        if let LoadOutcome::Truncated { kept, discarded, .. } = outcome {
+            warn!(filename, discarded, kept, "区块链文件中有无效区块，已截断");
+        }
+        Some(blockchain)
+    }
+
+    /// 从文件加载区块链数据，并报告完整性检查的结果
+    ///
+    /// 文件可能被手动修改或部分写入，加载时调用`verify_integrity`，
+    /// 发现问题时截断到问题最早出现的区块之前，UTXO集合和修改记录根据保留的区块重新计算。
+    /// 完整性检查不验证签名，之后再重放验证：旁边的UTXO快照与本地链一致时直接使用快照，
+    /// 快照之前的区块在保存时已经验证过，只重放快照之后的区块；否则从创世区块重放。
+    /// 截断只发生在内存中，文件在下一次保存时才会被覆盖
+    ///
+    /// # 参数
+    ///
+    /// * `filename` - 包含区块链数据的文件名
+    ///
+    /// 文件记录的哈希算法必须是内置算法，使用其他算法的链用[`Blockchain::load_verified_with_hasher`]加载
+    ///
+    /// # 返回值
+    ///
+    /// 返回加载的区块链和检查结果；文件不存在、格式错误、哈希算法未知或创世区块本身无效时返回None
+    pub fn load_verified(filename: &str) -> Option<(Self, LoadOutcome)> {
+        Self::load_stored(filename, None)
+    }
+
+    /// 使用指定的哈希算法从文件加载区块链数据，检查规则见[`Blockchain::load_verified`]
+    ///
+    /// # 参数
+    ///
+    /// * `filename` - 包含区块链数据的文件名
+    /// * `hasher` - 区块链使用的哈希算法
+    ///
+    /// # 返回值
+    ///
+    /// 返回加载的区块链和检查结果；文件记录的哈希算法与`hasher`不同时返回None
+    pub fn load_verified_with_hasher(filename: &str, hasher: Arc<dyn Hasher>) -> Option<(Self, LoadOutcome)> {
+        Self::load_stored(filename, Some(hasher))
+    }
+
+    /// 加载并检查区块链文件，`expected`见[`StoredChain::hasher`]
+    fn load_stored(filename: &str, expected: Option<Arc<dyn Hasher>>) -> Option<(Self, LoadOutcome)> {
+        if !Path::new(filename).exists() {
+            return None;
+        }
+
+        let contents = fs::read_to_string(filename).ok()?;
+        let stored: StoredChain = serde_json::from_str::<StoredFormat>(&contents).ok()?.into();
+        let hasher = match stored.hasher(expected) {
+            Ok(hasher) => hasher,
+            Err(e) => {
+                error!(filename, error = %e, "拒绝加载区块链");
+                return None;
+            }
+        };
+        let blocks = stored.blocks.into_owned();
+        
+        let difficulty = blocks.first()?.header.difficulty;
+        let mut blockchain = Self::from_stored_blocks(blocks, difficulty, hasher);
+        blockchain.data_file = filename.to_string();
+        
+        let outcome = match blockchain.verify_integrity() {
+            Ok(()) => LoadOutcome::Verified,
+            Err(errors) => {
+                let kept = errors.iter().map(IntegrityError::height).min().unwrap_or(0);
+                if kept == 0 {
+                    error!(filename, "创世区块无效，拒绝加载");
+                    return None;
+                }
+                let discarded = blockchain.blocks.len() - kept;
+                blockchain.blocks.truncate(kept);
+                LoadOutcome::Truncated { kept, discarded, errors }
+            }
+        };
+        
+        // 修改记录文件缺失或与区块不匹配时，根据区块重新计算
+        let undo_log: Option<Vec<BlockUndo>> = fs::read_to_string(Self::undo_filename(filename))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok());
+        match undo_log {
+            Some(undo_log) if undo_log.len() == blockchain.blocks.len() => blockchain.undo_log = undo_log,
+            _ => blockchain.rebuild_undo_log(),
+        }
+        
+        // 完整性检查不验证签名，再重放验证；快照与本地链一致时只重放快照之后的区块
+        let invalid_height = match blockchain.read_utxo_snapshot(Path::new(&Self::snapshot_filename(filename))) {
+            Some(snapshot) => {
+                let invalid_height = if snapshot.height == blockchain.height() {
+                    None
+                } else {
+                    let mut replay = blockchain.fresh_copy();
+                    replay.blocks = blockchain.blocks[..=snapshot.height].to_vec();
+                    replay.utxo_set = snapshot.utxo_set.clone();
+                    blockchain.first_invalid_after(replay)
+                };
+                blockchain.apply_utxo_snapshot(snapshot);
+                invalid_height
+            }
+            None => {
+                blockchain.update_utxo_set();
+                blockchain.first_invalid_after(blockchain.fresh_copy())
+            }
+        };
+        
+        match invalid_height {
+            Some(height) => {
+                warn!(filename, height, "区块链未通过验证，数据可能已损坏");
+                Some((blockchain, LoadOutcome::Invalid { height }))
+            }
+            None => Some((blockchain, outcome)),
+        }
+    }
+
+    /// 从创世区块开始重放验证整条链
+    ///
+    /// 以相同的创世区块创建新的UTXO集合，按顺序对每个区块调用`validate_block`
+    /// （检查工作量证明、`prev_hash`链接、Coinbase和所有交易），验证通过后再应用该区块
+    ///
+    /// # 返回值
+    ///
+    /// 所有区块都有效时返回true；遇到第一个无效区块时输出该区块的高度和哈希并返回false
+    pub fn validate_chain(&self) -> bool {
+        self.first_invalid_after(self.fresh_copy()).is_none()
+    }
+
+    /// 以`replay`为起点，逐个验证本链中高度不低于`replay.len()`的区块，UTXO集合增量更新
+    ///
+    /// # 参数
+    ///
+    /// * `replay` - 包含本链前若干个区块及对应UTXO集合的区块链
+    ///
+    /// # 返回值
+    ///
+    /// 所有区块都有效时返回None，否则返回第一个无效区块的高度
+    fn first_invalid_after(&self, mut replay: Self) -> Option<usize> {
+        let start = replay.blocks.len();
+        // validate_block会输出具体的失败原因
+        let added = replay.extend_validated(self.blocks[start..].to_vec());
+        let height = start + added;
+        let block = self.blocks.get(height)?;
+        warn!(height, block_hash = %self.block_hash(block), "区块链验证失败");
+        Some(height)
+    }
+
+    /// 检查整条链的内部一致性
+    ///
+    /// 与`validate_chain`不同，该方法不验证签名，也不在第一个问题处停止，
+    /// 而是遍历所有区块收集全部问题：`prev_hash`链接、按各区块难度检查工作量证明、
+    /// 默克尔根，以及每笔非Coinbase交易的输入总额等于输出总额加手续费。
+    /// 创世区块没有经过挖矿，其默克尔根在构建时按SHA-256由Coinbase交易计算、
+    /// 不随链的哈希算法变化，因此只检查它的`prev_hash`；
+    /// 默克尔根为空的区块（挖矿时尚未写入默克尔根）跳过默克尔根检查
+    ///
+    /// # 返回值
+    ///
+    /// 没有问题时返回Ok，否则返回发现的所有问题
+    pub fn verify_integrity(&self) -> Result<(), Vec<IntegrityError>> {
+        let mut errors = Vec::new();
+        let mut unspent: HashMap<(String, u32), u64> = HashMap::new();
+        let mut prev_hash = String::from("0");
+        
+        for (height, block) in self.blocks.iter().enumerate() {
+            if block.header.prev_hash != prev_hash {
+                errors.push(IntegrityError::BrokenLink {
+                    height,
+                    expected: prev_hash.clone(),
+                    found: block.header.prev_hash.clone(),
+                });
+            }
+            prev_hash = self.block_hash(block);
+            
+            if height > 0 {
+                if !block.is_valid_with(self.hasher.as_ref()) {
+                    errors.push(IntegrityError::InsufficientWork {
+                        height,
+                        hash: prev_hash.clone(),
+                        difficulty: block.header.difficulty,
+                    });
+                }
+                let merkle_root = block.compute_merkle_root_with(self.hasher.as_ref());
+                if !block.header.merkle_root.is_empty() && block.header.merkle_root != merkle_root {
+                    errors.push(IntegrityError::MerkleRootMismatch {
+                        height,
+                        expected: merkle_root,
+                        found: block.header.merkle_root.clone(),
+                    });
+                }
+            }
+            
+            for tx in &block.transactions {
+                let tx_id = self.calculate_tx_hash(tx);
+                if !tx.is_coinbase() {
+                    let mut inputs = 0u64;
+                    let mut complete = true;
+                    for input in &tx.inputs {
+                        match unspent.remove(&(input.prev_tx.clone(), input.prev_index)) {
+                            Some(value) => inputs += value,
+                            None => {
+                                complete = false;
+                                errors.push(IntegrityError::MissingInput {
+                                    height,
+                                    tx_id: tx_id.clone(),
+                                    prev_tx: input.prev_tx.clone(),
+                                    prev_index: input.prev_index,
+                                });
+                            }
+                        }
+                    }
+                    // 缺少输入时无法判断是否平衡，只报告缺失的输入
+                    let outputs: u64 = tx.outputs.iter().map(|output| output.value).sum();
+                    if complete && inputs != outputs + tx.fee {
+                        errors.push(IntegrityError::Unbalanced { height, tx_id: tx_id.clone(), inputs, outputs, fee: tx.fee });
+                    }
+                }
+                for (index, output) in tx.outputs.iter().enumerate().filter(|(_, output)| !output.is_unspendable()) {
+                    unspent.insert((tx_id.clone(), index as u32), output.value);
+                }
+            }
+        }
+        
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// 获取区块链汇总统计
+    ///
+    /// 统计结果在一次遍历中计算并缓存，直到区块链发生变化
+    pub fn statistics(&self) -> ChainStatistics {
+        self.stats_cache.get_or_init(|| self.compute_statistics()).clone()
+    }
+
+    /// 计算每个高度的累计工作量
+    ///
+    /// 区块的工作量为`2^difficulty`，高度H的累计工作量是从创世区块到H的工作量之和，
+    /// 可用于比较分叉和观察难度变化。结果会被缓存，直到区块链发生变化
+    ///
+    /// # 返回值
+    ///
+    /// 返回按高度排列的(区块高度, 累计工作量)列表
+    pub fn compute_chain_work(&self) -> Vec<(usize, u128)> {
+        self.chain_work().to_vec()
+    }
+
+    /// 获取指定高度的累计工作量，高度超出链长时返回0
+    ///
+    /// # 参数
+    ///
+    /// * `height` - 区块高度，创世区块高度为0
+    pub fn work_at_height(&self, height: usize) -> u128 {
+        self.chain_work().get(height).map_or(0, |(_, work)| *work)
+    }
+
+    /// 缓存的累计工作量
+    fn chain_work(&self) -> &[(usize, u128)] {
+        self.chain_work_cache.get_or_init(|| {
+            let mut total = 0u128;
+            self.blocks.iter().enumerate()
+                .map(|(height, block)| {
+                    total = total.saturating_add(Self::block_work(block));
+                    (height, total)
+                })
+                .collect()
+        })
+    }
+
+    /// 单个区块的工作量，难度过高时饱和到`u128::MAX`
+    fn block_work(block: &Block) -> u128 {
+        u32::try_from(block.header.difficulty)
+            .ok()
+            .and_then(|difficulty| 2u128.checked_pow(difficulty))
+            .unwrap_or(u128::MAX)
+    }
+
+    /// 记录首次见到交易的时间（以当时的链长度表示）
+    ///
+    /// 交易进入交易池时调用，确认后据此计算确认延迟；重复调用保留最早的记录。
+    /// 首次记录时调用交易接受回调
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 见到的交易
+    pub fn record_seen(&mut self, tx: &Transaction) {
+        let height = self.blocks.len();
+        if let std::collections::hash_map::Entry::Vacant(entry) = self.first_seen.entry(self.calculate_tx_hash(tx)) {
+            entry.insert(height);
+            for callback in &self.transaction_callbacks {
+                callback(tx);
+            }
+        }
+    }
+
+    /// 估算交易在`target_blocks`个区块内被打包需要的手续费
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 要估算的交易，按`Transaction::size`计算大小
+    /// * `target_blocks` - 期望确认的区块数
+    ///
+    /// # 返回值
+    ///
+    /// 返回估算的手续费，等于`estimate_fee_rate`乘以交易大小
+    pub fn estimate_fee(&self, tx: &Transaction, target_blocks: u32) -> u64 {
+        self.estimate_fee_rate(target_blocks) * tx.size() as u64
+    }
+
+    /// 估算在`target_blocks`个区块内被打包需要的手续费率（每字节）
+    ///
+    /// 统计最近`FEE_ESTIMATE_WINDOW`个区块中非Coinbase交易的(费率, 确认延迟)，
+    /// 只保留延迟不超过`target_blocks`的交易，按费率升序取`1/target_blocks`分位数。
+    /// 没有首次见到记录的交易视为在下一个区块确认。
+    /// 结果缓存`FEE_ESTIMATE_CACHE_BLOCKS`个区块
+    ///
+    /// # 参数
+    ///
+    /// * `target_blocks` - 期望确认的区块数，0按1处理
+    ///
+    /// # 返回值
+    ///
+    /// 返回估算的费率，至少为`MIN_FEE_RATE`
+    pub fn estimate_fee_rate(&self, target_blocks: u32) -> u64 {
+        let target = target_blocks.max(1);
+        let height = self.blocks.len();
+        if let Some(&(computed_at, rate)) = self.fee_estimate_cache.borrow().get(&target) {
+            // 回滚或替换链后缓存同样失效
+            if computed_at <= height && height < computed_at + FEE_ESTIMATE_CACHE_BLOCKS {
+                return rate;
+            }
+        }
+        
+        let rate = self.compute_fee_rate(target);
+        self.fee_estimate_cache.borrow_mut().insert(target, (height, rate));
+        rate
+    }
+
+    /// 根据最近区块计算手续费率，不使用缓存
+    fn compute_fee_rate(&self, target: u32) -> u64 {
+        let start = self.blocks.len().saturating_sub(FEE_ESTIMATE_WINDOW);
+        let mut rates: Vec<u64> = self.blocks.iter().enumerate().skip(start)
+            .flat_map(|(height, block)| block.transactions.iter().map(move |tx| (height, tx)))
+            .filter(|(_, tx)| !tx.is_coinbase())
+            .filter(|(height, tx)| {
+                let delay = match self.first_seen.get(&self.calculate_tx_hash(tx)) {
+                    Some(&seen) => (height + 1).saturating_sub(seen).max(1),
+                    None => 1,
+                };
+                delay <= target as usize
+            })
+            .map(|(_, tx)| tx.fee_per_byte().ceil() as u64)
+            .collect();
+        
+        if rates.is_empty() {
+            return MIN_FEE_RATE;
+        }
+        rates.sort_unstable();
+        // 目标越远，分位数越低：1个区块取最高费率，2个区块取中位数
+        let index = rates.len().div_ceil(target as usize) - 1;
+        rates[index].max(MIN_FEE_RATE)
+    }
+
+    /// 遍历一次区块计算汇总统计
+    fn compute_statistics(&self) -> ChainStatistics {
+        let mut total_transactions = 0;
+        let mut total_coinbase_amount = 0;
+        let mut total_fees = 0;
+
+        for block in &self.blocks {
+            total_transactions += block.transactions.len();
+            total_fees += block.total_fees();
+            total_coinbase_amount += block.transactions.iter()
+                .filter(|tx| tx.is_coinbase())
+                .flat_map(|tx| tx.outputs.iter())
+                .map(|output| output.value)
+                .sum::<u64>();
+        }
+
+        // 创世区块使用固定时间戳，只统计其后的出块间隔
+        let average_block_time_secs = if self.blocks.len() > 2 {
+            let span = self.tip().header.timestamp - self.blocks[1].header.timestamp;
+            span as f64 / (self.blocks.len() - 2) as f64
+        } else {
+            0.0
+        };
+
+        ChainStatistics {
+            total_blocks: self.blocks.len(),
+            total_transactions,
+            total_coinbase_amount,
+            total_fees,
+            average_block_time_secs,
+            average_txs_per_block: total_transactions as f64 / self.blocks.len() as f64,
+            utxo_count: self.utxo_set.values().map(Vec::len).sum(),
+            circulating_supply: total_coinbase_amount.saturating_sub(total_fees),
+        }
+    }
+
+    /// 收集区块导出行
+    fn block_rows(&self) -> Vec<BlockRow> {
+        self.blocks.iter().enumerate()
+            .map(|(height, block)| BlockRow {
+                height,
+                hash: self.block_hash(block),
+                prev_hash: block.header.prev_hash.clone(),
+                timestamp: block.header.timestamp,
+                nonce: block.header.nonce,
+                difficulty: block.header.difficulty,
+                tx_count: block.transactions.len(),
+            })
+            .collect()
+    }
+
+    /// 收集交易导出行
+    fn transaction_rows(&self) -> Vec<TransactionRow> {
+        self.blocks.iter().enumerate()
+            .flat_map(|(height, block)| {
+                block.transactions.iter().map(move |tx| TransactionRow {
+                    txid: self.calculate_tx_hash(tx),
+                    block_height: height,
+                    input_count: tx.inputs.len(),
+                    output_count: tx.outputs.len(),
+                    total_output_value: tx.outputs.iter().map(|output| output.value).sum(),
+                    fee: tx.fee,
+                })
+            })
+            .collect()
+    }
+
+    /// 将链数据以CSV格式导出
+    ///
+    /// 第一行为表头，字符串字段按CSV规则转义
+    ///
+    /// # 参数
+    ///
+    /// * `writer` - 输出目标
+    /// * `what` - 导出区块还是交易
+    pub fn export_csv(&self, mut writer: impl Write, what: ExportKind) -> io::Result<()> {
+        match what {
+            ExportKind::Blocks => {
+                writeln!(writer, "height,hash,prev_hash,timestamp,nonce,difficulty,tx_count")?;
+                for row in self.block_rows() {
+                    writeln!(writer, "{},{},{},{},{},{},{}",
+                             row.height, csv_field(&row.hash), csv_field(&row.prev_hash),
+                             row.timestamp, row.nonce, row.difficulty, row.tx_count)?;
+                }
+            }
+            ExportKind::Transactions => {
+                writeln!(writer, "txid,block_height,input_count,output_count,total_output_value,fee")?;
+                for row in self.transaction_rows() {
+                    writeln!(writer, "{},{},{},{},{},{}",
+                             csv_field(&row.txid), row.block_height, row.input_count,
+                             row.output_count, row.total_output_value, row.fee)?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// 将链数据以JSON-lines格式导出，每行一个JSON对象
+    ///
+    /// # 参数
+    ///
+    /// * `writer` - 输出目标
+    /// * `what` - 导出区块还是交易
+    pub fn export_json_lines(&self, mut writer: impl Write, what: ExportKind) -> io::Result<()> {
+        match what {
+            ExportKind::Blocks => {
+                for row in self.block_rows() {
+                    serde_json::to_writer(&mut writer, &row)?;
+                    writeln!(writer)?;
+                }
+            }
+            ExportKind::Transactions => {
+                for row in self.transaction_rows() {
+                    serde_json::to_writer(&mut writer, &row)?;
+                    writeln!(writer)?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// 获取地址余额
+    ///
+    /// # 参数
+    ///
+    /// * `address` - 要查询余额的地址
+    ///
+    /// # 返回值
+    ///
+    /// 返回指定地址的余额，包括尚未成熟的Coinbase输出
+    pub fn get_balance(&self, address: &str) -> u64 {
+        self.utxo_set.values()
+            .flatten()
+            .filter(|(_, _, script_pubkey)| script_pubkey == address)
+            .map(|(_, value, _)| value)
+            .sum()
+    }
+
+    /// 获取包含所有者信息的完整UTXO列表
+    ///
+    /// 结果按交易ID和输出索引排序，保证遍历顺序稳定
+    pub fn utxo_entries(&self) -> Vec<(OutPoint, UtxoEntry)> {
+        let mut entries: Vec<(OutPoint, UtxoEntry)> = self.utxo_set.iter()
+            .flat_map(|(tx_id, outputs)| {
+                outputs.iter().map(move |(index, value, script_pubkey)| (
+                    OutPoint { tx_id: tx_id.clone(), index: *index },
+                    UtxoEntry { value: *value, script_pubkey: script_pubkey.clone() },
+                ))
+            })
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| (&a.tx_id, a.index).cmp(&(&b.tx_id, b.index)));
+        entries
+    }
+
+    /// 获取属于指定地址的UTXO
+    ///
+    /// # 参数
+    ///
+    /// * `address` - 输出所有者的地址
+    ///
+    /// # 返回值
+    ///
+    /// 返回该地址拥有的可花费输出，尚未成熟的Coinbase输出不包括在内
+    pub fn get_utxos_for_address(&self, address: &str) -> Vec<(OutPoint, UtxoEntry)> {
+        let immature = self.immature_coinbases();
+        self.utxo_entries().into_iter()
+            .filter(|(outpoint, entry)| entry.script_pubkey == address && !immature.contains(&outpoint.tx_id))
+            .collect()
+    }
+
+    /// 验证区块是否有效
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 要验证的区块
+    ///
+    /// # 返回值
+    ///
+    /// 如果区块有效返回true，否则返回false
+    pub fn validate_block(&self, block: &Block) -> bool {
+        match self.check_block(block) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(block_hash = %self.block_hash(block), error = %e, "区块验证失败");
+                self.metrics.record_block_rejected();
+                false
+            }
+        }
+    }
+
+    /// 检查区块能否被接到当前链顶之后，不修改区块链状态
+    ///
+    /// 执行与接收区块时相同的全部检查，矿工可以在广播前用它确认自己挖出的区块会被网络接受
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 要检查的区块
+    ///
+    /// # 返回值
+    ///
+    /// 区块会被接受时返回Ok，否则返回第一个不满足的规则
+    pub fn check_block(&self, block: &Block) -> Result<(), BlockError> {
+        // 1. 验证区块哈希满足难度要求
+        if !block.is_valid_with(self.hasher.as_ref()) {
+            return Err(BlockError::InvalidProofOfWork);
+        }
+
+        // 2. 验证前一个区块哈希是否匹配，创世区块的前一个哈希应该是0
+        let expected = self.blocks.last()
+            .map(|prev_block| self.block_hash(prev_block))
+            .unwrap_or_else(|| String::from("0"));
+        if block.header.prev_hash != expected {
+            return Err(BlockError::PrevHashMismatch { expected, found: block.header.prev_hash.clone() });
+        }
+
+        // 3. 验证时间戳晚于过去中位时间，且不超前本地时间太多
+        let median_past_time = self.median_past_time();
+        if block.header.timestamp <= median_past_time {
+            return Err(BlockError::TimestampTooOld { timestamp: block.header.timestamp, median_past_time });
+        }
+        let max = Utc::now().timestamp() + MAX_FUTURE_BLOCK_TIME;
+        if block.header.timestamp > max {
+            return Err(BlockError::TimestampTooFarInFuture { timestamp: block.header.timestamp, max });
+        }
+
+        // 4. 验证交易数量和区块大小不超过上限，避免过大的区块拖慢验证
+        if block.transactions.len() > self.max_transactions {
+            return Err(BlockError::TooManyTransactions {
+                count: block.transactions.len(),
+                max: self.max_transactions,
+            });
+        }
+        let size = block.size();
+        if size > self.max_block_size {
+            return Err(BlockError::BlockTooLarge { size, max: self.max_block_size });
+        }
+
+        // 5. 验证Coinbase交易的数量和位置
+        Self::validate_coinbase(block)?;
+
+        // 6. 验证区块内没有两笔交易花费同一个输出
+        if let Some((first, second)) = Transaction::find_double_spend(&block.transactions) {
+            return Err(BlockError::DoubleSpend { first, second });
+        }
+
+        // 7. 验证所有交易，Coinbase的位置已在第5步检查
+        let validator = self.transaction_validator(true);
+        let view = self.utxo_view();
+        for (index, tx) in block.transactions.iter().enumerate() {
+            validator.validate(tx, &view)
+                .map_err(|error| BlockError::InvalidTransaction { index, error })?;
+        }
+
+        // 8. 验证区块整体的金额守恒，单笔交易的检查不包括Coinbase领取的金额
+        self.check_conservation(block, &view)
+    }
+
+    /// 检查区块整体的金额守恒
+    ///
+    /// Coinbase最多领取该高度的区块奖励（见[`Blockchain::block_reward_at_height`]）加上其他交易声明的手续费，
+    /// 全部输出不能超过全部输入加区块奖励，
+    /// 交易输入多出的部分被销毁，因此允许输出少于该上限
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 要检查的区块，所有输入都应已通过验证
+    /// * `view` - UTXO视图，用于查找输入的金额
+    ///
+    /// # 返回值
+    ///
+    /// 守恒时返回Ok，否则返回`BlockError::ExcessiveCoinbase`或`BlockError::ValueNotConserved`
+    fn check_conservation(&self, block: &Block, view: impl Fn(&OutPoint) -> Option<UtxoEntry>) -> Result<(), BlockError> {
+        let mut inputs = 0u64;
+        let mut outputs = 0u64;
+        let mut fees = 0u64;
+        let mut claimed = 0u64;
+        for tx in &block.transactions {
+            let value = tx.outputs.iter().fold(0u64, |sum, output| sum.saturating_add(output.value));
+            outputs = outputs.saturating_add(value);
+            if tx.is_coinbase() {
+                claimed = claimed.saturating_add(value);
+                continue;
+            }
+            fees = fees.saturating_add(tx.fee);
+            for input in &tx.inputs {
+                let outpoint = OutPoint { tx_id: input.prev_tx.clone(), index: input.prev_index };
+                inputs = inputs.saturating_add(view(&outpoint).map_or(0, |entry| entry.value));
+            }
+        }
+
+        // 区块接在链顶之后，高度等于当前区块数
+        let reward = self.block_reward_at_height(self.blocks.len());
+        let allowed = reward.saturating_add(fees);
+        if claimed > allowed {
+            return Err(BlockError::ExcessiveCoinbase { claimed, allowed });
+        }
+        if outputs > inputs.saturating_add(reward) {
+            return Err(BlockError::ValueNotConserved { inputs, outputs, reward });
+        }
+        Ok(())
+    }
+
+    /// 验证区块的Coinbase交易
+    ///
+    /// 区块必须恰好包含一笔Coinbase交易（输入引用全零交易ID），并且位于第一个位置
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 要验证的区块
+    ///
+    /// # 返回值
+    ///
+    /// 规则满足时返回Ok，否则返回对应的`BlockError`
+    pub fn validate_coinbase(block: &Block) -> Result<(), BlockError> {
+        let coinbase_indices: Vec<usize> = block.transactions.iter().enumerate()
+            .filter(|(_, tx)| tx.is_coinbase())
+            .map(|(index, _)| index)
+            .collect();
+
+        match coinbase_indices.as_slice() {
+            [] => Err(BlockError::MissingCoinbase),
+            [0] => Ok(()),
+            [0, extra, ..] => Err(BlockError::MultipleCoinbase { index: *extra }),
+            [first, ..] => Err(BlockError::MisplacedCoinbase { index: *first }),
+        }
+    }
+
+    /// 找出交易中引用的输出已被花费或不存在的输入
+    ///
+    /// # 参数
+    ///
+    /// * `transaction` - 要检查的交易
+    ///
+    /// # 返回值
+    ///
+    /// 不在UTXO集合中的输出引用，Coinbase输入会被跳过
+    pub fn missing_inputs(&self, transaction: &Transaction) -> Vec<OutPoint> {
+        transaction.inputs.iter()
+            .filter(|input| input.prev_tx != crate::block::COINBASE_TX_ID)
+            .filter(|input| {
+                !self.utxo_set.get(&input.prev_tx)
+                    .is_some_and(|outputs| outputs.iter().any(|(idx, ..)| *idx == input.prev_index))
+            })
+            .map(|input| OutPoint { tx_id: input.prev_tx.clone(), index: input.prev_index })
+            .collect()
+    }
+
+    /// 验证交易是否有效
+    ///
+    /// # 参数
+    ///
+    /// * `transaction` - 要验证的交易
+    ///
+    /// # 返回值
+    ///
+    /// 如果交易有效返回true，否则返回false
+    pub fn validate_transaction(&self, transaction: &Transaction) -> bool {
+        let result = self.check_transaction(transaction);
+        self.metrics.record_transaction(result.is_ok());
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(tx_hash = %self.calculate_tx_hash(transaction), error = %e, "交易验证失败");
+                false
+            }
+        }
+    }
+
+    /// 以当前UTXO集为视图验证一笔普通交易
+    ///
+    /// 使用[`TxValidator`]执行输入存在性、Coinbase成熟度、签名和金额守恒检查，
+    /// Coinbase交易只能出现在区块中，这里会被拒绝
     ///
     /// # 参数
     ///
-    /// * `difficulty` - 挖矿难度，影响新区块的哈希要求
+    /// * `transaction` - 要验证的交易
     ///
     /// # 返回值
     ///
-    /// 返回初始化的区块链实例，包含创世区块
-    pub fn new(difficulty: u64) -> Self {
-        let mut blockchain = Blockchain {
-            blocks: Vec::new(),
-            utxo_set: HashMap::new(),
-            difficulty,
-        };
-        
-        // 创建固定的创世区块，确保所有节点一致
-        blockchain.create_genesis_block();
-        blockchain.update_utxo_set();
-        blockchain
+    /// 交易有效时返回Ok，否则返回具体的`TxError`
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<(), TxError> {
+        self.transaction_validator(false).validate(transaction, self.utxo_view())
     }
-    
-    /// 创建固定的创世区块
-    fn create_genesis_block(&mut self) {
-        // 使用固定的时间戳和数据，确保所有节点的创世区块相同
-        let genesis_header = crate::block::BlockHeader {
-            prev_hash: String::from("0"),
-            timestamp: 1748793600, // 固定时间戳：2025-06-01 00:00:00
-            merkle_root: String::from("genesis_merkle_root"), // 固定的默克尔根
-            nonce: 0,
-            difficulty: self.difficulty,
-        };
-        
-        // 创世区块包含一个固定的coinbase交易
-        let genesis_coinbase = crate::block::Transaction::new(
-            vec![crate::block::TxInput {
-                prev_tx: String::from("0000000000000000000000000000000000000000000000000000000000000000"),
-                prev_index: 0,
-                script_sig: String::from("Genesis Block - Blockchain Demo"),
-            }],
-            vec![crate::block::TxOutput {
-                value: 100, // 创世区块奖励
-                script_pubkey: String::from("genesis_address"), // 固定的创世地址
-            }]
-        );
-        
-        let genesis_block = crate::block::Block {
-            header: genesis_header,
-            transactions: vec![genesis_coinbase],
-        };
-        
-        self.blocks.push(genesis_block);
+
+    /// 模拟提交一笔交易，不修改区块链状态
+    ///
+    /// 在UTXO集的副本上依次花费交易的输入，收集全部问题而不是在第一个问题处停止，
+    /// 用于在广播前确认交易会被接受。模拟不计入交易验证的统计
+    ///
+    /// # 参数
+    ///
+    /// * `transaction` - 要模拟的交易
+    ///
+    /// # 返回值
+    ///
+    /// 返回交易是否会被接受、消耗的输入总额、手续费和发现的全部问题
+    pub fn simulate_transaction(&self, transaction: &Transaction) -> SimulationResult {
+        let mut errors = Vec::new();
+        if transaction.inputs.is_empty() || transaction.outputs.is_empty() {
+            errors.push(SimulationError::EmptyTransaction);
+        }
+
+        let mut utxo_set = self.utxo_set.clone();
+        let immature = self.immature_coinbases();
+        let mut consumed = 0u64;
+        for (index, input) in transaction.inputs.iter().enumerate() {
+            let spent = utxo_set.get_mut(&input.prev_tx).and_then(|outputs| {
+                let position = outputs.iter().position(|(idx, ..)| *idx == input.prev_index)?;
+                Some(outputs.remove(position))
+            });
+            let Some((_, value, script_pubkey)) = spent else {
+                // 原UTXO集中存在的输出只可能被本交易之前的输入花费了
+                let spent_earlier = self.utxo_set.get(&input.prev_tx)
+                    .is_some_and(|outputs| outputs.iter().any(|(idx, ..)| *idx == input.prev_index));
+                let (prev_tx, prev_index) = (input.prev_tx.clone(), input.prev_index);
+                errors.push(if spent_earlier {
+                    SimulationError::DoubleSpend { index, prev_tx, prev_index }
+                } else {
+                    SimulationError::UtxoNotFound { index, prev_tx, prev_index }
+                });
+                continue;
+            };
+            consumed = consumed.saturating_add(value);
+
+            if immature.contains(&input.prev_tx) {
+                errors.push(SimulationError::LockTimeNotReached { index, prev_tx: input.prev_tx.clone() });
+            }
+            if !Wallet::verify_input(transaction, index, &UtxoEntry { value, script_pubkey }, self.network_id()) {
+                errors.push(SimulationError::InvalidSignature { index });
+            }
+        }
+
+        let outputs = transaction.outputs.iter().fold(0u64, |sum, output| sum.saturating_add(output.value));
+        if outputs.saturating_add(transaction.fee) > consumed {
+            errors.push(SimulationError::InsufficientFunds { inputs: consumed, outputs, fee: transaction.fee });
+        }
+
+        SimulationResult {
+            would_succeed: errors.is_empty(),
+            consumed,
+            fee: transaction.fee,
+            errors,
+        }
     }
 
-    /// 向区块链添加新区块
+    /// 创建使用当前成熟度规则的交易验证器
+    fn transaction_validator(&self, allow_coinbase: bool) -> TxValidator {
+        TxValidator {
+            allow_coinbase,
+            immature_coinbases: self.immature_coinbases(),
+            network_id: self.network_id().to_string(),
+        }
+    }
+
+    /// 以当前UTXO集构建UTXO视图
+    fn utxo_view(&self) -> impl Fn(&OutPoint) -> Option<UtxoEntry> + '_ {
+        move |outpoint: &OutPoint| {
+            let outputs = self.utxo_set.get(&outpoint.tx_id)?;
+            let (_, value, script_pubkey) = outputs.iter().find(|(idx, ..)| *idx == outpoint.index)?;
+            Some(UtxoEntry { value: *value, script_pubkey: script_pubkey.clone() })
+        }
+    }
+
+    /// 添加接收到的区块到区块链
     ///
     /// # 参数
     ///
-    /// * `transactions` - 要包含在新区块中的交易列表
-    pub fn add_block(&mut self, transactions: Vec<Transaction>) {
-        let prev_block = self.blocks.last().unwrap();
-        let prev_hash = prev_block.calculate_hash();
-        
-        let mut new_block = Block::new(prev_hash, self.difficulty);
-        new_block.transactions = transactions;
-        new_block.mine();
-        
-        self.blocks.push(new_block);
-        self.update_utxo_set();
-        self.save_to_file("blockchain.json");
+    /// * `block` - 要添加的区块
+    pub fn add_received_block(&mut self, block: Block) {
+        self.push_block(block);
+        self.persist();
+        self.notify_blocks_from(self.height());
     }
 
-    /// 更新UTXO集合
+    /// 批量添加接收到的区块，用于初次同步
     ///
-    /// 遍历区块链中的所有交易，重新构建UTXO集合
-    fn update_utxo_set(&mut self) {
-        self.utxo_set.clear();
-        
-        // 首先添加所有交易的输出
-        for block in &self.blocks {
-            for tx in &block.transactions {
-                let tx_id = self.calculate_tx_hash(tx);
-                
-                // 添加所有输出到UTXO集
-                for (index, output) in tx.outputs.iter().enumerate() {
-                    let outputs = self.utxo_set.entry(tx_id.clone())
-                        .or_insert_with(Vec::new);
-                    outputs.push((index as u32, output.value));
-                }
+    /// 逐个验证区块并增量更新UTXO集和修改记录，
+    /// 区块链文件只在最后保存一次，避免逐个添加时每个区块都写入文件。
+    /// 遇到无效区块时停止，之前添加的有效区块会保留
+    ///
+    /// # 参数
+    ///
+    /// * `blocks` - 按高度排列、接在当前链顶之后的区块
+    ///
+    /// # 返回值
+    ///
+    /// 返回成功添加的区块数；第一个区块就无效时返回`BlockchainError::InvalidBlock`
+    pub fn add_block_batch(&mut self, blocks: Vec<Block>) -> Result<usize, BlockchainError> {
+        let start = self.blocks.len();
+        let added = self.extend_validated(blocks);
+        if added == 0 {
+            return Err(BlockchainError::InvalidBlock { height: start });
+        }
+        self.persist();
+        self.notify_blocks_from(start);
+        Ok(added)
+    }
+
+    /// 逐个验证并追加区块，遇到第一个无效区块时停止
+    ///
+    /// 与`add_block_batch`不同，不写入文件也不触发区块接入回调，
+    /// 用于在`fresh_copy`创建的临时链上验证同步收到的区块链
+    ///
+    /// # 参数
+    ///
+    /// * `blocks` - 按高度排列、接在当前链顶之后的区块
+    ///
+    /// # 返回值
+    ///
+    /// 返回追加的区块数
+    pub fn extend_validated(&mut self, blocks: Vec<Block>) -> usize {
+        let start = self.blocks.len();
+        for block in blocks {
+            if !self.validate_block(&block) {
+                break;
             }
+            self.push_block(block);
         }
-        
-        // 然后移除所有被花费的输出
-        for block in &self.blocks {
-            for tx in &block.transactions {
-                // 处理输入，移除已花费的UTXO
-                for input in &tx.inputs {
-                    // 跳过coinbase交易的输入
-                    if input.prev_tx == "0000000000000000000000000000000000000000000000000000000000000000" {
-                        continue;
-                    }
-                    
-                    // 从UTXO集中移除已花费的输出
-                    if let Some(outputs) = self.utxo_set.get_mut(&input.prev_tx) {
-                        outputs.retain(|&(idx, _)| idx != input.prev_index);
-                        // 如果这个交易的所有输出都被花费了，移除整个条目
-                        if outputs.is_empty() {
-                            self.utxo_set.remove(&input.prev_tx);
-                        }
+        self.blocks.len() - start
+    }
+
+    /// 将一个区块的交易增量应用到UTXO集：移除被花费的输出，加入新创建的输出
+    ///
+    /// # 返回值
+    ///
+    /// 返回区块对UTXO集合的修改记录，被花费的输出取自应用前的UTXO集合
+    fn apply_block_to_utxo_set(&mut self, block: &Block) -> BlockUndo {
+        let mut undo = BlockUndo::default();
+        for tx in &block.transactions {
+            for input in tx.inputs.iter().filter(|input| input.prev_tx != crate::block::COINBASE_TX_ID) {
+                if let Some(outputs) = self.utxo_set.get_mut(&input.prev_tx) {
+                    let (spent, unspent): (Vec<_>, Vec<_>) = std::mem::take(outputs).into_iter()
+                        .partition(|(idx, ..)| *idx == input.prev_index);
+                    *outputs = unspent;
+                    if outputs.is_empty() {
+                        self.utxo_set.remove(&input.prev_tx);
                     }
+                    undo.spent_outputs.extend(spent.into_iter()
+                        .map(|(index, value, script_pubkey)| (input.prev_tx.clone(), index, value, script_pubkey)));
                 }
             }
+            
+            let tx_id = self.calculate_tx_hash(tx);
+            let outputs: Vec<_> = tx.outputs.iter().enumerate()
+                .filter(|(_, output)| !output.is_unspendable())
+                .map(|(index, output)| (index as u32, output.value, output.script_pubkey.clone()))
+                .collect();
+            undo.created_outputs.extend(outputs.iter()
+                .map(|(index, value, script_pubkey)| (tx_id.clone(), *index, *value, script_pubkey.clone())));
+            if !outputs.is_empty() {
+                self.utxo_set.entry(tx_id).or_default().extend(outputs);
+            }
         }
-        
-        // 清理空的条目
-        self.utxo_set.retain(|_, outputs| !outputs.is_empty());
+        undo
     }
 
-    /// 计算交易哈希值
+    /// 获取指定高度之后的区块，用于增量同步
     ///
     /// # 参数
     ///
-    /// * `tx` - 要计算哈希的交易
+    /// * `height` - 请求方已有的链顶高度
     ///
     /// # 返回值
     ///
-    /// 返回计算得到的交易哈希值（16进制字符串）
-    pub fn calculate_tx_hash(&self, tx: &Transaction) -> String {
-        let mut hasher = Sha256::new();
-        let serialized = serde_json::to_string(tx).unwrap();
-        hasher.update(serialized.as_bytes());
-        hex::encode(hasher.finalize())
+    /// 返回高度大于`height`的区块；`height`不低于本地链顶时返回空切片
+    pub fn get_blocks_since(&self, height: usize) -> &[Block] {
+        self.blocks.get(height + 1..).unwrap_or(&[])
     }
 
-    /// 将区块链数据保存到文件
+    /// 获取从指定高度开始的一页区块，用于分页同步
     ///
     /// # 参数
     ///
-    /// * `filename` - 保存区块链数据的文件名
-    pub fn save_to_file(&self, filename: &str) {
-        let serialized = serde_json::to_string_pretty(&self.blocks).unwrap();
-        fs::write(filename, serialized).expect("Unable to write blockchain to file");
+    /// * `from_height` - 第一个区块的高度
+    /// * `limit` - 最多返回的区块数
+    ///
+    /// # 返回值
+    ///
+    /// 返回高度从`from_height`开始的最多`limit`个区块；`from_height`超过链顶时返回空切片
+    pub fn blocks_from(&self, from_height: usize, limit: usize) -> &[Block] {
+        let blocks = self.blocks.get(from_height..).unwrap_or(&[]);
+        &blocks[..blocks.len().min(limit)]
     }
 
-    /// 从文件加载区块链数据
+    /// 构建区块定位器：从链顶开始按指数增长的间隔取区块哈希，最后一个总是创世区块
+    ///
+    /// 依次为链顶、链顶-1、链顶-2、链顶-4、链顶-8……直到创世区块，长度约为`log2(高度)`。
+    /// 对方据此找到双方最后一个共同区块，只需发送之后的区块
+    ///
+    /// # 返回值
+    ///
+    /// 返回按高度从高到低排列的区块哈希
+    pub fn block_locator(&self) -> Vec<String> {
+        let tip = self.height();
+        let mut locator = vec![self.block_hash(self.tip())];
+        let mut step = 1;
+        while step <= tip {
+            locator.push(self.block_hash(&self.blocks[tip - step]));
+            step *= 2;
+        }
+        if tip > 0 && locator.last() != Some(&self.block_hash(self.genesis())) {
+            locator.push(self.block_hash(self.genesis()));
+        }
+        locator
+    }
+
+    /// 在本地链中查找区块定位器中第一个存在的哈希，即双方最后一个共同区块
     ///
     /// # 参数
     ///
-    /// * `filename` - 包含区块链数据的文件名
+    /// * `locator` - 对方的区块定位器
     ///
     /// # 返回值
     ///
-    /// 如果文件存在并且格式正确，返回加载的区块链；否则返回None
-    pub fn load_from_file(filename: &str) -> Option<Self> {
-        if !Path::new(filename).exists() {
-            return None;
-        }
+    /// 返回共同区块的高度；连创世区块都不同时返回None
+    pub fn locate_fork(&self, locator: &[String]) -> Option<usize> {
+        let heights: HashMap<String, usize> = self.blocks.iter().enumerate()
+            .map(|(height, block)| (self.block_hash(block), height))
+            .collect();
+        locator.iter().find_map(|hash| heights.get(hash).copied())
+    }
 
-        let contents = fs::read_to_string(filename).ok()?;
-        let blocks: Vec<Block> = serde_json::from_str(&contents).ok()?;
-        
-        let difficulty = blocks[0].header.difficulty;
-        let mut blockchain = Blockchain {
-            blocks,
-            utxo_set: HashMap::new(),
-            difficulty,
-        };
-        
-        blockchain.update_utxo_set();
-        Some(blockchain)
+    /// 响应区块定位器：返回最后一个共同区块之后的本地区块
+    ///
+    /// # 参数
+    ///
+    /// * `locator` - 对方的区块定位器
+    ///
+    /// # 返回值
+    ///
+    /// 返回第一个区块的高度和之后的区块；没有共同区块时返回None，对方需要完整同步
+    pub fn blocks_after_locator(&self, locator: &[String]) -> Option<(usize, &[Block])> {
+        let fork = self.locate_fork(locator)?;
+        Some((fork + 1, self.get_blocks_since(fork)))
     }
 
-    /// 获取地址余额
+    /// 响应按定位器的分页请求：返回最后一个共同区块之后的一页本地区块
     ///
     /// # 参数
     ///
-    /// * `address` - 要查询余额的地址
+    /// * `locator` - 对方的区块定位器
+    /// * `limit` - 最多返回的区块数
     ///
     /// # 返回值
     ///
-    /// 返回指定地址的余额
-    pub fn get_balance(&self, address: &str) -> u64 {
-        let mut balance = 0;
-        
-        // 直接从UTXO集中计算余额，无需遍历所有区块
-        for (tx_id, outputs) in &self.utxo_set {
-            // 找到对应的交易来获取输出详情
-            let mut tx_found = None;
-            'outer: for block in &self.blocks {
-                for tx in &block.transactions {
-                    if self.calculate_tx_hash(tx) == *tx_id {
-                        tx_found = Some(tx);
-                        break 'outer;
-                    }
-                }
-            }
-            
-            if let Some(tx) = tx_found {
-                // 检查UTXO集中的每个输出
-                for &(output_idx, _amount) in outputs {
-                    if let Some(output) = tx.outputs.get(output_idx as usize) {
-                        if output.script_pubkey == address {
-                            balance += output.value;
-                        }
-                    }
-                }
-            }
-        }
-        
-        balance
+    /// 返回第一个区块的高度、最多`limit`个区块，以及之后是否还有区块；没有共同区块时返回None
+    pub fn page_after_locator(&self, locator: &[String], limit: usize) -> Option<(usize, &[Block], bool)> {
+        let (start_height, blocks) = self.blocks_after_locator(locator)?;
+        let page = &blocks[..blocks.len().min(limit)];
+        Some((start_height, page, page.len() < blocks.len()))
     }
 
-    /// 验证区块是否有效
+    /// 接入定位器同步收到的区块，必要时切换到累计工作量更大的分叉
+    ///
+    /// 区块与本地链重叠的部分都相同时等同于[`Blockchain::append_blocks_since`]；
+    /// 从某个高度开始不同时，用本地的共同前缀加上收到的区块构建候选链并逐个验证，
+    /// 候选链累计工作量更大时替换本地链
     ///
     /// # 参数
     ///
-    /// * `block` - 要验证的区块
+    /// * `start_height` - 第一个区块的高度，即共同区块高度加1
+    /// * `blocks` - 按高度排列的区块
     ///
     /// # 返回值
     ///
-    /// 如果区块有效返回true，否则返回false
-    pub fn validate_block(&self, block: &Block) -> bool {
-        // 1. 验证区块哈希满足难度要求
-        if !block.is_valid() {
-            println!("区块哈希不满足难度要求");
-            return false;
+    /// 返回新接入的区块数；不衔接、区块无效或分叉工作量不足时返回对应的错误
+    pub fn connect_blocks_since(&mut self, start_height: usize, blocks: Vec<Block>) -> Result<usize, BlockchainError> {
+        if start_height == 0 || start_height > self.blocks.len() {
+            return Err(BlockchainError::NonContiguousBlocks { start_height, len: self.blocks.len() });
         }
-
-        // 2. 验证前一个区块哈希是否匹配
-        if let Some(prev_block) = self.blocks.last() {
-            let prev_hash = prev_block.calculate_hash();
-            if block.header.prev_hash != prev_hash {
-                println!("区块前一个哈希不匹配");
-                return false;
-            }
-        } else if block.header.prev_hash != "0" {
-            // 如果是创世区块，前一个哈希应该是0
-            println!("创世区块前一个哈希应该是0");
-            return false;
+        let diverged = (start_height..).zip(&blocks)
+            .find(|(height, block)| self.blocks.get(*height).is_some_and(|local| self.block_hash(local) != self.block_hash(block)))
+            .map(|(height, _)| height);
+        let Some(fork_height) = diverged else {
+            return self.append_blocks_since(start_height, blocks);
+        };
+        
+        let mut candidate = self.fresh_copy();
+        let mut candidate_blocks = self.blocks[1..start_height].to_vec();
+        let expected = candidate_blocks.len() + blocks.len();
+        candidate_blocks.extend(blocks);
+        let added = candidate.extend_validated(candidate_blocks);
+        if added < expected {
+            return Err(BlockchainError::InvalidBlock { height: added + 1 });
         }
-
-        // 3. 验证所有交易
-        for tx in &block.transactions {
-            if !self.validate_transaction(tx) {
-                return false;
-            }
+        if candidate.work_at_height(candidate.height()) <= self.work_at_height(self.height()) {
+            return Err(BlockchainError::InsufficientForkWork { height: fork_height });
         }
-
-        true
+        
+        let connected = candidate.len() - fork_height;
+        info!(fork_height, connected, "切换到累计工作量更大的分叉");
+        self.replace_chain(candidate.blocks);
+        Ok(connected)
     }
 
-    /// 验证交易是否有效
+    /// 将增量同步收到的区块接到本地链之后
+    ///
+    /// `blocks`的第一个区块高度为`start_height`。多个节点可能响应同一个请求，
+    /// 与本地链重叠的区块必须与本地区块相同，会被跳过；其余区块逐个验证后追加，
+    /// 遇到无效区块时停止，之前追加的有效区块会保留
     ///
     /// # 参数
     ///
-    /// * `transaction` - 要验证的交易
+    /// * `start_height` - 第一个区块的高度
+    /// * `blocks` - 按高度排列的区块
     ///
     /// # 返回值
     ///
-    /// 如果交易有效返回true，否则返回false
-    pub fn validate_transaction(&self, transaction: &Transaction) -> bool {
-        // 1. 验证交易输入引用的UTXO是否存在
-        for input in &transaction.inputs {
-            // 对于Coinbase交易跳过验证
-            if input.prev_tx == "0000000000000000000000000000000000000000000000000000000000000000" {
+    /// 返回新追加的区块数；不衔接、与本地链分叉或区块无效时返回对应的错误
+    pub fn append_blocks_since(&mut self, start_height: usize, blocks: Vec<Block>) -> Result<usize, BlockchainError> {
+        if start_height == 0 || start_height > self.blocks.len() {
+            return Err(BlockchainError::NonContiguousBlocks { start_height, len: self.blocks.len() });
+        }
+        
+        let first_new = self.blocks.len();
+        let mut appended = 0;
+        for (height, block) in (start_height..).zip(blocks) {
+            if let Some(local) = self.blocks.get(height) {
+                if self.block_hash(local) != self.block_hash(&block) {
+                    return Err(BlockchainError::ForkDetected { height });
+                }
                 continue;
             }
-
-            // 检查UTXO是否存在
-            if let Some(outputs) = self.utxo_set.get(&input.prev_tx) {
-                let mut found = false;
-                for &(idx, _) in outputs {
-                    if idx == input.prev_index {
-                        found = true;
-                        break;
-                    }
-                }
-                if !found {
-                    println!("输入引用的UTXO不存在");
-                    return false;
+            if !self.validate_block(&block) {
+                if appended > 0 {
+                    self.persist();
+                    self.notify_blocks_from(first_new);
                 }
-            } else {
-                println!("输入引用的交易不在UTXO集中");
-                return false;
+                return Err(BlockchainError::InvalidBlock { height });
             }
+            self.push_block(block);
+            appended += 1;
         }
-
-        // 2. 验证交易签名 (实际实现中应该验证)
-        // 简化版暂不验证签名
-
-        // 3. 验证输入总额大于等于输出总额
-        // 这需要访问之前的交易，简化版暂不验证
-
-        true
-    }
-
-    /// 添加接收到的区块到区块链
-    ///
-    /// # 参数
-    ///
-    /// * `block` - 要添加的区块
-    pub fn add_received_block(&mut self, block: Block) {
-        self.blocks.push(block);
-        self.update_utxo_set();
-        self.save_to_file("blockchain.json");
+        
+        if appended > 0 {
+            self.persist();
+            self.notify_blocks_from(first_new);
+        }
+        Ok(appended)
     }
 
     /// 替换本地链
     ///
+    /// UTXO集合和修改记录根据新的区块重新计算后再保存，
+    /// 与原链不同的区块按高度顺序触发区块接入回调
+    ///
     /// # 参数
     ///
     /// * `blocks` - 新的区块列表
     pub fn replace_chain(&mut self, blocks: Vec<Block>) {
+        let fork_height = self.blocks.iter().zip(&blocks)
+            .take_while(|(local, new)| self.block_hash(local) == self.block_hash(new))
+            .count();
         self.blocks = blocks;
-        self.save_to_file("blockchain.json");
+        self.rebuild_undo_log();
+        self.update_utxo_set();
+        self.persist();
+        self.notify_blocks_from(fork_height);
     }
 
     /// 重建UTXO集
@@ -345,46 +2692,102 @@ impl Blockchain {
         self.update_utxo_set();
     }
     
+    /// 从区块数据重新扫描，重建UTXO集合及其派生的修改记录和缓存
+    ///
+    /// 用于UTXO集合与区块数据不一致（例如手动编辑过文件）时恢复，
+    /// 重建后会按修改记录重放一遍，检查两者是否互相一致
+    ///
+    /// # 返回值
+    ///
+    /// 返回与重建前相比的修正报告
+    pub fn rescan(&mut self) -> RescanReport {
+        let before: HashSet<(OutPoint, UtxoEntry)> = self.utxo_entries().into_iter().collect();
+        
+        self.update_utxo_set();
+        self.rebuild_undo_log();
+        self.fee_estimate_cache.borrow_mut().clear();
+        let after: HashSet<(OutPoint, UtxoEntry)> = self.utxo_entries().into_iter().collect();
+        
+        let sorted = |entries: Vec<&(OutPoint, UtxoEntry)>| {
+            let mut entries: Vec<(OutPoint, UtxoEntry)> = entries.into_iter().cloned().collect();
+            entries.sort_by(|(a, _), (b, _)| (&a.tx_id, a.index).cmp(&(&b.tx_id, b.index)));
+            entries
+        };
+        let added = sorted(after.difference(&before).collect());
+        let removed = sorted(before.difference(&after).collect());
+        
+        let mut balances: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+        for (_, entry) in &removed {
+            balances.entry(entry.script_pubkey.clone()).or_default().0 += entry.value;
+        }
+        for (_, entry) in &added {
+            balances.entry(entry.script_pubkey.clone()).or_default().1 += entry.value;
+        }
+        let balance_changes = balances.into_iter()
+            .filter(|(_, (lost, gained))| lost != gained)
+            .map(|(address, _)| {
+                let corrected = self.get_balance(&address);
+                let previous = before.iter()
+                    .filter(|(_, entry)| entry.script_pubkey == address)
+                    .map(|(_, entry)| entry.value)
+                    .sum();
+                (address, previous, corrected)
+            })
+            .collect();
+        
+        // 按修改记录重放，结果应与直接扫描区块得到的UTXO集合相同
+        let mut replayed: HashSet<(OutPoint, UtxoEntry)> = HashSet::new();
+        for undo in &self.undo_log {
+            for (tx_id, index, value, script_pubkey) in &undo.created_outputs {
+                replayed.insert((
+                    OutPoint { tx_id: tx_id.clone(), index: *index },
+                    UtxoEntry { value: *value, script_pubkey: script_pubkey.clone() },
+                ));
+            }
+            for (tx_id, index, value, script_pubkey) in &undo.spent_outputs {
+                replayed.remove(&(
+                    OutPoint { tx_id: tx_id.clone(), index: *index },
+                    UtxoEntry { value: *value, script_pubkey: script_pubkey.clone() },
+                ));
+            }
+        }
+        let consistent = replayed == after;
+        
+        info!(added = added.len(), removed = removed.len(), consistent, "重新扫描UTXO集合");
+        RescanReport { added, removed, balance_changes, consistent }
+    }
+
     /// 调试UTXO集，显示详细信息
     pub fn debug_utxo_set(&self, address: &str) {
-        println!("\n=== UTXO集调试信息 ===");
-        println!("查询地址: {}", address);
-        println!("UTXO集总条目数: {}", self.utxo_set.len());
+        info!(address, entries = self.utxo_set.len(), "UTXO集调试信息");
         
         let mut total_balance = 0;
         for (tx_id, outputs) in &self.utxo_set {
-            println!("交易ID: {}", tx_id);
-            
-            // 找到对应的交易
-            let mut tx_found = None;
-            'outer: for block in &self.blocks {
-                for tx in &block.transactions {
-                    if self.calculate_tx_hash(tx) == *tx_id {
-                        tx_found = Some(tx);
-                        break 'outer;
-                    }
-                }
-            }
-            
-            if let Some(tx) = tx_found {
-                for &(output_idx, amount) in outputs {
-                    if let Some(output) = tx.outputs.get(output_idx as usize) {
-                        println!("  输出[{}]: {} -> {} (金额: {})", 
-                                output_idx, output.script_pubkey, 
-                                if output.script_pubkey == address { "✅匹配" } else { "❌不匹配" },
-                                output.value);
-                        
-                        if output.script_pubkey == address {
-                            total_balance += output.value;
-                        }
-                    }
+            for (output_idx, value, script_pubkey) in outputs {
+                let matched = script_pubkey == address;
+                info!(
+                    tx_id = %tx_id,
+                    index = output_idx,
+                    owner = %script_pubkey,
+                    value,
+                    matched,
+                    "UTXO"
+                );
+                if matched {
+                    total_balance += value;
                 }
-            } else {
-                println!("  ⚠️ 找不到对应的交易！");
             }
         }
         
-        println!("计算出的余额: {}", total_balance);
-        println!("===================\n");
+        info!(address, balance = total_balance, "计算出的余额");
+    }
+}
+
+impl<'a> IntoIterator for &'a Blockchain {
+    type Item = &'a Block;
+    type IntoIter = std::slice::Iter<'a, Block>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.blocks.iter()
     }
 }
@@ -0,0 +1,97 @@
+//! # 节点配置模块
+//!
+//! 从TOML配置文件读取区块链共识参数（`[blockchain]`）和网络参数（`[network]`），
+//! 文件中没有给出的字段使用默认值，因此空文件也是有效的配置。
+
+use libp2p::Multiaddr;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use crate::blockchain::{BlockchainConfig, MAX_DIFFICULTY};
+use crate::network::NetworkConfig;
+
+/// 默认配置文件名，启动时若存在则自动读取
+pub const DEFAULT_CONFIG_FILE: &str = "blockchain.toml";
+
+/// 配置文件读取错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConfigError {
+    /// 配置文件无法读取
+    #[error("无法读取配置文件 {path}: {reason}")]
+    Io { path: String, reason: String },
+    /// 配置文件不是有效的TOML，或字段类型不匹配
+    #[error("无法解析配置文件 {path}: {reason}")]
+    Parse { path: String, reason: String },
+    /// 配置项的取值无效
+    #[error("配置文件 {path} 无效: {reason}")]
+    Invalid { path: String, reason: String },
+}
+
+/// 节点配置，对应配置文件的全部内容
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeConfig {
+    /// 区块链共识参数
+    pub blockchain: BlockchainConfig,
+    /// 网络参数
+    pub network: NetworkConfig,
+}
+
+impl NodeConfig {
+    /// 从TOML文件读取配置并检查取值
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 配置文件路径
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回配置，文件无法读取、解析失败或取值无效时返回`ConfigError`
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let display = path.display().to_string();
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::Io {
+            path: display.clone(),
+            reason: e.to_string(),
+        })?;
+        let config: NodeConfig = toml::from_str(&content).map_err(|e| ConfigError::Parse {
+            path: display.clone(),
+            reason: e.message().to_string(),
+        })?;
+        config.validate().map_err(|reason| ConfigError::Invalid { path: display, reason })?;
+        Ok(config)
+    }
+
+    /// 检查配置项的取值
+    ///
+    /// # 返回值
+    ///
+    /// 全部有效时返回Ok，否则返回第一个无效项的说明
+    fn validate(&self) -> Result<(), String> {
+        let chain = &self.blockchain;
+        let net = &self.network;
+        let positive = [
+            ("blockchain.max_block_size", chain.max_block_size as u64),
+            ("blockchain.halving_interval", chain.halving_interval as u64),
+            ("blockchain.target_block_time_secs", chain.target_block_time_secs),
+            ("blockchain.retarget_interval", chain.retarget_interval as u64),
+            ("network.max_connections", net.max_connections as u64),
+            ("network.gossipsub_heartbeat_secs", net.gossipsub_heartbeat_secs),
+            ("network.max_message_bytes", net.max_message_bytes as u64),
+            ("network.seen_cache_capacity", net.seen_cache_capacity as u64),
+            ("network.seen_cache_ttl_secs", net.seen_cache_ttl_secs),
+        ];
+        if let Some((name, _)) = positive.iter().find(|(_, value)| *value == 0) {
+            return Err(format!("{} 必须大于0", name));
+        }
+        if chain.difficulty > MAX_DIFFICULTY {
+            return Err(format!("blockchain.difficulty 不能超过{}", MAX_DIFFICULTY));
+        }
+        for peer in &net.bootstrap_peers {
+            if peer.parse::<Multiaddr>().is_err() {
+                return Err(format!("无效的引导节点地址: {}", peer));
+            }
+        }
+        Ok(())
+    }
+}
@@ -7,9 +7,19 @@
 //! * `block` - 定义区块、区块头和交易结构
 //! * `blockchain` - 实现区块链和UTXO集合管理
 //! * `wallet` - 提供密钥管理和交易签名功能
+//! * `mempool` - 管理待处理交易池
+//! * `validation` - 与区块链无关的交易验证流程
+//! * `metrics` - 节点运行指标计数器
+//! * `config` - 从TOML配置文件读取区块链和网络参数
 //! * `network` - 实现P2P网络通信功能
 
 pub mod block;
 pub mod blockchain;
 pub mod wallet;
-pub mod network;
\ No newline at end of file
+pub mod mempool;
+pub mod network;
+pub mod validation;
+pub mod metrics;
+pub mod config;
+
+pub use wallet::{decode_address, encode_address, validate_address, AddressError};
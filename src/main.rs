@@ -3,48 +3,263 @@
 //! 这是区块链演示项目的主程序入口，提供了一个简单的命令行界面，
 //! 用于与区块链系统进行交互，包括创建交易、挖掘区块、查看余额和区块链状态等功能。
 
-mod block;
-mod blockchain;
-mod wallet;
-mod network;
+use blockchain_demo::{block, blockchain, config, wallet, mempool, network};
 
 use tokio::sync::mpsc;
 use std::path::Path;
 use std::io::{self, Write};
-use tokio;
-use std::collections::{VecDeque, HashMap};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs;
-use serde_json;
 use std::sync::Arc;
 
-use network::NetworkEvent;
+use network::{NetworkCommand, NetworkEvent};
 
 /// 地址解析函数，将用户友好的名称转换为钱包地址
+///
+/// 解析结果必须是有效的Base58Check地址，避免输入错误的地址导致资金丢失
+///
+/// # 返回值
+///
+/// 返回解析后的地址；地址无效（包括校验和错误）时返回错误描述
 async fn resolve_address(
     input: &str, 
-    address_mapping: &Arc<tokio::sync::Mutex<HashMap<String, String>>>
-) -> String {
-    let mapping = address_mapping.lock().await;
+    address_book: &Arc<tokio::sync::Mutex<wallet::AddressBook>>
+) -> Result<String, String> {
+    // 有效地址优先于同名条目，不在地址簿中的输入按地址校验，以便报告具体的错误
+    let address = address_book.lock().await.resolve(input).unwrap_or_else(|| input.to_string());
     
-    // 如果输入已经是有效的钱包地址（40个十六进制字符），直接返回
-    if input.len() == 40 && input.chars().all(|c| c.is_ascii_hexdigit()) {
-        return input.to_string();
+    // 地址簿中的地址也需要校验，旧格式的十六进制地址没有校验和，同样拒绝
+    wallet::decode_address(&address)
+        .map(|_| address)
+        .map_err(|e| format!("'{}' 不是有效的地址: {}", input, e))
+}
+
+/// 创建支付给签名者地址的Coinbase交易
+///
+/// 挖矿奖励只需要签名者的地址，密钥保存在外部的签名者同样可以挖矿
+///
+/// # 参数
+///
+/// * `signer` - 接收奖励的签名者
+/// * `value` - 奖励金额
+fn coinbase_transaction(signer: &dyn wallet::Signer, value: u64) -> block::Transaction {
+    let coinbase_input = block::TxInput {
+        prev_tx: String::from(block::COINBASE_TX_ID),
+        prev_index: 0,
+        script_sig: String::from("挖矿奖励"),
+    };
+    let coinbase_output = block::TxOutput {
+        value,
+        script_pubkey: signer.address().to_string(),
+    };
+    block::Transaction::new(vec![coinbase_input], vec![coinbase_output])
+}
+
+/// 解析多接收者列表，格式为`addr1=10,addr2=25`
+///
+/// 每个地址都会经过`resolve_address`解析
+///
+/// # 参数
+///
+/// * `input` - 用户输入的接收者列表
+/// * `address_book` - 地址簿
+///
+/// # 返回值
+///
+/// 返回解析后的地址和金额列表；格式错误时返回错误描述
+async fn parse_recipients(
+    input: &str,
+    address_book: &Arc<tokio::sync::Mutex<wallet::AddressBook>>
+) -> Result<Vec<(String, u64)>, String> {
+    let mut recipients = Vec::new();
+    for entry in input.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (name, amount) = entry.split_once('=')
+            .ok_or_else(|| format!("Invalid recipient (expected addr=amount): {}", entry))?;
+        let amount: u64 = amount.trim().parse()
+            .map_err(|_| format!("Invalid amount: {}", amount.trim()))?;
+        recipients.push((resolve_address(name.trim(), address_book).await?, amount));
     }
-    
-    // 查找映射表
-    if let Some(address) = mapping.get(input) {
-        // 检查是否是占位符
-        if address.ends_with("_placeholder") {
-            println!("⚠️  警告: '{}' 是占位符地址，请使用菜单选项13更新为实际钱包地址", input);
-            return address.clone();
+    Ok(recipients)
+}
+
+/// 构建同步请求，携带本地链顶高度和区块定位器，对方只发送共同区块之后的区块
+fn sync_request(blockchain: &blockchain::Blockchain) -> NetworkCommand {
+    NetworkCommand::RequestBlocks {
+        from_height: blockchain.height() as u64 + 1,
+        locator: blockchain.block_locator(),
+    }
+}
+
+/// 构建分页同步请求，携带本地链顶高度和区块定位器，对方只返回共同区块之后的一页区块
+fn page_request(blockchain: &blockchain::Blockchain, peer_id: libp2p::PeerId) -> NetworkCommand {
+    NetworkCommand::RequestBlocksAfter {
+        peer_id,
+        known_height: blockchain.height() as u64,
+        locator: blockchain.block_locator(),
+    }
+}
+
+/// 分页同步分叉时最多暂存的区块数，分叉更长时改用定位器广播同步
+const MAX_FORK_SYNC_BLOCKS: usize = 1000;
+
+/// 暂存的孤块（父区块未知的区块）数量上限，超过时说明落后太多，改为同步整条链
+const MAX_ORPHAN_BLOCKS: usize = 16;
+
+/// 暂存父区块未知的区块，并构建向节点请求缺失父区块的命令
+///
+/// # 参数
+///
+/// * `blockchain` - 本地区块链
+/// * `orphans` - 孤块表，键为缺失的父区块哈希
+/// * `block` - 收到的区块
+/// * `peer` - 可以请求父区块的节点
+///
+/// # 返回值
+///
+/// 返回请求父区块的命令；区块不是缺少父区块、孤块过多或没有可请求的节点时返回None，
+/// 调用方应改为同步整条链
+fn request_missing_parent(
+    blockchain: &blockchain::Blockchain,
+    orphans: &mut HashMap<String, block::Block>,
+    block: block::Block,
+    peer: Option<libp2p::PeerId>,
+) -> Option<NetworkCommand> {
+    let parent = blockchain.missing_parent(&block)?.to_string();
+    let peer_id = peer?;
+    if orphans.len() >= MAX_ORPHAN_BLOCKS {
+        return None;
+    }
+    println!("⏳ 缺少父区块 {}，向节点 {} 请求", parent, peer_id);
+    orphans.insert(parent.clone(), block);
+    Some(NetworkCommand::RequestBlock { peer_id, query: network::BlockQuery::Hash(parent) })
+}
+
+/// 把等待父区块的孤块依次接到链上
+///
+/// # 参数
+///
+/// * `blockchain` - 本地区块链
+/// * `orphans` - 孤块表，键为缺失的父区块哈希
+/// * `parent_hash` - 刚添加到链上的区块哈希
+///
+/// # 返回值
+///
+/// 返回添加到链上的孤块，遇到无效的孤块时停止
+fn connect_orphans(
+    blockchain: &mut blockchain::Blockchain,
+    orphans: &mut HashMap<String, block::Block>,
+    mut parent_hash: String,
+) -> Vec<block::Block> {
+    let mut connected = Vec::new();
+    while let Some(orphan) = orphans.remove(&parent_hash) {
+        if !blockchain.validate_block(&orphan) {
+            break;
         }
-        return address.clone();
+        parent_hash = blockchain.block_hash(&orphan);
+        blockchain.add_received_block(orphan.clone());
+        connected.push(orphan);
+    }
+    connected
+}
+
+/// 把最近接入本地链的区块交给钱包观察器扫描
+///
+/// # 参数
+///
+/// * `watcher` - 钱包观察器
+/// * `blockchain` - 本地区块链
+/// * `count` - 链顶最近接入的区块数量
+fn notify_wallet(watcher: &mut wallet::WalletWatcher, blockchain: &blockchain::Blockchain, count: usize) {
+    let start = blockchain.len().saturating_sub(count);
+    for (height, block) in blockchain.iter().enumerate().skip(start) {
+        watcher.block_connected(block, height);
+    }
+}
+
+/// 区块接入本地链后清理交易池，移除已确认的交易和与这些区块双花的交易
+///
+/// # 参数
+///
+/// * `pending_transactions` - 待处理交易池
+/// * `blockchain` - 已接入这些区块的本地区块链
+/// * `blocks` - 接入的区块
+///
+/// # 返回值
+///
+/// 返回被移除的交易数量
+fn purge_mempool(pending_transactions: &mut mempool::Mempool, blockchain: &blockchain::Blockchain, blocks: &[block::Block]) -> usize {
+    blocks.iter()
+        .map(|block| pending_transactions.revalidate_against_new_block(block, blockchain))
+        .sum()
+}
+
+/// 把同步收到的区块接到本地链上，并更新钱包观察器和交易池
+///
+/// # 参数
+///
+/// * `blockchain` - 本地区块链
+/// * `watcher` - 钱包观察器
+/// * `pending_transactions` - 待处理交易池
+/// * `start_height` - `blocks`中第一个区块的高度
+/// * `blocks` - 收到的区块
+///
+/// # 返回值
+///
+/// 返回接入的区块数；区块无法衔接时返回错误，本地链保持不变
+fn connect_synced_blocks(
+    blockchain: &mut blockchain::Blockchain,
+    watcher: &mut wallet::WalletWatcher,
+    pending_transactions: &mut mempool::Mempool,
+    start_height: usize,
+    blocks: Vec<block::Block>,
+) -> Result<usize, blockchain::BlockchainError> {
+    let old_tip = blockchain.block_hash(blockchain.tip());
+    let old_height = blockchain.height();
+    let appended = blockchain.connect_blocks_since(start_height, blocks.clone())?;
+    println!("接入了 {} 个区块，当前高度: {}", appended, blockchain.height());
+    let extended = blockchain.get(old_height).is_some_and(|block| blockchain.block_hash(block) == old_tip);
+    if extended {
+        notify_wallet(watcher, blockchain, appended);
+    } else {
+        // 切换到了分叉，钱包观察器按新的UTXO集重新载入输出
+        watcher.resync(&blockchain.utxo_entries());
     }
     
-    // 如果没有找到映射，返回原始输入（可能是新的地址）
-    println!("ℹ️  未找到 '{}' 的地址映射，将作为原始地址使用", input);
-    input.to_string()
+    let removed_count = purge_mempool(pending_transactions, blockchain, &blocks);
+    if removed_count > 0 {
+        println!("🗑️ 同步后从待处理池中移除了 {} 个已确认或输入已被花费的交易", removed_count);
+    }
+    Ok(appended)
+}
+
+/// 初始化日志订阅器
+///
+/// 日志输出到标准错误，级别由`RUST_LOG`控制，未设置时只输出本项目的info及以上日志；
+/// 指定日志文件时，同时以JSON格式追加写入该文件
+///
+/// # 参数
+///
+/// * `log_file` - 可选的JSON日志文件路径
+fn init_logging(log_file: Option<&str>) {
+    use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("blockchain_demo=info"));
+    let json_layer = log_file.and_then(|path| {
+        match std::fs::OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(fmt::layer().json().with_writer(std::sync::Mutex::new(file))),
+            Err(e) => {
+                eprintln!("无法打开日志文件 {}: {}", path, e);
+                None
+            }
+        }
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(io::stderr))
+        .with(json_layer)
+        .init();
 }
 
 /// 程序的主入口函数
@@ -53,67 +268,219 @@ async fn resolve_address(
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = env::args().collect();
-    let user_id = if args.len() > 1 { &args[1] } else { "user1" };
+    // --开头的参数是开关，第一个其他参数是用户ID
+    let validate_chain = args.iter().skip(1).any(|arg| arg == "--validate-chain");
+    // --no-mempool-rebroadcast关闭新节点连接时重新广播待处理交易
+    let rebroadcast_mempool = !args.iter().skip(1).any(|arg| arg == "--no-mempool-rebroadcast");
+    let user_id = args.iter().skip(1).find(|arg| !arg.starts_with("--")).map(String::as_str).unwrap_or("user1");
     
-    // 使用user_id创建或加载钱包
-    let wallet_file = format!("{}_wallet.json", user_id);
-    let wallet = if Path::new(&wallet_file).exists() {
-        // 从文件加载钱包
-        wallet::Wallet::load_wallet(&wallet_file)
+    // 初始化日志，--log-file=<路径>额外写入JSON格式的日志文件
+    init_logging(args.iter().skip(1).find_map(|arg| arg.strip_prefix("--log-file=")));
+    
+    // --config=<路径>指定配置文件，未指定时若当前目录存在blockchain.toml则读取它
+    let config_file = args.iter().skip(1).find_map(|arg| arg.strip_prefix("--config="))
+        .map(Path::new)
+        .or_else(|| Some(Path::new(config::DEFAULT_CONFIG_FILE)).filter(|path| path.exists()));
+    let node_config = config_file.map(|path| match config::NodeConfig::load(path) {
+        Ok(node_config) => {
+            println!("已读取配置文件 {}", path.display());
+            node_config
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    });
+    
+    // 创建区块链，用于确定本节点的创世区块和参数
+    let fresh_chain = if args.iter().skip(1).any(|arg| arg == "--regtest") {
+        // 回归测试模式：难度为0，区块立即产生，使用独立的创世区块；配置文件中的难度被忽略
+        let mut chain = blockchain::Blockchain::regtest();
+        if let Some(node_config) = &node_config {
+            chain.apply_config(&blockchain::BlockchainConfig { difficulty: 0, ..node_config.blockchain.clone() });
+        }
+        chain
     } else {
-        // 创建新钱包并保存
-        let new_wallet = wallet::Wallet::new();
-        wallet::Wallet::save_wallet(&new_wallet, &wallet_file);
-        new_wallet
+        let chain_config = node_config.as_ref().map(|c| c.blockchain.clone()).unwrap_or_default();
+        blockchain::Blockchain::new_with_config(chain_config)
     };
     
-    // 使用相同的链数据文件
-    let blockchain_file = "blockchain.json";
-
-    // 初始化日志
-    env_logger::init();
-
-    // 创建区块链
-    let blockchain = Arc::new(tokio::sync::Mutex::new(blockchain::Blockchain::new(2)));
-    println!("Created new blockchain");
+    // 加载本地保存的区块链，UTXO快照与链顶一致时无需从创世区块重放；文件记录的哈希算法必须与本节点相同。
+    // 指定--validate-chain时文件必须存在，加载的链未通过验证时退出
+    let chain = match blockchain::Blockchain::load_verified_with_hasher(blockchain::DEFAULT_CHAIN_FILE, Arc::clone(fresh_chain.hasher())) {
+        Some((mut chain, outcome)) if chain.matches_genesis(fresh_chain.genesis()) => {
+            match &outcome {
+                // 完整性检查失败时链已被截断到最后一个有效区块
+                blockchain::LoadOutcome::Truncated { kept, discarded, errors } => {
+                    for error in errors {
+                        eprintln!("  {}", error);
+                    }
+                    println!("blockchain.json 中有无效区块，已丢弃{}个区块，保留{}个", discarded, kept);
+                }
+                blockchain::LoadOutcome::Invalid { height } => {
+                    eprintln!("区块链验证失败：高度{}的区块无效，请检查或移走 blockchain.json", height);
+                    std::process::exit(1);
+                }
+                blockchain::LoadOutcome::Verified => {}
+            }
+            println!("Loaded and validated blockchain ({} blocks)", chain.len());
+            chain.apply_config(&fresh_chain.config());
+            chain
+        }
+        Some(_) if validate_chain => {
+            eprintln!("blockchain.json 属于其他网络，无法验证区块链");
+            std::process::exit(1);
+        }
+        None if validate_chain => {
+            eprintln!("无法加载 blockchain.json，无法验证区块链");
+            std::process::exit(1);
+        }
+        _ => {
+            println!("Created new {} blockchain", fresh_chain.network_id());
+            fresh_chain
+        }
+    };
+    
+    // 使用user_id创建或加载钱包，作为钱包管理器中的默认钱包，新建的钱包属于区块链所在的网络
+    let mut wallets = wallet::WalletManager::new();
+    wallets.set_network_id(chain.network_id());
+    let wallet_file = format!("{}_wallet.json", user_id);
+    if let Err(e) = wallets.load_or_create(user_id, Path::new(&wallet_file)) {
+        eprintln!("无法加载钱包: {}", e);
+        // 只有用户明确确认后才创建新钱包，损坏的文件会被保留以便手动恢复
+        print!("是否将 {} 重命名为 {}.corrupt 并创建新钱包？输入 yes 确认: ", wallet_file, wallet_file);
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).unwrap();
+        if answer.trim() != "yes" {
+            println!("未创建新钱包，退出");
+            std::process::exit(1);
+        }
+        if let Err(e) = std::fs::rename(&wallet_file, format!("{}.corrupt", wallet_file)) {
+            eprintln!("无法重命名 {}: {}", wallet_file, e);
+            std::process::exit(1);
+        }
+        if let Err(e) = wallets.load_or_create(user_id, Path::new(&wallet_file)) {
+            eprintln!("无法创建钱包: {}", e);
+            std::process::exit(1);
+        }
+        println!("已创建新钱包，原文件保存在 {}.corrupt", wallet_file);
+    }
+    
+    // 钱包在其他网络上签名的交易不会被本链接受
+    if wallets.active_wallet().network_id != chain.network_id() {
+        println!("⚠️ 钱包 {} 属于网络 {}，当前区块链属于网络 {}，签名的交易将无法通过验证",
+            wallet_file, wallets.active_wallet().network_id, chain.network_id());
+    }
+    
+    // 观察当前钱包，区块接入本地链时通知收入和支出
+    let (wallet_event_tx, mut wallet_event_rx) = mpsc::channel(100);
+    let mut watcher = wallet::WalletWatcher::new(wallets.active_wallet(), wallet_event_tx);
+    watcher.watch(wallets.active_wallet(), &chain.utxo_entries());
+    let watcher = Arc::new(tokio::sync::Mutex::new(watcher));
+    tokio::spawn(async move {
+        while let Some(event) = wallet_event_rx.recv().await {
+            match event {
+                wallet::WalletEvent::Received { txid, amount, height } => {
+                    println!("\n🔔 收到 {} 个币（交易 {}，高度 {}）", amount, txid, height);
+                }
+                wallet::WalletEvent::Sent { txid, amount } => {
+                    println!("\n🔔 支出 {} 个币已确认（交易 {}）", amount, txid);
+                }
+            }
+        }
+    });
+    
+    // 交易池和网络层使用与区块链相同的哈希算法计算交易ID和区块哈希
+    let hasher = Arc::clone(chain.hasher());
+    let blockchain = Arc::new(tokio::sync::Mutex::new(chain));
 
     // 创建网络和通道
     let (app_tx, mut app_rx) = mpsc::channel(100);
-    let mut network = network::Network::new_with_channel(app_tx.clone()).await;
+    // 节点密钥保存在每个用户自己的文件中，重启后节点ID不变，其他节点的地址簿映射仍然有效
+    let node_key_file = format!("{}_node_key", user_id);
+    let network_config = node_config.map(|c| c.network).unwrap_or_default();
+    // 发现的节点保存在节点存储文件中，重启后优先连接
+    let network = network::Network::new_with_config(network_config, Arc::clone(&hasher), app_tx.clone()).await
+        .with_peer_store_file(network::PEER_STORE_FILE);
+    let mut network = match network.with_identity_file(&node_key_file) {
+        Ok(network) => network,
+        Err(e) => {
+            eprintln!("无法加载节点密钥: {}", e);
+            eprintln!("请修复或删除 {} 后重新启动（删除后会生成新的节点ID）", node_key_file);
+            std::process::exit(1);
+        }
+    };
+    // 区块链和网络层更新同一组指标
+    network.set_metrics(blockchain.lock().await.metrics().clone());
+    // 连接建立后向其他节点公告当前用户的钱包地址，免去手动添加映射
+    network.set_announce_address(wallets.active_wallet().address.clone(), user_id.to_string());
+    // 创世区块不同的节点无法同步，连接建立后交换创世区块哈希并断开不匹配的节点
+    {
+        let chain = blockchain.lock().await;
+        network.set_genesis_hash(chain.block_hash(chain.genesis()));
+    }
+    // --coinbase-maturity=<N>指定花费挖矿奖励需要的确认数
+    if let Some(maturity) = args.iter().skip(1).find_map(|arg| arg.strip_prefix("--coinbase-maturity=")) {
+        match maturity.parse() {
+            Ok(maturity) => blockchain.lock().await.set_coinbase_maturity(maturity),
+            Err(_) => eprintln!("无效的Coinbase成熟深度: {}", maturity),
+        }
+    }
+    // --relay=<地址>指定NAT之后使用的中继服务器
+    if let Some(relay) = args.iter().skip(1).find_map(|arg| arg.strip_prefix("--relay=")) {
+        match relay.parse() {
+            Ok(addr) => network.set_relay_server(addr),
+            Err(e) => eprintln!("无效的中继服务器地址 {}: {}", relay, e),
+        }
+    }
+    // --relay-server为NAT之后的节点提供中继服务，可以直接拨入时向其他节点公告
+    if args.iter().skip(1).any(|arg| arg == "--relay-server") {
+        network.enable_relay_service();
+    }
+    // --listen-addr=<地址>指定监听地址，--external-addr=<地址>指定向其他节点公布的地址
+    if let Some(listen) = args.iter().skip(1).find_map(|arg| arg.strip_prefix("--listen-addr=")) {
+        match listen.parse() {
+            Ok(addr) => network.set_listen_addr(addr),
+            Err(e) => eprintln!("无效的监听地址 {}: {}", listen, e),
+        }
+    }
+    if let Some(external) = args.iter().skip(1).find_map(|arg| arg.strip_prefix("--external-addr=")) {
+        match external.parse() {
+            Ok(addr) => network.set_external_addr(addr),
+            Err(e) => eprintln!("无效的外部地址 {}: {}", external, e),
+        }
+    }
     
     // 创建一个共享的待处理交易池
-    let pending_transactions: Arc<tokio::sync::Mutex<VecDeque<block::Transaction>>> = 
-        Arc::new(tokio::sync::Mutex::new(VecDeque::new()));
-    let pending_tx_for_network = pending_transactions.clone();
+    let pending_transactions: Arc<tokio::sync::Mutex<mempool::Mempool>> = 
+        Arc::new(tokio::sync::Mutex::new(mempool::Mempool::with_hasher(hasher)));
     let pending_tx_for_main = pending_transactions.clone();
     
-    // 创建地址映射表，支持用户名和节点ID到钱包地址的映射
-    let address_mapping: Arc<tokio::sync::Mutex<HashMap<String, String>>> = 
-        Arc::new(tokio::sync::Mutex::new(HashMap::new()));
-    let address_mapping_for_network = address_mapping.clone();
-    let address_mapping_for_main = address_mapping.clone();
+    // 加载地址簿，支持用户名和节点ID到钱包地址的映射，每次修改都会保存
+    let address_book_file = wallet::AddressBook::path_for(user_id);
+    let mut book = wallet::AddressBook::load_or_create(&address_book_file).unwrap_or_else(|e| {
+        eprintln!("⚠️ 加载地址簿失败，本次运行的修改不会保存: {}", e);
+        wallet::AddressBook::new()
+    });
     
     // 添加当前用户的映射
     {
-        let mut mapping = address_mapping.lock().await;
-        mapping.insert(user_id.to_string(), wallet.address.clone());
-        mapping.insert("me".to_string(), wallet.address.clone());
-        mapping.insert("self".to_string(), wallet.address.clone());
-        
-        // 添加一些常用的用户名映射（用户可以通过菜单13更新）
-        if user_id == "user1" {
-            // 为user1添加user2的预设映射（需要用户手动更新为实际地址）
-            mapping.insert("user2".to_string(), "user2_placeholder".to_string());
-        } else if user_id == "user2" {
-            // 为user2添加user1的预设映射（需要用户手动更新为实际地址）
-            mapping.insert("user1".to_string(), "user1_placeholder".to_string());
+        let wallet = wallets.active_wallet();
+        for name in [user_id, "me", "self"] {
+            if let Err(e) = book.add(name, &wallet.address) {
+                eprintln!("⚠️ 保存地址簿失败: {}", e);
+            }
         }
         
-        println!("📝 地址映射已初始化:");
+        println!("📝 地址簿已加载 ({} 个条目):", book.len());
         println!("  {} -> {}", user_id, wallet.address);
         println!("  me -> {}", wallet.address);
         println!("  self -> {}", wallet.address);
     }
+    let address_book: Arc<tokio::sync::Mutex<wallet::AddressBook>> = Arc::new(tokio::sync::Mutex::new(book));
+    let address_book_for_network = address_book.clone();
+    let address_book_for_main = address_book.clone();
     
     // 创建同步状态跟踪
     let sync_in_progress: Arc<tokio::sync::Mutex<bool>> = Arc::new(tokio::sync::Mutex::new(false));
@@ -123,7 +490,7 @@ async fn main() {
     let node_peer_id = network.peer_id();
     println!("节点ID: {}", node_peer_id);
 
-    // 获取网络的事件发送器，用于发送应用层事件到网络
+    // 获取网络的命令发送器，用于向网络任务发送应用层命令
     let network_tx = network.get_event_sender();
     
     // 创建网络实例的Arc包装，用于在主循环中访问网络信息
@@ -138,63 +505,83 @@ async fn main() {
         }
     });
 
+    // 定期清理交易池：驱逐过期交易和输入已失效的交易
+    let blockchain_for_mempool = blockchain.clone();
+    let pending_tx_for_mempool = pending_transactions.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let blockchain = blockchain_for_mempool.lock().await;
+            let mut pending_transactions = pending_tx_for_mempool.lock().await;
+            let expired = pending_transactions.evict_expired(mempool::DEFAULT_MAX_AGE);
+            let invalid = pending_transactions.revalidate(&blockchain.utxo_set);
+            if expired + invalid > 0 {
+                println!("🧹 交易池清理: 过期 {} 笔, 输入失效 {} 笔", expired, invalid);
+            }
+        }
+    });
+
     // 克隆必要的变量用于网络事件处理任务
     let blockchain_for_network = blockchain.clone();
     let network_tx_for_network = network_tx.clone();
     let pending_tx_for_network = pending_transactions.clone();
     let sync_state_for_task = sync_state_for_network.clone();
+    let watcher_for_network = watcher.clone();
 
     // 网络事件处理任务
     tokio::spawn(async move {
+        // 父区块未知、等待补齐的区块，以及可以请求区块的已连接节点
+        let mut orphan_blocks: HashMap<String, block::Block> = HashMap::new();
+        // 分页同步时累计工作量还不足以切换的分叉区块，键为提供区块的节点，值为起始高度和区块
+        let mut fork_pages: HashMap<libp2p::PeerId, (usize, Vec<block::Block>)> = HashMap::new();
+        let mut connected_peers: HashSet<libp2p::PeerId> = HashSet::new();
         while let Some(event) = app_rx.recv().await {
             match event {
                 NetworkEvent::NewBlock(block) => {
-                    println!("\n📦 收到新区块: {}", block.calculate_hash());
-                    
                     // 获取区块链的可变引用
                     let mut blockchain = blockchain_for_network.lock().await;
+                    let block_hash = blockchain.block_hash(&block);
+                    println!("\n📦 收到新区块: {}", block_hash);
                     
                     // 验证区块
                     if blockchain.validate_block(&block) {
                         println!("✅ 区块验证通过，添加到本地区块链");
                         
-                        // 添加区块到本地区块链
+                        // 添加区块到本地区块链，之前等待该区块的孤块随之接到链上
                         blockchain.add_received_block(block.clone());
+                        let mut added = connect_orphans(&mut blockchain, &mut orphan_blocks, block_hash);
+                        added.push(block);
+                        notify_wallet(&mut *watcher_for_network.lock().await, &blockchain, added.len());
                         
-                        println!("本地区块链已更新，当前高度: {}", blockchain.blocks.len());
+                        println!("本地区块链已更新，当前高度: {}", blockchain.len());
                         
-                        // 释放区块链锁，避免死锁
-                        drop(blockchain);
-                        
-                        // 从待处理交易池中移除已经被打包的交易
+                        // 从待处理交易池中移除已经被打包的交易和与之双花的交易
                         let mut pending_transactions = pending_tx_for_network.lock().await;
-                        let initial_count = pending_transactions.len();
-                        
-                        // 获取区块中的所有交易哈希
-                        let block_tx_hashes: std::collections::HashSet<String> = block.transactions.iter()
-                            .map(|tx| tx.calculate_hash())
-                            .collect();
-                        
-                        // 保留不在区块中的交易
-                        pending_transactions.retain(|tx| {
-                            let tx_hash = tx.calculate_hash();
-                            !block_tx_hashes.contains(&tx_hash)
-                        });
-                        
-                        let removed_count = initial_count - pending_transactions.len();
+                        let removed_count = purge_mempool(&mut pending_transactions, &blockchain, &added);
                         if removed_count > 0 {
-                            println!("🗑️ 从待处理池中移除了 {} 个已确认的交易", removed_count);
+                            println!("🗑️ 从待处理池中移除了 {} 个已确认或输入已被花费的交易", removed_count);
                             println!("📊 待处理交易池剩余: {} 个交易", pending_transactions.len());
                         }
                         
+                    } else if let Some(request) = request_missing_parent(
+                        &blockchain, &mut orphan_blocks, block.clone(), connected_peers.iter().next().copied(),
+                    ) {
+                        // 只缺少父区块时只请求父区块，不必同步整条链
+                        drop(blockchain);
+                        if let Err(e) = network_tx_for_network.send(request).await {
+                            eprintln!("请求父区块失败: {}", e);
+                        }
                     } else {
                         println!("❌ 区块验证失败，可能需要同步区块链");
                         
                         // 区块验证失败时，自动请求区块链同步
+                        orphan_blocks.clear();
+                        let request = sync_request(&blockchain);
                         drop(blockchain); // 释放锁
                         
                         println!("自动请求区块链同步...");
-                        if let Err(e) = network_tx_for_network.send(NetworkEvent::RequestBlocks).await {
+                        if let Err(e) = network_tx_for_network.send(request).await {
                             eprintln!("自动同步请求失败: {}", e);
                         } else {
                             println!("已发送区块链同步请求");
@@ -207,24 +594,20 @@ async fn main() {
                     println!("输出数量: {}", transaction.outputs.len());
                     
                     // 获取区块链的引用
-                    let blockchain = blockchain_for_network.lock().await;
+                    let mut blockchain = blockchain_for_network.lock().await;
                     
                     // 验证交易
                     let is_valid = blockchain.validate_transaction(&transaction);
                     if is_valid {
                         println!("交易验证通过，添加到待处理池");
+                        // 记录首次见到的高度，用于手续费估算
+                        blockchain.record_seen(&transaction);
                         
                         // 获取待处理交易的可变引用
                         let mut pending_transactions = pending_tx_for_network.lock().await;
                         
-                        // 检查交易是否已经在待处理池中
-                        let tx_hash = transaction.calculate_hash();
-                        let is_duplicate = pending_transactions.iter()
-                            .any(|tx| tx.calculate_hash() == tx_hash);
-                            
-                        if !is_duplicate {
-                            // 添加到待处理交易池
-                            pending_transactions.push_back(transaction);
+                        // 添加到待处理交易池（交易池会忽略重复交易）
+                        if pending_transactions.add(transaction) {
                             println!("交易已添加到待处理池");
                         } else {
                             println!("交易已存在于待处理池，忽略");
@@ -234,145 +617,288 @@ async fn main() {
                         println!("暂时添加到待处理池，等待区块链同步后重新验证");
                         
                         // 释放区块链锁
+                        let request = sync_request(&blockchain);
                         drop(blockchain);
                         
-                        // 获取待处理交易的可变引用
+                        // 暂时添加到待处理交易池
                         let mut pending_transactions = pending_tx_for_network.lock().await;
-                        
-                        // 检查交易是否已经在待处理池中
-                        let tx_hash = transaction.calculate_hash();
-                        let is_duplicate = pending_transactions.iter()
-                            .any(|tx| tx.calculate_hash() == tx_hash);
-                            
-                        if !is_duplicate {
-                            // 暂时添加到待处理交易池
-                            pending_transactions.push_back(transaction);
+                        if pending_transactions.add(transaction) {
                             println!("交易已暂时添加到待处理池");
                         }
                         
                         // 请求区块链同步
-                        if let Err(e) = network_tx_for_network.send(NetworkEvent::RequestBlocks).await {
+                        if let Err(e) = network_tx_for_network.send(request).await {
                             eprintln!("同步请求失败: {}", e);
                         } else {
                             println!("已发送区块链同步请求");
                         }
                     }
                 },
-                NetworkEvent::RequestBlocks => {
-                    println!("\n📋 收到区块同步请求（来自网络）");
+                NetworkEvent::BlocksRequested(peer_id) => {
+                    println!("\n📋 收到节点 {} 的区块同步请求", peer_id);
                     
                     // 获取区块链的引用
                     let blockchain = blockchain_for_network.lock().await;
                     
                     // 发送本地区块链数据作为响应
-                    let blocks_to_send = blockchain.blocks.clone();
+                    let blocks_to_send = blockchain.as_slice().to_vec();
                     println!("响应网络同步请求，发送 {} 个区块", blocks_to_send.len());
                     
                     // 释放区块链锁
                     drop(blockchain);
                     
                     // 通过网络发送区块链数据响应
-                    if let Err(e) = network_tx_for_network.send(NetworkEvent::SendBlocks(blocks_to_send)).await {
+                    if let Err(e) = network_tx_for_network.send(NetworkCommand::SendBlocksTo(peer_id, blocks_to_send)).await {
                         eprintln!("发送区块链响应失败: {}", e);
                     } else {
                         println!("区块链响应已发送");
                     }
                 },
-                NetworkEvent::SendBlocks(blocks) => {
+                NetworkEvent::BlocksSinceRequested { peer_id, known_height, known_hash } => {
+                    println!("\n📋 收到增量区块同步请求，对方高度: {}", known_height);
+                    let blockchain = blockchain_for_network.lock().await;
+                    
+                    // 对方的链顶在本地链上时只发送之后的区块，否则对方已分叉，发送完整的链
+                    let response = match blockchain.get(known_height) {
+                        Some(block) if blockchain.block_hash(block) == known_hash => {
+                            let blocks = blockchain.get_blocks_since(known_height).to_vec();
+                            println!("对方落后 {} 个区块，发送增量区块", blocks.len());
+                            NetworkCommand::SendBlocksSinceTo { peer_id, start_height: known_height + 1, blocks }
+                        }
+                        Some(_) => {
+                            println!("对方的链顶不在本地链上，发送完整区块链");
+                            NetworkCommand::SendBlocksTo(peer_id, blockchain.as_slice().to_vec())
+                        }
+                        None => {
+                            println!("对方的链比本地长，无需响应");
+                            continue;
+                        }
+                    };
+                    drop(blockchain);
+                    
+                    if let Err(e) = network_tx_for_network.send(response).await {
+                        eprintln!("发送增量区块响应失败: {}", e);
+                    }
+                },
+                NetworkEvent::BlocksLocatorRequested { peer_id, known_height, locator } => {
+                    println!("\n📋 收到定位器区块同步请求，对方高度: {}", known_height);
+                    let blockchain = blockchain_for_network.lock().await;
+                    
+                    // 只发送最后一个共同区块之后的区块，连创世区块都不同时不响应
+                    let response = match blockchain.blocks_after_locator(&locator) {
+                        Some((_, [])) => {
+                            println!("对方没有落后，无需响应");
+                            continue;
+                        }
+                        Some((start_height, blocks)) => {
+                            println!("共同区块高度 {}，发送之后的 {} 个区块", start_height - 1, blocks.len());
+                            NetworkCommand::SendBlocksSinceTo { peer_id, start_height, blocks: blocks.to_vec() }
+                        }
+                        None => {
+                            println!("对方的定位器与本地链没有共同区块，忽略");
+                            continue;
+                        }
+                    };
+                    drop(blockchain);
+                    
+                    if let Err(e) = network_tx_for_network.send(response).await {
+                        eprintln!("发送增量区块响应失败: {}", e);
+                    }
+                },
+                NetworkEvent::BlocksSinceReceived { start_height, blocks } => {
+                    println!("\n📦 收到增量区块响应，从高度 {} 开始的 {} 个区块", start_height, blocks.len());
+                    let mut blockchain = blockchain_for_network.lock().await;
+                    let connected = connect_synced_blocks(
+                        &mut blockchain,
+                        &mut *watcher_for_network.lock().await,
+                        &mut *pending_tx_for_network.lock().await,
+                        start_height,
+                        blocks,
+                    );
+                    drop(blockchain);
+                    if let Err(e) = connected {
+                        // 无法衔接时退回完整同步
+                        println!("增量同步失败: {}，请求完整区块链", e);
+                        if let Err(e) = network_tx_for_network.send(NetworkCommand::RequestBlocks { from_height: 0, locator: Vec::new() }).await {
+                            eprintln!("同步请求失败: {}", e);
+                        }
+                    }
+                    *sync_state_for_task.lock().await = false;
+                },
+                NetworkEvent::BlocksPageRequested { peer_id, request_id, from_height, limit } => {
+                    let blocks = blockchain_for_network.lock().await.blocks_from(from_height as usize, limit as usize).to_vec();
+                    println!("\n📋 节点 {} 请求从高度 {} 开始的区块，发送 {} 个", peer_id, from_height, blocks.len());
+                    if let Err(e) = network_tx_for_network.send(NetworkCommand::SendBlocksPage { request_id, blocks }).await {
+                        eprintln!("回复分页区块请求失败: {}", e);
+                    }
+                },
+                NetworkEvent::BlocksPageAfterRequested { peer_id, request_id, known_height, locator, limit } => {
+                    let page = blockchain_for_network.lock().await
+                        .page_after_locator(&locator, limit as usize)
+                        .map(|(start_height, blocks, more)| (start_height, blocks.to_vec(), more));
+                    // 没有共同区块时回复起始高度为0的空页，对方需要完整同步
+                    let (start_height, blocks, more) = page.unwrap_or((0, Vec::new(), false));
+                    println!("\n📋 节点 {} 的链顶高度为 {}，从高度 {} 开始发送 {} 个区块", peer_id, known_height, start_height, blocks.len());
+                    let command = NetworkCommand::SendBlocksPageAfter { request_id, start_height: start_height as u64, blocks, more };
+                    if let Err(e) = network_tx_for_network.send(command).await {
+                        eprintln!("回复分页区块请求失败: {}", e);
+                    }
+                },
+                NetworkEvent::BlocksPageReceived { peer_id, from_height, blocks, more } => {
+                    // 接着暂存的分叉区块继续接入
+                    let (start_height, blocks) = match fork_pages.remove(&peer_id) {
+                        Some((start, mut buffered)) if start + buffered.len() == from_height as usize => {
+                            buffered.extend(blocks);
+                            (start, buffered)
+                        }
+                        _ => (from_height as usize, blocks),
+                    };
+                    if start_height == 0 {
+                        println!("\n⚠️ 节点 {} 与本地链没有共同区块，请求完整区块链", peer_id);
+                        let request = NetworkCommand::RequestBlocks { from_height: 0, locator: Vec::new() };
+                        if let Err(e) = network_tx_for_network.send(request).await {
+                            eprintln!("同步请求失败: {}", e);
+                            *sync_state_for_task.lock().await = false;
+                        }
+                        continue;
+                    }
+                    if blocks.is_empty() {
+                        println!("\n✅ 已与节点 {} 同步到最新", peer_id);
+                        *sync_state_for_task.lock().await = false;
+                        continue;
+                    }
+                    println!("\n📦 收到节点 {} 从高度 {} 开始的 {} 个区块", peer_id, start_height, blocks.len());
+                    let mut blockchain = blockchain_for_network.lock().await;
+                    let connected = connect_synced_blocks(
+                        &mut blockchain,
+                        &mut *watcher_for_network.lock().await,
+                        &mut *pending_tx_for_network.lock().await,
+                        start_height,
+                        blocks.clone(),
+                    );
+                    // 接入成功时用新的定位器继续请求下一页，直到对方没有更多区块；
+                    // 分叉的累计工作量暂时不够时暂存已收到的区块，按高度继续请求对方链上之后的区块
+                    let command = match connected {
+                        Ok(_) if more => Some(page_request(&blockchain, peer_id)),
+                        Ok(_) => {
+                            println!("✅ 已与节点 {} 同步到最新", peer_id);
+                            None
+                        }
+                        Err(blockchain::BlockchainError::InsufficientForkWork { .. }) if more && blocks.len() < MAX_FORK_SYNC_BLOCKS => {
+                            let next_height = start_height + blocks.len();
+                            fork_pages.insert(peer_id, (start_height, blocks));
+                            Some(NetworkCommand::RequestBlocksFrom(peer_id, next_height as u64))
+                        }
+                        Err(e) => {
+                            println!("分页同步失败: {}，改用定位器同步", e);
+                            Some(sync_request(&blockchain))
+                        }
+                    };
+                    drop(blockchain);
+                    let Some(command) = command else {
+                        *sync_state_for_task.lock().await = false;
+                        continue;
+                    };
+                    if let Err(e) = network_tx_for_network.send(command).await {
+                        eprintln!("同步请求失败: {}", e);
+                        *sync_state_for_task.lock().await = false;
+                    }
+                },
+                NetworkEvent::BlocksPageFailed { peer_id, from_height } => {
+                    fork_pages.remove(&peer_id);
+                    // 对方不支持分页同步或连接已断开，广播同步请求
+                    println!("\n⚠️ 向节点 {} 请求高度 {} 之后的区块失败，改为广播同步请求", peer_id, from_height);
+                    let request = sync_request(&*blockchain_for_network.lock().await);
+                    if let Err(e) = network_tx_for_network.send(request).await {
+                        eprintln!("同步请求失败: {}", e);
+                        *sync_state_for_task.lock().await = false;
+                    }
+                },
+                NetworkEvent::BlocksReceived(blocks) => {
                     println!("\n📦 收到区块响应，总共 {} 个区块", blocks.len());
                     
                     if blocks.is_empty() {
                         println!("收到空区块列表，忽略");
-                        return;
+                        continue;
                     }
                     
                     // 获取区块链的可变引用
                     let mut blockchain = blockchain_for_network.lock().await;
                     
-                    println!("本地区块链长度: {}, 收到的区块链长度: {}", blockchain.blocks.len(), blocks.len());
+                    println!("本地区块链长度: {}, 收到的区块链长度: {}", blockchain.len(), blocks.len());
+                    
+                    // 收到的链以本地链为前缀时，只需批量导入新增的区块
+                    let extends_local = blocks.len() > blockchain.len()
+                        && blockchain.iter().zip(&blocks).all(|(local, block)| blockchain.block_hash(local) == blockchain.block_hash(block));
                     
                     // 智能同步检查：只有在收到的链更长时才进行同步
-                    if blocks.len() > blockchain.blocks.len() {
-                        println!("收到的区块链更长，开始验证和同步");
-                        
-                        // 创建临时区块链来验证整个链
-                        let mut temp_blockchain = blockchain::Blockchain::new(blockchain.difficulty);
-                        let mut is_valid_chain = true;
-                        
-                        // 验证整个区块链
-                        for (i, block) in blocks.iter().enumerate() {
-                            if i == 0 {
-                                // 第一个区块（创世区块）
-                                if block.header.prev_hash != "0" {
-                                    println!("创世区块验证失败");
-                                    is_valid_chain = false;
-                                    break;
+                    if extends_local {
+                        println!("收到的区块链包含本地链，批量导入新增的区块");
+                        let new_blocks = blocks[blockchain.len()..].to_vec();
+                        match blockchain.add_block_batch(new_blocks.clone()) {
+                            Ok(added) => {
+                                if added < new_blocks.len() {
+                                    println!("区块 #{} 验证失败，保留之前导入的区块", blockchain.len());
                                 }
-                                temp_blockchain.blocks.push(block.clone());
-                                temp_blockchain.rebuild_utxo_set();
-                            } else {
-                                // 验证后续区块
-                                if temp_blockchain.validate_block(block) {
-                                    temp_blockchain.add_received_block(block.clone());
-                                } else {
-                                    is_valid_chain = false;
-                                    println!("区块 #{} 验证失败", i);
-                                    break;
+                                notify_wallet(&mut *watcher_for_network.lock().await, &blockchain, added);
+                                println!("本地区块链已更新，当前高度: {}", blockchain.len());
+                                
+                                let mut pending_transactions = pending_tx_for_network.lock().await;
+                                let removed_count = purge_mempool(&mut pending_transactions, &blockchain, &new_blocks[..added]);
+                                if removed_count > 0 {
+                                    println!("🗑️ 同步后从待处理池中移除了 {} 个已确认或输入已被花费的交易", removed_count);
+                                    println!("📊 待处理交易池剩余: {} 个交易", pending_transactions.len());
                                 }
                             }
+                            Err(e) => println!("收到的区块链无效，保留本地链: {}", e),
                         }
+                    } else if blocks.len() > blockchain.len() {
+                        println!("收到的区块链更长，开始验证和同步");
+                        
+                        // 创建临时区块链来验证整个链，验证过程不写入文件
+                        let mut temp_blockchain = blockchain.fresh_copy();
+                        
+                        // 第一个区块（创世区块），临时链已包含本地创世区块，只需比对哈希
+                        let is_valid_chain = if blocks[0].header.prev_hash != "0"
+                            || !temp_blockchain.matches_genesis(&blocks[0]) {
+                            println!("创世区块验证失败，对方可能使用了不同的创世配置");
+                            false
+                        } else {
+                            // 批量验证后续区块
+                            let added = temp_blockchain.extend_validated(blocks[1..].to_vec());
+                            if added < blocks.len() - 1 {
+                                println!("区块 #{} 验证失败", added + 1);
+                            }
+                            added == blocks.len() - 1
+                        };
                         
                         if is_valid_chain {
                             println!("收到的区块链有效，替换本地链");
                             
                             // 替换本地区块链
                             blockchain.replace_chain(blocks.clone());
+                            // 整条链被替换，钱包观察器按新的UTXO集重新载入输出
+                            watcher_for_network.lock().await.resync(&blockchain.utxo_entries());
                             
-                            // 更新UTXO集
-                            blockchain.rebuild_utxo_set();
-                            
-                            println!("本地区块链已更新，当前高度: {}", blockchain.blocks.len());
-                            
-                            // 释放区块链锁
-                            drop(blockchain);
+                            println!("本地区块链已更新，当前高度: {}", blockchain.len());
                             
-                            // 更新待处理交易池，移除已经被确认的交易
+                            // 更新待处理交易池，移除已经被确认的交易和与之双花的交易
                             let mut pending_transactions = pending_tx_for_network.lock().await;
-                            let initial_count = pending_transactions.len();
-                            
-                            // 收集所有区块中的交易哈希
-                            let mut confirmed_tx_hashes = std::collections::HashSet::new();
-                            for block in &blocks {
-                                for tx in &block.transactions {
-                                    confirmed_tx_hashes.insert(tx.calculate_hash());
-                                }
-                            }
-                            
-                            // 保留不在任何区块中的交易
-                            pending_transactions.retain(|tx| {
-                                let tx_hash = tx.calculate_hash();
-                                !confirmed_tx_hashes.contains(&tx_hash)
-                            });
-                            
-                            let removed_count = initial_count - pending_transactions.len();
+                            let removed_count = purge_mempool(&mut pending_transactions, &blockchain, &blocks);
                             if removed_count > 0 {
-                                println!("🗑️ 同步后从待处理池中移除了 {} 个已确认的交易", removed_count);
+                                println!("🗑️ 同步后从待处理池中移除了 {} 个已确认或输入已被花费的交易", removed_count);
                                 println!("📊 待处理交易池剩余: {} 个交易", pending_transactions.len());
                             }
                         } else {
                             println!("收到的区块链无效，保留本地链");
                         }
-                    } else if blocks.len() == blockchain.blocks.len() {
+                    } else if blocks.len() == blockchain.len() {
                         // 检查是否是相同的链
                         let mut is_same_chain = true;
-                        for (i, block) in blocks.iter().enumerate() {
-                            if i < blockchain.blocks.len() {
-                                let local_hash = blockchain.blocks[i].calculate_hash();
-                                let received_hash = block.calculate_hash();
-                                if local_hash != received_hash {
-                                    is_same_chain = false;
-                                    break;
-                                }
+                        for (local_block, block) in blockchain.iter().zip(blocks.iter()) {
+                            if blockchain.block_hash(local_block) != blockchain.block_hash(block) {
+                                is_same_chain = false;
+                                break;
                             }
                         }
                         
@@ -388,25 +914,65 @@ async fn main() {
                     // 同步完成，重置同步状态
                     *sync_state_for_task.lock().await = false;
                 },
-                NetworkEvent::ConnectTo(_addr) => {
-                    // 连接逻辑已经在network模块中处理
+                NetworkEvent::BlockRequested { peer_id, request_id, query } => {
+                    let block = query.find(&*blockchain_for_network.lock().await).cloned();
+                    println!("\n📋 节点 {} 请求区块 {:?}，{}", peer_id, query, if block.is_some() { "发送区块" } else { "本地没有该区块" });
+                    if let Err(e) = network_tx_for_network.send(NetworkCommand::SendBlockTo { request_id, block }).await {
+                        eprintln!("回复区块请求失败: {}", e);
+                    }
+                },
+                NetworkEvent::BlockAnnounced { peer_id, hash, height } => {
+                    // 本地已经有该区块（例如自己挖出或已从其他节点获取）时不再请求区块内容
+                    if blockchain_for_network.lock().await.get_block_by_hash(&hash).is_some() {
+                        continue;
+                    }
+                    println!("\n📣 节点 {} 公告了新区块 {} (高度 {})，请求区块内容", peer_id, hash, height);
+                    let request = NetworkCommand::RequestBlock { peer_id, query: network::BlockQuery::Hash(hash) };
+                    if let Err(e) = network_tx_for_network.send(request).await {
+                        eprintln!("请求公告的区块失败: {}", e);
+                    }
+                },
+                NetworkEvent::BlockFetched { peer_id, block } => {
+                    let mut blockchain = blockchain_for_network.lock().await;
+                    let block_hash = blockchain.block_hash(&block);
+                    println!("\n📦 收到请求的区块: {}", block_hash);
+                    if blockchain.validate_block(&block) {
+                        blockchain.add_received_block(block.clone());
+                        let mut added = connect_orphans(&mut blockchain, &mut orphan_blocks, block_hash);
+                        added.push(block);
+                        notify_wallet(&mut *watcher_for_network.lock().await, &blockchain, added.len());
+                        println!("补齐了 {} 个区块，当前高度: {}", added.len(), blockchain.len());
+                        
+                        let removed_count = purge_mempool(&mut *pending_tx_for_network.lock().await, &blockchain, &added);
+                        if removed_count > 0 {
+                            println!("🗑️ 从待处理池中移除了 {} 个已确认或输入已被花费的交易", removed_count);
+                        }
+                    } else if let Some(request) = request_missing_parent(&blockchain, &mut orphan_blocks, block, Some(peer_id)) {
+                        // 请求到的区块的父区块也缺失，继续向同一节点请求
+                        drop(blockchain);
+                        if let Err(e) = network_tx_for_network.send(request).await {
+                            eprintln!("请求父区块失败: {}", e);
+                        }
+                    } else {
+                        println!("无法补齐缺失的区块，请求同步区块链");
+                        orphan_blocks.clear();
+                        let request = sync_request(&blockchain);
+                        drop(blockchain);
+                        if let Err(e) = network_tx_for_network.send(request).await {
+                            eprintln!("同步请求失败: {}", e);
+                        }
+                    }
                 },
                 NetworkEvent::PeerDiscovered(peer_id, addr) => {
                     println!("\n🔍 发现新节点: {} at {}", peer_id, addr);
                 },
                 NetworkEvent::PeerConnected(peer_id) => {
                     println!("\n✅ 节点已连接: {}", peer_id);
+                    connected_peers.insert(peer_id);
                     
-                    // 自动添加节点ID到地址映射表（暂时映射到节点ID本身，用户可以后续更新）
-                    {
-                        let mut mapping = address_mapping_for_network.lock().await;
-                        let peer_id_str = peer_id.to_string();
-                        if !mapping.contains_key(&peer_id_str) {
-                            // 暂时将节点ID映射到自己，用户可以通过菜单选项13更新为实际钱包地址
-                            mapping.insert(peer_id_str.clone(), peer_id_str.clone());
-                            println!("📝 节点ID已添加到地址映射表: {}", peer_id);
-                            println!("💡 提示: 你可以使用菜单选项13将此节点ID映射到实际钱包地址");
-                        }
+                    // 节点的钱包地址通常由地址公告写入地址簿，也可以手动添加
+                    if address_book_for_network.lock().await.get(&peer_id.to_string()).is_none() {
+                        println!("💡 提示: 你可以使用菜单选项13将此节点ID映射到实际钱包地址");
                     }
                     
                     // 检查是否已经在同步中
@@ -415,13 +981,10 @@ async fn main() {
                         *sync_in_progress = true;
                         drop(sync_in_progress); // 释放锁
                         
-                        // 等待一下让Gossipsub建立网格连接
-                        println!("等待网格连接建立...");
-                        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-                        
-                        // 发送网络同步请求（通过网络广播）
-                        println!("发送网络同步请求...");
-                        if let Err(e) = network_tx_for_network.send(NetworkEvent::RequestBlocks).await {
+                        // 只向新连接的节点分页请求共同区块之后的区块，不广播同步请求
+                        println!("向节点 {} 请求缺少的区块...", peer_id);
+                        let request = page_request(&*blockchain_for_network.lock().await, peer_id);
+                        if let Err(e) = network_tx_for_network.send(request).await {
                             eprintln!("发送网络同步请求失败: {}", e);
                             // 重置同步状态
                             *sync_state_for_task.lock().await = false;
@@ -431,13 +994,39 @@ async fn main() {
                     } else {
                         println!("同步已在进行中，跳过此次同步请求");
                     }
+                    
+                    // 对方离线期间创建的交易只存在于交易池中，重新广播让新节点也能收到
+                    if rebroadcast_mempool {
+                        let transactions = pending_tx_for_network.lock().await.pending_for_rebroadcast();
+                        if !transactions.is_empty() {
+                            println!("重新广播 {} 笔待处理交易", transactions.len());
+                        }
+                        for tx in transactions {
+                            if let Err(e) = network_tx_for_network.send(NetworkCommand::BroadcastTransaction(tx)).await {
+                                eprintln!("重新广播交易失败: {}", e);
+                                break;
+                            }
+                        }
+                    }
                 },
                 NetworkEvent::PeerDisconnected(peer_id) => {
                     println!("\n❌ 节点已断开: {}", peer_id);
+                    connected_peers.remove(&peer_id);
                 },
-                NetworkEvent::ConnectionInfo { connected_peers, all_peers } => {
+                NetworkEvent::AddressAnnounced { peer_id, wallet_address, nickname } => {
+                    let mut book = address_book_for_network.lock().await;
+                    if network::apply_address_announce(&mut book, &peer_id, &wallet_address, &nickname) {
+                        println!("📝 地址映射已更新: {} / {} -> {}", nickname, peer_id, wallet_address);
+                    } else {
+                        println!("📝 地址映射已更新: {} -> {}", peer_id, wallet_address);
+                    }
+                },
+                NetworkEvent::ConnectionInfo { connected_peers, all_peers, listen_addrs } => {
                     // 处理连接信息响应
                     println!("当前节点ID: {}", node_peer_id);
+                    for addr in &listen_addrs {
+                        println!("监听地址: {}", addr);
+                    }
                     println!("连接状态: {} 个连接", connected_peers.len());
                     println!();
                     
@@ -451,26 +1040,23 @@ async fn main() {
                                 println!("     网络地址: {}", address);
                             }
                             
-                            // 查找地址映射
-                            let mapping = address_mapping_for_network.lock().await;
+                            // 查找地址簿
+                            let book = address_book_for_network.lock().await;
                             let peer_id_str = peer_id.to_string();
-                            if let Some(mapped_addr) = mapping.get(&peer_id_str) {
-                                if mapped_addr != &peer_id_str {
-                                    println!("     钱包地址: {}", mapped_addr);
-                                } else {
-                                    println!("     钱包地址: 未设置 (使用菜单13添加映射)");
+                            match book.get(&peer_id_str) {
+                                Some(wallet_address) => {
+                                    println!("     钱包地址: {}", wallet_address);
+                                    
+                                    // 查找映射到同一钱包地址的用户名
+                                    let user_names: Vec<&str> = book.list().into_iter()
+                                        .filter(|(name, address)| *address == wallet_address && *name != peer_id_str)
+                                        .map(|(name, _)| name)
+                                        .collect();
+                                    if !user_names.is_empty() {
+                                        println!("     用户名: {}", user_names.join(", "));
+                                    }
                                 }
-                            }
-                            
-                            // 查找用户名映射
-                            let mut user_names = Vec::new();
-                            for (name, addr) in mapping.iter() {
-                                if addr == &peer_id_str && name != &peer_id_str {
-                                    user_names.push(name.clone());
-                                }
-                            }
-                            if !user_names.is_empty() {
-                                println!("     用户名: {}", user_names.join(", "));
+                                None => println!("     钱包地址: 未设置 (使用菜单13添加映射)"),
                             }
                             println!();
                         }
@@ -487,26 +1073,16 @@ async fn main() {
                             println!("  📱 节点ID: {}", peer_id);
                             println!("     网络地址: {}", addr);
                             
-                            // 查找地址映射
-                            let mapping = address_mapping_for_network.lock().await;
-                            let peer_id_str = peer_id.to_string();
-                            if let Some(mapped_addr) = mapping.get(&peer_id_str) {
-                                if mapped_addr != &peer_id_str {
-                                    println!("     钱包地址: {}", mapped_addr);
-                                }
+                            // 查找地址簿
+                            if let Some(wallet_address) = address_book_for_network.lock().await.get(&peer_id.to_string()) {
+                                println!("     钱包地址: {}", wallet_address);
                             }
                             println!();
                         }
                     }
                     
-                    // 显示地址映射统计
-                    let mapping = address_mapping_for_network.lock().await;
-                    println!("📋 地址映射统计:");
-                    println!("  总映射数: {}", mapping.len());
-                    let placeholder_count = mapping.values().filter(|v| v.ends_with("_placeholder")).count();
-                    if placeholder_count > 0 {
-                        println!("  占位符映射: {} (需要更新)", placeholder_count);
-                    }
+                    // 显示地址簿统计
+                    println!("📋 地址簿条目数: {}", address_book_for_network.lock().await.len());
                     
                     println!("================\n");
                 },
@@ -517,144 +1093,301 @@ async fn main() {
 
     // 命令行界面
     loop {
-        print!("\nBlockchain Demo Menu:\n");
-        print!("1. Create new transaction\n");
-        print!("2. Mine new block\n");
-        print!("3. Show balance\n");
-        print!("4. Show blockchain\n");
-        print!("5. Exit\n");
-        print!("6. Show pending transactions\n");
-        print!("7. Show all transactions\n");
-        print!("8. Connect to node\n");
-        print!("9. Sync blockchain\n");
-        print!("10. Show network status\n");
-        print!("11. Debug UTXO set\n");
-        print!("12. Show address mapping\n");
-        print!("13. Add address mapping\n");
-        print!("14. Show connected users\n");
+        println!("\nBlockchain Demo Menu:");
+        println!("1. Create new transaction");
+        println!("2. Mine new block");
+        println!("3. Show balance");
+        println!("4. Show blockchain");
+        println!("5. Exit");
+        println!("6. Show pending transactions");
+        println!("7. Show all transactions");
+        println!("8. Connect to node");
+        println!("9. Sync blockchain");
+        println!("10. Show network status");
+        println!("11. Debug UTXO set");
+        println!("12. Show address book");
+        println!("13. Add or remove address book entry");
+        println!("14. Show connected users");
+        println!("15. Export chain data (CSV/JSON-lines)");
+        println!("16. Create new wallet");
+        println!("17. List wallets");
+        println!("18. Switch active wallet");
+        println!("19. Import wallet from file");
+        println!("20. Restore wallet from recovery phrase");
+        println!("21. Show recovery phrase");
+        println!("22. New receiving address");
+        println!("23. Import private key (hex/WIF)");
+        println!("24. Export private key (WIF/hex)");
+        println!("25. Add watch-only wallet");
+        println!("26. Sign unsigned transaction file");
+        println!("27. Import signed transaction and broadcast");
+        println!("28. List unspent outputs");
+        println!("29. Sign message");
+        println!("30. Verify message");
+        println!("31. Show node metrics");
+        println!("32. Export chain or block as JSON");
+        println!("33. Embed data in chain (OP_RETURN)");
+        println!("34. Import/export address book (CSV)");
+        println!("35. Rescan blocks and rebuild UTXO set");
+        println!("36. Show block reward schedule");
         print!("Enter your choice: ");
         io::stdout().flush().unwrap();
         
         let mut choice = String::new();
         io::stdin().read_line(&mut choice).unwrap();
         
+        let wallet = wallets.active_wallet();
+        
         match choice.trim() {
             "1" => {
                 // 创建新交易
-                print!("Enter recipient address (支持: 钱包地址/用户名/节点ID): ");
+                print!("Enter recipient address (支持: 钱包地址/用户名/节点ID，多个接收者: addr1=10,addr2=25): ");
                 io::stdout().flush().unwrap();
                 let mut to_address = String::new();
                 io::stdin().read_line(&mut to_address).unwrap();
                 
-                // 解析地址
-                let resolved_address = resolve_address(to_address.trim(), &address_mapping_for_main).await;
+                // 解析接收者列表，单个接收者时再询问金额
+                let recipients = if to_address.contains('=') {
+                    match parse_recipients(to_address.trim(), &address_book_for_main).await {
+                        Ok(recipients) => recipients,
+                        Err(e) => {
+                            println!("{}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    let resolved_address = match resolve_address(to_address.trim(), &address_book_for_main).await {
+                        Ok(address) => address,
+                        Err(e) => {
+                            println!("{}", e);
+                            continue;
+                        }
+                    };
+                    
+                    print!("Enter amount: ");
+                    io::stdout().flush().unwrap();
+                    let mut amount = String::new();
+                    io::stdin().read_line(&mut amount).unwrap();
+                    
+                    match amount.trim().parse() {
+                        Ok(amount) => vec![(resolved_address, amount)],
+                        Err(_) => {
+                            println!("Invalid amount: {}", amount.trim());
+                            continue;
+                        }
+                    }
+                };
+                let amount: u64 = recipients.iter().map(|(_, value)| value).sum();
+                let resolved: Vec<&str> = recipients.iter().map(|(address, _)| address.as_str()).collect();
                 
-                print!("Enter amount: ");
+                // 可以按近期区块的手续费率估算，留空使用钱包的默认费率
+                print!("Target confirmation blocks (Enter for default fee rate): ");
                 io::stdout().flush().unwrap();
-                let mut amount = String::new();
-                io::stdin().read_line(&mut amount).unwrap();
+                let mut target = String::new();
+                io::stdin().read_line(&mut target).unwrap();
+                let target: Option<u32> = match target.trim() {
+                    "" => None,
+                    value => match value.parse() {
+                        Ok(target) => Some(target),
+                        Err(_) => {
+                            println!("Invalid block count: {}", value);
+                            continue;
+                        }
+                    },
+                };
                 
-                let amount: u64 = amount.trim().parse().unwrap();
+                // 获取钱包所有地址的UTXO，等待用户确认时不持有区块链锁；
+                // 已被待处理交易花费的UTXO被锁定，不会再次选为输入
+                let (own_utxos, locked_amount, chain_fee_rate): (Vec<_>, u64, Option<u64>) = {
+                    let blockchain = blockchain.lock().await;
+                    let utxos: Vec<_> = wallet.addresses().iter()
+                        .flat_map(|address| blockchain.get_utxos_for_address(address))
+                        .collect();
+                    let unlocked = pending_tx_for_main.lock().await.unlocked_utxos(&utxos);
+                    let total: u64 = utxos.iter().map(|(_, entry)| entry.value).sum();
+                    let available: u64 = unlocked.iter().map(|(_, entry)| entry.value).sum();
+                    (unlocked, total - available, target.map(|target| blockchain.estimate_fee_rate(target)))
+                };
+                let wallet_file = format!("{}_wallet.json", wallets.active_name());
+                let wallet = wallets.active_wallet_mut();
                 
-                // 获取区块链的锁以访问UTXO集
-                let blockchain_lock = blockchain.lock().await;
+                // 发送前估算并确认手续费
+                let estimate = match chain_fee_rate {
+                    Some(rate) => {
+                        println!("Estimated fee rate: {} per byte", rate);
+                        wallet.estimate_fee_multi_with_policy(&recipients, wallet::FeePolicy::PerByte(rate), &own_utxos)
+                            .map_err(|e| e.to_string())
+                    }
+                    None => wallet.estimate_fee_multi(&recipients, &own_utxos).ok_or_else(|| {
+                        // 无法估算时给出具体原因
+                        wallet.create_transaction_multi(&recipients, 0, &own_utxos)
+                            .err()
+                            .map(|e| e.to_string())
+                            .unwrap_or_else(|| String::from("余额不足以支付手续费"))
+                    }),
+                };
+                let fee = match estimate {
+                    Ok(fee) => fee,
+                    Err(reason) => {
+                        println!("Failed to create transaction: {}", reason);
+                        if locked_amount > 0 {
+                            println!("另有{}的资金被待处理交易锁定，交易确认后才能再次使用", locked_amount);
+                        }
+                        continue;
+                    }
+                };
+                print!("Amount {} + fee {} = {}. Confirm? (y/n): ", amount, fee, amount + fee);
+                io::stdout().flush().unwrap();
+                let mut confirm = String::new();
+                io::stdin().read_line(&mut confirm).unwrap();
+                if !confirm.trim().eq_ignore_ascii_case("y") {
+                    println!("Transaction cancelled");
+                    continue;
+                }
                 
-                if let Some(mut tx) = wallet.create_transaction(
-                    &resolved_address,
-                    amount,
-                    &blockchain_lock.utxo_set,
-                ) {
-                    wallet.sign_transaction(&mut tx);
-                    
-                    // 释放区块链锁，不再需要
-                    drop(blockchain_lock);
-                    
-                    // 添加到待处理交易池
-                    pending_tx_for_main.lock().await.push_back(tx.clone());
-                    
-                    // 使用通道发送交易
-                    if let Err(e) = network_tx.send(NetworkEvent::NewTransaction(tx)).await {
-                        eprintln!("Failed to send transaction: {}", e);
+                match wallet.create_transaction_multi(&recipients, fee, &own_utxos) {
+                    Ok(mut tx) => {
+                        // 观察钱包只能构建未签名交易，导出到文件交给离线钱包签名
+                        if wallet.is_watch_only() {
+                            print!("Enter file to export unsigned transaction: ");
+                            io::stdout().flush().unwrap();
+                            let mut path = String::new();
+                            io::stdin().read_line(&mut path).unwrap();
+                            
+                            let exported = wallet::UnsignedTransaction::new(tx, &own_utxos)
+                                .map(|unsigned| wallet::UnsignedTransaction { network_id: wallet.network_id.clone(), ..unsigned })
+                                .map_err(|e| e.to_string())
+                                .and_then(|unsigned| std::fs::write(path.trim(), unsigned.to_json()).map_err(|e| e.to_string()));
+                            match exported {
+                                Ok(()) => println!("未签名交易已导出到 {}，请用离线钱包签名后导入（菜单26、27）", path.trim()),
+                                Err(e) => println!("导出未签名交易失败: {}", e),
+                            }
+                            continue;
+                        }
+                        if let Err(e) = wallet.sign_transaction(&mut tx) {
+                            println!("Transaction created but not signed: {}", e);
+                            continue;
+                        }
+                        // 找零可能发送到了新派生的地址，立即保存钱包以免丢失密钥
+                        if let Err(e) = wallet::Wallet::save_wallet(wallet, &wallet_file) {
+                            eprintln!("⚠️ 保存钱包失败: {}", e);
+                        }
+                        
+                        // 添加到待处理交易池，并记录首次见到的高度用于手续费估算
+                        blockchain.lock().await.record_seen(&tx);
+                        pending_tx_for_main.lock().await.add(tx.clone());
+                        
+                        // 使用通道发送交易
+                        if let Err(e) = network_tx.send(NetworkCommand::BroadcastTransaction(tx)).await {
+                            eprintln!("Failed to send transaction: {}", e);
+                        }
+                        println!("Transaction created and added to pending pool!");
+                        println!("发送给: {} (解析为: {})", to_address.trim(), resolved.join(", "));
+                    }
+                    Err(e) => {
+                        println!("Failed to create transaction: {}", e);
+                        println!("目标地址: {} (解析为: {})", to_address.trim(), resolved.join(", "));
                     }
-                    println!("Transaction created and added to pending pool!");
-                    println!("发送给: {} (解析为: {})", to_address.trim(), resolved_address);
-                } else {
-                    println!("Failed to create transaction: insufficient funds");
-                    println!("目标地址: {} (解析为: {})", to_address.trim(), resolved_address);
                 }
             }
             "2" => {
-                // 创建Coinbase交易（挖矿奖励）
-                let coinbase_input = block::TxInput {
-                    prev_tx: String::from("0000000000000000000000000000000000000000000000000000000000000000"),
-                    prev_index: 0,
-                    script_sig: String::from("挖矿奖励"),
+                // 创建Coinbase交易（挖矿奖励），奖励支付给当前钱包，新区块接在链顶之后
+                let reward = {
+                    let blockchain = blockchain.lock().await;
+                    blockchain.block_reward_at_height(blockchain.len())
                 };
+                let mut coinbase_tx = coinbase_transaction(wallet, reward);
                 
-                let coinbase_output = block::TxOutput {
-                    value: 50, // 挖矿奖励
-                    script_pubkey: wallet.address.clone(),
+                // 从待处理交易池中按手续费率挑选交易（最多 N 个，并为Coinbase预留一个位置），
+                // 区块头和Coinbase交易之外的空间用于普通交易
+                let (max_tx_per_block, max_bytes) = {
+                    let blockchain = blockchain.lock().await;
+                    let mut template = block::Block::new(blockchain.block_hash(blockchain.tip()), blockchain.difficulty());
+                    template.transactions.push(coinbase_tx.clone());
+                    (10.min(blockchain.max_transactions() - 1), blockchain.max_block_size().saturating_sub(template.size()))
                 };
+                let selected = pending_tx_for_main.lock().await.select_transactions_within(max_tx_per_block, max_bytes);
+                let total_fees: u64 = selected.iter().map(|tx| tx.fee).sum();
+                // 挖矿奖励加上交易手续费，金额是定长编码，不影响区块大小
+                coinbase_tx.outputs[0].value += total_fees;
                 
-                let coinbase_tx = block::Transaction::new(
-                    vec![coinbase_input],
-                    vec![coinbase_output]
-                );
-                
-                // 从待处理交易池中获取交易
-                let mut transactions = Vec::new();
-                transactions.push(coinbase_tx);
+                // Coinbase交易在最前，其余交易保持手续费率从高到低的顺序
+                let mut transactions = vec![coinbase_tx];
+                transactions.extend(selected);
                 
-                // 添加所有待处理的交易（或者最多 N 个）
-                let max_tx_per_block = 10;
-                let mut tx_count = 0;
-                
-                while !pending_tx_for_main.lock().await.is_empty() && tx_count < max_tx_per_block {
-                    if let Some(tx) = pending_tx_for_main.lock().await.pop_front() {
-                        transactions.push(tx);
-                        tx_count += 1;
+                // 挖掘新区块，广播前先确认网络会接受它
+                let (block, height) = {
+                    let mut blockchain = blockchain.lock().await;
+                    let block = blockchain.mine_block(transactions);
+                    if let Err(e) = blockchain.check_block(&block) {
+                        println!("Mined block rejected, not broadcasting: {}", e);
+                        continue;
                     }
-                }
-                
-                // 挖掘新区块
-                blockchain.lock().await.add_block(transactions);
+                    blockchain.add_received_block(block.clone());
+                    notify_wallet(&mut *watcher.lock().await, &blockchain, 1);
+                    // 从待处理交易池中移除已打包的交易和与之双花的交易
+                    pending_tx_for_main.lock().await.revalidate_against_new_block(&block, &blockchain);
+                    (block, blockchain.height() as u64)
+                };
                 
                 // 使用通道广播新区块
-                if let Some(block) = blockchain.lock().await.blocks.last() {
-                    if let Err(e) = network_tx.send(NetworkEvent::NewBlock(block.clone())).await {
-                        eprintln!("Failed to broadcast block: {}", e);
-                    }
+                if let Err(e) = network_tx.send(NetworkCommand::BroadcastBlock { block, height }).await {
+                    eprintln!("Failed to broadcast block: {}", e);
                 }
                 println!("New block mined!");
             }
             "3" => {
-                // 显示余额
-                println!("{}'s balance: {}", user_id ,blockchain.lock().await.get_balance(&wallet.address));
+                // 显示余额，汇总钱包所有地址，并列出交易池中未确认的收支
+                let blockchain = blockchain.lock().await;
+                let addresses = wallet.addresses();
+                let breakdown = wallet.balance_detailed(&blockchain, &*pending_tx_for_main.lock().await);
+                println!("{}'s balance: {}", wallets.active_name(), breakdown.confirmed);
+                println!("  可花费: {}", breakdown.spendable);
+                if breakdown.pending_in > 0 || breakdown.pending_out > 0 {
+                    println!("  未确认收入: {}，未确认支出: {}", breakdown.pending_in, breakdown.pending_out);
+                }
+                // 未成熟的Coinbase奖励计入余额但暂时不能花费
+                let immature = breakdown.confirmed.saturating_sub(breakdown.pending_out + breakdown.spendable);
+                if immature > 0 {
+                    println!("  未成熟的Coinbase奖励: {}", immature);
+                }
+                if addresses.len() > 1 {
+                    for address in &addresses {
+                        println!("  {}: {}", address, blockchain.get_balance(address));
+                    }
+                }
             }
             "4" => {
                 // 显示区块链状态
+                let blockchain = blockchain.lock().await;
+                let stats = blockchain.statistics();
                 println!("Blockchain:");
-                for (i, block) in blockchain.lock().await.blocks.iter().enumerate() {
-                    println!("Block #{}", i);
-                    println!("  Hash: {}", block.calculate_hash());
-                    println!("  Previous Hash: {}", block.header.prev_hash);
-                    println!("  Timestamp: {}", block.header.timestamp);
-                    println!("  Nonce: {}", block.header.nonce);
-                    println!("  Transactions: {}", block.transactions.len());
+                println!("  Blocks: {}  Transactions: {}  UTXOs: {}",
+                    stats.total_blocks, stats.total_transactions, stats.utxo_count);
+                println!("  Circulating supply: {}  Total fees: {}",
+                    stats.circulating_supply, stats.total_fees);
+                println!("  Avg block time: {:.1}s  Avg txs per block: {:.2}",
+                    stats.average_block_time_secs, stats.average_txs_per_block);
+                println!("  Chain work: {}", blockchain.work_at_height(blockchain.height()));
+                println!();
+                for (i, block) in blockchain.iter().enumerate() {
+                    println!("#{} {}", i, block);
+                    println!("  Cumulative work: {}", blockchain.work_at_height(i));
                     println!();
                 }
             }
             "5" => {
-                // 退出程序
+                // 退出程序，通知网络任务保存节点存储后停止
+                let _ = network_tx.send(NetworkCommand::Shutdown).await;
                 println!("Goodbye!");
                 break;
             }
             "6" => {
                 // 显示待处理交易
-                println!("Pending Transactions: {}", pending_tx_for_main.lock().await.len());
-                for (i, tx) in pending_tx_for_main.lock().await.iter().enumerate() {
-                    println!("Transaction #{}", i);
-                    // 显示交易详情
+                let pending_transactions = pending_tx_for_main.lock().await;
+                println!("Pending Transactions: {} ({} bytes)", pending_transactions.len(), pending_transactions.total_size());
+                for (i, tx) in pending_transactions.iter().enumerate() {
+                    println!("#{} {:#}", i, tx);
                 }
             }
             "7" => {
@@ -678,7 +1411,7 @@ async fn main() {
                 match addr.trim().parse::<libp2p::Multiaddr>() {
                     Ok(multiaddr) => {
                         // 发送连接请求
-                        if let Err(e) = network_tx.send(NetworkEvent::ConnectTo(multiaddr.clone())).await {
+                        if let Err(e) = network_tx.send(NetworkCommand::Dial(multiaddr.clone())).await {
                             eprintln!("发送连接请求失败: {}", e);
                         } else {
                             println!("已发送连接请求: {}", addr.trim());
@@ -692,7 +1425,8 @@ async fn main() {
             "9" => {
                 // 同步区块链
                 println!("Requesting blockchain sync...");
-                if let Err(e) = network_tx.send(NetworkEvent::RequestBlocks).await {
+                let request = sync_request(&*blockchain.lock().await);
+                if let Err(e) = network_tx.send(request).await {
                     eprintln!("Failed to send block request: {}", e);
                 } else {
                     println!("Block request sent!");
@@ -723,41 +1457,594 @@ async fn main() {
                 blockchain_lock.debug_utxo_set(&address_to_debug);
             }
             "12" => {
-                // 显示地址映射表
-                println!("\n=== 地址映射表 ===");
-                let mapping = address_mapping.lock().await;
-                for (key, value) in mapping.iter() {
-                    println!("{}: {}", key, value);
+                // 显示地址簿
+                println!("\n=== 地址簿 ===");
+                let book = address_book.lock().await;
+                for (name, address) in book.list() {
+                    println!("{}: {}", name, address);
                 }
                 println!("================\n");
             }
             "13" => {
-                // 添加地址映射
-                print!("Enter address to map: ");
+                // 添加、更新或删除地址簿条目，修改立即保存
+                print!("Enter name: ");
                 io::stdout().flush().unwrap();
-                let mut new_address = String::new();
-                io::stdin().read_line(&mut new_address).unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                let name = name.trim();
+                if name.is_empty() {
+                    println!("名称不能为空");
+                    continue;
+                }
                 
-                print!("Enter mapped address: ");
+                print!("Enter wallet address (empty to remove): ");
                 io::stdout().flush().unwrap();
-                let mut mapped_address = String::new();
-                io::stdin().read_line(&mut mapped_address).unwrap();
+                let mut address = String::new();
+                io::stdin().read_line(&mut address).unwrap();
+                let address = address.trim();
                 
-                let mut mapping = address_mapping.lock().await;
-                mapping.insert(new_address.trim().to_string(), mapped_address.trim().to_string());
-                println!("地址映射已添加");
+                let mut book = address_book.lock().await;
+                if address.is_empty() {
+                    match book.remove(name) {
+                        Ok(Some(_)) => println!("已从地址簿删除: {}", name),
+                        Ok(None) => println!("地址簿中没有: {}", name),
+                        Err(e) => eprintln!("⚠️ 保存地址簿失败: {}", e),
+                    }
+                } else if let Err(e) = wallet::decode_address(address) {
+                    println!("'{}' 不是有效的地址: {}", address, e);
+                } else {
+                    match book.add(name, address) {
+                        Ok(Some(previous)) => println!("已更新 {}: {} -> {}", name, previous, address),
+                        Ok(None) => println!("已添加 {}: {}", name, address),
+                        Err(e) => eprintln!("⚠️ 保存地址簿失败: {}", e),
+                    }
+                }
             }
             "14" => {
                 // 显示连接用户信息
                 println!("\n=== 连接用户信息 ===");
                 
                 // 发送连接信息请求
-                if let Err(e) = network_tx.send(NetworkEvent::RequestConnectionInfo).await {
+                if let Err(e) = network_tx.send(NetworkCommand::QueryPeers).await {
                     eprintln!("发送连接信息请求失败: {}", e);
                 } else {
                     println!("正在获取连接信息...");
                 }
             }
+            "15" => {
+                // 导出链数据用于分析
+                print!("Export blocks or transactions? (b/t): ");
+                io::stdout().flush().unwrap();
+                let mut kind = String::new();
+                io::stdin().read_line(&mut kind).unwrap();
+                let kind = if kind.trim().eq_ignore_ascii_case("t") {
+                    blockchain::ExportKind::Transactions
+                } else {
+                    blockchain::ExportKind::Blocks
+                };
+                
+                print!("Enter output path (.csv or .jsonl): ");
+                io::stdout().flush().unwrap();
+                let mut path = String::new();
+                io::stdin().read_line(&mut path).unwrap();
+                let path = path.trim();
+                
+                let blockchain_lock = blockchain.lock().await;
+                let result = std::fs::File::create(path).and_then(|file| {
+                    let writer = io::BufWriter::new(file);
+                    if path.ends_with(".jsonl") {
+                        blockchain_lock.export_json_lines(writer, kind)
+                    } else {
+                        blockchain_lock.export_csv(writer, kind)
+                    }
+                });
+                match result {
+                    Ok(()) => println!("链数据已导出到: {}", path),
+                    Err(e) => eprintln!("导出失败: {}", e),
+                }
+            }
+            "16" => {
+                // 创建新钱包并保存到文件
+                print!("Enter new wallet name: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                let name = name.trim();
+                
+                if name.is_empty() || wallets.get(name).is_some() {
+                    println!("钱包名称为空或已存在");
+                    continue;
+                }
+                // 从新生成的助记词派生钱包，助记词可通过菜单21查看、菜单20恢复
+                let phrase = wallet::Wallet::generate_mnemonic();
+                let new_wallet = wallets.restore(name, &phrase).expect("新生成的助记词有效");
+                if let Err(e) = wallet::Wallet::save_wallet(new_wallet, &format!("{}_wallet.json", name)) {
+                    eprintln!("⚠️ 保存钱包失败: {}", e);
+                }
+                if let Err(e) = address_book.lock().await.add(name, &new_wallet.address) {
+                    eprintln!("⚠️ 保存地址簿失败: {}", e);
+                }
+                println!("已创建钱包 {}: {}", name, new_wallet.address);
+                println!("请妥善保存恢复短语: {}", phrase);
+            }
+            "17" => {
+                // 列出所有钱包
+                println!("\n=== 钱包列表 ===");
+                for (name, w) in wallets.iter() {
+                    let marker = if name == wallets.active_name() { "*" } else { " " };
+                    let kind = if w.is_watch_only() { " (watch-only)" } else { "" };
+                    println!("{} {}: {}{}", marker, name, w.address, kind);
+                }
+                println!("================\n");
+            }
+            "18" => {
+                // 切换当前钱包，并更新me/self映射
+                print!("Enter wallet name: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                
+                match wallets.set_active(name.trim()) {
+                    Ok(()) => {
+                        let address = wallets.active_wallet().address.clone();
+                        // 先锁区块链再锁观察器，与网络任务的加锁顺序一致
+                        let utxos = blockchain.lock().await.utxo_entries();
+                        watcher.lock().await.watch(wallets.active_wallet(), &utxos);
+                        let mut book = address_book.lock().await;
+                        for name in ["me", "self"] {
+                            if let Err(e) = book.add(name, &address) {
+                                eprintln!("⚠️ 保存地址簿失败: {}", e);
+                            }
+                        }
+                        drop(book);
+                        if let Err(e) = network_tx.send(NetworkCommand::BroadcastAddress(address.clone())).await {
+                            eprintln!("广播地址公告失败: {}", e);
+                        }
+                        println!("当前钱包: {} ({})", wallets.active_name(), address);
+                    }
+                    Err(e) => println!("切换钱包失败: {}", e),
+                }
+            }
+            "19" => {
+                // 从文件导入钱包
+                print!("Enter wallet file path: ");
+                io::stdout().flush().unwrap();
+                let mut path = String::new();
+                io::stdin().read_line(&mut path).unwrap();
+                
+                print!("Enter wallet name: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                let name = name.trim();
+                
+                match wallets.import(name, Path::new(path.trim())) {
+                    Ok(imported) => {
+                        if let Err(e) = address_book.lock().await.add(name, &imported.address) {
+                            eprintln!("⚠️ 保存地址簿失败: {}", e);
+                        }
+                        println!("已导入钱包 {}: {}", name, imported.address);
+                        let network_id = blockchain.lock().await.network_id().to_string();
+                        if imported.network_id != network_id {
+                            println!("⚠️ 该钱包属于网络 {}，当前区块链属于网络 {}", imported.network_id, network_id);
+                        }
+                    }
+                    Err(e) => println!("导入钱包失败: {}", e),
+                }
+            }
+            "20" => {
+                // 从助记词恢复钱包
+                print!("Enter recovery phrase: ");
+                io::stdout().flush().unwrap();
+                let mut phrase = String::new();
+                io::stdin().read_line(&mut phrase).unwrap();
+                
+                print!("Enter wallet name: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                let name = name.trim();
+                
+                match wallets.restore(name, &phrase) {
+                    Ok(restored) => {
+                        if let Err(e) = wallet::Wallet::save_wallet(restored, &format!("{}_wallet.json", name)) {
+                            eprintln!("⚠️ 保存钱包失败: {}", e);
+                        }
+                        if let Err(e) = address_book.lock().await.add(name, &restored.address) {
+                            eprintln!("⚠️ 保存地址簿失败: {}", e);
+                        }
+                        println!("已恢复钱包 {}: {}", name, restored.address);
+                    }
+                    Err(e) => println!("恢复钱包失败: {}", e),
+                }
+            }
+            "21" => {
+                // 显示当前钱包的恢复短语
+                match &wallet.mnemonic {
+                    Some(phrase) => {
+                        println!("⚠️ 任何获得恢复短语的人都能控制该钱包的资金");
+                        println!("钱包 {} 的恢复短语: {}", wallets.active_name(), phrase);
+                    }
+                    None => println!("钱包 {} 是随机生成的，没有恢复短语，请备份钱包文件", wallets.active_name()),
+                }
+            }
+            "22" => {
+                // 为当前钱包派生新的接收地址
+                let wallet_file = format!("{}_wallet.json", wallets.active_name());
+                let wallet = wallets.active_wallet_mut();
+                match wallet.new_address() {
+                    Ok(address) => {
+                        if let Err(e) = wallet::Wallet::save_wallet(wallet, &wallet_file) {
+                            eprintln!("⚠️ 保存钱包失败: {}", e);
+                        }
+                        let utxos = blockchain.lock().await.utxo_entries();
+                        watcher.lock().await.watch(wallet, &utxos);
+                        println!("新的接收地址: {}", address);
+                    }
+                    Err(e) => println!("无法派生新地址: {}", e),
+                }
+            }
+            "23" => {
+                // 导入十六进制或WIF格式的私钥
+                print!("Enter private key (hex or WIF): ");
+                io::stdout().flush().unwrap();
+                let mut key = String::new();
+                io::stdin().read_line(&mut key).unwrap();
+                
+                print!("Enter wallet name: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                let name = name.trim();
+                
+                match wallets.import_private_key(name, &key) {
+                    Ok(imported) => {
+                        if let Err(e) = wallet::Wallet::save_wallet(imported, &format!("{}_wallet.json", name)) {
+                            eprintln!("⚠️ 保存钱包失败: {}", e);
+                        }
+                        if let Err(e) = address_book.lock().await.add(name, &imported.address) {
+                            eprintln!("⚠️ 保存地址簿失败: {}", e);
+                        }
+                        println!("已导入钱包 {}: {}", name, imported.address);
+                    }
+                    Err(e) => println!("导入私钥失败: {}", e),
+                }
+            }
+            "24" => {
+                // 以WIF和十六进制格式导出当前钱包的主私钥，连同地址二维码组成纸钱包
+                print!("Private keys control all funds of this wallet. Show them? (y/N): ");
+                io::stdout().flush().unwrap();
+                let mut confirm = String::new();
+                io::stdin().read_line(&mut confirm).unwrap();
+                if !confirm.trim().eq_ignore_ascii_case("y") {
+                    println!("已取消导出");
+                    continue;
+                }
+                
+                match wallet.export_wif().and_then(|wif| Ok((wif, wallet.export_private_key_hex()?))) {
+                    Ok((wif, hex_key)) => {
+                        println!("⚠️ 任何获得私钥的人都能控制该地址的资金");
+                        println!("钱包 {} 的私钥(WIF): {}", wallets.active_name(), wif);
+                        println!("钱包 {} 的私钥(十六进制): {}", wallets.active_name(), hex_key);
+                        println!("地址 {}:", wallet.address);
+                        println!("{}", wallet.address_qr());
+                    }
+                    Err(e) => println!("无法导出私钥: {}", e),
+                }
+            }
+            "25" => {
+                // 添加观察钱包，只保存公钥或地址，不需要私钥
+                print!("Enter public key (hex) or address to watch: ");
+                io::stdout().flush().unwrap();
+                let mut key = String::new();
+                io::stdin().read_line(&mut key).unwrap();
+                
+                print!("Enter wallet name: ");
+                io::stdout().flush().unwrap();
+                let mut name = String::new();
+                io::stdin().read_line(&mut name).unwrap();
+                let name = name.trim();
+                
+                match wallets.watch(name, &key) {
+                    Ok(watched) => {
+                        if let Err(e) = wallet::Wallet::save_wallet(watched, &format!("{}_wallet.json", name)) {
+                            eprintln!("⚠️ 保存钱包失败: {}", e);
+                        }
+                        println!("已添加观察钱包 {}: {}", name, watched.address);
+                    }
+                    Err(e) => println!("添加观察钱包失败: {}", e),
+                }
+            }
+            "26" => {
+                // 离线签名：用当前钱包签名观察钱包导出的未签名交易
+                print!("Enter unsigned transaction file: ");
+                io::stdout().flush().unwrap();
+                let mut path = String::new();
+                io::stdin().read_line(&mut path).unwrap();
+                
+                let mut unsigned = match std::fs::read_to_string(path.trim()).map_err(|e| e.to_string())
+                    .and_then(|contents| wallet::UnsignedTransaction::from_json(&contents).map_err(|e| e.to_string())) {
+                    Ok(unsigned) => unsigned,
+                    Err(e) => {
+                        println!("无法读取未签名交易: {}", e);
+                        continue;
+                    }
+                };
+                if let Err(e) = wallet.sign_unsigned(&mut unsigned) {
+                    println!("签名失败: {}", e);
+                    continue;
+                }
+                
+                print!("Enter file to write signed transaction: ");
+                io::stdout().flush().unwrap();
+                let mut output = String::new();
+                io::stdin().read_line(&mut output).unwrap();
+                match std::fs::write(output.trim(), unsigned.to_json()) {
+                    Ok(()) => println!("已签名交易已写入 {}", output.trim()),
+                    Err(e) => println!("写入已签名交易失败: {}", e),
+                }
+            }
+            "27" => {
+                // 导入离线签名的交易，验证后加入交易池并广播
+                print!("Enter signed transaction file: ");
+                io::stdout().flush().unwrap();
+                let mut path = String::new();
+                io::stdin().read_line(&mut path).unwrap();
+                
+                let signed = match std::fs::read_to_string(path.trim()).map_err(|e| e.to_string())
+                    .and_then(|contents| wallet::UnsignedTransaction::from_json(&contents).map_err(|e| e.to_string())) {
+                    Ok(signed) => signed,
+                    Err(e) => {
+                        println!("无法读取已签名交易: {}", e);
+                        continue;
+                    }
+                };
+                if !signed.is_complete() {
+                    println!("交易尚未完成签名，不能广播");
+                    continue;
+                }
+                
+                let tx = signed.transaction;
+                // 先模拟提交，导出后引用的输出可能已被花费
+                let simulation = blockchain.lock().await.simulate_transaction(&tx);
+                if !simulation.would_succeed {
+                    println!("交易不会被接受:");
+                    for error in &simulation.errors {
+                        println!("  - {}", error);
+                    }
+                    continue;
+                }
+                print!("Are you sure? This transaction would consume {} coins and pay {} fee. (y/n): ",
+                    simulation.consumed, simulation.fee);
+                io::stdout().flush().unwrap();
+                let mut confirm = String::new();
+                io::stdin().read_line(&mut confirm).unwrap();
+                if !confirm.trim().eq_ignore_ascii_case("y") {
+                    println!("Transaction cancelled");
+                    continue;
+                }
+                {
+                    let mut blockchain = blockchain.lock().await;
+                    if !blockchain.validate_transaction(&tx) {
+                        println!("交易验证失败");
+                        continue;
+                    }
+                    blockchain.record_seen(&tx);
+                }
+                pending_tx_for_main.lock().await.add(tx.clone());
+                if let Err(e) = network_tx.send(NetworkCommand::BroadcastTransaction(tx)).await {
+                    eprintln!("Failed to send transaction: {}", e);
+                }
+                println!("已签名交易已加入交易池并广播");
+            }
+            "28" => {
+                // 列出当前钱包的未花费输出
+                let blockchain = blockchain.lock().await;
+                let unspent = wallet.list_unspent(&blockchain.utxo_set, &blockchain);
+                if unspent.is_empty() {
+                    println!("{} has no unspent outputs", wallets.active_name());
+                    continue;
+                }
+                println!("Unspent outputs of {}:", wallets.active_name());
+                for output in &unspent {
+                    println!("  {}:{}  value: {}  confirmations: {}",
+                        output.tx_id, output.output_index, output.value, output.confirmations);
+                }
+            }
+            "29" => {
+                // 用当前钱包的主地址签名消息，证明地址所有权
+                print!("Enter message to sign: ");
+                io::stdout().flush().unwrap();
+                let mut message = String::new();
+                io::stdin().read_line(&mut message).unwrap();
+                
+                match wallet.sign_message(message.trim().as_bytes()) {
+                    Ok(signature) => {
+                        println!("Address: {}", wallet.address);
+                        println!("Signature: {}", signature);
+                    }
+                    Err(e) => println!("签名失败: {}", e),
+                }
+            }
+            "30" => {
+                // 验证其他节点提供的消息签名
+                let mut fields = Vec::new();
+                for prompt in ["Enter address: ", "Enter message: ", "Enter signature: "] {
+                    print!("{}", prompt);
+                    io::stdout().flush().unwrap();
+                    let mut field = String::new();
+                    io::stdin().read_line(&mut field).unwrap();
+                    fields.push(field.trim().to_string());
+                }
+                
+                if wallet::verify_message(&fields[0], fields[1].as_bytes(), &fields[2]) {
+                    println!("✅ 签名有效，消息由 {} 的所有者签名", fields[0]);
+                } else {
+                    println!("❌ 签名无效");
+                }
+            }
+            "31" => {
+                let snapshot = blockchain.lock().await.metrics().snapshot();
+                println!("\n=== 节点指标 ===");
+                println!("{}", serde_json::to_string_pretty(&snapshot).unwrap());
+            }
+            "32" => {
+                // 导出区块链结构，便于交给其他工具处理
+                print!("Enter block height (empty for whole chain): ");
+                io::stdout().flush().unwrap();
+                let mut height = String::new();
+                io::stdin().read_line(&mut height).unwrap();
+                
+                let json = {
+                    let blockchain_lock = blockchain.lock().await;
+                    match height.trim() {
+                        "" => blockchain_lock.export_json(),
+                        height => match height.parse().ok().and_then(|h| blockchain_lock.export_block_json(h)) {
+                            Some(json) => json,
+                            None => {
+                                println!("区块不存在");
+                                continue;
+                            }
+                        },
+                    }
+                };
+                
+                print!("Enter output path (empty for stdout): ");
+                io::stdout().flush().unwrap();
+                let mut path = String::new();
+                io::stdin().read_line(&mut path).unwrap();
+                let path = path.trim();
+                
+                if path.is_empty() {
+                    println!("{}", json);
+                } else {
+                    match std::fs::write(path, json) {
+                        Ok(()) => println!("JSON已导出到: {}", path),
+                        Err(e) => eprintln!("导出失败: {}", e),
+                    }
+                }
+            }
+            "33" => {
+                // 在链上记录少量数据，例如文件哈希
+                print!("Enter data to embed (text, max {} bytes): ", block::MAX_OP_RETURN_DATA);
+                io::stdout().flush().unwrap();
+                let mut data = String::new();
+                io::stdin().read_line(&mut data).unwrap();
+                let data = data.trim_end_matches(['\r', '\n']);
+                
+                print!("Enter fee: ");
+                io::stdout().flush().unwrap();
+                let mut fee = String::new();
+                io::stdin().read_line(&mut fee).unwrap();
+                let fee: u64 = match fee.trim().parse() {
+                    Ok(fee) => fee,
+                    Err(_) => {
+                        println!("Invalid fee!");
+                        continue;
+                    }
+                };
+                
+                // 与转账相同，不使用已被待处理交易花费的UTXO
+                let own_utxos = {
+                    let blockchain = blockchain.lock().await;
+                    let utxos: Vec<_> = wallet.addresses().iter()
+                        .flat_map(|address| blockchain.get_utxos_for_address(address))
+                        .collect();
+                    pending_tx_for_main.lock().await.unlocked_utxos(&utxos)
+                };
+                let wallet = wallets.active_wallet_mut();
+                let mut tx = match wallet.embed_data(data.as_bytes(), fee, &own_utxos) {
+                    Ok(tx) => tx,
+                    Err(e) => {
+                        println!("Failed to create transaction: {}", e);
+                        continue;
+                    }
+                };
+                if let Err(e) = wallet.sign_transaction(&mut tx) {
+                    println!("Transaction created but not signed: {}", e);
+                    continue;
+                }
+                
+                blockchain.lock().await.record_seen(&tx);
+                pending_tx_for_main.lock().await.add(tx.clone());
+                if let Err(e) = network_tx.send(NetworkCommand::BroadcastTransaction(tx)).await {
+                    eprintln!("Failed to send transaction: {}", e);
+                }
+                println!("数据交易已加入待处理交易池，挖矿确认后即可在链上查到");
+            }
+            "34" => {
+                // 以CSV格式导入或导出地址簿，导入的条目立即保存
+                print!("Import or export? (i/e): ");
+                io::stdout().flush().unwrap();
+                let mut direction = String::new();
+                io::stdin().read_line(&mut direction).unwrap();
+                
+                print!("Enter CSV path: ");
+                io::stdout().flush().unwrap();
+                let mut path = String::new();
+                io::stdin().read_line(&mut path).unwrap();
+                let path = Path::new(path.trim());
+                
+                let mut book = address_book.lock().await;
+                if direction.trim().eq_ignore_ascii_case("i") {
+                    match book.import_csv(path) {
+                        Ok(count) => println!("已导入 {} 个条目，地址簿共 {} 个条目", count, book.len()),
+                        Err(e) => eprintln!("导入失败: {}", e),
+                    }
+                } else {
+                    match book.export_csv(path) {
+                        Ok(()) => println!("已导出 {} 个条目到: {}", book.len(), path.display()),
+                        Err(e) => eprintln!("导出失败: {}", e),
+                    }
+                }
+            }
+            "35" => {
+                // 从区块数据重建UTXO集合，修正与区块不一致的余额
+                let mut blockchain = blockchain.lock().await;
+                let report = blockchain.rescan();
+                if report.is_clean() {
+                    println!("✅ UTXO集合与区块数据一致，无需修正");
+                } else {
+                    println!("已修正UTXO集合: 补充 {} 个输出，移除 {} 个输出", report.added.len(), report.removed.len());
+                    for (address, previous, corrected) in &report.balance_changes {
+                        println!("  {}: {} -> {}", address, previous, corrected);
+                    }
+                    if let Err(e) = blockchain.save() {
+                        println!("❌ 保存区块链失败: {}", e);
+                    }
+                }
+                if !report.consistent {
+                    println!("❌ 重建后的UTXO集合与修改记录不一致");
+                }
+            }
+            "36" => {
+                // 显示区块奖励和下一次减半，可以查看指定区块的Coinbase交易
+                print!("Enter block height to show its coinbase (or press Enter to skip): ");
+                io::stdout().flush().unwrap();
+                let mut height = String::new();
+                io::stdin().read_line(&mut height).unwrap();
+                
+                let blockchain = blockchain.lock().await;
+                if !height.trim().is_empty() {
+                    match height.trim().parse::<usize>() {
+                        Ok(height) => match blockchain.get_coinbase_transaction(height) {
+                            Some(coinbase) => {
+                                println!("Reward at height {}: {}", height, blockchain.block_reward_at_height(height));
+                                println!("{:#}", coinbase);
+                            }
+                            None => println!("高度 {} 没有区块或区块没有Coinbase交易", height),
+                        },
+                        Err(_) => println!("无效的区块高度"),
+                    }
+                }
+                let next_height = blockchain.len();
+                println!("Next block height: {}  Reward: {}", next_height, blockchain.block_reward_at_height(next_height));
+                match blockchain.next_halving_height() {
+                    Some(halving) => println!("Next halving at height {} ({} blocks away), reward becomes {}",
+                        halving, halving - blockchain.height(), blockchain.block_reward_at_height(halving)),
+                    None => println!("Block reward never halves"),
+                }
+            }
             _ => {
                 println!("Invalid choice!");
             }
@@ -0,0 +1,346 @@
+//! # 交易池模块
+//!
+//! 实现待处理交易池（Mempool），保存已广播但尚未被打包进区块的交易。
+//!
+//! 该模块负责交易去重、按手续费挑选交易用于构建区块，在区块确认后清理交易，
+//! 驱逐长时间未被打包或输入已失效的交易，以及锁定待处理交易已经花费的输出。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::block::{Block, Hasher, OutPoint, Sha256Hasher, Transaction, UtxoEntry};
+use crate::blockchain::Blockchain;
+
+/// 交易在交易池中的默认最长停留时间
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// 同一笔交易两次重新广播之间的最短间隔
+pub const REBROADCAST_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 每次重新广播最多发送的交易数量
+pub const MAX_REBROADCAST_BATCH: usize = 100;
+
+/// 交易池条目，记录交易及其加入交易池的时间
+#[derive(Debug, Clone)]
+struct MempoolEntry {
+    tx: Transaction,
+    /// 加入时间（Unix秒）
+    added_at: i64,
+    /// 最近一次被选中重新广播的时间（Unix秒），None表示尚未重新广播
+    last_rebroadcast: Option<i64>,
+}
+
+/// 待处理交易池，按加入顺序保存尚未确认的交易
+///
+/// 交易按不包含签名的交易ID（见[`Transaction::txid`]）去重，
+/// 重新签名的同一笔交易不会被当作新交易重复加入
+#[derive(Clone)]
+pub struct Mempool {
+    /// 待处理的交易条目
+    entries: Vec<MempoolEntry>,
+    /// 计算交易ID使用的哈希算法，与区块链相同
+    hasher: Arc<dyn Hasher>,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mempool {
+    /// 创建空的交易池，交易ID使用默认的SHA256计算
+    pub fn new() -> Self {
+        Self::with_hasher(Arc::new(Sha256Hasher))
+    }
+
+    /// 创建空的交易池，交易ID使用指定的哈希算法计算
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法，见[`Blockchain::hasher`]
+    pub fn with_hasher(hasher: Arc<dyn Hasher>) -> Self {
+        Mempool {
+            entries: Vec::new(),
+            hasher,
+        }
+    }
+
+    /// 计算交易ID，与`Blockchain::calculate_tx_hash`的结果相同
+    fn tx_id(&self, tx: &Transaction) -> String {
+        tx.txid_with(self.hasher.as_ref())
+    }
+
+    /// 添加交易到交易池
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 要添加的交易
+    ///
+    /// # 返回值
+    ///
+    /// 如果交易是新的并已添加返回true；如果交易已存在返回false
+    pub fn add(&mut self, tx: Transaction) -> bool {
+        self.add_at(tx, chrono::Utc::now().timestamp())
+    }
+
+    /// 以指定的加入时间添加交易到交易池
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 要添加的交易
+    /// * `added_at` - 加入时间（Unix秒）
+    ///
+    /// # 返回值
+    ///
+    /// 如果交易是新的并已添加返回true；如果交易ID相同的交易已存在（包括签名不同的副本）返回false
+    pub fn add_at(&mut self, tx: Transaction, added_at: i64) -> bool {
+        if self.contains(&self.tx_id(&tx)) {
+            return false;
+        }
+        self.entries.push(MempoolEntry { tx, added_at, last_rebroadcast: None });
+        true
+    }
+
+    /// 检查交易池中是否存在指定交易ID的交易
+    pub fn contains(&self, tx_id: &str) -> bool {
+        self.iter().any(|tx| self.tx_id(tx) == tx_id)
+    }
+
+    /// 获取交易池中的交易数量
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 交易池是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 按加入顺序遍历交易池中的交易
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> + '_ {
+        self.entries.iter().map(|entry| &entry.tx)
+    }
+
+    /// 获取被交易池中交易花费的输出
+    ///
+    /// 这些输出在交易确认前不应再次被选为新交易的输入。
+    /// 锁定直接由交易池中的交易决定，交易被确认、驱逐或移除后对应的锁定随之释放
+    pub fn locked_outpoints(&self) -> HashSet<OutPoint> {
+        self.iter()
+            .flat_map(|tx| tx.inputs.iter())
+            .map(|input| OutPoint { tx_id: input.prev_tx.clone(), index: input.prev_index })
+            .collect()
+    }
+
+    /// 检查输出是否已被交易池中的交易花费
+    pub fn is_locked(&self, outpoint: &OutPoint) -> bool {
+        self.iter()
+            .flat_map(|tx| tx.inputs.iter())
+            .any(|input| input.prev_tx == outpoint.tx_id && input.prev_index == outpoint.index)
+    }
+
+    /// 过滤掉已被交易池中的交易锁定的UTXO
+    ///
+    /// # 参数
+    ///
+    /// * `utxos` - 候选的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回尚未被待处理交易花费的UTXO，顺序与输入相同
+    pub fn unlocked_utxos(&self, utxos: &[(OutPoint, UtxoEntry)]) -> Vec<(OutPoint, UtxoEntry)> {
+        let locked = self.locked_outpoints();
+        utxos.iter()
+            .filter(|(outpoint, _)| !locked.contains(outpoint))
+            .cloned()
+            .collect()
+    }
+
+    /// 挑选用于构建新区块的交易
+    ///
+    /// 交易按每字节手续费从高到低排列，交易不会从交易池中移除，
+    /// 区块确认后应调用`remove_confirmed`清理
+    ///
+    /// # 参数
+    ///
+    /// * `max_count` - 最多挑选的交易数量
+    ///
+    /// # 返回值
+    ///
+    /// 返回按手续费率排序的交易列表
+    pub fn select_transactions(&self, max_count: usize) -> Vec<Transaction> {
+        self.select_transactions_within(max_count, usize::MAX)
+    }
+
+    /// 挑选用于构建新区块的交易，总大小不超过给定的字节数
+    ///
+    /// 按每字节手续费从高到低依次挑选，放不下的交易被跳过，之后较小的交易仍可能被选中
+    ///
+    /// # 参数
+    ///
+    /// * `max_count` - 最多挑选的交易数量
+    /// * `max_bytes` - 挑选的交易按`Transaction::size`计算的总字节数上限
+    ///
+    /// # 返回值
+    ///
+    /// 返回按手续费率排序的交易列表
+    pub fn select_transactions_within(&self, max_count: usize, max_bytes: usize) -> Vec<Transaction> {
+        let transactions: Vec<Transaction> = self.iter().cloned().collect();
+        let mut remaining = max_bytes;
+        Block::transactions_sorted_by_fee(&transactions)
+            .into_iter()
+            .filter(|tx| match remaining.checked_sub(tx.size()) {
+                Some(left) => {
+                    remaining = left;
+                    true
+                }
+                None => false,
+            })
+            .take(max_count)
+            .cloned()
+            .collect()
+    }
+
+    /// 交易池中全部交易按`Transaction::size`计算的总字节数
+    pub fn total_size(&self) -> usize {
+        self.iter().map(Transaction::size).sum()
+    }
+
+    /// 移除已经被区块确认的交易，按交易ID比较，签名不同的同一交易也会被移除
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 已添加到区块链的区块
+    ///
+    /// # 返回值
+    ///
+    /// 返回被移除的交易数量
+    pub fn remove_confirmed(&mut self, block: &Block) -> usize {
+        let confirmed: HashSet<String> = block.transactions.iter()
+            .map(|tx| self.tx_id(tx))
+            .collect();
+
+        let initial_count = self.entries.len();
+        let hasher = Arc::clone(&self.hasher);
+        self.entries.retain(|entry| !confirmed.contains(&entry.tx.txid_with(hasher.as_ref())));
+        initial_count - self.entries.len()
+    }
+
+    /// 驱逐在交易池中停留超过指定时长的交易
+    ///
+    /// # 参数
+    ///
+    /// * `max_age` - 交易允许的最长停留时间
+    ///
+    /// # 返回值
+    ///
+    /// 返回被驱逐的交易数量
+    pub fn evict_expired(&mut self, max_age: Duration) -> usize {
+        let cutoff = chrono::Utc::now().timestamp() - max_age.as_secs() as i64;
+
+        let initial_count = self.entries.len();
+        self.entries.retain(|entry| entry.added_at >= cutoff);
+        initial_count - self.entries.len()
+    }
+
+    /// 选出需要重新广播的交易，例如有新节点连接时
+    ///
+    /// 在[`REBROADCAST_INTERVAL`]内已经重新广播过的交易不会再次选中，
+    /// 多个节点先后连接时同一笔交易不会反复发送。每次最多选出[`MAX_REBROADCAST_BATCH`]笔，
+    /// 选中的交易记录为已重新广播
+    ///
+    /// # 返回值
+    ///
+    /// 按加入顺序返回需要重新广播的交易
+    pub fn pending_for_rebroadcast(&mut self) -> Vec<Transaction> {
+        self.pending_for_rebroadcast_at(chrono::Utc::now().timestamp())
+    }
+
+    /// 以指定的当前时间选出需要重新广播的交易
+    ///
+    /// # 参数
+    ///
+    /// * `now` - 当前时间（Unix秒）
+    ///
+    /// # 返回值
+    ///
+    /// 按加入顺序返回需要重新广播的交易
+    pub fn pending_for_rebroadcast_at(&mut self, now: i64) -> Vec<Transaction> {
+        let interval = REBROADCAST_INTERVAL.as_secs() as i64;
+        self.entries.iter_mut()
+            .filter(|entry| entry.last_rebroadcast.is_none_or(|last| now - last >= interval))
+            .take(MAX_REBROADCAST_BATCH)
+            .map(|entry| {
+                entry.last_rebroadcast = Some(now);
+                entry.tx.clone()
+            })
+            .collect()
+    }
+
+    /// 区块接入本地链后清理交易池
+    ///
+    /// 先移除被区块确认的交易（按交易ID比较，签名不同的同一交易也会被移除），
+    /// 再移除输入已被花费的交易，例如与区块中的交易双花的交易。
+    /// 输入可以是UTXO集合中的输出，也可以是交易池中其他交易的输出，
+    /// 被移除交易的后代交易随之移除。交易没有锁定时间或序列号字段，不需要按新的高度重新评估
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 刚接入本地链的区块
+    /// * `blockchain` - 已接入该区块、UTXO集合已更新的区块链
+    ///
+    /// # 返回值
+    ///
+    /// 返回被移除的交易数量
+    pub fn revalidate_against_new_block(&mut self, block: &Block, blockchain: &Blockchain) -> usize {
+        let initial_count = self.entries.len();
+        let confirmed: HashSet<String> = block.transactions.iter()
+            .map(|tx| blockchain.calculate_tx_hash(tx))
+            .collect();
+        self.entries.retain(|entry| !confirmed.contains(&blockchain.calculate_tx_hash(&entry.tx)));
+        
+        loop {
+            let pool_outputs: HashSet<OutPoint> = self.entries.iter()
+                .flat_map(|entry| {
+                    let tx_id = blockchain.calculate_tx_hash(&entry.tx);
+                    (0..entry.tx.outputs.len() as u32).map(move |index| OutPoint { tx_id: tx_id.clone(), index })
+                })
+                .collect();
+            let before = self.entries.len();
+            self.entries.retain(|entry| {
+                entry.tx.inputs.iter().all(|input| {
+                    let unspent = blockchain.utxo_set.get(&input.prev_tx)
+                        .is_some_and(|outputs| outputs.iter().any(|(index, ..)| *index == input.prev_index));
+                    unspent || pool_outputs.contains(&OutPoint { tx_id: input.prev_tx.clone(), index: input.prev_index })
+                })
+            });
+            if self.entries.len() == before {
+                break;
+            }
+        }
+        initial_count - self.entries.len()
+    }
+
+    /// 根据当前UTXO集合重新验证交易池
+    ///
+    /// 任何输入已不在UTXO集合中的交易（例如输入已被其他区块中的交易花费）都会被移除
+    ///
+    /// # 参数
+    ///
+    /// * `utxo_set` - 当前的UTXO集合
+    ///
+    /// # 返回值
+    ///
+    /// 返回被移除的交易数量
+    pub fn revalidate(&mut self, utxo_set: &HashMap<String, Vec<(u32, u64, String)>>) -> usize {
+        let initial_count = self.entries.len();
+        self.entries.retain(|entry| {
+            entry.tx.inputs.iter().all(|input| {
+                utxo_set.get(&input.prev_tx)
+                    .is_some_and(|outputs| outputs.iter().any(|(index, ..)| *index == input.prev_index))
+            })
+        });
+        initial_count - self.entries.len()
+    }
+}
@@ -0,0 +1,119 @@
+//! # 节点指标模块
+//!
+//! 使用原子计数器记录节点活动（出块、收块、同步、交易验证、连接数），
+//! 区块链和网络层共享同一个`Metrics`实例，`snapshot`返回可序列化的当前值。
+
+use serde::{Serialize, Deserialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 节点运行指标
+///
+/// 所有计数器都是原子的，可以通过`Arc<Metrics>`在线程间共享而无需加锁
+#[derive(Debug, Default)]
+pub struct Metrics {
+    blocks_mined: AtomicU64,
+    blocks_received: AtomicU64,
+    blocks_rejected: AtomicU64,
+    blocks_synced: AtomicU64,
+    transactions_accepted: AtomicU64,
+    transactions_rejected: AtomicU64,
+    peers: AtomicU64,
+    seen_cache_hits: AtomicU64,
+    seen_cache_misses: AtomicU64,
+}
+
+/// 某一时刻的指标值
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// 本节点挖出的区块数（挖出后未必被接受）
+    pub blocks_mined: u64,
+    /// 收到的其他节点广播的区块数
+    pub blocks_received: u64,
+    /// 验证失败被拒绝的区块数
+    pub blocks_rejected: u64,
+    /// 分页同步时从其他节点收到的区块数
+    pub blocks_synced: u64,
+    /// 验证通过的交易数
+    pub transactions_accepted: u64,
+    /// 验证失败被拒绝的交易数
+    pub transactions_rejected: u64,
+    /// 当前连接的节点数
+    pub peers: u64,
+    /// 已处理消息缓存的命中数：丢弃的重复消息和拒绝重新广播的消息
+    #[serde(default)]
+    pub seen_cache_hits: u64,
+    /// 已处理消息缓存的未命中数：首次收到的消息和可以广播的本地消息
+    #[serde(default)]
+    pub seen_cache_misses: u64,
+}
+
+impl Metrics {
+    /// 创建所有计数器为零的指标
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录挖出一个区块
+    pub fn record_block_mined(&self) {
+        self.blocks_mined.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录收到一个其他节点广播的区块
+    pub fn record_block_received(&self) {
+        self.blocks_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录拒绝一个区块
+    pub fn record_block_rejected(&self) {
+        self.blocks_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录分页同步收到的一页区块
+    ///
+    /// # 参数
+    ///
+    /// * `count` - 该页的区块数
+    pub fn record_blocks_synced(&self, count: usize) {
+        self.blocks_synced.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// 记录一次交易验证的结果
+    ///
+    /// # 参数
+    ///
+    /// * `accepted` - 交易是否通过验证
+    pub fn record_transaction(&self, accepted: bool) {
+        let counter = if accepted { &self.transactions_accepted } else { &self.transactions_rejected };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 设置当前连接的节点数
+    pub fn set_peers(&self, peers: usize) {
+        self.peers.store(peers as u64, Ordering::Relaxed);
+    }
+
+    /// 记录一次已处理消息缓存的查询结果
+    ///
+    /// # 参数
+    ///
+    /// * `hit` - 消息是否已经处理过
+    pub fn record_seen_cache(&self, hit: bool) {
+        let counter = if hit { &self.seen_cache_hits } else { &self.seen_cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 读取所有指标的当前值
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            blocks_mined: self.blocks_mined.load(Ordering::Relaxed),
+            blocks_received: self.blocks_received.load(Ordering::Relaxed),
+            blocks_rejected: self.blocks_rejected.load(Ordering::Relaxed),
+            blocks_synced: self.blocks_synced.load(Ordering::Relaxed),
+            transactions_accepted: self.transactions_accepted.load(Ordering::Relaxed),
+            transactions_rejected: self.transactions_rejected.load(Ordering::Relaxed),
+            peers: self.peers.load(Ordering::Relaxed),
+            seen_cache_hits: self.seen_cache_hits.load(Ordering::Relaxed),
+            seen_cache_misses: self.seen_cache_misses.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -5,50 +5,271 @@
 //! 该模块基于libp2p库构建，提供了分布式网络通信的基础设施。
 
 use libp2p::{
+    autonat,
     identity,
     ping,
-    swarm::{NetworkBehaviour, SwarmEvent, Swarm},
+    relay,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent, Swarm},
     PeerId,
     futures::StreamExt,
     gossipsub,
     mdns,
     kad,
+    request_response,
     Multiaddr,
+    StreamProtocol,
+    multiaddr::Protocol,
 };
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use lru::LruCache;
 use tokio::sync::mpsc;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use std::collections::{HashMap, HashSet};
-use std::time::Duration;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::error::Error;
 use serde::{Serialize, Deserialize};
-use crate::block::{Block, Transaction};
+use thiserror::Error;
+use tracing::{debug, error, info, warn};
+use crate::block::{Block, Hasher, Transaction};
 use crate::blockchain::Blockchain;
+use crate::metrics::Metrics;
+use crate::wallet::AddressBook;
+use std::sync::Arc;
 
-/// 网络事件枚举，表示节点间可以传递的消息类型
+/// 网络事件枚举，网络任务发给应用层的通知
+///
+/// 应用层发给网络任务的操作见[`NetworkCommand`]，两者不共用同一个通道，
+/// 收到的事件一定来自网络
 #[derive(Debug, Clone)]
 pub enum NetworkEvent {
-    /// 新区块事件，包含一个完整的区块
+    /// 收到其他节点广播的新区块
     NewBlock(Block),
-    /// 新交易事件，包含一个待处理的交易
+    /// 其他节点公告了新区块，应用层本地没有该区块时用`NetworkCommand::RequestBlock`向`peer_id`请求区块内容
+    ///
+    /// 同一哈希在[`ANNOUNCE_FETCH_TIMEOUT`]内只转发一次
+    BlockAnnounced {
+        peer_id: PeerId,
+        hash: String,
+        height: u64,
+    },
+    /// 收到其他节点广播的待处理交易
     NewTransaction(Transaction),
-    /// 请求区块事件，向其他节点请求区块数据
-    RequestBlocks,
-    /// 发送区块事件，响应区块请求
-    SendBlocks(Vec<Block>),
-    /// 连接到指定地址的节点
-    ConnectTo(libp2p::Multiaddr),
+    /// 节点请求完整的区块链，应用层用`NetworkCommand::SendBlocksTo`回复
+    BlocksRequested(PeerId),
+    /// 收到完整同步的区块响应
+    BlocksReceived(Vec<Block>),
+    /// 节点请求已知高度之后的区块
+    BlocksSinceRequested {
+        peer_id: PeerId,
+        known_height: usize,
+        known_hash: String,
+    },
+    /// 节点按区块定位器请求区块，`locator`见`Blockchain::block_locator`，只需发送最后一个共同区块之后的区块
+    BlocksLocatorRequested {
+        peer_id: PeerId,
+        known_height: usize,
+        locator: Vec<String>,
+    },
+    /// 收到增量同步响应，`blocks`从`start_height`开始
+    BlocksSinceReceived {
+        start_height: usize,
+        blocks: Vec<Block>,
+    },
     /// 发现新节点事件
     PeerDiscovered(PeerId, Multiaddr),
     /// 节点连接事件
     PeerConnected(PeerId),
     /// 节点断开事件
     PeerDisconnected(PeerId),
-    /// 请求连接信息事件
-    RequestConnectionInfo,
-    /// 连接信息响应事件
+    /// `NetworkCommand::QueryPeers`的响应
+    ///
+    /// `connected_peers`为已连接节点及其地址，`all_peers`为已发现节点、地址和是否已连接，
+    /// `listen_addrs`为本节点的监听地址
     ConnectionInfo {
         connected_peers: Vec<(PeerId, Option<String>)>,
         all_peers: Vec<(PeerId, String, bool)>,
+        listen_addrs: Vec<Multiaddr>,
+    },
+    /// 收到其他节点的钱包地址公告
+    AddressAnnounced {
+        peer_id: PeerId,
+        wallet_address: String,
+        nickname: String,
+    },
+    /// 收到其他节点的单个区块请求，应用层查找区块后用`NetworkCommand::SendBlockTo`回复
+    BlockRequested {
+        peer_id: PeerId,
+        request_id: request_response::RequestId,
+        query: BlockQuery,
+    },
+    /// 收到单个区块请求的响应
+    BlockFetched {
+        peer_id: PeerId,
+        block: Block,
+    },
+    /// 节点请求一页区块，应用层用`NetworkCommand::SendBlocksPage`回复，`limit`已限制在[`SYNC_PAGE_SIZE`]以内
+    BlocksPageRequested {
+        peer_id: PeerId,
+        request_id: request_response::RequestId,
+        from_height: u64,
+        limit: u64,
+    },
+    /// 节点按区块定位器请求共同区块之后的一页区块，应用层用`NetworkCommand::SendBlocksPageAfter`回复，
+    /// `limit`已限制在[`SYNC_PAGE_SIZE`]以内
+    BlocksPageAfterRequested {
+        peer_id: PeerId,
+        request_id: request_response::RequestId,
+        known_height: u64,
+        locator: Vec<String>,
+        limit: u64,
+    },
+    /// 收到一页区块，`blocks`从`from_height`开始，`more`为false表示对方没有更多区块
+    BlocksPageReceived {
+        peer_id: PeerId,
+        from_height: u64,
+        blocks: Vec<Block>,
+        more: bool,
+    },
+    /// 分页区块请求失败，例如对方不支持分页同步或连接已断开，应用层应改用广播同步
+    BlocksPageFailed {
+        peer_id: PeerId,
+        from_height: u64,
+    },
+    /// 网络服务开始监听，`peer_id`为swarm实际使用的本地节点ID
+    Listening {
+        peer_id: PeerId,
+        address: Multiaddr,
+    },
+}
+
+/// 网络命令枚举，应用层发给网络任务执行的操作
+#[derive(Debug, Clone)]
+pub enum NetworkCommand {
+    /// 连接到指定地址的节点
+    Dial(Multiaddr),
+    /// 广播本地产生的区块
+    ///
+    /// 所有节点都支持[`FEATURE_BLOCK_ANNOUNCE`]时只公告区块哈希和高度，对方按需请求区块内容；
+    /// 否则广播完整区块
+    BroadcastBlock {
+        block: Block,
+        height: u64,
+    },
+    /// 广播本地产生的交易
+    BroadcastTransaction(Transaction),
+    /// 请求从`from_height`开始的区块
+    ///
+    /// `locator`为本地区块定位器，为空时请求完整的区块链
+    RequestBlocks {
+        from_height: u64,
+        locator: Vec<String>,
+    },
+    /// 以完整同步响应回复节点的区块请求
+    ///
+    /// 区块响应经gossipsub发布，目标节点已经断开时不再发送
+    SendBlocksTo(PeerId, Vec<Block>),
+    /// 以增量同步响应回复节点的区块请求，`blocks`从`start_height`开始
+    SendBlocksSinceTo {
+        peer_id: PeerId,
+        start_height: usize,
+        blocks: Vec<Block>,
+    },
+    /// 向指定节点请求从指定高度开始的一页区块，结果以`NetworkEvent::BlocksPageReceived`返回
+    RequestBlocksFrom(PeerId, u64),
+    /// 回复分页区块请求
+    SendBlocksPage {
+        request_id: request_response::RequestId,
+        blocks: Vec<Block>,
+    },
+    /// 向指定节点发送本地链顶高度和区块定位器，请求双方共同区块之后的一页区块，
+    /// 结果以`NetworkEvent::BlocksPageReceived`返回
+    RequestBlocksAfter {
+        peer_id: PeerId,
+        known_height: u64,
+        locator: Vec<String>,
+    },
+    /// 回复按定位器的分页区块请求，`blocks`从`start_height`开始，`more`表示之后还有区块
+    SendBlocksPageAfter {
+        request_id: request_response::RequestId,
+        start_height: u64,
+        blocks: Vec<Block>,
+        more: bool,
+    },
+    /// 查询连接信息，结果以`NetworkEvent::ConnectionInfo`返回
+    QueryPeers,
+    /// 向已连接节点广播本节点的钱包地址（例如切换钱包之后）
+    BroadcastAddress(String),
+    /// 按NAT探测结果更新监听方式，节点位于NAT之后时通过中继服务器接受入站连接
+    UpdateNatStatus(NatStatus),
+    /// 断开与指定节点的连接，例如对方的协议版本过旧
+    DisconnectPeer(PeerId),
+    /// 向指定节点请求单个区块，不广播
+    RequestBlock {
+        peer_id: PeerId,
+        query: BlockQuery,
     },
+    /// 回复单个区块请求，`block`为None表示本地没有该区块
+    SendBlockTo {
+        request_id: request_response::RequestId,
+        block: Option<Block>,
+    },
+    /// 保存节点存储并停止网络任务
+    Shutdown,
+}
+
+/// 单个区块请求的查询条件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockQuery {
+    /// 按区块哈希查询
+    Hash(String),
+    /// 按区块高度查询
+    Height(usize),
+}
+
+impl BlockQuery {
+    /// 在本地区块链中查找满足条件的区块
+    ///
+    /// # 参数
+    ///
+    /// * `blockchain` - 本地区块链
+    pub fn find<'a>(&self, blockchain: &'a Blockchain) -> Option<&'a Block> {
+        match self {
+            BlockQuery::Hash(hash) => blockchain.get_block_by_hash(hash),
+            BlockQuery::Height(height) => blockchain.get(*height),
+        }
+    }
+}
+
+impl From<BlockQuery> for NetworkMessage {
+    fn from(query: BlockQuery) -> Self {
+        match query {
+            BlockQuery::Hash(hash) => NetworkMessage::GetBlock(hash),
+            BlockQuery::Height(height) => NetworkMessage::GetBlockByHeight(height),
+        }
+    }
+}
+
+/// 网络操作错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NetworkError {
+    /// 目标节点当前没有连接
+    #[error("节点{0}没有连接")]
+    PeerNotConnected(PeerId),
+    /// 网络事件通道已满或已关闭，网络任务可能已经停止
+    #[error("网络事件通道不可用")]
+    ChannelClosed,
+    /// 节点身份密钥文件无法读取或写入
+    #[error("无法访问节点密钥文件{path}: {reason}")]
+    IdentityIo { path: String, reason: String },
+    /// 节点身份密钥文件内容损坏
+    #[error("节点密钥文件{path}无效: {reason}")]
+    InvalidIdentity { path: String, reason: String },
 }
 
 /// 网络消息包装结构，用于网络传输
@@ -62,6 +283,377 @@ pub enum NetworkMessage {
     BlockRequest,
     /// 区块响应消息
     BlockResponse(Vec<Block>),
+    /// 钱包地址公告消息，在连接建立后发送
+    AddressAnnounce {
+        wallet_address: String,
+        nickname: String,
+    },
+    /// 二进制编码的区块消息（[`Block::to_bytes`]）
+    BlockBinary(Vec<u8>),
+    /// 二进制编码的区块响应消息
+    BlockResponseBinary(Vec<Vec<u8>>),
+    /// 能力声明消息，在连接建立后发送，用于协商是否使用二进制线路格式和压缩
+    Capabilities {
+        binary_blocks: bool,
+        /// 能否解压`compress_payload`压缩的消息，旧节点的声明中没有该字段
+        #[serde(default)]
+        compression: bool,
+    },
+    /// 中继服务器公告，NAT之后的节点可以通过该地址注册中继
+    RelayServerAnnounce(Multiaddr),
+    /// 增量区块请求，携带请求方链顶的高度和哈希
+    BlockRequestSince {
+        known_height: usize,
+        known_hash: String,
+    },
+    /// 增量区块响应，只包含请求方已知高度之后的区块
+    BlockResponseSince {
+        start_height: usize,
+        blocks: Vec<Block>,
+    },
+    /// 握手消息，在连接建立后发送，声明协议版本和支持的功能（见`FEATURE_*`常量）
+    Handshake {
+        protocol_version: u32,
+        supported_features: Vec<String>,
+    },
+    /// 按哈希请求单个区块，只通过定向请求发送，响应为`Block`或`BlockNotFound`
+    GetBlock(String),
+    /// 按高度请求单个区块，只通过定向请求发送
+    GetBlockByHeight(usize),
+    /// 单个区块请求的响应：对方没有请求的区块
+    BlockNotFound,
+    /// 创世区块握手，携带本节点的创世区块哈希，哈希不同的节点不能同步，连接会被断开
+    GenesisHandshake(String),
+    /// 区块定位器请求，携带请求方链顶高度和按指数间隔取的区块哈希，响应为`BlockResponseSince`
+    BlockRequestLocator {
+        known_height: usize,
+        locator: Vec<String>,
+    },
+    /// 分页区块请求，请求从`from_height`开始的最多`limit`个区块，只通过定向请求发送，响应为`Blocks`
+    GetBlocks {
+        from_height: u64,
+        limit: u64,
+    },
+    /// 分页区块响应，空列表表示响应方没有更多区块
+    Blocks(Vec<Block>),
+    /// 按定位器的分页区块请求，携带请求方链顶高度和区块定位器，只通过定向请求发送，响应为`BlocksPage`
+    GetBlocksAfter {
+        known_height: u64,
+        locator: Vec<String>,
+        limit: u64,
+    },
+    /// 按定位器的分页区块响应，`blocks`从`start_height`开始，`more`为false表示响应方没有更多区块；
+    /// 没有共同区块时`blocks`为空且`start_height`为0
+    BlocksPage {
+        start_height: u64,
+        blocks: Vec<Block>,
+        more: bool,
+    },
+    /// 新区块公告，只携带区块哈希和高度，接收方缺少该区块时通过定向请求`GetBlock`获取区块内容
+    BlockAnnounce {
+        hash: String,
+        height: u64,
+    },
+}
+
+impl NetworkMessage {
+    /// 计算消息内容的去重键
+    ///
+    /// 区块按区块哈希、交易按交易哈希去重，JSON和二进制编码的同一区块得到相同的键；
+    /// 请求、响应、公告等控制消息不去重
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法
+    pub fn dedup_key(&self, hasher: &dyn Hasher) -> Option<gossipsub::MessageId> {
+        match self {
+            NetworkMessage::Block(block) => Some(Self::block_key(&block.calculate_hash_with(hasher))),
+            NetworkMessage::BlockBinary(data) => Some(Self::block_key(&Block::from_bytes(data).ok()?.calculate_hash_with(hasher))),
+            NetworkMessage::Transaction(tx) => Some(Self::transaction_key(tx, hasher)),
+            _ => None,
+        }
+    }
+
+    /// 区块的去重键
+    fn block_key(hash: &str) -> gossipsub::MessageId {
+        gossipsub::MessageId::from(format!("block:{}", hash))
+    }
+
+    /// 交易的去重键
+    fn transaction_key(tx: &Transaction, hasher: &dyn Hasher) -> gossipsub::MessageId {
+        gossipsub::MessageId::from(format!("tx:{}", tx.calculate_hash_with(hasher)))
+    }
+}
+
+/// 二进制消息帧的魔数，JSON消息总是以`{`或`"`开头，不会与之冲突
+const BINARY_FRAME_MAGIC: u8 = 0xBC;
+
+/// 当前的二进制消息格式版本
+pub const WIRE_FORMAT_VERSION: u8 = 1;
+
+/// 压缩消息帧的标志字节，其后是gzip压缩的完整消息帧（JSON或二进制）
+const COMPRESSED_FRAME_FLAG: u8 = 0xCF;
+
+/// 超过该大小（字节）的消息在发送前压缩
+pub const COMPRESSION_THRESHOLD: usize = 16 * 1024;
+
+/// 解压后的消息大小上限，防止恶意节点发送压缩炸弹
+const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// 当前的网络协议版本，协议变更记录见CHANGELOG.md
+pub const PROTOCOL_VERSION: u32 = 8;
+
+/// 能够连接的最低协议版本，低于该版本的节点会被断开
+pub const MIN_SUPPORTED_VERSION: u32 = 1;
+
+/// 只发送`Capabilities`、不发送握手消息的旧节点视为该协议版本
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// 功能：二进制编码的区块消息
+pub const FEATURE_BINARY_BLOCKS: &str = "binary_blocks";
+
+/// 功能：压缩的消息帧
+pub const FEATURE_COMPRESSION: &str = "compression";
+
+/// 功能：增量区块同步（`BlockRequestSince`/`BlockResponseSince`）
+pub const FEATURE_INCREMENTAL_SYNC: &str = "incremental_sync";
+
+/// 功能：定向请求单个区块（`GetBlock`/`GetBlockByHeight`）
+pub const FEATURE_BLOCK_FETCH: &str = "block_fetch";
+
+/// 功能：区块定位器同步
+pub const FEATURE_BLOCK_LOCATOR: &str = "block_locator";
+
+/// 功能：通过定向请求分页同步区块（`GetBlocks`/`Blocks`）
+pub const FEATURE_PAGED_SYNC: &str = "paged_sync";
+
+/// 功能：按区块定位器分页同步（`GetBlocksAfter`/`BlocksPage`），分叉的节点只传输共同区块之后的区块
+pub const FEATURE_PAGED_LOCATOR: &str = "paged_locator";
+
+/// 功能：区块主题只广播区块公告（`BlockAnnounce`），区块内容通过定向请求获取
+pub const FEATURE_BLOCK_ANNOUNCE: &str = "block_announce";
+
+/// 分页同步时每页请求的区块数，也是响应方每页发送的区块数上限
+pub const SYNC_PAGE_SIZE: u64 = 100;
+
+/// 本节点支持的全部功能
+const SUPPORTED_FEATURES: [&str; 8] = [
+    FEATURE_BINARY_BLOCKS,
+    FEATURE_COMPRESSION,
+    FEATURE_INCREMENTAL_SYNC,
+    FEATURE_BLOCK_FETCH,
+    FEATURE_BLOCK_LOCATOR,
+    FEATURE_PAGED_SYNC,
+    FEATURE_PAGED_LOCATOR,
+    FEATURE_BLOCK_ANNOUNCE,
+];
+
+/// 节点的NAT状态，与AutoNAT探测的结果对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NatStatus {
+    /// 尚未完成探测
+    #[default]
+    Unknown,
+    /// 其他节点可以直接拨入
+    Public,
+    /// 节点位于NAT之后，只能通过中继接受入站连接
+    Private,
+}
+
+impl From<&autonat::NatStatus> for NatStatus {
+    fn from(status: &autonat::NatStatus) -> Self {
+        match status {
+            autonat::NatStatus::Public(_) => NatStatus::Public,
+            autonat::NatStatus::Private => NatStatus::Private,
+            autonat::NatStatus::Unknown => NatStatus::Unknown,
+        }
+    }
+}
+
+/// 计算通过中继服务器监听的电路地址（`<relay>/p2p-circuit`）
+///
+/// # 参数
+///
+/// * `relay` - 中继服务器地址，应包含中继节点的`/p2p/<peer_id>`
+///
+/// # 返回值
+///
+/// 在中继上预留监听位置的地址
+pub fn relay_circuit_addr(relay: &Multiaddr) -> Multiaddr {
+    relay.clone().with(Protocol::P2pCircuit)
+}
+
+/// 网络消息的线路格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// 旧的JSON格式，过渡期间用于尚未声明支持二进制格式的节点
+    Json,
+    /// 带版本标记的bincode格式：魔数 + 版本号 + bincode编码的消息
+    Binary,
+}
+
+/// 网络消息解码错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CodecError {
+    /// 消息为空
+    #[error("空消息")]
+    Empty,
+    /// 二进制消息的格式版本不受支持
+    #[error("不支持的消息格式版本: {0}")]
+    UnsupportedVersion(u8),
+    /// 消息内容无法解析
+    #[error("消息解析失败: {0}")]
+    Malformed(String),
+}
+
+/// 编码网络消息，发送路径统一使用该函数
+///
+/// # 参数
+///
+/// * `message` - 要发送的消息
+/// * `format` - 线路格式
+///
+/// # 返回值
+///
+/// 返回编码后的字节
+pub fn encode_message(message: &NetworkMessage, format: WireFormat) -> Vec<u8> {
+    match format {
+        WireFormat::Json => serde_json::to_vec(message).expect("网络消息可以被JSON编码"),
+        WireFormat::Binary => {
+            let mut data = vec![BINARY_FRAME_MAGIC, WIRE_FORMAT_VERSION];
+            bincode::serialize_into(&mut data, message).expect("网络消息可以被bincode编码");
+            data
+        }
+    }
+}
+
+/// 压缩超过`COMPRESSION_THRESHOLD`的已编码消息
+///
+/// 压缩后的消息以`COMPRESSED_FRAME_FLAG`开头，`decode_message`会自动解压；
+/// 小消息或压缩后没有变小的消息原样返回
+///
+/// # 参数
+///
+/// * `data` - `encode_message`编码的消息
+///
+/// # 返回值
+///
+/// 返回可能经过压缩的消息字节
+pub fn compress_payload(data: Vec<u8>) -> Vec<u8> {
+    if data.len() <= COMPRESSION_THRESHOLD {
+        return data;
+    }
+    
+    let mut encoder = GzEncoder::new(vec![COMPRESSED_FRAME_FLAG], Compression::default());
+    encoder.write_all(&data).expect("写入内存缓冲区不会失败");
+    let compressed = encoder.finish().expect("写入内存缓冲区不会失败");
+    if compressed.len() < data.len() { compressed } else { data }
+}
+
+/// 解码网络消息，接收路径统一使用该函数，自动识别压缩帧、二进制格式和旧的JSON格式
+///
+/// # 参数
+///
+/// * `data` - 收到的消息字节
+///
+/// # 返回值
+///
+/// 成功时返回解码的消息，失败时返回`CodecError`
+pub fn decode_message(data: &[u8]) -> Result<NetworkMessage, CodecError> {
+    match data {
+        [COMPRESSED_FRAME_FLAG, compressed @ ..] => {
+            let mut frame = Vec::new();
+            GzDecoder::new(compressed)
+                .take(MAX_DECOMPRESSED_SIZE + 1)
+                .read_to_end(&mut frame)
+                .map_err(|e| CodecError::Malformed(e.to_string()))?;
+            if frame.len() as u64 > MAX_DECOMPRESSED_SIZE {
+                return Err(CodecError::Malformed(String::from("解压后的消息过大")));
+            }
+            // 压缩帧内不允许再嵌套压缩帧
+            decode_frame(&frame)
+        }
+        _ => decode_frame(data),
+    }
+}
+
+/// 解码未压缩的消息帧
+fn decode_frame(data: &[u8]) -> Result<NetworkMessage, CodecError> {
+    match data {
+        [] => Err(CodecError::Empty),
+        [BINARY_FRAME_MAGIC, WIRE_FORMAT_VERSION, payload @ ..] => {
+            bincode::deserialize(payload).map_err(|e| CodecError::Malformed(e.to_string()))
+        }
+        [BINARY_FRAME_MAGIC, version, ..] => Err(CodecError::UnsupportedVersion(*version)),
+        _ => serde_json::from_slice(data).map_err(|e| CodecError::Malformed(e.to_string())),
+    }
+}
+
+/// 定向区块请求协议的名称
+pub const BLOCK_FETCH_PROTOCOL: StreamProtocol = StreamProtocol::new("/blockchain-demo/block-fetch/1");
+
+/// 定向请求和响应的大小上限（字节）
+const BLOCK_FETCH_MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// 定向区块请求的编解码器
+///
+/// 请求和响应都是`encode_message`编码的消息帧，编解码器只负责读写字节，
+/// 由网络层选择线路格式并解码，与gossipsub消息的处理方式一致
+#[derive(Debug, Clone, Default)]
+pub struct BlockFetchCodec;
+
+impl BlockFetchCodec {
+    async fn read_frame<T>(io: &mut T) -> io::Result<Vec<u8>>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut frame = Vec::new();
+        io.take(BLOCK_FETCH_MAX_SIZE).read_to_end(&mut frame).await?;
+        Ok(frame)
+    }
+
+    async fn write_frame<T>(io: &mut T, frame: Vec<u8>) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.write_all(&frame).await?;
+        io.close().await
+    }
+}
+
+#[async_trait::async_trait]
+impl request_response::Codec for BlockFetchCodec {
+    type Protocol = StreamProtocol;
+    type Request = Vec<u8>;
+    type Response = Vec<u8>;
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Vec<u8>>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Self::read_frame(io).await
+    }
+
+    async fn read_response<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Vec<u8>>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Self::read_frame(io).await
+    }
+
+    async fn write_request<T>(&mut self, _: &StreamProtocol, io: &mut T, request: Vec<u8>) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        Self::write_frame(io, request).await
+    }
+
+    async fn write_response<T>(&mut self, _: &StreamProtocol, io: &mut T, response: Vec<u8>) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        Self::write_frame(io, response).await
+    }
 }
 
 /// 自定义网络行为事件类型
@@ -75,6 +667,14 @@ pub enum MyBehaviourEvent {
     Mdns(mdns::Event),
     /// Kademlia事件
     Kademlia(kad::Event),
+    /// 定向区块请求事件
+    BlockFetch(request_response::Event<Vec<u8>, Vec<u8>>),
+    /// AutoNAT探测事件
+    Autonat(autonat::Event),
+    /// 中继客户端事件
+    RelayClient(relay::client::Event),
+    /// 中继服务器事件
+    Relay(relay::Event),
 }
 
 impl From<ping::Event> for MyBehaviourEvent {
@@ -101,6 +701,30 @@ impl From<kad::Event> for MyBehaviourEvent {
     }
 }
 
+impl From<request_response::Event<Vec<u8>, Vec<u8>>> for MyBehaviourEvent {
+    fn from(event: request_response::Event<Vec<u8>, Vec<u8>>) -> Self {
+        MyBehaviourEvent::BlockFetch(event)
+    }
+}
+
+impl From<autonat::Event> for MyBehaviourEvent {
+    fn from(event: autonat::Event) -> Self {
+        MyBehaviourEvent::Autonat(event)
+    }
+}
+
+impl From<relay::client::Event> for MyBehaviourEvent {
+    fn from(event: relay::client::Event) -> Self {
+        MyBehaviourEvent::RelayClient(event)
+    }
+}
+
+impl From<relay::Event> for MyBehaviourEvent {
+    fn from(event: relay::Event) -> Self {
+        MyBehaviourEvent::Relay(event)
+    }
+}
+
 /// 网络行为定义，实现了libp2p的NetworkBehaviour trait
 #[derive(NetworkBehaviour)]
 #[behaviour(to_swarm = "MyBehaviourEvent")]
@@ -113,20 +737,529 @@ pub struct MyBehaviour {
     mdns: mdns::tokio::Behaviour,
     /// Kademlia DHT 行为，用于分布式节点发现
     kademlia: kad::Behaviour<kad::store::MemoryStore>,
+    /// 请求-响应行为，用于向单个节点请求指定区块
+    block_fetch: request_response::Behaviour<BlockFetchCodec>,
+    /// AutoNAT 行为，探测本节点能否被其他节点直接拨入
+    autonat: autonat::Behaviour,
+    /// 中继客户端行为，位于NAT之后时在中继服务器上预留监听位置
+    relay_client: relay::client::Behaviour,
+    /// 中继服务器行为，只在启用中继服务时开启
+    relay: Toggle<relay::Behaviour>,
+}
+
+/// 默认的节点存储文件
+pub const PEER_STORE_FILE: &str = "peers.json";
+
+/// 节点记录的有效期（秒），超过7天未见的节点会被清理
+pub const PEER_EXPIRY_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// 节点存储的刷新间隔
+const PEER_STORE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 已转发消息缓存的默认容量，至少能容纳10个区块 × 5个节点的重复消息
+pub const SEEN_MESSAGES_CAPACITY: usize = 1000;
+
+/// 已转发消息缓存的默认有效期（秒），超过有效期的记录视为未见过
+pub const DEFAULT_SEEN_MESSAGES_TTL_SECS: u64 = 120;
+
+/// 最近公告区块缓存的容量
+pub const RECENT_ANNOUNCES_CAPACITY: usize = 256;
+
+/// 同一区块公告的去重时间，超时后同一哈希的公告会再次转发，以便从其他节点重新请求
+pub const ANNOUNCE_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 同一节点两次地址公告之间的最小间隔（秒）
+pub const ANNOUNCE_MIN_INTERVAL_SECS: u64 = 30;
+
+/// 每个节点消息令牌桶的默认容量
+pub const DEFAULT_RATE_LIMIT_CAPACITY: u32 = 100;
+
+/// 每个节点消息令牌桶每秒补充的默认令牌数
+///
+/// 长期平均每秒10条消息；桶初始是满的，因此最初10秒内最多可以收到容量加补充共200条
+pub const DEFAULT_RATE_LIMIT_REFILL: u32 = 10;
+
+/// 不当行为计分达到该值的节点被断开并拒绝再次连接
+pub const MISBEHAVIOR_BAN_THRESHOLD: u32 = 100;
+
+/// 断线重连的初始等待时间，之后每次失败翻倍
+pub const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(5);
+
+/// 断线重连等待时间的上限
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// 连续重连失败达到该次数后放弃重连
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+
+/// 默认为主动连接保留的连接槽位数，入站连接不能占用这些槽位
+pub const DEFAULT_OUTBOUND_RESERVE: usize = 2;
+
+/// 默认最大连接数
+pub const DEFAULT_MAX_CONNECTIONS: usize = 10;
+
+/// 没有指定监听地址时依次尝试的固定端口
+pub const FIXED_LISTEN_PORTS: std::ops::RangeInclusive<u16> = 40000..=40010;
+
+/// 默认的gossipsub心跳间隔（秒）
+pub const DEFAULT_GOSSIPSUB_HEARTBEAT_SECS: u64 = 10;
+
+/// 默认的单条gossipsub消息最大字节数，与libp2p的默认值一致
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 65536;
+
+/// 网络参数
+///
+/// 可以从配置文件读取，未给出的字段使用默认值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// 最大连接数
+    pub max_connections: usize,
+    /// 启动后主动连接的节点地址（multiaddr格式）
+    pub bootstrap_peers: Vec<String>,
+    /// gossipsub心跳间隔（秒）
+    pub gossipsub_heartbeat_secs: u64,
+    /// 单条gossipsub消息最大字节数
+    pub max_message_bytes: usize,
+    /// 已转发消息缓存的容量（条）
+    pub seen_cache_capacity: usize,
+    /// 已转发消息缓存的有效期（秒）
+    pub seen_cache_ttl_secs: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            bootstrap_peers: Vec::new(),
+            gossipsub_heartbeat_secs: DEFAULT_GOSSIPSUB_HEARTBEAT_SECS,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            seen_cache_capacity: SEEN_MESSAGES_CAPACITY,
+            seen_cache_ttl_secs: DEFAULT_SEEN_MESSAGES_TTL_SECS,
+        }
+    }
+}
+
+/// 节点记录在文件中的序列化形式
+#[derive(Debug, Serialize, Deserialize)]
+struct PeerRecord {
+    peer_id: String,
+    address: String,
+    last_seen: u64,
+}
+
+/// 节点存储，在重启之间持久化已知节点的地址
+///
+/// 每条记录包含节点ID、可拨号地址和最后一次见到该节点的时间（Unix秒）
+#[derive(Debug, Clone, Default)]
+pub struct PeerStore {
+    /// 存储文件路径，为None时只保存在内存中
+    path: Option<PathBuf>,
+    /// 节点记录列表
+    entries: Vec<(PeerId, Multiaddr, u64)>,
+}
+
+impl PeerStore {
+    /// 创建不落盘的空节点存储
+    pub fn in_memory() -> Self {
+        PeerStore::default()
+    }
+
+    /// 从文件加载节点存储
+    ///
+    /// 文件不存在或格式错误时返回空存储，加载后会清理过期的节点
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 存储文件路径
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let records: Vec<PeerRecord> = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        // 无法解析的记录直接丢弃
+        let entries = records.into_iter()
+            .filter_map(|record| {
+                let peer_id = record.peer_id.parse::<PeerId>().ok()?;
+                let address = record.address.parse::<Multiaddr>().ok()?;
+                Some((peer_id, address, record.last_seen))
+            })
+            .collect();
+
+        let mut store = PeerStore { path: Some(path), entries };
+        store.prune(unix_now());
+        store
+    }
+
+    /// 将节点存储写入文件
+    ///
+    /// 内存存储没有文件路径，直接返回成功
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let records: Vec<PeerRecord> = self.entries.iter()
+            .map(|(peer_id, address, last_seen)| PeerRecord {
+                peer_id: peer_id.to_string(),
+                address: address.to_string(),
+                last_seen: *last_seen,
+            })
+            .collect();
+        let serialized = serde_json::to_string_pretty(&records)?;
+        fs::write(path, serialized)
+    }
+
+    /// 记录节点地址，已存在的节点会更新地址和最后见到的时间
+    pub fn record(&mut self, peer_id: PeerId, address: Multiaddr, last_seen: u64) {
+        match self.entries.iter_mut().find(|(id, _, _)| *id == peer_id) {
+            Some(entry) => {
+                entry.1 = address;
+                entry.2 = entry.2.max(last_seen);
+            }
+            None => self.entries.push((peer_id, address, last_seen)),
+        }
+    }
+
+    /// 更新已知节点最后见到的时间
+    ///
+    /// # 返回值
+    ///
+    /// 如果节点存在于存储中返回true
+    pub fn touch(&mut self, peer_id: &PeerId, last_seen: u64) -> bool {
+        match self.entries.iter_mut().find(|(id, _, _)| id == peer_id) {
+            Some(entry) => {
+                entry.2 = entry.2.max(last_seen);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 清理超过有效期的节点
+    ///
+    /// # 参数
+    ///
+    /// * `now` - 当前时间（Unix秒）
+    ///
+    /// # 返回值
+    ///
+    /// 返回被清理的节点数量
+    pub fn prune(&mut self, now: u64) -> usize {
+        let initial_count = self.entries.len();
+        self.entries.retain(|(_, _, last_seen)| now.saturating_sub(*last_seen) <= PEER_EXPIRY_SECS);
+        initial_count - self.entries.len()
+    }
+
+    /// 获取最近见到的N个节点，按最后见到的时间从新到旧排列
+    pub fn best_peers(&self, n: usize) -> Vec<(PeerId, Multiaddr)> {
+        let mut entries: Vec<&(PeerId, Multiaddr, u64)> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.2));
+        entries.into_iter()
+            .take(n)
+            .map(|(peer_id, address, _)| (*peer_id, address.clone()))
+            .collect()
+    }
+
+    /// 获取节点最后见到的时间
+    pub fn last_seen(&self, peer_id: &PeerId) -> Option<u64> {
+        self.entries.iter()
+            .find(|(id, _, _)| id == peer_id)
+            .map(|(_, _, last_seen)| *last_seen)
+    }
+
+    /// 获取存储中的节点数量
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 存储是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// 从文件加载节点身份密钥，文件不存在时生成新的ed25519密钥并保存
+///
+/// 密钥以libp2p的protobuf编码保存，Unix系统上文件权限为0600，只有当前用户可读写。
+/// 损坏的文件不会被覆盖，以便手动恢复
+///
+/// # 参数
+///
+/// * `path` - 密钥文件路径
+///
+/// # 返回值
+///
+/// 成功返回密钥；文件无法读写返回`NetworkError::IdentityIo`，内容损坏返回`NetworkError::InvalidIdentity`
+pub fn load_or_create_identity(path: &Path) -> Result<identity::Keypair, NetworkError> {
+    let io_error = |e: io::Error| NetworkError::IdentityIo { path: path.display().to_string(), reason: e.to_string() };
+    match fs::read(path) {
+        Ok(data) => identity::Keypair::from_protobuf_encoding(&data).map_err(|e| NetworkError::InvalidIdentity {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let keypair = identity::Keypair::generate_ed25519();
+            let encoded = keypair.to_protobuf_encoding().expect("ed25519密钥可以被编码");
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            options.open(path).and_then(|mut file| file.write_all(&encoded)).map_err(io_error)?;
+            info!(path = %path.display(), "已生成新的节点密钥");
+            Ok(keypair)
+        }
+        Err(e) => Err(io_error(e)),
+    }
+}
+
+/// 地址公告限速器，防止节点刷屏式地发送地址公告
+///
+/// 对每个节点记录最近一次被接受的公告时间，间隔不足的公告会被丢弃
+#[derive(Debug)]
+pub struct AnnounceRateLimiter {
+    min_interval: u64,
+    last_accepted: HashMap<PeerId, u64>,
+}
+
+impl AnnounceRateLimiter {
+    /// 创建限速器
+    ///
+    /// # 参数
+    ///
+    /// * `min_interval` - 同一节点两次公告之间的最小间隔（秒）
+    pub fn new(min_interval: u64) -> Self {
+        AnnounceRateLimiter {
+            min_interval,
+            last_accepted: HashMap::new(),
+        }
+    }
+
+    /// 判断是否接受来自节点的公告，接受时记录时间
+    ///
+    /// # 参数
+    ///
+    /// * `peer_id` - 发送公告的节点ID
+    /// * `now` - 当前Unix时间（秒）
+    ///
+    /// # 返回值
+    ///
+    /// 距离该节点上次被接受的公告已超过最小间隔时返回true
+    pub fn allow(&mut self, peer_id: PeerId, now: u64) -> bool {
+        match self.last_accepted.get(&peer_id) {
+            Some(&last) if now.saturating_sub(last) < self.min_interval => false,
+            _ => {
+                self.last_accepted.insert(peer_id, now);
+                true
+            }
+        }
+    }
+}
+
+impl Default for AnnounceRateLimiter {
+    fn default() -> Self {
+        Self::new(ANNOUNCE_MIN_INTERVAL_SECS)
+    }
+}
+
+/// 断线重连的指数退避状态
+///
+/// 每个节点独立记录连续失败次数，等待时间从`base`开始逐次翻倍直到`max`，
+/// 连接成功后清零，连续失败超过`max_attempts`次后放弃
+#[derive(Debug)]
+pub struct ReconnectBackoff {
+    base: Duration,
+    max: Duration,
+    max_attempts: u32,
+    attempts: HashMap<PeerId, u32>,
+}
+
+impl ReconnectBackoff {
+    /// 创建退避状态
+    ///
+    /// # 参数
+    ///
+    /// * `base` - 第一次重连前的等待时间
+    /// * `max` - 等待时间的上限
+    /// * `max_attempts` - 放弃前的最大重连次数
+    pub fn new(base: Duration, max: Duration, max_attempts: u32) -> Self {
+        ReconnectBackoff {
+            base,
+            max,
+            max_attempts,
+            attempts: HashMap::new(),
+        }
+    }
+
+    /// 记录一次断线或重连失败，计算下一次重连前的等待时间
+    ///
+    /// # 参数
+    ///
+    /// * `peer_id` - 断开的节点
+    ///
+    /// # 返回值
+    ///
+    /// 返回等待时间；重连次数已用完时返回None，并清除该节点的状态
+    pub fn next_delay(&mut self, peer_id: PeerId) -> Option<Duration> {
+        let attempts = self.attempts.entry(peer_id).or_insert(0);
+        if *attempts >= self.max_attempts {
+            self.attempts.remove(&peer_id);
+            return None;
+        }
+        let delay = self.base.saturating_mul(2u32.saturating_pow(*attempts)).min(self.max);
+        *attempts += 1;
+        Some(delay)
+    }
+
+    /// 节点是否正在等待重连
+    pub fn is_pending(&self, peer_id: &PeerId) -> bool {
+        self.attempts.contains_key(peer_id)
+    }
+
+    /// 连接成功后清除节点的退避状态
+    pub fn reset(&mut self, peer_id: &PeerId) {
+        self.attempts.remove(peer_id);
+    }
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self::new(RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY, MAX_RECONNECT_ATTEMPTS)
+    }
+}
+
+/// 令牌桶限速器，限制单个节点发送gossipsub消息的速率
+///
+/// 桶中最多保存`capacity`个令牌，每秒补充`refill_rate`个，每条消息消耗一个令牌
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    capacity: u32,
+    refill_rate: u32,
+    tokens: u32,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// 创建装满令牌的限速器
+    ///
+    /// # 参数
+    ///
+    /// * `capacity` - 令牌桶容量，即允许的突发消息数
+    /// * `refill_rate` - 每秒补充的令牌数
+    pub fn new(capacity: u32, refill_rate: u32) -> Self {
+        RateLimiter {
+            capacity,
+            refill_rate,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// 尝试消耗一个令牌
+    ///
+    /// # 返回值
+    ///
+    /// 桶中还有令牌时返回true
+    pub fn try_acquire(&mut self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    /// 在指定时刻尝试消耗一个令牌，先按经过的时间补充令牌
+    ///
+    /// 只补充整数个令牌，不足一个令牌的时间留到下次补充，避免频繁调用时令牌丢失
+    ///
+    /// # 参数
+    ///
+    /// * `now` - 当前时刻
+    ///
+    /// # 返回值
+    ///
+    /// 桶中还有令牌时返回true
+    pub fn try_acquire_at(&mut self, now: Instant) -> bool {
+        if self.refill_rate > 0 {
+            let elapsed = now.saturating_duration_since(self.last_refill);
+            let refill = elapsed.as_millis() * u128::from(self.refill_rate) / 1000;
+            if refill > 0 {
+                self.tokens = u128::from(self.tokens).saturating_add(refill).min(u128::from(self.capacity)) as u32;
+                let used_millis = (refill * 1000 / u128::from(self.refill_rate)) as u64;
+                self.last_refill += Duration::from_millis(used_millis);
+            }
+        }
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+
+    /// 当前剩余的令牌数
+    pub fn available(&self) -> u32 {
+        self.tokens
+    }
+}
+
+/// 将收到的地址公告写入地址簿
+///
+/// 节点ID总是映射到公告的钱包地址；昵称为空、是保留名（me/self）
+/// 或已指向本地钱包时不写入，避免远程节点覆盖本地用户的映射。
+///
+/// # 参数
+///
+/// * `address_book` - 地址簿，名称为用户名或节点ID
+/// * `peer_id` - 发送公告的节点ID
+/// * `wallet_address` - 公告的钱包地址
+/// * `nickname` - 公告的昵称
+///
+/// # 返回值
+///
+/// 昵称映射是否被写入
+pub fn apply_address_announce(
+    address_book: &mut AddressBook,
+    peer_id: &PeerId,
+    wallet_address: &str,
+    nickname: &str,
+) -> bool {
+    if let Err(e) = address_book.add(&peer_id.to_string(), wallet_address) {
+        warn!(error = %e, "无法保存地址簿");
+    }
+
+    let nickname = nickname.trim();
+    if nickname.is_empty() || nickname == "me" || nickname == "self" {
+        return false;
+    }
+    let own_address = address_book.get("me");
+    if own_address.is_some() && address_book.get(nickname) == own_address {
+        return false;
+    }
+    if let Err(e) = address_book.add(nickname, wallet_address) {
+        warn!(error = %e, "无法保存地址簿");
+    }
+    true
+}
+
+/// 当前Unix时间（秒）
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
 }
 
 /// 网络结构，封装P2P网络功能
 pub struct Network {
+    /// 节点身份密钥，启动swarm时使用同一密钥，保证节点ID在整个生命周期内不变
+    keypair: identity::Keypair,
     /// 节点ID
     peer_id: PeerId,
     /// 已知节点列表，键为节点ID，值为节点地址
     peers: HashMap<PeerId, String>,
     /// 连接的节点集合
     connected_peers: HashSet<PeerId>,
-    /// 事件发送器，用于向网络发送事件
-    event_sender: mpsc::Sender<NetworkEvent>,
-    /// 事件接收器，用于接收网络事件
-    event_receiver: mpsc::Receiver<NetworkEvent>,
+    /// 命令发送器，用于向网络任务发送命令
+    command_sender: mpsc::Sender<NetworkCommand>,
+    /// 命令接收器，网络任务从中读取应用层的命令
+    command_receiver: mpsc::Receiver<NetworkCommand>,
     /// 区块主题
     blocks_topic: gossipsub::IdentTopic,
     /// 交易主题
@@ -137,18 +1270,97 @@ pub struct Network {
     auto_connect_enabled: bool,
     /// 最大连接数
     max_connections: usize,
+    /// 为主动连接保留的槽位数
+    outbound_reserve: usize,
+    /// 由对方发起的入站连接节点集合
+    inbound_peers: HashSet<PeerId>,
     /// 应用层事件发送器
     app_event_sender: Option<mpsc::Sender<NetworkEvent>>,
+    /// 持久化的节点存储
+    peer_store: PeerStore,
+    /// 连接建立后公告的钱包地址和昵称
+    announce: Option<(String, String)>,
+    /// 地址公告限速器
+    announce_limiter: AnnounceRateLimiter,
+    /// 断线重连的退避状态
+    reconnect_backoff: ReconnectBackoff,
+    /// 每个节点的gossipsub消息限速器
+    peer_rate_limits: HashMap<PeerId, RateLimiter>,
+    /// 新建限速器使用的令牌桶容量和每秒补充数
+    rate_limit: (u32, u32),
+    /// 节点的不当行为计分，每条被限速丢弃的消息计1分，达到`MISBEHAVIOR_BAN_THRESHOLD`后断开并拒绝连接
+    misbehavior_scores: HashMap<PeerId, u32>,
+    /// 声明支持二进制线路格式的节点集合
+    binary_peers: HashSet<PeerId>,
+    /// 声明能够解压消息的节点集合
+    compression_peers: HashSet<PeerId>,
+    /// 最近处理过的区块和交易，值为(首次见到的时间, 是否从网络收到)，
+    /// 用于丢弃重复到达的消息，并拒绝重新广播刚从网络收到的内容
+    seen_messages: LruCache<gossipsub::MessageId, (Instant, bool)>,
+    /// 已处理消息记录的有效期
+    seen_ttl: Duration,
+    /// 配置的中继服务器地址
+    relay_server: Option<Multiaddr>,
+    /// 最近一次探测到的NAT状态
+    nat_status: NatStatus,
+    /// 已在中继上注册的电路地址
+    relay_listen_addr: Option<Multiaddr>,
+    /// 是否为其他节点提供中继服务
+    relay_service: bool,
+    /// AutoNAT确认的公网地址
+    public_addr: Option<Multiaddr>,
+    /// 节点协商的协议版本，旧节点记为`LEGACY_PROTOCOL_VERSION`
+    peer_versions: HashMap<PeerId, u32>,
+    /// 节点在握手中声明支持的功能
+    peer_features: HashMap<PeerId, HashSet<String>>,
+    /// 本节点的创世区块哈希，未设置时不交换创世区块握手
+    genesis_hash: Option<String>,
+    /// 创世区块与本节点不同的节点，不再接受与它们的连接
+    incompatible_peers: HashSet<PeerId>,
+    /// 节点运行指标，通常与区块链共享
+    metrics: Arc<Metrics>,
+    /// 区块公告和消息去重使用的哈希算法，与区块链一致
+    hasher: Arc<dyn Hasher>,
+    /// 等待应用层回复的单个区块请求，值为请求方和响应通道
+    pending_block_requests: HashMap<request_response::RequestId, (PeerId, request_response::ResponseChannel<Vec<u8>>)>,
+    /// 已发出、尚未收到响应的分页区块请求，值为请求的起始高度
+    pending_page_requests: HashMap<request_response::RequestId, u64>,
+    /// 最近收到或发出的区块公告，值为首次见到的时间，避免同时重复请求同一区块
+    recent_announces: LruCache<String, Instant>,
+    /// 已发出、尚未收到响应的按哈希区块请求，值为请求的区块哈希
+    pending_hash_requests: HashMap<request_response::RequestId, String>,
+    /// 分页同步时每页请求的区块数
+    sync_page_size: u64,
+    /// gossipsub心跳间隔
+    gossipsub_heartbeat: Duration,
+    /// 单条gossipsub消息最大字节数
+    max_message_bytes: usize,
+    /// 启动后主动连接的节点地址
+    bootstrap_peers: Vec<String>,
+    /// 指定的监听地址，未设置时依次尝试固定端口
+    listen_addr: Option<Multiaddr>,
+    /// 向其他节点公布的外部地址，用于NAT或端口映射之后的节点
+    external_addr: Option<Multiaddr>,
 }
 
 impl Network {
     /// 创建新的网络实例
     ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法，一般传入`Blockchain::hasher`的克隆。
+    ///   公告的区块哈希由对方在本地链上查找，必须与区块链使用同一算法
+    ///
     /// # 返回值
     ///
     /// 返回初始化的网络实例
-    pub async fn new() -> Self {
-        let (event_sender, event_receiver) = mpsc::channel(100);
+    pub async fn new(hasher: Arc<dyn Hasher>) -> Self {
+        Self::build(hasher, None)
+    }
+
+    /// 创建网络实例，节点存储默认只保存在内存中，见[`Network::with_peer_store_file`]
+    fn build(hasher: Arc<dyn Hasher>, app_event_sender: Option<mpsc::Sender<NetworkEvent>>) -> Self {
+        let (command_sender, command_receiver) = mpsc::channel(100);
         
         let id_keys = identity::Keypair::generate_ed25519();
         let peer_id = PeerId::from(id_keys.public());
@@ -157,30 +1369,567 @@ impl Network {
         let transactions_topic = gossipsub::IdentTopic::new("transactions");
         
         Network {
+            keypair: id_keys,
             peer_id,
             peers: HashMap::new(),
             connected_peers: HashSet::new(),
-            event_sender,
-            event_receiver,
+            command_sender,
+            command_receiver,
             blocks_topic,
             transactions_topic,
             swarm: None,
             auto_connect_enabled: true,
-            max_connections: 10,
-            app_event_sender: None,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            outbound_reserve: DEFAULT_OUTBOUND_RESERVE,
+            inbound_peers: HashSet::new(),
+            app_event_sender,
+            peer_store: PeerStore::in_memory(),
+            announce: None,
+            announce_limiter: AnnounceRateLimiter::default(),
+            reconnect_backoff: ReconnectBackoff::default(),
+            peer_rate_limits: HashMap::new(),
+            rate_limit: (DEFAULT_RATE_LIMIT_CAPACITY, DEFAULT_RATE_LIMIT_REFILL),
+            misbehavior_scores: HashMap::new(),
+            binary_peers: HashSet::new(),
+            compression_peers: HashSet::new(),
+            seen_messages: LruCache::new(NonZeroUsize::new(SEEN_MESSAGES_CAPACITY).unwrap()),
+            seen_ttl: Duration::from_secs(DEFAULT_SEEN_MESSAGES_TTL_SECS),
+            relay_server: None,
+            nat_status: NatStatus::Unknown,
+            relay_listen_addr: None,
+            relay_service: false,
+            public_addr: None,
+            peer_versions: HashMap::new(),
+            peer_features: HashMap::new(),
+            genesis_hash: None,
+            incompatible_peers: HashSet::new(),
+            metrics: Arc::new(Metrics::new()),
+            hasher,
+            pending_block_requests: HashMap::new(),
+            pending_page_requests: HashMap::new(),
+            recent_announces: LruCache::new(NonZeroUsize::new(RECENT_ANNOUNCES_CAPACITY).unwrap()),
+            pending_hash_requests: HashMap::new(),
+            sync_page_size: SYNC_PAGE_SIZE,
+            gossipsub_heartbeat: Duration::from_secs(DEFAULT_GOSSIPSUB_HEARTBEAT_SECS),
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            bootstrap_peers: Vec::new(),
+            listen_addr: None,
+            external_addr: None,
         }
     }
 
     /// 启用或禁用自动连接
     pub fn set_auto_connect(&mut self, enabled: bool) {
         self.auto_connect_enabled = enabled;
-        println!("自动连接已{}", if enabled { "启用" } else { "禁用" });
+        info!(enabled, "自动连接设置已更新");
+    }
+
+    /// 使用保存在文件中的节点身份，重启后节点ID保持不变
+    ///
+    /// 必须在`start`之前调用
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 密钥文件路径，不存在时生成并保存新密钥
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回使用该身份的网络实例，错误见[`load_or_create_identity`]
+    pub fn with_identity_file(mut self, path: impl AsRef<Path>) -> Result<Self, NetworkError> {
+        let keypair = load_or_create_identity(path.as_ref())?;
+        self.peer_id = PeerId::from(keypair.public());
+        self.keypair = keypair;
+        Ok(self)
+    }
+
+    /// 从文件加载节点存储，之后发现的节点保存到该文件
+    ///
+    /// 必须在`start`之前调用，未调用时节点存储只保存在内存中
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 节点存储文件路径，例如[`PEER_STORE_FILE`]
+    pub fn with_peer_store_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.peer_store = PeerStore::load(path);
+        self
+    }
+
+    /// 设置连接建立后向其他节点公告的钱包地址和昵称
+    ///
+    /// # 参数
+    ///
+    /// * `address` - 本节点的钱包地址
+    /// * `nickname` - 本节点用户的昵称
+    pub fn set_announce_address(&mut self, address: String, nickname: String) {
+        self.announce = Some((address, nickname));
+    }
+
+    /// 设置本节点的创世区块哈希，连接建立后通过`GenesisHandshake`发送给其他节点
+    ///
+    /// # 参数
+    ///
+    /// * `hash` - 本地区块链的创世区块哈希
+    pub fn set_genesis_hash(&mut self, hash: String) {
+        self.genesis_hash = Some(hash);
+    }
+
+    /// 处理节点发来的创世区块哈希
+    ///
+    /// 创世区块不同的两个节点各自维护互不相容的链，同步永远不会成功，
+    /// 因此记录该节点并拒绝之后与它的连接
+    ///
+    /// # 参数
+    ///
+    /// * `peer_id` - 发送握手的节点
+    /// * `hash` - 对方的创世区块哈希
+    ///
+    /// # 返回值
+    ///
+    /// 创世区块一致或本节点未设置创世区块哈希时返回true；需要断开连接时返回false
+    pub fn handle_genesis_handshake(&mut self, peer_id: PeerId, hash: &str) -> bool {
+        match &self.genesis_hash {
+            Some(local) if local != hash => {
+                self.incompatible_peers.insert(peer_id);
+                false
+            }
+            _ => {
+                self.incompatible_peers.remove(&peer_id);
+                true
+            }
+        }
+    }
+
+    /// 设置节点运行指标
+    ///
+    /// # 参数
+    ///
+    /// * `metrics` - 共享的指标实例，一般传入`Blockchain::metrics`的克隆
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        metrics.set_peers(self.connected_peers.len());
+        self.metrics = metrics;
+    }
+
+    /// 获取节点运行指标
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// 指定监听地址，例如在容器中固定端口
+    ///
+    /// 必须在`start`之前调用。设置后`start`只尝试该地址，绑定失败时立即返回错误
+    ///
+    /// # 参数
+    ///
+    /// * `addr` - 监听地址，例如`/ip4/0.0.0.0/tcp/40000`
+    pub fn set_listen_addr(&mut self, addr: Multiaddr) {
+        self.listen_addr = Some(addr);
+    }
+
+    /// 指定向其他节点公布的外部地址，用于NAT或端口映射之后、监听地址无法从外部拨入的节点
+    ///
+    /// 必须在`start`之前调用
+    ///
+    /// # 参数
+    ///
+    /// * `addr` - 其他节点可以拨入的地址
+    pub fn set_external_addr(&mut self, addr: Multiaddr) {
+        self.external_addr = Some(addr);
+    }
+
+    /// 设置NAT之后使用的中继服务器
+    ///
+    /// # 参数
+    ///
+    /// * `addr` - 中继服务器地址，应包含中继节点的`/p2p/<peer_id>`
+    pub fn set_relay_server(&mut self, addr: Multiaddr) {
+        self.relay_server = Some(addr);
+    }
+
+    /// 为位于NAT之后的节点提供中继服务
+    ///
+    /// 必须在`start`之前调用。AutoNAT确认本节点可以直接拨入，或者设置了外部地址时，
+    /// 向其他节点广播[`NetworkMessage::RelayServerAnnounce`]
+    pub fn enable_relay_service(&mut self) {
+        self.relay_service = true;
+    }
+
+    /// 获取最近一次探测到的NAT状态
+    pub fn nat_status(&self) -> NatStatus {
+        self.nat_status
+    }
+
+    /// 记录新的NAT状态，决定是否需要在中继上注册
+    ///
+    /// 节点位于NAT之后且配置了中继服务器时返回需要监听的电路地址，已经注册过则不再重复；
+    /// 节点可以直接拨入时放弃中继地址
+    ///
+    /// # 参数
+    ///
+    /// * `status` - AutoNAT探测的结果
+    ///
+    /// # 返回值
+    ///
+    /// 需要监听的中继电路地址
+    pub fn handle_nat_status(&mut self, status: NatStatus) -> Option<Multiaddr> {
+        self.nat_status = status;
+        match status {
+            NatStatus::Private if self.relay_listen_addr.is_none() => {
+                let circuit = relay_circuit_addr(self.relay_server.as_ref()?);
+                self.relay_listen_addr = Some(circuit.clone());
+                Some(circuit)
+            }
+            NatStatus::Public => {
+                self.relay_listen_addr = None;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// 记录节点的握手信息
+    ///
+    /// 版本低于`MIN_SUPPORTED_VERSION`的节点不被接受；其余节点按声明的功能决定可以发送的消息
+    ///
+    /// # 参数
+    ///
+    /// * `peer_id` - 发送握手消息的节点
+    /// * `protocol_version` - 对方的协议版本
+    /// * `supported_features` - 对方支持的功能
+    ///
+    /// # 返回值
+    ///
+    /// 接受该节点返回true；需要断开连接时返回false
+    pub fn handle_handshake(&mut self, peer_id: PeerId, protocol_version: u32, supported_features: Vec<String>) -> bool {
+        if protocol_version < MIN_SUPPORTED_VERSION {
+            self.forget_peer_protocol(&peer_id);
+            return false;
+        }
+        let features: HashSet<String> = supported_features.into_iter().collect();
+        if features.contains(FEATURE_BINARY_BLOCKS) {
+            self.binary_peers.insert(peer_id);
+        } else {
+            self.binary_peers.remove(&peer_id);
+        }
+        if features.contains(FEATURE_COMPRESSION) {
+            self.compression_peers.insert(peer_id);
+        } else {
+            self.compression_peers.remove(&peer_id);
+        }
+        self.peer_versions.insert(peer_id, protocol_version.min(PROTOCOL_VERSION));
+        self.peer_features.insert(peer_id, features);
+        true
+    }
+
+    /// 获取与节点协商的协议版本，尚未收到握手或能力声明时返回None
+    pub fn peer_version(&self, peer_id: &PeerId) -> Option<u32> {
+        self.peer_versions.get(peer_id).copied()
+    }
+
+    /// 检查所有已连接或已协商的节点是否都声明支持某个功能
+    ///
+    /// 没有任何节点时返回false，尚未握手的节点视为不支持
+    pub fn all_peers_support(&self, feature: &str) -> bool {
+        let mut peers = self.connected_peers.iter().chain(self.peer_versions.keys()).peekable();
+        peers.peek().is_some()
+            && peers.all(|peer| self.peer_features.get(peer).is_some_and(|features| features.contains(feature)))
+    }
+
+    /// 构建同步请求消息
+    ///
+    /// 所有节点都支持增量同步时只请求已知高度之后的区块，否则回退到旧节点也能理解的完整同步请求
+    ///
+    /// # 参数
+    ///
+    /// * `known_height` - 本地链顶高度
+    /// * `known_hash` - 本地链顶哈希
+    pub fn sync_request_message(&self, known_height: usize, known_hash: String) -> NetworkMessage {
+        if self.all_peers_support(FEATURE_INCREMENTAL_SYNC) {
+            NetworkMessage::BlockRequestSince { known_height, known_hash }
+        } else {
+            NetworkMessage::BlockRequest
+        }
+    }
+
+    /// 构建定位器同步请求消息
+    ///
+    /// 所有节点都支持定位器同步时发送完整的定位器，分叉的节点也只需接收分叉之后的区块；
+    /// 否则退回[`Network::sync_request_message`]，以定位器中的链顶哈希请求增量或完整同步
+    ///
+    /// # 参数
+    ///
+    /// * `known_height` - 本地链顶高度
+    /// * `locator` - 本地区块定位器，第一个哈希为链顶
+    pub fn locator_request_message(&self, known_height: usize, locator: Vec<String>) -> NetworkMessage {
+        if self.all_peers_support(FEATURE_BLOCK_LOCATOR) {
+            return NetworkMessage::BlockRequestLocator { known_height, locator };
+        }
+        let known_hash = locator.into_iter().next().unwrap_or_default();
+        self.sync_request_message(known_height, known_hash)
+    }
+
+    /// 删除节点的协议协商信息
+    fn forget_peer_protocol(&mut self, peer_id: &PeerId) {
+        self.inbound_peers.remove(peer_id);
+        self.peer_rate_limits.remove(peer_id);
+        self.binary_peers.remove(peer_id);
+        self.compression_peers.remove(peer_id);
+        self.peer_versions.remove(peer_id);
+        self.peer_features.remove(peer_id);
+    }
+
+    /// 根据NAT状态进行NAT穿透：通过中继传输监听电路地址，
+    /// 中继客户端连接中继服务器并预留位置，预留成功后电路地址作为外部地址交给Kademlia公告给其他节点
+    fn nat_traversal(&mut self, swarm: &mut Swarm<MyBehaviour>, status: NatStatus) {
+        let Some(circuit) = self.handle_nat_status(status) else {
+            return;
+        };
+        info!(circuit = %circuit, "节点位于NAT之后，通过中继监听");
+        if let Err(e) = swarm.listen_on(circuit) {
+            error!(error = %e, "在中继上监听失败");
+            self.relay_listen_addr = None;
+        }
+    }
+
+    /// 处理AutoNAT探测结果：位于NAT之后时注册中继，确认可直接拨入且提供中继服务时公告中继地址
+    fn handle_autonat_event(&mut self, swarm: &mut Swarm<MyBehaviour>, event: autonat::Event) {
+        let autonat::Event::StatusChanged { old, new } = event else {
+            return;
+        };
+        info!(old = ?old, new = ?new, "AutoNAT探测结果变化");
+        self.public_addr = match &new {
+            autonat::NatStatus::Public(addr) => Some(addr.clone()),
+            _ => None,
+        };
+        self.nat_traversal(swarm, NatStatus::from(&new));
+        if self.public_addr.is_some() {
+            self.publish_relay_announce(swarm);
+        }
+    }
+
+    /// 处理中继客户端事件，在中继上预留成功后公告电路地址
+    fn handle_relay_client_event(&mut self, swarm: &mut Swarm<MyBehaviour>, event: relay::client::Event) {
+        match event {
+            relay::client::Event::ReservationReqAccepted { relay_peer_id, renewal: false, .. } => {
+                info!(relay = %relay_peer_id, "中继服务器接受了预留");
+                if let Some(circuit) = &self.relay_listen_addr {
+                    swarm.add_external_address(circuit.clone().with(Protocol::P2p(self.peer_id)));
+                }
+            }
+            relay::client::Event::ReservationReqFailed { relay_peer_id, error, .. } => {
+                warn!(relay = %relay_peer_id, error = %error, "中继预留失败");
+                // 清除注册记录，之后再次探测到位于NAT之后时重新注册
+                self.relay_listen_addr = None;
+            }
+            event => debug!(event = ?event, "中继客户端事件"),
+        }
+    }
+
+    /// 本节点作为中继服务器时公告的地址：AutoNAT确认的公网地址，其次是设置的外部地址
+    fn relay_announce_addr(&self) -> Option<Multiaddr> {
+        if !self.relay_service {
+            return None;
+        }
+        let addr = self.public_addr.clone().or_else(|| self.external_addr.clone())?;
+        if addr.iter().any(|protocol| matches!(protocol, Protocol::P2p(_))) {
+            Some(addr)
+        } else {
+            Some(addr.with(Protocol::P2p(self.peer_id)))
+        }
+    }
+
+    /// 广播本节点的中继服务器公告，未提供中继服务或没有可拨入的地址时不做任何事
+    fn publish_relay_announce(&self, swarm: &mut Swarm<MyBehaviour>) {
+        let Some(addr) = self.relay_announce_addr() else {
+            return;
+        };
+        let data = encode_message(&NetworkMessage::RelayServerAnnounce(addr), self.wire_format());
+        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), data) {
+            error!(error = %e, "广播中继服务器公告失败");
+        }
     }
 
     /// 设置最大连接数
     pub fn set_max_connections(&mut self, max: usize) {
         self.max_connections = max;
-        println!("最大连接数设置为: {}", max);
+        info!(max_connections = max, "最大连接数已更新");
+    }
+
+    /// 设置为主动连接保留的槽位数
+    ///
+    /// 已连接节点数达到`max_connections - reserve`后不再接受入站连接，
+    /// 剩余槽位只留给本节点主动发起的连接，避免被入站连接占满
+    pub fn set_outbound_reserve(&mut self, reserve: usize) {
+        self.outbound_reserve = reserve;
+        info!(outbound_reserve = reserve, "主动连接保留槽位已更新");
+    }
+
+    /// 决定是否接受新建立的连接，接受时记录为已连接节点
+    ///
+    /// 入站连接只能使用未保留的槽位，主动连接可以使用全部`max_connections`个槽位
+    ///
+    /// # 参数
+    ///
+    /// * `peer_id` - 对方节点ID
+    /// * `inbound` - 连接是否由对方发起
+    ///
+    /// # 返回值
+    ///
+    /// 接受连接时返回true，超出限制时返回false，调用方应断开该连接
+    pub fn admit_connection(&mut self, peer_id: PeerId, inbound: bool) -> bool {
+        if self.connected_peers.contains(&peer_id) {
+            return true;
+        }
+        if self.incompatible_peers.contains(&peer_id) {
+            warn!(peer_id = %peer_id, "节点的创世区块与本节点不同，拒绝连接");
+            return false;
+        }
+        if self.is_banned(&peer_id) {
+            warn!(peer_id = %peer_id, "节点的不当行为计分过高，拒绝连接");
+            return false;
+        }
+        let limit = if inbound {
+            self.max_connections.saturating_sub(self.outbound_reserve)
+        } else {
+            self.max_connections
+        };
+        if self.connected_peers.len() >= limit {
+            warn!(peer_id = %peer_id, inbound, connections = self.connected_peers.len(), limit, "连接数已达上限，拒绝连接");
+            return false;
+        }
+        self.connected_peers.insert(peer_id);
+        if inbound {
+            self.inbound_peers.insert(peer_id);
+        }
+        self.metrics.set_peers(self.connected_peers.len());
+        true
+    }
+
+    /// 设置分页同步时每页请求的区块数
+    ///
+    /// 响应方每页最多发送[`SYNC_PAGE_SIZE`]个区块，超过该值的设置不会增大实际的页
+    ///
+    /// # 参数
+    ///
+    /// * `page_size` - 每页请求的区块数，至少为1
+    pub fn set_sync_page_size(&mut self, page_size: u64) {
+        self.sync_page_size = page_size.max(1);
+    }
+
+    /// 设置已处理消息缓存的容量和有效期，已有的记录被清空
+    ///
+    /// # 参数
+    ///
+    /// * `capacity` - 最多记录的消息数，至少为1
+    /// * `ttl` - 记录的有效期，超过后同一内容再次到达时视为新消息
+    pub fn set_seen_cache(&mut self, capacity: usize, ttl: Duration) {
+        self.seen_messages = LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap());
+        self.seen_ttl = ttl;
+    }
+
+    /// 查询消息是否在有效期内处理过
+    ///
+    /// # 参数
+    ///
+    /// * `key` - 消息的去重键，见[`NetworkMessage::dedup_key`]
+    /// * `from_network_only` - 为true时只有从网络收到的记录才算命中，本节点自己广播的不算
+    fn seen_recently(&mut self, key: &gossipsub::MessageId, from_network_only: bool) -> bool {
+        self.seen_messages.get(key)
+            .is_some_and(|(seen_at, from_network)| seen_at.elapsed() < self.seen_ttl && (*from_network || !from_network_only))
+    }
+
+    /// 记录本节点将要广播的区块或交易
+    ///
+    /// 本节点广播过的内容被其他节点转发回来时会被丢弃，但之后仍然可以重新广播（例如交易池的定期重播）；
+    /// 刚从网络收到的内容已经在网络中传播，不再重新广播
+    ///
+    /// # 返回值
+    ///
+    /// 可以广播时返回true，内容刚从网络收到时返回false
+    fn mark_published(&mut self, key: gossipsub::MessageId) -> bool {
+        if self.seen_recently(&key, true) {
+            return false;
+        }
+        self.seen_messages.put(key, (Instant::now(), false));
+        true
+    }
+
+    /// 设置每个节点的消息速率限制，已有的限速器按新配置重新开始计数
+    ///
+    /// # 参数
+    ///
+    /// * `capacity` - 令牌桶容量，即允许的突发消息数
+    /// * `refill_rate` - 每秒补充的令牌数
+    pub fn set_rate_limit(&mut self, capacity: u32, refill_rate: u32) {
+        self.rate_limit = (capacity, refill_rate);
+        self.peer_rate_limits.clear();
+        info!(capacity, refill_rate, "消息速率限制已更新");
+    }
+
+    /// 为来自节点的一条消息消耗令牌
+    ///
+    /// 令牌耗尽时消息应被丢弃，同时增加该节点的不当行为计分；
+    /// 计分达到`MISBEHAVIOR_BAN_THRESHOLD`时断开该节点，之后不再接受它的连接
+    ///
+    /// # 参数
+    ///
+    /// * `peer_id` - 转发消息的节点ID
+    ///
+    /// # 返回值
+    ///
+    /// 消息未超出速率限制时返回true
+    pub fn message_rate_limiter(&mut self, peer_id: PeerId) -> bool {
+        let (capacity, refill_rate) = self.rate_limit;
+        let limiter = self.peer_rate_limits
+            .entry(peer_id)
+            .or_insert_with(|| RateLimiter::new(capacity, refill_rate));
+        if limiter.try_acquire() {
+            return true;
+        }
+        let score = self.misbehavior_scores.entry(peer_id).or_insert(0);
+        *score += 1;
+        debug!(peer_id = %peer_id, score = *score, "消息超出速率限制，已丢弃");
+        if *score == MISBEHAVIOR_BAN_THRESHOLD {
+            warn!(peer_id = %peer_id, score = *score, "节点持续超出速率限制，断开连接");
+            let _ = self.command_sender.try_send(NetworkCommand::DisconnectPeer(peer_id));
+        }
+        false
+    }
+
+    /// 获取节点的不当行为计分
+    pub fn misbehavior_score(&self, peer_id: &PeerId) -> u32 {
+        self.misbehavior_scores.get(peer_id).copied().unwrap_or(0)
+    }
+
+    /// 节点的不当行为计分是否已达到`MISBEHAVIOR_BAN_THRESHOLD`
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.misbehavior_score(peer_id) >= MISBEHAVIOR_BAN_THRESHOLD
+    }
+
+    /// 按退避时间安排一次断线重连
+    ///
+    /// 等待在独立任务中进行，到期后通过事件通道发送`ConnectTo`，不阻塞事件循环
+    fn schedule_reconnect(&mut self, peer_id: PeerId) {
+        if !self.auto_connect_enabled || self.connected_peers.len() >= self.max_connections || self.is_banned(&peer_id) {
+            return;
+        }
+        let Some(addr) = self.peers.get(&peer_id).and_then(|addr| addr.parse::<Multiaddr>().ok()) else {
+            return;
+        };
+        let Some(delay) = self.reconnect_backoff.next_delay(peer_id) else {
+            info!(peer_id = %peer_id, "重连次数已用完，放弃重连");
+            return;
+        };
+        debug!(peer_id = %peer_id, delay_secs = delay.as_secs(), "安排断线重连");
+        let sender = self.command_sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = sender.send(NetworkCommand::Dial(addr)).await;
+        });
+    }
+
+    /// 获取入站连接的节点数量
+    pub fn inbound_peer_count(&self) -> usize {
+        self.inbound_peers.len()
     }
 
     /// 启动网络服务
@@ -188,20 +1937,22 @@ impl Network {
     /// 初始化libp2p swarm并开始监听网络事件
     pub async fn start(&mut self) -> Result<(), Box<dyn Error>> {
         // 使用简化方法创建 swarm
-        let mut swarm = libp2p::SwarmBuilder::with_new_identity()
+        let mut swarm = libp2p::SwarmBuilder::with_existing_identity(self.keypair.clone())
             .with_tokio()
             .with_tcp(
                 libp2p::tcp::Config::default(),
                 libp2p::noise::Config::new,
                 libp2p::yamux::Config::default,
             )?
-            .with_behaviour(|key| {
+            // 中继传输用于监听`/p2p-circuit`地址
+            .with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)?
+            .with_behaviour(|key, relay_client| {
                 let peer_id = PeerId::from(key.public());
-                self.peer_id = peer_id;
                 
                 // 配置 gossipsub
                 let gossipsub_config = gossipsub::ConfigBuilder::default()
-                    .heartbeat_interval(Duration::from_secs(10))
+                    .heartbeat_interval(self.gossipsub_heartbeat)
+                    .max_transmit_size(self.max_message_bytes)
                     .validation_mode(gossipsub::ValidationMode::Permissive)
                     .mesh_outbound_min(0)
                     .mesh_n_low(0)
@@ -241,69 +1992,90 @@ impl Network {
                     gossipsub,
                     mdns,
                     kademlia,
+                    block_fetch: request_response::Behaviour::new(
+                        [(BLOCK_FETCH_PROTOCOL, request_response::ProtocolSupport::Full)],
+                        request_response::Config::default(),
+                    ),
+                    autonat: autonat::Behaviour::new(peer_id, autonat::Config::default()),
+                    relay_client,
+                    relay: self.relay_service
+                        .then(|| relay::Behaviour::new(peer_id, relay::Config::default()))
+                        .into(),
                 })
             })?
             .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(300)))
             .build();
 
-        // 开始监听
-        // 尝试一系列固定端口
-        println!("尝试绑定到固定端口...");
-        let fixed_ports = vec![40000, 40001, 40002, 40003, 40004, 40005, 40006, 40007, 40008, 40009, 40010];
-        let mut listen_success = false;
-        
-        for port in fixed_ports {
-            println!("尝试端口 {}...", port);
-            let listen_addr = format!("/ip4/0.0.0.0/tcp/{}", port);
-            
-            match swarm.listen_on(listen_addr.parse()?) {
-                Ok(_) => {
-                    println!("成功监听在端口 {}", port);
-                    listen_success = true;
-                    break;
-                },
-                Err(e) => {
-                    println!("端口 {} 绑定失败: {}", port, e);
-                    // 继续尝试下一个端口
-                }
-            }
-        }
-        
-        // 如果所有固定端口都失败，尝试随机端口
-        if !listen_success {
-            println!("所有固定端口都绑定失败，尝试使用随机端口...");
-            if let Err(e) = swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?) {
-                eprintln!("启动监听失败: {}", e);
+        // 开始监听，指定了监听地址时只尝试该地址
+        if let Some(addr) = self.listen_addr.clone() {
+            if let Err(e) = swarm.listen_on(addr.clone()) {
+                error!(address = %addr, error = %e, "无法监听指定地址");
                 return Err(e.into());
             }
+        } else {
+            self.listen_on_fixed_ports(&mut swarm)?;
+        }
+        if let Some(addr) = self.external_addr.clone() {
+            info!(address = %addr, "公布外部地址");
+            swarm.add_external_address(addr);
+        }
+        // 配置的中继服务器同时作为AutoNAT探测服务器
+        if let Some(Protocol::P2p(relay_peer)) = self.relay_server.as_ref().and_then(|addr| addr.iter().last()) {
+            swarm.behaviour_mut().autonat.add_server(relay_peer, self.relay_server.clone());
         }
 
-        println!("P2P 网络启动，节点 ID: {}", self.peer_id);
+        info!(peer_id = %self.peer_id, "P2P网络启动");
         
         // 等待监听地址分配
-        println!("等待监听地址分配...");
+        debug!("等待监听地址分配");
         loop {
-            match swarm.select_next_some().await {
-                SwarmEvent::NewListenAddr { address, .. } => {
-                    println!("分配的监听地址: {}", address);
-                    break;
+            if let SwarmEvent::NewListenAddr { address, .. } = swarm.select_next_some().await {
+                info!(address = %address, "分配的监听地址");
+                if let Some(app_sender) = &self.app_event_sender {
+                    let event = NetworkEvent::Listening { peer_id: *swarm.local_peer_id(), address };
+                    if let Err(e) = app_sender.send(event).await {
+                        error!(error = %e, "发送监听事件到应用层失败");
+                    }
                 }
-                _ => {}
+                break;
             }
         }
 
-        // 显示节点信息
-        println!("==========================================================");
-        println!("P2P 节点已启动");
-        println!("节点ID: {}", self.peer_id);
+        // 记录节点信息
         if let Some(addr) = swarm.listeners().next() {
-            println!("监听地址: {}", addr);
-            println!("其他节点可以通过菜单选项8连接到此地址");
-            if self.auto_connect_enabled {
-                println!("自动连接已启用，将自动发现并连接到其他节点");
+            info!(
+                peer_id = %self.peer_id,
+                listen_addr = %addr,
+                auto_connect = self.auto_connect_enabled,
+                "P2P节点已启动，其他节点可以通过菜单选项8连接到此地址"
+            );
+        }
+
+        // 重新连接上次运行时最近见到的节点
+        for (peer_id, addr) in self.peer_store.best_peers(self.max_connections) {
+            if peer_id == self.peer_id {
+                continue;
+            }
+            info!(peer_id = %peer_id, address = %addr, "连接已保存的节点");
+            swarm.behaviour_mut().kademlia.add_address(&peer_id, addr.clone());
+            self.peers.insert(peer_id, addr.to_string());
+            if let Err(e) = swarm.dial(addr) {
+                warn!(error = %e, "连接已保存的节点失败");
+            }
+        }
+
+        // 连接配置文件中的引导节点
+        for peer in &self.bootstrap_peers {
+            match peer.parse::<Multiaddr>() {
+                Ok(addr) => {
+                    info!(address = %addr, "连接引导节点");
+                    if let Err(e) = swarm.dial(addr) {
+                        warn!(error = %e, "连接引导节点失败");
+                    }
+                }
+                Err(e) => warn!(address = %peer, error = %e, "忽略无效的引导节点地址"),
             }
         }
-        println!("==========================================================");
 
         // 存储swarm实例
         self.swarm = Some(swarm);
@@ -315,13 +2087,27 @@ impl Network {
     /// 运行主事件循环
     async fn run_event_loop(&mut self) -> Result<(), Box<dyn Error>> {
         let mut swarm = self.swarm.take().unwrap();
+        let mut flush_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + PEER_STORE_FLUSH_INTERVAL,
+            PEER_STORE_FLUSH_INTERVAL,
+        );
         
         loop {
             tokio::select! {
-                // 处理应用层事件
-                event = self.event_receiver.recv() => {
-                    if let Some(event) = event {
-                        self.handle_application_event(&mut swarm, event).await?;
+                // 定期清理并保存节点存储
+                _ = flush_interval.tick() => {
+                    self.save_peers();
+                }
+                
+                // 处理应用层命令
+                command = self.command_receiver.recv() => {
+                    match command {
+                        Some(NetworkCommand::Shutdown) | None => {
+                            info!("网络任务停止");
+                            self.save_peers();
+                            return Ok(());
+                        }
+                        Some(command) => self.handle_command(&mut swarm, command).await?,
                     }
                 }
                 
@@ -333,111 +2119,264 @@ impl Network {
         }
     }
 
-    /// 处理应用层事件
-    async fn handle_application_event(
+    /// 没有指定监听地址时依次尝试固定端口，全部失败时监听随机端口
+    fn listen_on_fixed_ports(&self, swarm: &mut Swarm<MyBehaviour>) -> Result<(), Box<dyn Error>> {
+        debug!("尝试绑定到固定端口");
+        for port in FIXED_LISTEN_PORTS {
+            debug!(port, "尝试端口");
+            let listen_addr = format!("/ip4/0.0.0.0/tcp/{}", port);
+            
+            match swarm.listen_on(listen_addr.parse()?) {
+                Ok(_) => {
+                    info!(port, "成功监听端口");
+                    return Ok(());
+                },
+                Err(e) => {
+                    warn!(port, error = %e, "端口绑定失败");
+                    // 继续尝试下一个端口
+                }
+            }
+        }
+        
+        // 如果所有固定端口都失败，尝试随机端口
+        warn!("所有固定端口都绑定失败，尝试使用随机端口");
+        if let Err(e) = swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?) {
+            error!(error = %e, "启动监听失败");
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// 发布区块同步响应
+    ///
+    /// gossipsub无法单独发送给某个节点，响应发布到区块主题；请求方已经断开时不再发布
+    fn publish_block_response(&self, swarm: &mut Swarm<MyBehaviour>, peer_id: PeerId, message: NetworkMessage) {
+        if !self.connected_peers.contains(&peer_id) {
+            debug!(peer_id = %peer_id, "请求方已断开，不再发送区块响应");
+            return;
+        }
+        let format = self.wire_format();
+        let mut data = encode_message(&message, format);
+        // 长链的区块响应可能有数MB，所有节点都能解压时压缩以节省同步带宽
+        if self.compression_enabled() {
+            data = compress_payload(data);
+        }
+        
+        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), data) {
+            error!(error = %e, "广播区块响应失败");
+        } else {
+            debug!(peer_id = %peer_id, "区块响应已广播");
+        }
+    }
+
+    /// 执行应用层命令
+    async fn handle_command(
         &mut self,
         swarm: &mut Swarm<MyBehaviour>,
-        event: NetworkEvent,
+        command: NetworkCommand,
     ) -> Result<(), Box<dyn Error>> {
-        match event {
-            NetworkEvent::NewBlock(block) => {
-                println!("广播新区块: {}", block.calculate_hash());
-                let message = NetworkMessage::Block(block);
-                let data = serde_json::to_vec(&message)?;
+        match command {
+            NetworkCommand::BroadcastBlock { block, height } => {
+                let hash = block.calculate_hash_with(self.hasher.as_ref());
+                if !self.mark_published(NetworkMessage::block_key(&hash)) {
+                    debug!(block_hash = %hash, "区块刚从网络收到，不再重新广播");
+                    return Ok(());
+                }
+                info!(block_hash = %hash, height, "广播新区块");
+                // 对方会向本节点请求区块内容，本节点不需要再请求自己公告的区块
+                self.recent_announces.put(hash.clone(), Instant::now());
+                let format = self.wire_format();
+                let message = if self.all_peers_support(FEATURE_BLOCK_ANNOUNCE) {
+                    NetworkMessage::BlockAnnounce { hash, height }
+                } else {
+                    match format {
+                        WireFormat::Binary => NetworkMessage::BlockBinary(block.to_bytes()),
+                        WireFormat::Json => NetworkMessage::Block(block),
+                    }
+                };
+                let data = encode_message(&message, format);
                 
                 if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), data) {
-                    eprintln!("广播区块失败: {}", e);
+                    error!(error = %e, "广播区块失败");
                 }
             }
-            NetworkEvent::NewTransaction(transaction) => {
-                println!("广播新交易");
+            NetworkCommand::BroadcastTransaction(transaction) => {
+                if !self.mark_published(NetworkMessage::transaction_key(&transaction, self.hasher.as_ref())) {
+                    debug!(tx_hash = %transaction.calculate_hash_with(self.hasher.as_ref()), "交易刚从网络收到，不再重新广播");
+                    return Ok(());
+                }
+                info!("广播新交易");
                 let message = NetworkMessage::Transaction(transaction);
-                let data = serde_json::to_vec(&message)?;
+                let data = encode_message(&message, self.wire_format());
                 
                 if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.transactions_topic.clone(), data) {
-                    eprintln!("广播交易失败: {}", e);
+                    error!(error = %e, "广播交易失败");
                 }
             }
-            NetworkEvent::RequestBlocks => {
-                // 广播区块请求，让其他节点响应
-                println!("广播区块同步请求");
-                let message = NetworkMessage::BlockRequest;
-                let data = serde_json::to_vec(&message)?;
+            NetworkCommand::RequestBlocks { from_height, locator } => {
+                // 没有定位器时请求完整链，否则按定位器请求，不支持定位器的节点回退到增量或完整同步
+                let message = if locator.is_empty() {
+                    info!("广播区块同步请求");
+                    NetworkMessage::BlockRequest
+                } else {
+                    let known_height = (from_height as usize).saturating_sub(1);
+                    info!(known_height, locator = locator.len(), "广播定位器区块同步请求");
+                    self.locator_request_message(known_height, locator)
+                };
+                let data = encode_message(&message, self.wire_format());
                 
                 if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), data) {
-                    eprintln!("广播区块请求失败: {}", e);
+                    error!(error = %e, "广播区块请求失败");
                 } else {
-                    println!("区块同步请求已广播");
+                    debug!("区块同步请求已广播");
+                }
+            }
+            NetworkCommand::SendBlocksTo(peer_id, blocks) => {
+                info!(peer_id = %peer_id, blocks = blocks.len(), "回复区块同步请求");
+                let message = match self.wire_format() {
+                    WireFormat::Binary => NetworkMessage::BlockResponseBinary(blocks.iter().map(Block::to_bytes).collect()),
+                    WireFormat::Json => NetworkMessage::BlockResponse(blocks),
+                };
+                self.publish_block_response(swarm, peer_id, message);
+            }
+            NetworkCommand::SendBlocksSinceTo { peer_id, start_height, blocks } => {
+                info!(peer_id = %peer_id, start_height, blocks = blocks.len(), "回复增量区块同步请求");
+                self.publish_block_response(swarm, peer_id, NetworkMessage::BlockResponseSince { start_height, blocks });
+            }
+            NetworkCommand::Dial(addr) => {
+                info!(address = %addr, "尝试连接节点");
+                if let Err(e) = swarm.dial(addr.clone()) {
+                    warn!(error = %e, "连接失败");
+                } else {
+                    debug!("连接请求已发送");
+                }
+            }
+            NetworkCommand::BroadcastAddress(address) => {
+                let nickname = self.announce.take().map(|(_, nickname)| nickname).unwrap_or_default();
+                self.announce = Some((address, nickname));
+                self.publish_announce(swarm);
+            }
+            NetworkCommand::UpdateNatStatus(status) => {
+                self.nat_traversal(swarm, status);
+            }
+            NetworkCommand::DisconnectPeer(peer_id) => {
+                let _ = swarm.disconnect_peer_id(peer_id);
+            }
+            NetworkCommand::RequestBlock { peer_id, query } => {
+                info!(peer_id = %peer_id, query = ?query, "向节点请求区块");
+                let hash = match &query {
+                    BlockQuery::Hash(hash) => Some(hash.clone()),
+                    BlockQuery::Height(_) => None,
+                };
+                let data = encode_message(&NetworkMessage::from(query), WireFormat::Json);
+                let request_id = swarm.behaviour_mut().block_fetch.send_request(&peer_id, data);
+                if let Some(hash) = hash {
+                    self.pending_hash_requests.insert(request_id, hash);
+                }
+            }
+            NetworkCommand::SendBlockTo { request_id, block } => {
+                let Some((peer_id, channel)) = self.pending_block_requests.remove(&request_id) else {
+                    warn!(request_id = %request_id, "区块请求已失效，无法回复");
+                    return Ok(());
+                };
+                // 只有请求方收到响应，按请求方声明的能力选择线路格式
+                let format = if self.binary_peers.contains(&peer_id) { WireFormat::Binary } else { WireFormat::Json };
+                let message = match block {
+                    Some(block) => {
+                        debug!(peer_id = %peer_id, block_hash = %block.calculate_hash_with(self.hasher.as_ref()), "回复区块请求");
+                        NetworkMessage::Block(block)
+                    }
+                    None => NetworkMessage::BlockNotFound,
+                };
+                if swarm.behaviour_mut().block_fetch.send_response(channel, encode_message(&message, format)).is_err() {
+                    warn!(peer_id = %peer_id, "回复区块请求失败，连接可能已断开");
                 }
             }
-            NetworkEvent::SendBlocks(blocks) => {
-                // 广播区块响应，让请求的节点接收
-                println!("广播区块响应，包含 {} 个区块", blocks.len());
-                let message = NetworkMessage::BlockResponse(blocks);
-                let data = serde_json::to_vec(&message)?;
-                
-                if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), data) {
-                    eprintln!("广播区块响应失败: {}", e);
-                } else {
-                    println!("区块响应已广播");
+            NetworkCommand::RequestBlocksFrom(peer_id, from_height) => {
+                info!(peer_id = %peer_id, from_height, "向节点请求一页区块");
+                let message = NetworkMessage::GetBlocks { from_height, limit: self.sync_page_size };
+                let request_id = swarm.behaviour_mut().block_fetch.send_request(&peer_id, encode_message(&message, WireFormat::Json));
+                self.pending_page_requests.insert(request_id, from_height);
+            }
+            NetworkCommand::SendBlocksPage { request_id, blocks } => {
+                let Some((peer_id, channel)) = self.pending_block_requests.remove(&request_id) else {
+                    warn!(request_id = %request_id, "分页区块请求已失效，无法回复");
+                    return Ok(());
+                };
+                debug!(peer_id = %peer_id, blocks = blocks.len(), "回复分页区块请求");
+                let data = encode_message(&NetworkMessage::Blocks(blocks), WireFormat::Json);
+                if swarm.behaviour_mut().block_fetch.send_response(channel, data).is_err() {
+                    warn!(peer_id = %peer_id, "回复分页区块请求失败，连接可能已断开");
                 }
             }
-            NetworkEvent::ConnectTo(addr) => {
-                println!("尝试连接到: {}", addr);
-                if let Err(e) = swarm.dial(addr.clone()) {
-                    eprintln!("连接失败: {}", e);
-                } else {
-                    println!("连接请求已发送");
+            NetworkCommand::RequestBlocksAfter { peer_id, known_height, locator } => {
+                info!(peer_id = %peer_id, known_height, "按区块定位器向节点请求一页区块");
+                let message = NetworkMessage::GetBlocksAfter { known_height, locator, limit: self.sync_page_size };
+                let request_id = swarm.behaviour_mut().block_fetch.send_request(&peer_id, encode_message(&message, WireFormat::Json));
+                self.pending_page_requests.insert(request_id, known_height + 1);
+            }
+            NetworkCommand::SendBlocksPageAfter { request_id, start_height, blocks, more } => {
+                let Some((peer_id, channel)) = self.pending_block_requests.remove(&request_id) else {
+                    warn!(request_id = %request_id, "分页区块请求已失效，无法回复");
+                    return Ok(());
+                };
+                debug!(peer_id = %peer_id, start_height, blocks = blocks.len(), more, "回复按定位器的分页区块请求");
+                let data = encode_message(&NetworkMessage::BlocksPage { start_height, blocks, more }, WireFormat::Json);
+                if swarm.behaviour_mut().block_fetch.send_response(channel, data).is_err() {
+                    warn!(peer_id = %peer_id, "回复分页区块请求失败，连接可能已断开");
                 }
             }
-            NetworkEvent::RequestConnectionInfo => {
+            NetworkCommand::QueryPeers => {
                 // 收集连接信息并发送回应用层
                 let connected_peers = self.get_connected_peers_info();
                 let all_peers = self.get_all_peers_info();
+                let listen_addrs = swarm.listeners().cloned().collect();
                 
                 if let Some(app_sender) = &self.app_event_sender {
                     let response = NetworkEvent::ConnectionInfo {
                         connected_peers,
                         all_peers,
+                        listen_addrs,
                     };
                     if let Err(e) = app_sender.send(response).await {
-                        eprintln!("发送连接信息响应失败: {}", e);
+                        error!(error = %e, "发送连接信息响应失败");
                     }
                 }
             }
-            _ => {}
+            // 停止命令在事件循环中处理
+            NetworkCommand::Shutdown => {}
         }
         Ok(())
     }
 
     /// 处理Swarm网络事件
-    async fn handle_swarm_event(
+    async fn handle_swarm_event<E>(
         &mut self,
         swarm: &mut Swarm<MyBehaviour>,
-        event: SwarmEvent<MyBehaviourEvent, libp2p::swarm::THandlerErr<MyBehaviour>>,
+        event: SwarmEvent<MyBehaviourEvent, E>,
     ) -> Result<(), Box<dyn Error>> {
         match event {
             SwarmEvent::NewListenAddr { address, .. } => {
-                println!("正在监听地址: {}", address);
+                info!(address = %address, "正在监听地址");
             }
             SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
                 for (peer_id, multiaddr) in list {
                     // 防止自连接：跳过自己的节点ID
                     if peer_id == self.peer_id {
-                        println!("🚫 跳过自己的节点: {}", peer_id);
+                        debug!(peer_id = %peer_id, "跳过自己的节点");
                         continue;
                     }
                     
-                    println!("🔍 mDNS发现新节点: {} at {}", peer_id, multiaddr);
+                    info!(peer_id = %peer_id, address = %multiaddr, "mDNS发现新节点");
                     
                     // 自动连接到发现的节点
                     if self.auto_connect_enabled && 
                        !self.connected_peers.contains(&peer_id) && 
                        self.connected_peers.len() < self.max_connections {
                         
-                        println!("🔗 自动连接到发现的节点: {}", peer_id);
+                        info!(peer_id = %peer_id, "自动连接到发现的节点");
                         if let Err(e) = swarm.dial(multiaddr.clone()) {
-                            eprintln!("自动连接失败: {}", e);
+                            warn!(error = %e, "自动连接失败");
                         }
                     }
                     
@@ -450,150 +2389,562 @@ impl Network {
             }
             SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
                 for (peer_id, _multiaddr) in list {
-                    println!("📤 mDNS节点过期: {}", peer_id);
+                    info!(peer_id = %peer_id, "mDNS节点过期");
                     self.peers.remove(&peer_id);
                     self.connected_peers.remove(&peer_id);
+                    self.inbound_peers.remove(&peer_id);
                 }
+                self.metrics.set_peers(self.connected_peers.len());
             }
-            SwarmEvent::Behaviour(MyBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed { result, .. })) => {
-                match result {
-                    kad::QueryResult::GetClosestPeers(Ok(kad::GetClosestPeersOk { peers, .. })) => {
-                        println!("🌐 Kademlia发现 {} 个节点", peers.len());
-                        for peer in peers {
-                            // 防止自连接：跳过自己的节点ID
-                            if peer == self.peer_id {
-                                continue;
-                            }
-                            
-                            if self.auto_connect_enabled && 
-                               !self.connected_peers.contains(&peer) && 
-                               self.connected_peers.len() < self.max_connections {
-                                
-                                // 尝试通过已知地址连接
-                                if let Some(addr_str) = self.peers.get(&peer) {
-                                    if let Ok(addr) = addr_str.parse::<Multiaddr>() {
-                                        println!("🔗 通过Kademlia自动连接到: {} at {}", peer, addr);
-                                        if let Err(e) = swarm.dial(addr) {
-                                            eprintln!("Kademlia自动连接失败: {}", e);
-                                        }
-                                    }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed {
+                result: kad::QueryResult::GetClosestPeers(Ok(kad::GetClosestPeersOk { peers, .. })),
+                ..
+            })) => {
+                info!(peers = peers.len(), "Kademlia发现节点");
+                for peer in peers {
+                    // 防止自连接：跳过自己的节点ID
+                    if peer == self.peer_id {
+                        continue;
+                    }
+                    
+                    if self.auto_connect_enabled && 
+                       !self.connected_peers.contains(&peer) && 
+                       self.connected_peers.len() < self.max_connections {
+                        
+                        // 尝试通过已知地址连接
+                        if let Some(addr_str) = self.peers.get(&peer) {
+                            if let Ok(addr) = addr_str.parse::<Multiaddr>() {
+                                info!(peer_id = %peer, address = %addr, "通过Kademlia自动连接节点");
+                                if let Err(e) = swarm.dial(addr) {
+                                    warn!(error = %e, "Kademlia自动连接失败");
                                 }
                             }
                         }
                     }
-                    _ => {}
                 }
             }
-            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                // 检查是否是新连接，避免重复输出
-                if !self.connected_peers.contains(&peer_id) {
-                    self.connected_peers.insert(peer_id);
-                    println!("✅ 新连接建立: {} (总连接数: {})", peer_id, self.connected_peers.len());
-                    
-                    // 发送连接事件到应用层
-                    if let Some(app_sender) = &self.app_event_sender {
-                        if let Err(e) = app_sender.send(NetworkEvent::PeerConnected(peer_id)).await {
-                            eprintln!("发送连接事件到应用层失败: {}", e);
-                        }
+            // 只处理新连接，同一节点的重复连接静默忽略，避免重复输出
+            SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } if !self.connected_peers.contains(&peer_id) => {
+                // 超出连接上限时立即断开，入站连接不能占用为主动连接保留的槽位
+                if !self.admit_connection(peer_id, !endpoint.is_dialer()) {
+                    let _ = swarm.disconnect_peer_id(peer_id);
+                    return Ok(());
+                }
+                self.reconnect_backoff.reset(&peer_id);
+                
+                // 记录可拨号的地址：优先使用发现的地址，其次是主动拨出的地址
+                let known_addr = self.peers.get(&peer_id).and_then(|addr| addr.parse::<Multiaddr>().ok());
+                let dialed_addr = endpoint.is_dialer().then(|| endpoint.get_remote_address().clone());
+                if let Some(addr) = known_addr.or(dialed_addr) {
+                    self.peer_store.record(peer_id, addr, unix_now());
+                }
+                info!(peer_id = %peer_id, connections = self.connected_peers.len(), "新连接建立");
+                
+                // 发送连接事件到应用层
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::PeerConnected(peer_id)).await {
+                        error!(error = %e, "发送连接事件到应用层失败");
                     }
-                } else {
-                    // 已存在的连接，可能是多个连接到同一节点
-                    // 静默处理，不输出重复信息
                 }
             }
-            SwarmEvent::ConnectionClosed { peer_id, .. } => {
-                // 只有当节点真正断开时才输出和处理
-                if self.connected_peers.contains(&peer_id) {
-                    self.connected_peers.remove(&peer_id);
-                    println!("❌ 连接断开: {} (剩余连接数: {})", peer_id, self.connected_peers.len());
-                    
-                    // 发送断开事件到应用层
-                    if let Some(app_sender) = &self.app_event_sender {
-                        if let Err(e) = app_sender.send(NetworkEvent::PeerDisconnected(peer_id)).await {
-                            eprintln!("发送断开事件到应用层失败: {}", e);
-                        }
-                    }
-                    
-                    // 自动重连机制（静默处理）
-                    if self.auto_connect_enabled && self.connected_peers.len() < self.max_connections {
-                        if let Some(addr_str) = self.peers.get(&peer_id) {
-                            if let Ok(addr) = addr_str.parse::<Multiaddr>() {
-                                // 延迟重连，避免立即重连
-                                tokio::time::sleep(Duration::from_secs(5)).await;
-                                
-                                if let Err(_e) = swarm.dial(addr) {
-                                    // 静默处理重连失败，避免日志干扰
-                                }
-                            }
-                        }
+            // 只有当节点真正断开时才输出和处理
+            SwarmEvent::ConnectionClosed { peer_id, .. } if self.connected_peers.contains(&peer_id) => {
+                self.connected_peers.remove(&peer_id);
+                self.metrics.set_peers(self.connected_peers.len());
+                self.forget_peer_protocol(&peer_id);
+                self.peer_store.touch(&peer_id, unix_now());
+                info!(peer_id = %peer_id, connections = self.connected_peers.len(), "连接断开");
+                
+                // 发送断开事件到应用层
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::PeerDisconnected(peer_id)).await {
+                        error!(error = %e, "发送断开事件到应用层失败");
                     }
                 }
+                
+                // 自动重连机制，按指数退避延迟重连
+                self.schedule_reconnect(peer_id);
+            }
+            // 重连失败时继续退避，直到重连次数用完
+            SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), .. } if self.reconnect_backoff.is_pending(&peer_id) => {
+                self.schedule_reconnect(peer_id);
+            }
+            // 对方订阅区块主题后才能收到广播，此时公告本节点的钱包地址
+            SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Subscribed { topic, .. }))
+                if topic == self.blocks_topic.hash() => {
+                self.publish_capabilities(swarm);
+                self.publish_announce(swarm);
+                self.publish_relay_announce(swarm);
             }
             SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
-                propagation_source: _peer_id,
+                propagation_source,
                 message_id: _id,
                 message,
             })) => {
-                // 处理接收到的gossipsub消息
-                match serde_json::from_slice::<NetworkMessage>(&message.data) {
-                    Ok(NetworkMessage::Block(block)) => {
-                        println!("📦 收到区块广播: {}", block.calculate_hash());
-                        // 转发到应用层
-                        if let Some(app_sender) = &self.app_event_sender {
-                            if let Err(e) = app_sender.send(NetworkEvent::NewBlock(block)).await {
-                                eprintln!("转发区块事件到应用层失败: {}", e);
-                            }
-                        }
+                // 按直接转发消息的节点限速，超出限制的消息直接丢弃
+                if !self.message_rate_limiter(propagation_source) {
+                    return Ok(());
+                }
+                self.handle_relayed_gossip_message(Some(propagation_source), message.source, &message.data).await;
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::BlockFetch(event)) => {
+                self.handle_block_fetch_event(event).await;
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Autonat(event)) => {
+                self.handle_autonat_event(swarm, event);
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::RelayClient(event)) => {
+                self.handle_relay_client_event(swarm, event);
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Relay(event)) => {
+                debug!(event = ?event, "中继服务器事件");
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Ping(ping_event)) => {
+                // 只在ping失败或连接问题时输出，减少日志干扰
+                match ping_event.result {
+                    Ok(_) => {
+                        // 成功的ping不输出，避免干扰用户界面
+                        // 可以选择性地记录连接健康状态
                     }
-                    Ok(NetworkMessage::Transaction(transaction)) => {
-                        println!("💰 收到交易广播");
-                        // 转发到应用层
-                        if let Some(app_sender) = &self.app_event_sender {
-                            if let Err(e) = app_sender.send(NetworkEvent::NewTransaction(transaction)).await {
-                                eprintln!("转发交易事件到应用层失败: {}", e);
-                            }
+                    Err(e) => {
+                        warn!(peer_id = %ping_event.peer, error = %e, "Ping失败");
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 处理收到的gossipsub消息
+    ///
+    /// 同一个区块或交易可能经由多个节点重复到达，已经转发过的内容会被丢弃，
+    /// 避免应用层重复验证和更新UTXO集合
+    ///
+    /// # 参数
+    ///
+    /// * `source` - 消息的原始发布者
+    /// * `data` - 消息内容
+    pub async fn handle_gossip_message(&mut self, source: Option<PeerId>, data: &[u8]) {
+        self.handle_relayed_gossip_message(source, source, data).await;
+    }
+
+    /// 处理经其他节点转发的gossipsub消息，见[`Network::handle_gossip_message`]
+    ///
+    /// # 参数
+    ///
+    /// * `propagation_source` - 直接转发消息的节点
+    /// * `source` - 消息的原始发布者
+    /// * `data` - 消息内容
+    pub async fn handle_relayed_gossip_message(&mut self, propagation_source: Option<PeerId>, source: Option<PeerId>, data: &[u8]) {
+        let message = match decode_message(data) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(error = %e, "解析网络消息失败");
+                return;
+            }
+        };
+        
+        let key = message.dedup_key(self.hasher.as_ref());
+        if let Some(key) = &key {
+            // 只统计收到的消息，本节点广播前的检查不计入缓存命中率
+            let hit = self.seen_recently(key, false);
+            self.metrics.record_seen_cache(hit);
+            if hit {
+                debug!(key = %key, "丢弃已处理过的消息");
+                return;
+            }
+        }
+        self.forward_gossip_message(propagation_source, source, message).await;
+        if let Some(key) = key {
+            self.seen_messages.put(key, (Instant::now(), true));
+        }
+    }
+
+    /// 处理定向区块请求协议的事件
+    ///
+    /// 收到的请求转发到应用层查找区块，响应通道保存到应用层回复为止；
+    /// 收到的区块以`BlockFetched`事件转发，应用层据此知道区块来自哪个节点
+    async fn handle_block_fetch_event(&mut self, event: request_response::Event<Vec<u8>, Vec<u8>>) {
+        match event {
+            request_response::Event::Message { peer, message: request_response::Message::Request { request_id, request, channel } } => {
+                let event = match decode_message(&request) {
+                    Ok(NetworkMessage::GetBlock(hash)) => {
+                        NetworkEvent::BlockRequested { peer_id: peer, request_id, query: BlockQuery::Hash(hash) }
+                    }
+                    Ok(NetworkMessage::GetBlockByHeight(height)) => {
+                        NetworkEvent::BlockRequested { peer_id: peer, request_id, query: BlockQuery::Height(height) }
+                    }
+                    Ok(NetworkMessage::GetBlocks { from_height, limit }) => {
+                        let limit = limit.min(SYNC_PAGE_SIZE);
+                        NetworkEvent::BlocksPageRequested { peer_id: peer, request_id, from_height, limit }
+                    }
+                    Ok(NetworkMessage::GetBlocksAfter { known_height, locator, limit }) => {
+                        let limit = limit.min(SYNC_PAGE_SIZE);
+                        NetworkEvent::BlocksPageAfterRequested { peer_id: peer, request_id, known_height, locator, limit }
+                    }
+                    Ok(_) => {
+                        warn!(peer_id = %peer, "忽略不支持的定向请求");
+                        return;
+                    }
+                    Err(e) => {
+                        warn!(peer_id = %peer, error = %e, "解析区块请求失败");
+                        return;
+                    }
+                };
+                let Some(app_sender) = &self.app_event_sender else {
+                    return;
+                };
+                info!(peer_id = %peer, request = ?event, "收到区块请求");
+                self.pending_block_requests.insert(request_id, (peer, channel));
+                if let Err(e) = app_sender.send(event).await {
+                    error!(error = %e, "转发区块请求到应用层失败");
+                }
+            }
+            request_response::Event::Message { peer, message: request_response::Message::Response { request_id, response } } => {
+                if let Some(from_height) = self.pending_page_requests.remove(&request_id) {
+                    self.forward_blocks_page(peer, from_height, &response).await;
+                    return;
+                }
+                // 没有拿到区块时允许同一区块的下一次公告重新请求
+                let requested_hash = self.pending_hash_requests.remove(&request_id);
+                let block = match decode_message(&response) {
+                    Ok(NetworkMessage::Block(block)) => block,
+                    Ok(NetworkMessage::BlockBinary(data)) => match Block::from_bytes(&data) {
+                        Ok(block) => block,
+                        Err(e) => {
+                            warn!(peer_id = %peer, error = %e, "解码二进制区块失败");
+                            return;
+                        }
+                    },
+                    Ok(NetworkMessage::BlockNotFound) => {
+                        info!(peer_id = %peer, "对方没有请求的区块");
+                        if let Some(hash) = requested_hash {
+                            self.recent_announces.pop(&hash);
                         }
+                        return;
                     }
-                    Ok(NetworkMessage::BlockRequest) => {
-                        // 处理区块请求：响应本地区块链数据
-                        println!("📋 收到区块同步请求，准备响应");
-                        // 转发到应用层处理
-                        if let Some(app_sender) = &self.app_event_sender {
-                            if let Err(e) = app_sender.send(NetworkEvent::RequestBlocks).await {
-                                eprintln!("转发区块请求到应用层失败: {}", e);
-                            }
+                    Ok(_) => {
+                        warn!(peer_id = %peer, "忽略意外的区块请求响应");
+                        return;
+                    }
+                    Err(e) => {
+                        warn!(peer_id = %peer, error = %e, "解析区块请求响应失败");
+                        return;
+                    }
+                };
+                info!(peer_id = %peer, block_hash = %block.calculate_hash_with(self.hasher.as_ref()), "收到请求的区块");
+                self.metrics.record_block_received();
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::BlockFetched { peer_id: peer, block }).await {
+                        error!(error = %e, "转发区块事件到应用层失败");
+                    }
+                }
+            }
+            request_response::Event::OutboundFailure { peer, request_id, error } => {
+                warn!(peer_id = %peer, error = %error, "区块请求失败");
+                if let Some(from_height) = self.pending_page_requests.remove(&request_id) {
+                    self.send_page_failed(peer, from_height).await;
+                }
+                if let Some(hash) = self.pending_hash_requests.remove(&request_id) {
+                    self.recent_announces.pop(&hash);
+                }
+            }
+            request_response::Event::InboundFailure { peer, request_id, error } => {
+                debug!(peer_id = %peer, error = %error, "回复区块请求失败");
+                self.pending_block_requests.remove(&request_id);
+            }
+            request_response::Event::ResponseSent { .. } => {}
+        }
+    }
+
+    /// 把分页区块请求的响应转发到应用层，无法解析的响应按请求失败处理
+    ///
+    /// `from_height`为请求的起始高度，按定位器请求时以响应中的起始高度为准
+    async fn forward_blocks_page(&mut self, peer: PeerId, from_height: u64, response: &[u8]) {
+        let (from_height, blocks, more) = match decode_message(response) {
+            Ok(NetworkMessage::Blocks(blocks)) => {
+                let more = !blocks.is_empty();
+                (from_height, blocks, more)
+            }
+            Ok(NetworkMessage::BlocksPage { start_height, blocks, more }) => (start_height, blocks, more),
+            Ok(_) => {
+                warn!(peer_id = %peer, "忽略意外的分页区块响应");
+                self.send_page_failed(peer, from_height).await;
+                return;
+            }
+            Err(e) => {
+                warn!(peer_id = %peer, error = %e, "解析分页区块响应失败");
+                self.send_page_failed(peer, from_height).await;
+                return;
+            }
+        };
+        info!(peer_id = %peer, from_height, blocks = blocks.len(), more, "收到一页区块");
+        self.metrics.record_blocks_synced(blocks.len());
+        if let Some(app_sender) = &self.app_event_sender {
+            if let Err(e) = app_sender.send(NetworkEvent::BlocksPageReceived { peer_id: peer, from_height, blocks, more }).await {
+                error!(error = %e, "转发分页区块到应用层失败");
+            }
+        }
+    }
+
+    /// 通知应用层分页区块请求失败
+    async fn send_page_failed(&mut self, peer_id: PeerId, from_height: u64) {
+        if let Some(app_sender) = &self.app_event_sender {
+            if let Err(e) = app_sender.send(NetworkEvent::BlocksPageFailed { peer_id, from_height }).await {
+                error!(error = %e, "转发分页区块请求失败事件到应用层失败");
+            }
+        }
+    }
+
+    /// 将解码后的gossipsub消息转发到应用层
+    async fn forward_gossip_message(&mut self, propagation_source: Option<PeerId>, source: Option<PeerId>, message: NetworkMessage) {
+        match message {
+            NetworkMessage::BlockAnnounce { hash, height } => {
+                // 原始发布者一定有该区块，已连接时优先向它请求，否则向转发公告的节点请求
+                let Some(peer_id) = source.filter(|peer| self.connected_peers.contains(peer)).or(propagation_source) else {
+                    return;
+                };
+                if self.recent_announces.get(&hash).is_some_and(|seen| seen.elapsed() < ANNOUNCE_FETCH_TIMEOUT) {
+                    debug!(block_hash = %hash, peer_id = %peer_id, "忽略重复的区块公告");
+                    return;
+                }
+                self.recent_announces.put(hash.clone(), Instant::now());
+                info!(block_hash = %hash, height, peer_id = %peer_id, "收到区块公告");
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::BlockAnnounced { peer_id, hash, height }).await {
+                        error!(error = %e, "转发区块公告到应用层失败");
+                    }
+                }
+            }
+            NetworkMessage::Block(block) => {
+                info!(block_hash = %block.calculate_hash_with(self.hasher.as_ref()), "收到区块广播");
+                self.metrics.record_block_received();
+                // 转发到应用层
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::NewBlock(block)).await {
+                        error!(error = %e, "转发区块事件到应用层失败");
+                    }
+                }
+            }
+            NetworkMessage::Transaction(transaction) => {
+                info!("收到交易广播");
+                // 转发到应用层
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::NewTransaction(transaction)).await {
+                        error!(error = %e, "转发交易事件到应用层失败");
+                    }
+                }
+            }
+            NetworkMessage::BlockRequest => {
+                // 处理区块请求：响应本地区块链数据
+                info!("收到区块同步请求，准备响应");
+                // 区块响应需要知道请求方，来源未知的请求无法回复
+                let Some(peer_id) = source else {
+                    return;
+                };
+                // 转发到应用层处理
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::BlocksRequested(peer_id)).await {
+                        error!(error = %e, "转发区块请求到应用层失败");
+                    }
+                }
+            }
+            NetworkMessage::BlockResponse(blocks) => {
+                // 处理区块响应：接收其他节点的区块链数据
+                info!(blocks = blocks.len(), "收到区块同步响应");
+                // 转发到应用层处理
+                if let Some(app_sender) = &self.app_event_sender {
+                    if let Err(e) = app_sender.send(NetworkEvent::BlocksReceived(blocks)).await {
+                        error!(error = %e, "转发区块响应到应用层失败");
+                    }
+                }
+            }
+            NetworkMessage::AddressAnnounce { wallet_address, nickname } => {
+                let Some(peer_id) = source else {
+                    return;
+                };
+                if !crate::wallet::validate_address(&wallet_address) {
+                    warn!(peer_id = %peer_id, wallet_address = %wallet_address, "忽略无效的地址公告");
+                    return;
+                }
+                if !self.announce_limiter.allow(peer_id, unix_now()) {
+                    return;
+                }
+                info!(nickname = %nickname, peer_id = %peer_id, wallet_address = %wallet_address, "收到地址公告");
+                if let Some(app_sender) = &self.app_event_sender {
+                    let event = NetworkEvent::AddressAnnounced { peer_id, wallet_address, nickname };
+                    if let Err(e) = app_sender.send(event).await {
+                        error!(error = %e, "转发地址公告到应用层失败");
+                    }
+                }
+            }
+            NetworkMessage::BlockBinary(data) => match Block::from_bytes(&data) {
+                Ok(block) => {
+                    info!(block_hash = %block.calculate_hash_with(self.hasher.as_ref()), "收到二进制区块广播");
+                    self.metrics.record_block_received();
+                    if let Some(app_sender) = &self.app_event_sender {
+                        if let Err(e) = app_sender.send(NetworkEvent::NewBlock(block)).await {
+                            error!(error = %e, "转发区块事件到应用层失败");
                         }
                     }
-                    Ok(NetworkMessage::BlockResponse(blocks)) => {
-                        // 处理区块响应：接收其他节点的区块链数据
-                        println!("📦 收到区块同步响应，包含 {} 个区块", blocks.len());
-                        // 转发到应用层处理
+                }
+                Err(e) => warn!(error = %e, "解码二进制区块失败"),
+            },
+            NetworkMessage::BlockResponseBinary(encoded) => {
+                match encoded.iter().map(|data| Block::from_bytes(data)).collect::<Result<Vec<_>, _>>() {
+                    Ok(blocks) => {
+                        info!(blocks = blocks.len(), "收到二进制区块同步响应");
                         if let Some(app_sender) = &self.app_event_sender {
-                            if let Err(e) = app_sender.send(NetworkEvent::SendBlocks(blocks)).await {
-                                eprintln!("转发区块响应到应用层失败: {}", e);
+                            if let Err(e) = app_sender.send(NetworkEvent::BlocksReceived(blocks)).await {
+                                error!(error = %e, "转发区块响应到应用层失败");
                             }
                         }
                     }
-                    Err(e) => {
-                        eprintln!("解析网络消息失败: {}", e);
+                    Err(e) => warn!(error = %e, "解码二进制区块失败"),
+                }
+            }
+            NetworkMessage::Capabilities { binary_blocks, compression } => {
+                if let Some(peer_id) = source {
+                    // 新节点同时发送握手消息，握手中的声明优先
+                    if self.peer_features.contains_key(&peer_id) {
+                        return;
+                    }
+                    self.peer_versions.entry(peer_id).or_insert(LEGACY_PROTOCOL_VERSION);
+                    if binary_blocks {
+                        self.binary_peers.insert(peer_id);
+                    } else {
+                        self.binary_peers.remove(&peer_id);
+                    }
+                    if compression {
+                        self.compression_peers.insert(peer_id);
+                    } else {
+                        self.compression_peers.remove(&peer_id);
                     }
                 }
             }
-            SwarmEvent::Behaviour(MyBehaviourEvent::Ping(ping_event)) => {
-                // 只在ping失败或连接问题时输出，减少日志干扰
-                match ping_event.result {
-                    Ok(_) => {
-                        // 成功的ping不输出，避免干扰用户界面
-                        // 可以选择性地记录连接健康状态
+            NetworkMessage::BlockRequestSince { known_height, known_hash } => {
+                info!(known_height, "收到增量区块同步请求");
+                let Some(peer_id) = source else {
+                    return;
+                };
+                if let Some(app_sender) = &self.app_event_sender {
+                    let event = NetworkEvent::BlocksSinceRequested { peer_id, known_height, known_hash };
+                    if let Err(e) = app_sender.send(event).await {
+                        error!(error = %e, "转发增量区块请求到应用层失败");
                     }
-                    Err(e) => {
-                        println!("⚠️ Ping失败 {}: {}", ping_event.peer, e);
+                }
+            }
+            NetworkMessage::BlockRequestLocator { known_height, locator } => {
+                info!(known_height, locator = locator.len(), "收到定位器区块同步请求");
+                let Some(peer_id) = source else {
+                    return;
+                };
+                if let Some(app_sender) = &self.app_event_sender {
+                    let event = NetworkEvent::BlocksLocatorRequested { peer_id, known_height, locator };
+                    if let Err(e) = app_sender.send(event).await {
+                        error!(error = %e, "转发定位器区块请求到应用层失败");
                     }
                 }
             }
-            _ => {}
+            NetworkMessage::BlockResponseSince { start_height, blocks } => {
+                info!(start_height, blocks = blocks.len(), "收到增量区块同步响应");
+                if let Some(app_sender) = &self.app_event_sender {
+                    let event = NetworkEvent::BlocksSinceReceived { start_height, blocks };
+                    if let Err(e) = app_sender.send(event).await {
+                        error!(error = %e, "转发增量区块响应到应用层失败");
+                    }
+                }
+            }
+            NetworkMessage::Handshake { protocol_version, supported_features } => {
+                let Some(peer_id) = source else {
+                    return;
+                };
+                if !self.handle_handshake(peer_id, protocol_version, supported_features) {
+                    warn!(peer_id = %peer_id, protocol_version, "节点的协议版本过旧，断开连接");
+                    let _ = self.command_sender.try_send(NetworkCommand::DisconnectPeer(peer_id));
+                }
+            }
+            NetworkMessage::GenesisHandshake(hash) => {
+                let Some(peer_id) = source else {
+                    return;
+                };
+                if !self.handle_genesis_handshake(peer_id, &hash) {
+                    warn!(peer_id = %peer_id, genesis_hash = %hash, "节点的创世区块与本节点不同，断开连接");
+                    let _ = self.command_sender.try_send(NetworkCommand::DisconnectPeer(peer_id));
+                }
+            }
+            NetworkMessage::GetBlock(_)
+            | NetworkMessage::GetBlockByHeight(_)
+            | NetworkMessage::BlockNotFound
+            | NetworkMessage::GetBlocks { .. }
+            | NetworkMessage::Blocks(_)
+            | NetworkMessage::GetBlocksAfter { .. }
+            | NetworkMessage::BlocksPage { .. } => {
+                debug!("忽略只用于定向请求的广播消息");
+            }
+            NetworkMessage::RelayServerAnnounce(addr) => {
+                if self.relay_server.is_some() {
+                    return;
+                }
+                info!(address = %addr, "发现中继服务器");
+                self.relay_server = Some(addr);
+                // 已经确认位于NAT之后时立即注册中继
+                if self.nat_status == NatStatus::Private {
+                    let _ = self.command_sender.try_send(NetworkCommand::UpdateNatStatus(NatStatus::Private));
+                }
+            }
+        }
+    }
+
+    /// 选择发送消息使用的线路格式
+    ///
+    /// 所有已连接节点都声明支持二进制格式时才使用二进制格式，否则回退到JSON。
+    /// gossipsub会把消息转发给间接相连的节点，这里只能保证直接相连的节点能够解码
+    fn wire_format(&self) -> WireFormat {
+        let all_binary = !self.connected_peers.is_empty()
+            && self.connected_peers.iter().all(|peer| self.binary_peers.contains(peer));
+        if all_binary { WireFormat::Binary } else { WireFormat::Json }
+    }
+
+    /// 所有已连接节点都声明能够解压时才压缩大消息
+    fn compression_enabled(&self) -> bool {
+        !self.connected_peers.is_empty()
+            && self.connected_peers.iter().all(|peer| self.compression_peers.contains(peer))
+    }
+
+    /// 广播本节点的能力声明和握手消息，协商完成前对方只能解码JSON，因此始终使用JSON编码。
+    /// 旧节点无法解析握手消息，只会忽略它并使用能力声明
+    fn publish_capabilities(&self, swarm: &mut Swarm<MyBehaviour>) {
+        let data = encode_message(&NetworkMessage::Capabilities { binary_blocks: true, compression: true }, WireFormat::Json);
+        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), data) {
+            error!(error = %e, "广播能力声明失败");
+        }
+        let handshake = NetworkMessage::Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            supported_features: SUPPORTED_FEATURES.iter().map(|feature| feature.to_string()).collect(),
+        };
+        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), encode_message(&handshake, WireFormat::Json)) {
+            error!(error = %e, "广播握手消息失败");
+        }
+        if let Some(hash) = &self.genesis_hash {
+            let genesis = encode_message(&NetworkMessage::GenesisHandshake(hash.clone()), WireFormat::Json);
+            if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), genesis) {
+                error!(error = %e, "广播创世区块握手失败");
+            }
+        }
+    }
+
+    /// 广播本节点的钱包地址公告，未设置公告地址时不做任何事
+    fn publish_announce(&self, swarm: &mut Swarm<MyBehaviour>) {
+        let Some((wallet_address, nickname)) = self.announce.clone() else {
+            return;
+        };
+        let message = NetworkMessage::AddressAnnounce { wallet_address, nickname };
+        let data = encode_message(&message, self.wire_format());
+        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(self.blocks_topic.clone(), data) {
+            error!(error = %e, "广播地址公告失败");
         }
-        Ok(())
     }
 
     /// 获取节点ID
@@ -601,9 +2952,9 @@ impl Network {
         self.peer_id
     }
 
-    /// 获取事件发送器
-    pub fn get_event_sender(&self) -> mpsc::Sender<NetworkEvent> {
-        self.event_sender.clone()
+    /// 获取命令发送器，应用层通过它向网络任务发送[`NetworkCommand`]
+    pub fn get_event_sender(&self) -> mpsc::Sender<NetworkCommand> {
+        self.command_sender.clone()
     }
 
     /// 获取连接的节点数量
@@ -636,87 +2987,140 @@ impl Network {
             .collect()
     }
 
+    /// 获取节点存储
+    pub fn peer_store(&self) -> &PeerStore {
+        &self.peer_store
+    }
+
+    /// 清理过期节点并将节点存储写入文件
+    pub fn save_peers(&mut self) {
+        self.peer_store.prune(unix_now());
+        if let Err(e) = self.peer_store.save() {
+            error!(error = %e, "保存节点存储失败");
+        }
+    }
+
     /// 手动触发节点发现
     pub async fn discover_peers(&mut self) {
         if let Some(swarm) = &mut self.swarm {
             // 启动Kademlia查询来发现更多节点
             let _ = swarm.behaviour_mut().kademlia.get_closest_peers(self.peer_id);
-            println!("🔍 启动节点发现查询...");
+            debug!("启动节点发现查询");
         }
     }
 
     /// 显示网络状态
     pub fn show_network_status(&self) {
-        println!("\n=== 网络状态 ===");
-        println!("节点ID: {}", self.peer_id);
-        println!("已连接节点: {}", self.connected_peers.len());
-        println!("已发现节点: {}", self.peers.len());
-        println!("自动连接: {}", if self.auto_connect_enabled { "启用" } else { "禁用" });
-        println!("最大连接数: {}", self.max_connections);
-        
-        if !self.connected_peers.is_empty() {
-            println!("连接的节点:");
-            for peer in &self.connected_peers {
-                println!("  - {}", peer);
-            }
+        info!(
+            peer_id = %self.peer_id,
+            connected = self.connected_peers.len(),
+            discovered = self.peers.len(),
+            auto_connect = self.auto_connect_enabled,
+            max_connections = self.max_connections,
+            outbound_reserve = self.outbound_reserve,
+            inbound = self.inbound_peers.len(),
+            "网络状态"
+        );
+        for (peer, addr) in &self.peers {
+            info!(peer_id = %peer, address = %addr, connected = self.connected_peers.contains(peer), "发现的节点");
         }
-        
-        if !self.peers.is_empty() {
-            println!("发现的节点:");
-            for (peer, addr) in &self.peers {
-                let status = if self.connected_peers.contains(peer) { "已连接" } else { "未连接" };
-                println!("  - {} ({}) - {}", peer, status, addr);
-            }
+        for peer in self.connected_peers.iter().filter(|peer| !self.peers.contains_key(peer)) {
+            info!(peer_id = %peer, connected = true, "连接的节点");
         }
-        println!("================\n");
     }
 
     // 保留原有的方法以保持兼容性
-    pub async fn broadcast_block(&self, block: Block) {
-        if let Err(e) = self.event_sender.send(NetworkEvent::NewBlock(block)).await {
-            eprintln!("发送区块广播事件失败: {}", e);
+    pub async fn broadcast_block(&self, block: Block, height: u64) {
+        if let Err(e) = self.command_sender.send(NetworkCommand::BroadcastBlock { block, height }).await {
+            error!(error = %e, "发送区块广播事件失败");
         }
     }
 
     pub async fn broadcast_transaction(&self, transaction: Transaction) {
-        if let Err(e) = self.event_sender.send(NetworkEvent::NewTransaction(transaction)).await {
-            eprintln!("发送交易广播事件失败: {}", e);
+        if let Err(e) = self.command_sender.send(NetworkCommand::BroadcastTransaction(transaction)).await {
+            error!(error = %e, "发送交易广播事件失败");
         }
     }
 
-    pub async fn sync_chain(&self, _blockchain: &Blockchain) {
-        if let Err(e) = self.event_sender.send(NetworkEvent::RequestBlocks).await {
-            eprintln!("发送区块同步请求失败: {}", e);
+    /// 请求本地链顶之后的区块，其他节点按区块定位器找到共同区块，只需发送缺少的部分
+    pub async fn sync_chain(&self, blockchain: &Blockchain) {
+        let command = NetworkCommand::RequestBlocks {
+            from_height: blockchain.height() as u64 + 1,
+            locator: blockchain.block_locator(),
+        };
+        if let Err(e) = self.command_sender.send(command).await {
+            error!(error = %e, "发送区块同步请求失败");
         }
     }
 
-    pub async fn new_with_channel(app_event_sender: mpsc::Sender<NetworkEvent>) -> Self {
-        let (event_sender, event_receiver) = mpsc::channel(100);
-        
-        let id_keys = identity::Keypair::generate_ed25519();
-        let peer_id = PeerId::from(id_keys.public());
-        
-        let blocks_topic = gossipsub::IdentTopic::new("blocks");
-        let transactions_topic = gossipsub::IdentTopic::new("transactions");
-        
-        Network {
-            peer_id,
-            peers: HashMap::new(),
-            connected_peers: HashSet::new(),
-            event_sender,
-            event_receiver,
-            blocks_topic,
-            transactions_topic,
-            swarm: None,
-            auto_connect_enabled: true,
-            max_connections: 10,
-            app_event_sender: Some(app_event_sender),
+    /// 向指定节点请求单个区块
+    ///
+    /// 请求只发送给该节点，不经过gossipsub广播，收到的区块以`NetworkEvent::BlockFetched`
+    /// 转发到应用层。用于补齐缺失的父区块，而不必同步整条链
+    ///
+    /// # 参数
+    ///
+    /// * `peer_id` - 目标节点，必须已经连接
+    /// * `hash` - 请求的区块哈希
+    ///
+    /// # 返回值
+    ///
+    /// 请求已提交给网络任务时返回Ok
+    pub fn request_specific_block(&mut self, peer_id: PeerId, hash: String) -> Result<(), NetworkError> {
+        if !self.connected_peers.contains(&peer_id) {
+            return Err(NetworkError::PeerNotConnected(peer_id));
+        }
+        self.command_sender
+            .try_send(NetworkCommand::RequestBlock { peer_id, query: BlockQuery::Hash(hash) })
+            .map_err(|_| NetworkError::ChannelClosed)
+    }
+
+    /// 创建向应用层发送事件的网络实例
+    ///
+    /// # 参数
+    ///
+    /// * `hasher` - 区块链使用的哈希算法，见[`Network::new`]
+    /// * `app_event_sender` - 应用层事件发送器
+    pub async fn new_with_channel(hasher: Arc<dyn Hasher>, app_event_sender: mpsc::Sender<NetworkEvent>) -> Self {
+        Self::build(hasher, Some(app_event_sender))
+    }
+
+    /// 使用指定的网络参数创建网络实例
+    ///
+    /// # 参数
+    ///
+    /// * `config` - 网络参数
+    /// * `hasher` - 区块链使用的哈希算法，见[`Network::new`]
+    /// * `app_event_sender` - 应用层事件发送器
+    ///
+    /// # 返回值
+    ///
+    /// 返回初始化的网络实例，引导节点在`start`后连接
+    pub async fn new_with_config(config: NetworkConfig, hasher: Arc<dyn Hasher>, app_event_sender: mpsc::Sender<NetworkEvent>) -> Self {
+        let mut network = Self::new_with_channel(hasher, app_event_sender).await;
+        network.max_connections = config.max_connections;
+        network.gossipsub_heartbeat = Duration::from_secs(config.gossipsub_heartbeat_secs);
+        network.max_message_bytes = config.max_message_bytes;
+        network.bootstrap_peers = config.bootstrap_peers;
+        network.set_seen_cache(config.seen_cache_capacity, Duration::from_secs(config.seen_cache_ttl_secs));
+        network
+    }
+
+    /// 获取当前使用的网络参数
+    pub fn config(&self) -> NetworkConfig {
+        NetworkConfig {
+            max_connections: self.max_connections,
+            bootstrap_peers: self.bootstrap_peers.clone(),
+            gossipsub_heartbeat_secs: self.gossipsub_heartbeat.as_secs(),
+            max_message_bytes: self.max_message_bytes,
+            seen_cache_capacity: self.seen_messages.cap().get(),
+            seen_cache_ttl_secs: self.seen_ttl.as_secs(),
         }
     }
 
     pub async fn dial(&self, addr: libp2p::Multiaddr) -> Result<(), Box<dyn Error>> {
-        if let Err(e) = self.event_sender.send(NetworkEvent::ConnectTo(addr)).await {
-            eprintln!("发送连接请求失败: {}", e);
+        if let Err(e) = self.command_sender.send(NetworkCommand::Dial(addr)).await {
+            error!(error = %e, "发送连接请求失败");
             return Err(e.into());
         }
         Ok(())
@@ -0,0 +1,158 @@
+//! # 交易验证模块
+//!
+//! 提供与区块链结构无关的交易验证流程：交易针对一个UTXO视图（根据输出引用查找未花费输出的函数）
+//! 进行验证，因此区块链、交易池和测试都可以用任意的UTXO快照验证交易。
+
+use crate::block::{OutPoint, Transaction, UtxoEntry, MAX_OP_RETURN_DATA};
+use crate::blockchain::DEFAULT_NETWORK_ID;
+use crate::wallet::Wallet;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// 交易验证错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TxError {
+    /// 交易没有输入
+    #[error("交易没有输入")]
+    NoInputs,
+    /// 交易没有输出
+    #[error("交易没有输出")]
+    NoOutputs,
+    /// 交易有多个数据输出
+    #[error("输出{index}是多余的数据输出，每笔交易最多一个")]
+    MultipleDataOutputs { index: usize },
+    /// 数据输出的数据不是有效的十六进制或超过长度上限
+    #[error("输出{index}的数据无效或超过{}字节", MAX_OP_RETURN_DATA)]
+    InvalidDataOutput { index: usize },
+    /// 不允许Coinbase交易的场合（例如交易池）收到了Coinbase交易
+    #[error("Coinbase交易只能出现在区块的第一个位置")]
+    UnexpectedCoinbase,
+    /// Coinbase交易必须恰好有一个输入
+    #[error("Coinbase交易有{inputs}个输入，必须恰好有一个")]
+    MalformedCoinbase { inputs: usize },
+    /// 同一交易的两个输入花费了同一个输出
+    #[error("输入{index}重复花费了输出{prev_tx}:{prev_index}")]
+    DuplicateInput { index: usize, prev_tx: String, prev_index: u32 },
+    /// 输入引用的输出不存在或已被花费
+    #[error("输入{index}引用的输出{prev_tx}:{prev_index}不存在或已被花费")]
+    MissingInput { index: usize, prev_tx: String, prev_index: u32 },
+    /// 输入引用的Coinbase输出尚未达到成熟深度
+    #[error("输入{index}引用的Coinbase输出{prev_tx}尚未成熟")]
+    ImmatureCoinbase { index: usize, prev_tx: String },
+    /// 输入的签名无效或签名公钥不属于被花费输出的所有者
+    #[error("输入{index}的签名无效")]
+    InvalidSignature { index: usize },
+    /// 输出总额加手续费超过了输入总额
+    #[error("输出{outputs}加手续费{fee}超过了输入总额{inputs}")]
+    InsufficientInputs { inputs: u64, outputs: u64, fee: u64 },
+}
+
+/// 交易验证器
+///
+/// 按结构、Coinbase规则、输入存在性、签名、金额守恒的顺序检查交易，返回遇到的第一个问题。
+/// 验证策略（是否接受Coinbase、哪些Coinbase输出尚未成熟、签名所属的网络）由字段配置，
+/// 默认配置适用于默认网络交易池中的普通交易
+#[derive(Debug, Clone)]
+pub struct TxValidator {
+    /// 是否接受Coinbase交易，验证区块中的交易时为true
+    pub allow_coinbase: bool,
+    /// 尚未成熟、不能花费的Coinbase交易ID
+    pub immature_coinbases: HashSet<String>,
+    /// 网络标识，签名必须是针对该网络生成的
+    pub network_id: String,
+}
+
+impl Default for TxValidator {
+    fn default() -> Self {
+        TxValidator {
+            allow_coinbase: false,
+            immature_coinbases: HashSet::new(),
+            network_id: String::from(DEFAULT_NETWORK_ID),
+        }
+    }
+}
+
+impl TxValidator {
+    /// 验证交易
+    ///
+    /// Coinbase交易没有被花费的输出，只检查结构；
+    /// 输出总额加手续费不能超过输入总额，输入多出的部分被销毁
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 要验证的交易
+    /// * `view` - UTXO视图，根据输出引用查找未花费输出
+    ///
+    /// # 返回值
+    ///
+    /// 交易有效时返回Ok，否则返回遇到的第一个问题
+    pub fn validate(&self, tx: &Transaction, view: impl Fn(&OutPoint) -> Option<UtxoEntry>) -> Result<(), TxError> {
+        if tx.inputs.is_empty() {
+            return Err(TxError::NoInputs);
+        }
+        if tx.outputs.is_empty() {
+            return Err(TxError::NoOutputs);
+        }
+        Self::check_data_outputs(tx)?;
+        if tx.is_coinbase() {
+            if !self.allow_coinbase {
+                return Err(TxError::UnexpectedCoinbase);
+            }
+            if tx.inputs.len() != 1 {
+                return Err(TxError::MalformedCoinbase { inputs: tx.inputs.len() });
+            }
+            return Ok(());
+        }
+
+        let mut seen = HashSet::new();
+        let mut entries = Vec::with_capacity(tx.inputs.len());
+        for (index, input) in tx.inputs.iter().enumerate() {
+            if !seen.insert((input.prev_tx.as_str(), input.prev_index)) {
+                return Err(TxError::DuplicateInput {
+                    index,
+                    prev_tx: input.prev_tx.clone(),
+                    prev_index: input.prev_index,
+                });
+            }
+            let outpoint = OutPoint { tx_id: input.prev_tx.clone(), index: input.prev_index };
+            let Some(entry) = view(&outpoint) else {
+                return Err(TxError::MissingInput {
+                    index,
+                    prev_tx: input.prev_tx.clone(),
+                    prev_index: input.prev_index,
+                });
+            };
+            if self.immature_coinbases.contains(&input.prev_tx) {
+                return Err(TxError::ImmatureCoinbase { index, prev_tx: input.prev_tx.clone() });
+            }
+            entries.push(entry);
+        }
+
+        for (index, entry) in entries.iter().enumerate() {
+            if !Wallet::verify_input(tx, index, entry, &self.network_id) {
+                return Err(TxError::InvalidSignature { index });
+            }
+        }
+
+        let inputs = entries.iter().fold(0u64, |sum, entry| sum.saturating_add(entry.value));
+        let outputs = tx.outputs.iter().fold(0u64, |sum, output| sum.saturating_add(output.value));
+        if outputs.saturating_add(tx.fee) > inputs {
+            return Err(TxError::InsufficientInputs { inputs, outputs, fee: tx.fee });
+        }
+        Ok(())
+    }
+
+    /// 检查数据输出：每笔交易最多一个，数据不超过`MAX_OP_RETURN_DATA`字节
+    fn check_data_outputs(tx: &Transaction) -> Result<(), TxError> {
+        let mut data_outputs = tx.outputs.iter().enumerate().filter(|(_, output)| output.is_unspendable());
+        if let Some((index, output)) = data_outputs.next() {
+            if output.embedded_data().is_none_or(|data| data.len() > MAX_OP_RETURN_DATA) {
+                return Err(TxError::InvalidDataOutput { index });
+            }
+        }
+        match data_outputs.next() {
+            Some((index, _)) => Err(TxError::MultipleDataOutputs { index }),
+            None => Ok(()),
+        }
+    }
+}
@@ -4,24 +4,843 @@
 //! 
 //! 该模块使用secp256k1椭圆曲线算法进行密钥生成和交易签名。
 
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use secp256k1::ecdsa::Signature;
 use secp256k1::{PublicKey, SecretKey};
-use sha2::{Sha256, Digest};
+use sha2::{Sha256, Sha512, Digest};
+use hmac::{Hmac, Mac};
 use hex;
-use std::collections::HashMap;
-use crate::block::{Transaction, TxInput, TxOutput};
+use crate::block::{Block, OutPoint, Transaction, TxInput, TxOutput, UtxoEntry, MAX_OP_RETURN_DATA};
+use crate::blockchain::{csv_field, Blockchain, DEFAULT_NETWORK_ID};
+use crate::mempool::Mempool;
 use rand;
 use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use zeroize::Zeroize;
 
-/// 钱包结构，包含密钥对和地址
-#[derive(Serialize, Deserialize)]
-pub struct Wallet {
-    /// 私钥，用于交易签名
+/// 默认手续费率（每千字节）
+pub const DEFAULT_FEE_RATE: u64 = 10;
+
+/// 默认粉尘阈值，低于该金额的支付输出花费成本高于其价值，会被拒绝，见[`Wallet::dust_limit`]
+pub const DUST_THRESHOLD: u64 = 5;
+
+/// 钱包文件格式版本
+///
+/// 版本2开始保存多个密钥对；版本3开始私钥保存在带格式标记的`secret`字段中。
+/// 没有版本字段的旧文件只包含单个密钥，与版本2一样直接保存`private_key`，仍然可以加载
+pub const WALLET_FORMAT_VERSION: u32 = 3;
+
+/// 钱包文件中明文十六进制私钥的格式标记
+const SECRET_FORMAT_PLAINTEXT_HEX: &str = "plaintext-hex";
+
+/// 签名消息的前缀
+///
+/// 消息摘要加上前缀后不会与交易签名摘要相同，避免签名请求被用来伪造交易签名
+const MESSAGE_PREFIX: &[u8] = b"Blockchain Demo Signed Message:\n";
+
+/// WIF格式的版本字节
+const WIF_VERSION: u8 = 0x80;
+
+/// WIF格式中表示压缩公钥的后缀字节
+const WIF_COMPRESSED_FLAG: u8 = 0x01;
+
+/// 多重签名锁定脚本的类型标记，完整格式为`multisig:<阈值>:<公钥1>,<公钥2>,...`
+pub const MULTISIG_PREFIX: &str = "multisig:";
+
+/// 多重签名允许的最大公钥数量
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// 地址的版本字节
+///
+/// 地址为Base58Check编码：版本字节 + 20字节公钥哈希 + 4字节校验和（两次SHA256的前4字节），
+/// 与比特币的P2PKH地址格式相同，因此地址以`1`开头
+pub const ADDRESS_VERSION: u8 = 0x00;
+
+/// 地址解码错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AddressError {
+    /// 包含Base58字母表以外的字符，或长度不足以包含校验和
+    #[error("地址不是有效的Base58编码: {0}")]
+    InvalidEncoding(String),
+    /// 校验和不匹配，通常是地址输入错误
+    #[error("地址校验和错误")]
+    InvalidChecksum,
+    /// 版本字节不是`ADDRESS_VERSION`
+    #[error("地址版本错误: {0:#04x}")]
+    WrongVersion(u8),
+    /// 公钥哈希不是20字节
+    #[error("地址长度错误：公钥哈希应为20字节，实际{0}字节")]
+    InvalidLength(usize),
+}
+
+/// 将公钥哈希编码为Base58Check地址
+///
+/// # 参数
+///
+/// * `pubkey_hash` - 公钥的RIPEMD160(SHA256)哈希
+pub fn encode_address(pubkey_hash: &[u8; 20]) -> String {
+    bs58::encode(pubkey_hash).with_check_version(ADDRESS_VERSION).into_string()
+}
+
+/// 解码Base58Check地址
+///
+/// # 参数
+///
+/// * `address` - Base58Check编码的地址
+///
+/// # 返回值
+///
+/// 返回地址中的公钥哈希；编码、校验和、版本或长度错误时返回对应的错误
+pub fn decode_address(address: &str) -> Result<[u8; 20], AddressError> {
+    decode_address_with(address, false)
+}
+
+/// 解码地址，可选地接受旧格式
+///
+/// 早期版本的地址是公钥哈希的40位十六进制字符串，没有校验和。
+/// 已经写入区块链的输出仍然使用旧格式，验证这些输出时需要设置`allow_legacy`
+///
+/// # 参数
+///
+/// * `address` - 要解码的地址
+/// * `allow_legacy` - 是否接受40位十六进制的旧格式地址
+///
+/// # 返回值
+///
+/// 返回地址中的公钥哈希；地址无效时返回对应的错误
+pub fn decode_address_with(address: &str, allow_legacy: bool) -> Result<[u8; 20], AddressError> {
+    if allow_legacy && address.len() == 40 && address.chars().all(|c| c.is_ascii_hexdigit()) {
+        let mut hash = [0u8; 20];
+        hex::decode_to_slice(address, &mut hash).map_err(|e| AddressError::InvalidEncoding(e.to_string()))?;
+        return Ok(hash);
+    }
+    
+    let payload = bs58::decode(address).with_check(None).into_vec().map_err(|e| match e {
+        bs58::decode::Error::InvalidChecksum { .. } => AddressError::InvalidChecksum,
+        other => AddressError::InvalidEncoding(other.to_string()),
+    })?;
+    match payload.split_first() {
+        Some((&ADDRESS_VERSION, hash)) => hash.try_into().map_err(|_| AddressError::InvalidLength(hash.len())),
+        Some((&version, _)) => Err(AddressError::WrongVersion(version)),
+        None => Err(AddressError::InvalidLength(0)),
+    }
+}
+
+/// 检查地址是否为有效的Base58Check地址
+///
+/// # 参数
+///
+/// * `address` - 要检查的地址
+pub fn validate_address(address: &str) -> bool {
+    decode_address(address).is_ok()
+}
+
+/// 创建交易时的错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TransactionError {
+    /// 发送金额为零
+    #[error("发送金额不能为零")]
+    ZeroAmount,
+    /// 没有指定任何接收者
+    #[error("至少需要一个接收者")]
+    NoRecipients,
+    /// 发送金额低于粉尘阈值
+    #[error("发送金额{amount}低于粉尘阈值{threshold}")]
+    DustAmount { amount: u64, threshold: u64 },
+    /// 接收地址格式无效
+    #[error("接收地址格式无效: {0}")]
+    InvalidAddress(String),
+    /// 钱包没有任何可花费的UTXO
+    #[error("钱包没有可用的UTXO")]
+    NoUtxos,
+    /// 所有可用UTXO的总额不足以支付金额
+    #[error("余额不足：需要{needed}，可用{available}")]
+    InsufficientFunds { needed: u64, available: u64 },
+    /// 余额足以支付金额，但不足以同时支付手续费
+    #[error("余额不足以支付手续费：金额{amount} + 手续费{fee}，可用{available}")]
+    InsufficientFundsForFee { amount: u64, fee: u64, available: u64 },
+    /// 给定的UTXO总额足够，但属于本钱包的部分不足
+    #[error("钱包自有余额不足：需要{needed}，自有{owned}，其余UTXO属于其他地址")]
+    InsufficientOwnedFunds { needed: u64, owned: u64 },
+    /// 可合并的UTXO少于两个，合并没有意义
+    #[error("只有{available}个UTXO，至少需要两个才能合并")]
+    NothingToConsolidate { available: usize },
+    /// 要记录的数据超过数据输出的长度上限
+    #[error("数据长度为{size}字节，超过上限{max}字节")]
+    DataTooLarge { size: usize, max: usize },
+}
+
+/// 校验接收者列表并合并重复地址
+///
+/// 同一地址的金额相加，合并后的输出保持该地址第一次出现的位置
+///
+/// # 参数
+///
+/// * `recipients` - 接收者地址和金额列表
+///
+/// # 返回值
+///
+/// 返回合并后的接收者列表；列表为空、任一金额为零或低于`dust_limit`、任一地址无效时返回对应的错误
+fn merge_recipients(recipients: &[(String, u64)], dust_limit: u64) -> Result<Vec<(String, u64)>, TransactionError> {
+    if recipients.is_empty() {
+        return Err(TransactionError::NoRecipients);
+    }
+    
+    let mut merged: Vec<(String, u64)> = Vec::new();
+    for (address, amount) in recipients {
+        if *amount == 0 {
+            return Err(TransactionError::ZeroAmount);
+        }
+        if *amount < dust_limit {
+            return Err(TransactionError::DustAmount { amount: *amount, threshold: dust_limit });
+        }
+        if !validate_address(address) && MultisigScript::parse(address).is_none() {
+            return Err(TransactionError::InvalidAddress(address.clone()));
+        }
+        match merged.iter_mut().find(|(existing, _)| existing == address) {
+            Some((_, total)) => *total += amount,
+            None => merged.push((address.clone(), *amount)),
+        }
+    }
+    Ok(merged)
+}
+
+/// 创建交易时的手续费策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeePolicy {
+    /// 固定手续费
+    Fixed(u64),
+    /// 按交易估算大小（含签名，见`Transaction::size_bytes`）每字节收取的费率
+    PerByte(u64),
+}
+
+impl FeePolicy {
+    /// 计算指定大小的交易需要的手续费
+    ///
+    /// # 参数
+    ///
+    /// * `size` - 交易的估算大小（字节）
+    pub fn fee_for_size(&self, size: usize) -> u64 {
+        match *self {
+            FeePolicy::Fixed(fee) => fee,
+            FeePolicy::PerByte(rate) => size as u64 * rate,
+        }
+    }
+}
+
+/// 选择交易输入的策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoinSelectionStrategy {
+    /// 分支限界：寻找总额与需要的金额相差不超过1%的输入组合，找到时不产生找零输出，
+    /// 多出的部分并入手续费；找不到时回退到`LargestFirst`
+    #[default]
+    BranchAndBound,
+    /// 从金额最大的UTXO开始选择，输入数量最少
+    LargestFirst,
+    /// 从金额最小的UTXO开始选择，顺便合并小额输出
+    SmallestFirst,
+    /// 随机顺序选择，避免暴露钱包的选择规律
+    Random,
+}
+
+/// 分支限界搜索最多尝试的组合数，超过后放弃精确匹配
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// 分支限界允许的超出比例的分母：超出需要金额的部分不超过其1/100
+const BNB_TOLERANCE_DIVISOR: u64 = 100;
+
+/// 按策略从候选UTXO中选择输入
+///
+/// # 参数
+///
+/// * `candidates` - 属于本钱包的UTXO
+/// * `needed` - 需要覆盖的金额（支付金额加手续费）
+/// * `strategy` - 选择策略
+///
+/// # 返回值
+///
+/// 返回选择的输入和是否为精确匹配；余额不足时返回全部候选，由调用方报告错误。
+/// 即使需要的金额为零也至少选择一个输入
+fn select_coins(
+    mut candidates: Vec<&(OutPoint, UtxoEntry)>,
+    needed: u64,
+    strategy: CoinSelectionStrategy,
+) -> (Vec<&(OutPoint, UtxoEntry)>, bool) {
+    match strategy {
+        CoinSelectionStrategy::BranchAndBound => {
+            candidates.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.value));
+            if let Some(exact) = branch_and_bound(&candidates, needed) {
+                return (exact, true);
+            }
+        }
+        CoinSelectionStrategy::LargestFirst => candidates.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.value)),
+        CoinSelectionStrategy::SmallestFirst => candidates.sort_by_key(|(_, entry)| entry.value),
+        CoinSelectionStrategy::Random => {
+            use rand::seq::SliceRandom;
+            candidates.shuffle(&mut rand::thread_rng());
+        }
+    }
+    
+    let mut selected = Vec::new();
+    let mut total = 0u64;
+    for candidate in candidates {
+        if total >= needed && !selected.is_empty() {
+            break;
+        }
+        total += candidate.1.value;
+        selected.push(candidate);
+    }
+    (selected, false)
+}
+
+/// 深度优先搜索总额落在`[needed, needed + needed / 100]`内的非空输入组合
+///
+/// `candidates`需要按金额从大到小排序，剩余金额不足或已超出上限的分支会被剪掉
+fn branch_and_bound<'a>(candidates: &[&'a (OutPoint, UtxoEntry)], needed: u64) -> Option<Vec<&'a (OutPoint, UtxoEntry)>> {
+    let values: Vec<u64> = candidates.iter().map(|(_, entry)| entry.value).collect();
+    // remaining[i]为第i个及之后所有候选的总额
+    let mut remaining = vec![0u64; values.len() + 1];
+    for i in (0..values.len()).rev() {
+        remaining[i] = remaining[i + 1].saturating_add(values[i]);
+    }
+    let mut search = BnbSearch {
+        values: &values,
+        remaining: &remaining,
+        needed,
+        upper: needed.saturating_add(needed / BNB_TOLERANCE_DIVISOR),
+        chosen: Vec::new(),
+        tries: 0,
+    };
+    search.search(0, 0)
+        .then(|| search.chosen.iter().map(|&i| candidates[i]).collect())
+}
+
+/// 分支限界搜索的状态
+struct BnbSearch<'a> {
+    values: &'a [u64],
+    remaining: &'a [u64],
+    needed: u64,
+    upper: u64,
+    chosen: Vec<usize>,
+    tries: usize,
+}
+
+impl BnbSearch<'_> {
+    /// 从第`index`个候选开始搜索，`total`为已选择的总额，找到时`chosen`为选择的下标
+    fn search(&mut self, index: usize, total: u64) -> bool {
+        self.tries += 1;
+        if self.tries > BNB_MAX_TRIES || total > self.upper {
+            return false;
+        }
+        if total >= self.needed && !self.chosen.is_empty() {
+            return true;
+        }
+        if index == self.values.len() || total.saturating_add(self.remaining[index]) < self.needed {
+            return false;
+        }
+        // 先尝试选择当前候选，再尝试跳过
+        self.chosen.push(index);
+        if self.search(index + 1, total + self.values[index]) {
+            return true;
+        }
+        self.chosen.pop();
+        self.search(index + 1, total)
+    }
+}
+
+/// 钱包拥有的一个未花费输出
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnspentOutput {
+    /// 输出所在交易的ID
+    pub tx_id: String,
+    /// 输出在交易中的索引
+    pub output_index: u32,
+    /// 输出金额
+    pub value: u64,
+    /// 确认数，输出所在区块为链顶时为1
+    pub confirmations: usize,
+}
+
+/// 钱包余额明细
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BalanceBreakdown {
+    /// 链上已确认的余额，包括尚未成熟的Coinbase奖励
+    pub confirmed: u64,
+    /// 交易池中支付给本钱包的金额，包括找零
+    pub pending_in: u64,
+    /// 交易池中的交易花费的本钱包已确认输出总额
+    pub pending_out: u64,
+    /// 已成熟且未被待处理交易锁定、可以立即花费的余额
+    pub spendable: u64,
+}
+
+/// 钱包管理错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WalletError {
+    /// 指定名称的钱包不存在
+    #[error("钱包不存在: {0}")]
+    UnknownWallet(String),
+    /// 钱包文件无法读取或写入
+    #[error("无法访问钱包文件 {path}: {reason}")]
+    Io { path: String, reason: String },
+    /// 钱包文件内容损坏或不是钱包文件
+    #[error("无法解析钱包文件 {path}: {reason}")]
+    Parse { path: String, reason: String },
+    /// 钱包文件由更新版本的程序写入，当前版本无法读取
+    #[error("钱包文件 {path} 的格式版本{version}高于支持的版本{}", WALLET_FORMAT_VERSION)]
+    UnsupportedVersion { path: String, version: u32 },
+    /// 助记词无效（单词不在词表中、单词数量错误或校验和不匹配）
+    #[error("无效的助记词: {0}")]
+    InvalidMnemonic(String),
+    /// 私钥无效（既不是64位十六进制，也不是有效的WIF）
+    #[error("无效的私钥: {0}")]
+    InvalidPrivateKey(String),
+    /// 观察钱包没有私钥，无法签名、派生地址或导出私钥
+    #[error("观察钱包没有私钥，无法执行该操作")]
+    WatchOnly,
+    /// 公钥或地址无效
+    #[error("无效的公钥或地址: {0}")]
+    InvalidAddress(String),
+    /// 未签名交易的输入没有对应的未花费输出
+    #[error("找不到输入引用的未花费输出: {0}")]
+    MissingInput(String),
+    /// 输入花费的地址不属于本钱包，无法签名
+    #[error("地址不属于本钱包: {0}")]
+    NotOwned(String),
+    /// 多重签名的公钥列表或阈值无效
+    #[error("无效的多重签名配置: {0}")]
+    InvalidMultisig(String),
+    /// 钱包与要签名的交易属于不同的网络
+    #[error("钱包属于网络{wallet}，交易属于网络{transaction}")]
+    NetworkMismatch { wallet: String, transaction: String },
+    /// 签名者无法签名
+    #[error("签名失败: {0}")]
+    Sign(#[from] SignError),
+}
+
+/// 钱包中主地址之外的密钥对
+///
+/// `Debug`输出不包含私钥，离开作用域时私钥会被清零
+#[derive(Clone)]
+pub struct KeyPair {
+    /// 私钥
     pub private_key: SecretKey,
-    /// 公钥，用于验证签名
+    /// 公钥
     pub public_key: PublicKey,
+    /// 公钥对应的地址
+    pub address: String,
+}
+
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("private_key", &"<redacted>")
+            .field("public_key", &self.public_key)
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+impl Drop for KeyPair {
+    fn drop(&mut self) {
+        wipe_secret(&mut self.private_key);
+    }
+}
+
+/// 签名错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignError {
+    /// 签名者没有可用的密钥，例如观察钱包
+    #[error("签名者没有可用的密钥")]
+    NoKey,
+    /// 外部签名者拒绝签名或无法访问
+    #[error("外部签名者失败: {0}")]
+    External(String),
+    /// 输入花费的地址没有匹配的签名者
+    #[error("没有签名者持有地址{0}的密钥")]
+    NotOwned(String),
+}
+
+/// 交易签名者
+///
+/// 签名者只需要对签名摘要签名，不需要暴露私钥，因此密钥可以保存在其他进程或硬件设备中。
+/// [`Wallet`]以主密钥实现，[`KeyPair`]以自身实现，[`CallbackSigner`]把摘要转发给调用方提供的函数
+pub trait Signer {
+    /// 签名者的地址，花费该地址的输出时使用本签名者
+    fn address(&self) -> &str;
+
+    /// 对32字节的签名摘要签名
+    ///
+    /// # 参数
+    ///
+    /// * `digest` - 签名摘要，见[`Transaction::signature_hash`]
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<Signature, SignError>;
+
+    /// 签名者的公钥，写入`script_sig`供验证方检查；只有地址的观察钱包返回None
+    fn public_key(&self) -> Option<PublicKey>;
+}
+
+impl Signer for KeyPair {
+    fn address(&self) -> &str {
+        &self.address
+    }
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<Signature, SignError> {
+        Ok(sign_with_key(digest, &self.private_key))
+    }
+
+    fn public_key(&self) -> Option<PublicKey> {
+        Some(self.public_key)
+    }
+}
+
+/// 把签名摘要转发给回调函数的签名者，用于连接其他进程或设备中的密钥
+pub struct CallbackSigner<F> {
+    /// 公钥对应的地址
+    address: String,
+    /// 外部密钥的公钥
+    public_key: PublicKey,
+    /// 对摘要签名的回调
+    callback: F,
+}
+
+impl<F> CallbackSigner<F>
+where
+    F: Fn(&[u8; 32]) -> Result<Signature, SignError>,
+{
+    /// 创建回调签名者，地址由公钥计算
+    ///
+    /// # 参数
+    ///
+    /// * `public_key` - 外部密钥的公钥
+    /// * `callback` - 对摘要签名的函数
+    pub fn new(public_key: PublicKey, callback: F) -> Self {
+        CallbackSigner {
+            address: Wallet::public_key_to_address(&public_key),
+            public_key,
+            callback,
+        }
+    }
+}
+
+impl<F> Signer for CallbackSigner<F>
+where
+    F: Fn(&[u8; 32]) -> Result<Signature, SignError>,
+{
+    fn address(&self) -> &str {
+        &self.address
+    }
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<Signature, SignError> {
+        (self.callback)(digest)
+    }
+
+    fn public_key(&self) -> Option<PublicKey> {
+        Some(self.public_key)
+    }
+}
+
+/// 用私钥对签名摘要签名
+fn sign_with_key(digest: &[u8; 32], private_key: &SecretKey) -> Signature {
+    let message = secp256k1::Message::from_slice(digest).unwrap();
+    secp256k1::Secp256k1::signing_only().sign_ecdsa(&message, private_key)
+}
+
+/// 用一组签名者签名交易
+///
+/// 未签名输入的`script_sig`是所花费输出的地址（见[`Wallet::create_transaction`]），
+/// 据此选择地址匹配的签名者。
+/// 签名后每个输入的`script_sig`为`公钥十六进制:签名十六进制`
+///
+/// # 参数
+///
+/// * `tx` - 要签名的交易
+/// * `signers` - 可用的签名者
+/// * `network_id` - 交易所属网络的标识，签名只在该网络有效
+///
+/// # 返回值
+///
+/// 全部输入签名成功返回Ok；没有带密钥的签名者时返回`SignError::NoKey`，
+/// 任一输入没有地址匹配的签名者时返回`SignError::NotOwned`，任一签名者失败时返回其错误，
+/// 出错时交易保持不变
+pub fn sign_transaction_with(tx: &mut Transaction, signers: &[&dyn Signer], network_id: &str) -> Result<(), SignError> {
+    if signers.iter().all(|signer| signer.public_key().is_none()) {
+        return Err(SignError::NoKey);
+    }
+    
+    let signatures = tx.inputs.iter().enumerate()
+        .map(|(index, input)| {
+            let address = input.script_sig.as_str();
+            let (signer, public_key) = signers.iter()
+                .find_map(|signer| {
+                    signer.public_key()
+                        .filter(|public_key| Wallet::public_key_matches(public_key, address))
+                        .map(|public_key| (signer, public_key))
+                })
+                .ok_or_else(|| SignError::NotOwned(address.to_string()))?;
+            let signature = signer.sign_digest(&tx.signature_hash(index, address, network_id))?;
+            Ok(format!("{}:{}", hex::encode(public_key.serialize()), hex::encode(signature.serialize_compact())))
+        })
+        .collect::<Result<Vec<String>, SignError>>()?;
+    
+    for (input, script_sig) in tx.inputs.iter_mut().zip(signatures) {
+        input.script_sig = script_sig;
+    }
+    Ok(())
+}
+
+/// 将内存中的私钥清零
+fn wipe_secret(key: &mut SecretKey) {
+    // SAFETY: SecretKey只包含一个32字节数组，没有Drop实现，全零字节是有效的数组；
+    // 清零后的私钥不再被使用
+    unsafe { zeroize::zeroize_flat_type(key) }
+}
+
+/// 钱包文件中保存的私钥
+///
+/// `format`说明`data`的编码方式，目前只有明文十六进制（`plaintext-hex`）；
+/// 加密格式可以使用相同的结构，钱包文件的其余部分保持不变
+#[derive(Serialize, Deserialize)]
+struct StoredSecret {
+    format: String,
+    data: String,
+}
+
+impl StoredSecret {
+    /// 以明文十六进制保存私钥
+    fn plaintext(key: &SecretKey) -> Self {
+        let mut bytes = key.secret_bytes();
+        let data = hex::encode(bytes);
+        bytes.zeroize();
+        StoredSecret { format: String::from(SECRET_FORMAT_PLAINTEXT_HEX), data }
+    }
+
+    /// 还原私钥，不支持的格式返回错误
+    fn to_secret_key(&self) -> Result<SecretKey, String> {
+        if self.format != SECRET_FORMAT_PLAINTEXT_HEX {
+            return Err(format!("不支持的私钥格式: {}", self.format));
+        }
+        let mut bytes = hex::decode(&self.data).map_err(|e| e.to_string())?;
+        let key = SecretKey::from_slice(&bytes).map_err(|e| e.to_string());
+        bytes.zeroize();
+        key
+    }
+}
+
+impl Drop for StoredSecret {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
+
+/// 钱包文件中的密钥对
+#[derive(Serialize, Deserialize)]
+struct KeyPairFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<StoredSecret>,
+    public_key: PublicKey,
+    address: String,
+    /// 版本2的文件直接保存私钥，只在读取旧文件时使用
+    #[serde(default, skip_serializing)]
+    private_key: Option<SecretKey>,
+}
+
+/// 钱包文件的结构，见[`WALLET_FORMAT_VERSION`]
+#[derive(Serialize, Deserialize)]
+struct WalletFile {
+    #[serde(default)]
+    version: u32,
+    address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_key: Option<PublicKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<StoredSecret>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mnemonic: Option<String>,
+    #[serde(default)]
+    extra_keys: Vec<KeyPairFile>,
+    #[serde(default = "default_fresh_change_address")]
+    fresh_change_address: bool,
+    #[serde(default)]
+    watch_only: bool,
+    #[serde(default = "default_dust_limit")]
+    dust_limit: u64,
+    #[serde(default = "default_network_id")]
+    network_id: String,
+    /// 版本2及更早的文件直接保存私钥，只在读取旧文件时使用
+    #[serde(default, skip_serializing)]
+    private_key: Option<SecretKey>,
+}
+
+impl Drop for WalletFile {
+    fn drop(&mut self) {
+        if let Some(key) = self.private_key.as_mut() {
+            wipe_secret(key);
+        }
+        if let Some(mnemonic) = self.mnemonic.as_mut() {
+            mnemonic.zeroize();
+        }
+    }
+}
+
+/// 还原文件中的私钥，优先使用新格式的`secret`字段
+fn restore_secret(secret: &Option<StoredSecret>, legacy: Option<SecretKey>) -> Result<Option<SecretKey>, String> {
+    match secret {
+        Some(secret) => secret.to_secret_key().map(Some),
+        None => Ok(legacy),
+    }
+}
+
+/// 钱包结构，包含密钥对和地址
+///
+/// `private_key`/`public_key`/`address`是钱包的主密钥对，
+/// 通过[`Wallet::new_address`]派生的其他密钥对保存在`extra_keys`中。
+/// 观察钱包（见[`Wallet::watch_only`]）没有私钥，只能查询余额和构建未签名交易。
+///
+/// 序列化格式见[`WALLET_FORMAT_VERSION`]；`Debug`输出不包含私钥和助记词，
+/// 钱包离开作用域时私钥和助记词会被清零
+pub struct Wallet {
+    /// 私钥，用于交易签名；观察钱包没有私钥，保存时也不写入该字段
+    pub private_key: Option<SecretKey>,
+    /// 公钥，用于验证签名；只由地址创建的观察钱包没有公钥
+    pub public_key: Option<PublicKey>,
     /// 钱包地址，公钥的哈希表示
     pub address: String,
+    /// 恢复短语（BIP39助记词），随机生成的钱包没有助记词
+    pub mnemonic: Option<String>,
+    /// 钱包文件格式版本，旧的单密钥格式没有该字段
+    pub version: u32,
+    /// 主地址之外的其他密钥对，按派生顺序排列
+    extra_keys: Vec<KeyPair>,
+    /// 找零是否发送到新派生的地址，为false时找零回到主地址
+    pub fresh_change_address: bool,
+    /// 是否为观察钱包
+    watch_only: bool,
+    /// 粉尘阈值：低于该金额的支付被拒绝，低于该金额的找零并入手续费，默认为[`DUST_THRESHOLD`]
+    pub dust_limit: u64,
+    /// 钱包所属网络的标识，签名摘要包含该标识，默认为[`DEFAULT_NETWORK_ID`]
+    pub network_id: String,
+}
+
+impl Serialize for Wallet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WalletFile {
+            version: self.version,
+            address: self.address.clone(),
+            public_key: self.public_key,
+            secret: self.private_key.as_ref().map(StoredSecret::plaintext),
+            mnemonic: self.mnemonic.clone(),
+            extra_keys: self.extra_keys.iter()
+                .map(|key| KeyPairFile {
+                    secret: Some(StoredSecret::plaintext(&key.private_key)),
+                    public_key: key.public_key,
+                    address: key.address.clone(),
+                    private_key: None,
+                })
+                .collect(),
+            fresh_change_address: self.fresh_change_address,
+            watch_only: self.watch_only,
+            dust_limit: self.dust_limit,
+            network_id: self.network_id.clone(),
+            private_key: None,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Wallet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+        let mut file = WalletFile::deserialize(deserializer)?;
+        let private_key = restore_secret(&file.secret, file.private_key).map_err(D::Error::custom)?;
+        let extra_keys = file.extra_keys.iter()
+            .map(|key| match restore_secret(&key.secret, key.private_key) {
+                Ok(Some(private_key)) => Ok(KeyPair { private_key, public_key: key.public_key, address: key.address.clone() }),
+                Ok(None) => Err(D::Error::custom(format!("密钥对{}缺少私钥", key.address))),
+                Err(e) => Err(D::Error::custom(e)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Wallet {
+            private_key,
+            public_key: file.public_key,
+            address: std::mem::take(&mut file.address),
+            mnemonic: file.mnemonic.take(),
+            version: file.version,
+            extra_keys,
+            fresh_change_address: file.fresh_change_address,
+            watch_only: file.watch_only,
+            dust_limit: file.dust_limit,
+            network_id: std::mem::take(&mut file.network_id),
+        })
+    }
+}
+
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |present: bool| if present { "<redacted>" } else { "None" };
+        f.debug_struct("Wallet")
+            .field("private_key", &redacted(self.private_key.is_some()))
+            .field("public_key", &self.public_key)
+            .field("address", &self.address)
+            .field("mnemonic", &redacted(self.mnemonic.is_some()))
+            .field("version", &self.version)
+            .field("extra_keys", &self.extra_keys)
+            .field("fresh_change_address", &self.fresh_change_address)
+            .field("watch_only", &self.watch_only)
+            .field("dust_limit", &self.dust_limit)
+            .field("network_id", &self.network_id)
+            .finish()
+    }
+}
+
+impl Drop for Wallet {
+    fn drop(&mut self) {
+        if let Some(key) = self.private_key.as_mut() {
+            wipe_secret(key);
+        }
+        if let Some(mnemonic) = self.mnemonic.as_mut() {
+            mnemonic.zeroize();
+        }
+    }
+}
+
+fn default_fresh_change_address() -> bool {
+    true
+}
+
+fn default_dust_limit() -> u64 {
+    DUST_THRESHOLD
+}
+
+fn default_network_id() -> String {
+    String::from(DEFAULT_NETWORK_ID)
+}
+
+impl Signer for Wallet {
+    fn address(&self) -> &str {
+        &self.address
+    }
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<Signature, SignError> {
+        let private_key = self.private_key.as_ref().ok_or(SignError::NoKey)?;
+        Ok(sign_with_key(digest, private_key))
+    }
+
+    fn public_key(&self) -> Option<PublicKey> {
+        self.public_key
+    }
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Wallet {
@@ -39,137 +858,1962 @@ impl Wallet {
         let address = Self::public_key_to_address(&public_key);
         
         Wallet {
-            private_key: secret_key,
-            public_key,
+            private_key: Some(secret_key),
+            public_key: Some(public_key),
             address,
+            mnemonic: None,
+            version: WALLET_FORMAT_VERSION,
+            extra_keys: Vec::new(),
+            fresh_change_address: true,
+            watch_only: false,
+            dust_limit: DUST_THRESHOLD,
+            network_id: default_network_id(),
         }
     }
 
-    /// 将公钥转换为钱包地址
+    /// 由公钥创建观察钱包
     ///
-    /// 使用SHA256和RIPEMD160哈希算法对公钥进行双重哈希，然后转换为十六进制字符串
+    /// 观察钱包的地址与对应的完整钱包相同，可以查询余额、构建未签名交易，
+    /// 但签名、派生新地址和导出私钥会返回`WalletError::WatchOnly`，找零回到主地址
     ///
     /// # 参数
     ///
-    /// * `public_key` - 要转换的公钥
+    /// * `public_key` - 要观察的钱包的公钥
+    pub fn watch_only(public_key: PublicKey) -> Wallet {
+        let address = Self::public_key_to_address(&public_key);
+        Self::watching(Some(public_key), address)
+    }
+
+    /// 由地址创建观察钱包
+    ///
+    /// 与[`Wallet::watch_only`]相比缺少公钥，只能用于查询余额和构建未签名交易
+    ///
+    /// # 参数
+    ///
+    /// * `address` - 要观察的地址，也接受旧格式的十六进制地址
     ///
     /// # 返回值
     ///
-    /// 返回生成的钱包地址（十六进制字符串）
-    fn public_key_to_address(public_key: &PublicKey) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(&public_key.serialize_uncompressed());
-        let result = hasher.finalize();
-        
-        // 使用RIPEMD160进行二次哈希
-        let mut ripemd = ripemd::Ripemd160::new();
-        ripemd.update(&result);
-        let result = ripemd.finalize();
-        
-        hex::encode(result)
+    /// 地址有效时返回观察钱包，否则返回`WalletError::InvalidAddress`
+    pub fn from_address(address: &str) -> Result<Wallet, WalletError> {
+        decode_address_with(address, true).map_err(|e| WalletError::InvalidAddress(e.to_string()))?;
+        Ok(Self::watching(None, address.to_string()))
     }
 
-    /// 创建新的交易
+    /// 创建没有私钥的观察钱包
+    fn watching(public_key: Option<PublicKey>, address: String) -> Wallet {
+        Wallet {
+            private_key: None,
+            public_key,
+            address,
+            mnemonic: None,
+            version: WALLET_FORMAT_VERSION,
+            extra_keys: Vec::new(),
+            fresh_change_address: false,
+            watch_only: true,
+            dust_limit: DUST_THRESHOLD,
+            network_id: default_network_id(),
+        }
+    }
+
+    /// 是否为观察钱包
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only
+    }
+
+    /// 创建带助记词备份的新钱包
     ///
-    /// # 参数
+    /// # 返回值
     ///
-    /// * `to_address` - 接收者的地址
-    /// * `amount` - 要发送的金额
-    /// * `utxo_set` - 当前UTXO集合
+    /// 返回新钱包和对应的12个单词的助记词，助记词可通过[`Wallet::from_mnemonic`]恢复出相同的钱包
+    pub fn new_with_mnemonic() -> (Wallet, String) {
+        let phrase = Self::generate_mnemonic();
+        let wallet = Self::from_mnemonic(&phrase).expect("新生成的助记词有效");
+        (wallet, phrase)
+    }
+
+    /// 生成新的12个单词的BIP39助记词
+    ///
+    /// 助记词可用于通过[`Wallet::from_mnemonic`]恢复钱包，需要妥善保存
     ///
     /// # 返回值
     ///
-    /// 如果有足够的UTXO余额，返回创建的交易；否则返回None
-    pub fn create_transaction(
-        &self,
-        to_address: &str,
-        amount: u64,
-        utxo_set: &HashMap<String, Vec<(u32, u64)>>,
-    ) -> Option<Transaction> {
-        let mut inputs = Vec::new();
-        let mut total_input = 0u64;
-        
-        // 查找可用的UTXO
-        for (tx_id, outputs) in utxo_set {
-            for (index, value) in outputs {
-                if total_input >= amount {
-                    break;
-                }
-                
-                inputs.push(TxInput {
-                    prev_tx: tx_id.clone(),
-                    prev_index: *index,
-                    script_sig: self.address.clone(),
-                });
-                
-                total_input += value;
-            }
-        }
-        
-        if total_input < amount {
-            return None;
-        }
-        
-        // 创建输出
-        let mut outputs = vec![
-            TxOutput {
-                value: amount,
-                script_pubkey: to_address.to_string(),
-            },
-        ];
-        
-        // 添加找零输出
-        if total_input > amount {
-            outputs.push(TxOutput {
-                value: total_input - amount,
-                script_pubkey: self.address.clone(),
-            });
-        }
-        
-        Some(Transaction::new(inputs, outputs))
+    /// 返回以空格分隔的英文助记词
+    pub fn generate_mnemonic() -> String {
+        let entropy: [u8; 16] = rand::random();
+        bip39::Mnemonic::from_entropy(&entropy)
+            .expect("16字节熵对应12个单词的有效助记词")
+            .to_string()
     }
 
-    /// 签名交易
+    /// 从BIP39助记词确定性地恢复钱包
     ///
-    /// 使用钱包的私钥对交易进行签名，使其能被区块链网络验证
+    /// 派生路径：助记词经PBKDF2（空口令）生成64字节种子，再按BIP32主密钥的方式
+    /// 计算`HMAC-SHA512(key = "Bitcoin seed", data = seed)`，取前32字节作为secp256k1私钥
     ///
     /// # 参数
     ///
-    /// * `tx` - 要签名的交易
-    pub fn sign_transaction(&self, tx: &mut Transaction) {
-        let secp = secp256k1::Secp256k1::new();
-        let mut hasher = sha2::Sha256::new();
-        let serialized = serde_json::to_string(tx).unwrap();
-        hasher.update(serialized.as_bytes());
-        let hash = hasher.finalize();
-        
-        let message = secp256k1::Message::from_slice(&hash).unwrap();
-        let signature = secp.sign_ecdsa(&message, &self.private_key);
-        
-        for input in &mut tx.inputs {
-            input.script_sig = format!("{}:{}", self.address, hex::encode(signature.serialize_compact()));
-        }
+    /// * `phrase` - 以空格分隔的12或24个单词，大小写和多余空白会被忽略
+    ///
+    /// # 返回值
+    ///
+    /// 成功时返回恢复的钱包；单词无效或校验和不匹配时返回`WalletError::InvalidMnemonic`
+    pub fn from_mnemonic(phrase: &str) -> Result<Wallet, WalletError> {
+        let normalized = phrase
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mnemonic = bip39::Mnemonic::parse_normalized(&normalized)
+            .map_err(|e| WalletError::InvalidMnemonic(e.to_string()))?;
+        let seed = mnemonic.to_seed_normalized("");
+
+        let mut mac = Hmac::<Sha512>::new_from_slice(b"Bitcoin seed")
+            .expect("HMAC接受任意长度的密钥");
+        mac.update(&seed);
+        let master = mac.finalize().into_bytes();
+        let secret_key = SecretKey::from_slice(&master[..32])
+            .map_err(|e| WalletError::InvalidMnemonic(e.to_string()))?;
+
+        let mut wallet = Self::from_secret_key(secret_key);
+        wallet.mnemonic = Some(normalized);
+        Ok(wallet)
     }
 
-    /// 保存钱包到文件
+    /// 导入其他工具生成的私钥
     ///
     /// # 参数
     ///
-    /// * `wallet` - 要保存的钱包实例
-    /// * `filename` - 保存钱包的文件名
-    pub fn save_wallet(wallet: &Wallet, filename: &str) {
-        let serialized = serde_json::to_string(wallet).unwrap();
-        fs::write(filename, serialized).expect("Unable to write wallet to file");
+    /// * `wif_or_hex` - 64位十六进制私钥，或Base58Check编码的WIF私钥
+    ///
+    /// # 返回值
+    ///
+    /// 成功时返回以该私钥为主密钥的钱包；格式或校验和错误时返回`WalletError::InvalidPrivateKey`
+    pub fn import_private_key(wif_or_hex: &str) -> Result<Wallet, WalletError> {
+        let key = wif_or_hex.trim();
+        if key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Self::import_private_key_hex(key);
+        }
+        Self::import_wif(key)
     }
 
-    /// 从文件加载钱包
+    /// 由WIF格式的私钥创建钱包
     ///
     /// # 参数
     ///
-    /// * `filename` - 要加载的钱包文件名
+    /// * `wif` - [`Wallet::export_wif`]导出的Base58Check编码私钥，压缩标志可有可无
     ///
-    pub fn load_wallet(filename: &str) -> Wallet {
-        let contents = fs::read_to_string(filename).expect("Unable to read wallet file");
-        serde_json::from_str(&contents).expect("Unable to parse wallet file")
-    } 
-} 
\ No newline at end of file
+    /// # 返回值
+    ///
+    /// 成功时返回以该私钥为主密钥的钱包；版本字节、长度或校验和错误时返回`WalletError::InvalidPrivateKey`
+    pub fn import_wif(wif: &str) -> Result<Wallet, WalletError> {
+        let key = wif.trim();
+        let invalid = |reason: String| WalletError::InvalidPrivateKey(reason);
+        
+        // WIF: 版本字节 + 32字节私钥 + 可选的压缩标志，整体使用Base58Check编码
+        let mut payload = bs58::decode(key).with_check(None).into_vec()
+            .map_err(|e| invalid(e.to_string()))?;
+        let secret_key = match payload.as_slice() {
+            [WIF_VERSION, secret @ ..] if secret.len() == 32 => SecretKey::from_slice(secret),
+            [WIF_VERSION, secret @ .., WIF_COMPRESSED_FLAG] if secret.len() == 32 => SecretKey::from_slice(secret),
+            _ => {
+                payload.zeroize();
+                return Err(invalid(String::from("不是WIF格式的私钥")));
+            }
+        };
+        payload.zeroize();
+        
+        let secret_key = secret_key.map_err(|e| invalid(e.to_string()))?;
+        Ok(Self::from_secret_key(secret_key))
+    }
+
+    /// 以WIF格式导出主私钥
+    ///
+    /// # 返回值
+    ///
+    /// 返回Base58Check编码的字符串（版本字节0x80，带压缩标志0x01）；
+    /// 观察钱包返回`WalletError::WatchOnly`
+    pub fn export_wif(&self) -> Result<String, WalletError> {
+        let private_key = self.private_key.as_ref().ok_or(WalletError::WatchOnly)?;
+        let mut payload = Vec::with_capacity(34);
+        payload.push(WIF_VERSION);
+        payload.extend_from_slice(&private_key.secret_bytes());
+        payload.push(WIF_COMPRESSED_FLAG);
+        Ok(bs58::encode(payload).with_check().into_string())
+    }
+
+    /// 以十六进制导出主私钥
+    ///
+    /// 钱包文件只在`secret`字段中保存私钥，在钱包之间转移密钥应使用本方法
+    /// 和[`Wallet::import_private_key_hex`]（或WIF格式的[`Wallet::export_wif`]和[`Wallet::import_wif`]），而不是复制钱包文件的字段
+    ///
+    /// # 返回值
+    ///
+    /// 返回64位小写十六进制字符串；观察钱包返回`WalletError::WatchOnly`
+    pub fn export_private_key_hex(&self) -> Result<String, WalletError> {
+        let private_key = self.private_key.as_ref().ok_or(WalletError::WatchOnly)?;
+        let mut bytes = private_key.secret_bytes();
+        let encoded = hex::encode(bytes);
+        bytes.zeroize();
+        Ok(encoded)
+    }
+
+    /// 将主地址渲染为可以在终端中扫描的二维码
+    ///
+    /// 每个字符用Unicode半块字符表示上下两个模块，深色背景的终端可能需要反转颜色才能扫描
+    ///
+    /// # 返回值
+    ///
+    /// 返回多行字符串，四周带有二维码要求的空白边框
+    pub fn address_qr(&self) -> String {
+        let code = QrCode::new(self.address.as_bytes()).expect("地址长度远小于二维码容量");
+        code.render::<unicode::Dense1x2>().build()
+    }
+
+    /// 由十六进制私钥创建钱包
+    ///
+    /// # 参数
+    ///
+    /// * `hex_key` - [`Wallet::export_private_key_hex`]导出的64位十六进制私钥
+    ///
+    /// # 返回值
+    ///
+    /// 成功时返回以该私钥为主密钥的钱包；不是有效私钥时返回`WalletError::InvalidPrivateKey`
+    pub fn import_private_key_hex(hex_key: &str) -> Result<Wallet, WalletError> {
+        let hex_key = hex_key.trim();
+        if hex_key.len() != 64 {
+            return Err(WalletError::InvalidPrivateKey(format!("需要64位十六进制，实际为{}位", hex_key.len())));
+        }
+        let mut bytes = hex::decode(hex_key).map_err(|e| WalletError::InvalidPrivateKey(e.to_string()))?;
+        let secret_key = SecretKey::from_slice(&bytes);
+        bytes.zeroize();
+        let secret_key = secret_key.map_err(|e| WalletError::InvalidPrivateKey(e.to_string()))?;
+        Ok(Self::from_secret_key(secret_key))
+    }
+
+    /// 以给定私钥作为主密钥创建钱包
+    fn from_secret_key(private_key: SecretKey) -> Wallet {
+        let secp = secp256k1::Secp256k1::new();
+        let public_key = PublicKey::from_secret_key(&secp, &private_key);
+        let address = Self::public_key_to_address(&public_key);
+        Wallet {
+            private_key: Some(private_key),
+            public_key: Some(public_key),
+            address,
+            mnemonic: None,
+            version: WALLET_FORMAT_VERSION,
+            extra_keys: Vec::new(),
+            fresh_change_address: true,
+            watch_only: false,
+            dust_limit: DUST_THRESHOLD,
+            network_id: default_network_id(),
+        }
+    }
+
+    /// 派生一个新的接收地址
+    ///
+    /// 第i个地址的私钥为`HMAC-SHA512(key = 主私钥, data = i的大端字节)`的前32字节，
+    /// 因此从助记词恢复钱包后按相同次数调用本方法会得到相同的地址序列
+    ///
+    /// # 返回值
+    ///
+    /// 返回新派生的地址；观察钱包没有主私钥，返回`WalletError::WatchOnly`
+    pub fn new_address(&mut self) -> Result<String, WalletError> {
+        let master = self.private_key.as_ref().ok_or(WalletError::WatchOnly)?;
+        let index = self.extra_keys.len() as u32 + 1;
+        let mut mac = Hmac::<Sha512>::new_from_slice(&master.secret_bytes())
+            .expect("HMAC接受任意长度的密钥");
+        mac.update(&index.to_be_bytes());
+        let derived = mac.finalize().into_bytes();
+        let private_key = SecretKey::from_slice(&derived[..32])
+            .expect("派生出无效私钥的概率可以忽略");
+
+        let secp = secp256k1::Secp256k1::new();
+        let public_key = PublicKey::from_secret_key(&secp, &private_key);
+        let address = Self::public_key_to_address(&public_key);
+        self.extra_keys.push(KeyPair {
+            private_key,
+            public_key,
+            address: address.clone(),
+        });
+        Ok(address)
+    }
+
+    /// 获取钱包的所有地址，主地址在最前
+    pub fn addresses(&self) -> Vec<String> {
+        std::iter::once(self.address.clone())
+            .chain(self.extra_keys.iter().map(|key| key.address.clone()))
+            .collect()
+    }
+
+    /// 检查地址是否属于本钱包
+    pub fn owns_address(&self, address: &str) -> bool {
+        self.address == address || self.extra_keys.iter().any(|key| key.address == address)
+    }
+
+    /// 计算UTXO列表中属于本钱包任一地址的可花费余额
+    ///
+    /// # 参数
+    ///
+    /// * `utxos` - UTXO列表，例如`Blockchain::utxo_entries`的结果
+    pub fn spendable_balance(&self, utxos: &[(OutPoint, UtxoEntry)]) -> u64 {
+        utxos.iter()
+            .filter(|(_, entry)| self.owns_address(&entry.script_pubkey))
+            .map(|(_, entry)| entry.value)
+            .sum()
+    }
+
+    /// 计算本钱包所有地址在链上已确认的余额
+    ///
+    /// # 参数
+    ///
+    /// * `chain` - 区块链
+    pub fn balance(&self, chain: &Blockchain) -> u64 {
+        self.spendable_balance(&chain.utxo_entries())
+    }
+
+    /// 计算包含交易池中未确认交易的余额明细
+    ///
+    /// # 参数
+    ///
+    /// * `chain` - 区块链
+    /// * `mempool` - 待处理交易池
+    ///
+    /// # 返回值
+    ///
+    /// 返回已确认、待收入、待支出和可花费金额
+    pub fn balance_detailed(&self, chain: &Blockchain, mempool: &Mempool) -> BalanceBreakdown {
+        let locked = mempool.locked_outpoints();
+        let owned: Vec<(OutPoint, UtxoEntry)> = chain.utxo_entries().into_iter()
+            .filter(|(_, entry)| self.owns_address(&entry.script_pubkey))
+            .collect();
+
+        let pending_out = owned.iter()
+            .filter(|(outpoint, _)| locked.contains(outpoint))
+            .map(|(_, entry)| entry.value)
+            .sum();
+        let pending_in = mempool.iter()
+            .flat_map(|tx| tx.outputs.iter())
+            .filter(|output| self.owns_address(&output.script_pubkey))
+            .map(|output| output.value)
+            .sum();
+        let spendable = self.addresses().iter()
+            .flat_map(|address| chain.get_utxos_for_address(address))
+            .filter(|(outpoint, _)| !locked.contains(outpoint))
+            .map(|(_, entry)| entry.value)
+            .sum();
+
+        BalanceBreakdown {
+            confirmed: owned.iter().map(|(_, entry)| entry.value).sum(),
+            pending_in,
+            pending_out,
+            spendable,
+        }
+    }
+
+    /// 列出属于本钱包任一地址的未花费输出
+    ///
+    /// 只遍历一次区块链来确定每个输出所在的高度，结果按金额从大到小排列
+    ///
+    /// # 参数
+    ///
+    /// * `utxo_set` - UTXO集合，通常是`Blockchain::utxo_set`
+    /// * `blockchain` - 用于查找输出所在高度的区块链
+    ///
+    /// # 返回值
+    ///
+    /// 返回本钱包的未花费输出及其确认数
+    pub fn list_unspent(&self, utxo_set: &HashMap<String, Vec<(u32, u64, String)>>, blockchain: &Blockchain) -> Vec<UnspentOutput> {
+        let tip_height = blockchain.height();
+        let mut unspent = Vec::new();
+        for (height, block) in blockchain.iter().enumerate() {
+            for tx in &block.transactions {
+                let tx_id = blockchain.calculate_tx_hash(tx);
+                let Some(outputs) = utxo_set.get(&tx_id) else {
+                    continue;
+                };
+                for (index, value, script_pubkey) in outputs {
+                    if self.owns_address(script_pubkey) {
+                        unspent.push(UnspentOutput {
+                            tx_id: tx_id.clone(),
+                            output_index: *index,
+                            value: *value,
+                            confirmations: tip_height - height + 1,
+                        });
+                    }
+                }
+            }
+        }
+        unspent.sort_by(|a, b| b.value.cmp(&a.value)
+            .then_with(|| a.tx_id.cmp(&b.tx_id))
+            .then_with(|| a.output_index.cmp(&b.output_index)));
+        unspent
+    }
+
+    /// 检查交易所属的网络与钱包相同
+    fn check_network(&self, network_id: &str) -> Result<(), WalletError> {
+        if self.network_id != network_id {
+            return Err(WalletError::NetworkMismatch {
+                wallet: self.network_id.clone(),
+                transaction: network_id.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// 钱包的全部签名者，主密钥在前，之后是派生的密钥
+    fn signers(&self) -> Vec<&dyn Signer> {
+        let mut signers: Vec<&dyn Signer> = vec![self];
+        signers.extend(self.extra_keys.iter().map(|key| key as &dyn Signer));
+        signers
+    }
+
+    /// 查找公钥对应的私钥
+    fn key_for_public_key(&self, public_key: &PublicKey) -> Option<&SecretKey> {
+        if self.public_key.as_ref() == Some(public_key) {
+            return self.private_key.as_ref();
+        }
+        self.extra_keys.iter()
+            .find(|key| key.public_key == *public_key)
+            .map(|key| &key.private_key)
+    }
+
+    /// 创建多重签名地址
+    ///
+    /// 地址就是输出的锁定脚本，公钥直接嵌入其中，花费时需要其中至少`threshold`个公钥的签名。
+    /// 可以像普通地址一样作为`create_transaction`的接收地址
+    ///
+    /// # 参数
+    ///
+    /// * `pubkeys` - 参与签名的公钥，顺序会保留在地址中
+    /// * `threshold` - 花费需要的签名数量
+    ///
+    /// # 返回值
+    ///
+    /// 返回`multisig:`开头的多重签名地址；公钥为空、重复、超过`MAX_MULTISIG_KEYS`个，
+    /// 或阈值为零、大于公钥数量时返回`WalletError::InvalidMultisig`
+    pub fn create_multisig_address(pubkeys: &[PublicKey], threshold: usize) -> Result<String, WalletError> {
+        let script = MultisigScript { threshold, pubkeys: pubkeys.to_vec() };
+        script.check()?;
+        Ok(script.to_script_pubkey())
+    }
+
+    /// 将公钥转换为钱包地址
+    ///
+    /// 对公钥进行SHA256和RIPEMD160双重哈希，然后编码为Base58Check地址
+    ///
+    /// # 参数
+    ///
+    /// * `public_key` - 要转换的公钥
+    ///
+    /// # 返回值
+    ///
+    /// 返回生成的钱包地址
+    fn public_key_to_address(public_key: &PublicKey) -> String {
+        encode_address(&Self::public_key_hash(public_key))
+    }
+
+    /// 计算公钥哈希（RIPEMD160(SHA256(公钥))）
+    fn public_key_hash(public_key: &PublicKey) -> [u8; 20] {
+        let mut hasher = Sha256::new();
+        hasher.update(public_key.serialize_uncompressed());
+        let result = hasher.finalize();
+        
+        // 使用RIPEMD160进行二次哈希
+        let mut ripemd = ripemd::Ripemd160::new();
+        ripemd.update(result);
+        ripemd.finalize().into()
+    }
+
+    /// 检查公钥是否属于地址，地址可以是旧格式
+    fn public_key_matches(public_key: &PublicKey, address: &str) -> bool {
+        decode_address_with(address, true).is_ok_and(|hash| hash == Self::public_key_hash(public_key))
+    }
+
+    /// 创建新的交易
+    ///
+    /// 只会选择`script_pubkey`属于本钱包任一地址的UTXO作为输入，
+    /// 可以传入`Blockchain::get_utxos_for_address`的结果，也可以传入完整的UTXO列表。
+    /// `fresh_change_address`为true时找零发送到新派生的地址。
+    /// 返回的交易尚未签名（`Transaction::is_signed`为false），观察钱包同样可以创建
+    ///
+    /// # 参数
+    ///
+    /// * `to_address` - 接收者的地址
+    /// * `amount` - 要发送的金额
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 如果本钱包拥有足够的UTXO余额，返回创建的交易；
+    /// 金额为零或低于粉尘阈值、地址无效、没有自有UTXO或余额不足时返回对应的错误
+    pub fn create_transaction(
+        &mut self,
+        to_address: &str,
+        amount: u64,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Result<Transaction, TransactionError> {
+        self.create_transaction_with_fee(to_address, amount, 0, utxos)
+    }
+
+    /// 创建支付指定手续费的交易
+    ///
+    /// 选择的输入需要覆盖金额和手续费，找零为输入总额减去金额和手续费
+    ///
+    /// # 参数
+    ///
+    /// * `to_address` - 接收者的地址
+    /// * `amount` - 要发送的金额
+    /// * `fee` - 交易手续费
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 如果本钱包拥有足够的UTXO余额，返回创建的交易；否则返回对应的错误
+    pub fn create_transaction_with_fee(
+        &mut self,
+        to_address: &str,
+        amount: u64,
+        fee: u64,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Result<Transaction, TransactionError> {
+        self.create_transaction_multi(&[(to_address.to_string(), amount)], fee, utxos)
+    }
+
+    /// 按指定的输入选择策略创建交易
+    ///
+    /// `create_transaction`等方法使用默认的`CoinSelectionStrategy::BranchAndBound`
+    ///
+    /// # 参数
+    ///
+    /// * `to_address` - 接收者的地址
+    /// * `amount` - 要发送的金额
+    /// * `fee` - 交易手续费
+    /// * `utxos` - 可供选择的UTXO列表
+    /// * `strategy` - 输入选择策略
+    ///
+    /// # 返回值
+    ///
+    /// 如果本钱包拥有足够的UTXO余额，返回创建的交易；否则返回对应的错误
+    pub fn create_transaction_with_strategy(
+        &mut self,
+        to_address: &str,
+        amount: u64,
+        fee: u64,
+        utxos: &[(OutPoint, UtxoEntry)],
+        strategy: CoinSelectionStrategy,
+    ) -> Result<Transaction, TransactionError> {
+        let recipients = merge_recipients(&[(to_address.to_string(), amount)], self.dust_limit)?;
+        let mut tx = self.build_transaction(&recipients, fee, utxos, &self.address, strategy)?;
+        self.use_fresh_change_address(&mut tx, recipients.len());
+        Ok(tx)
+    }
+
+    /// 创建向多个地址付款的交易
+    ///
+    /// 每个接收者对应一个输出，找零（如果有）位于所有支付输出之后。
+    /// 同一地址出现多次时，金额合并为一个输出，输出顺序为该地址第一次出现的位置
+    ///
+    /// # 参数
+    ///
+    /// * `recipients` - 接收者地址和金额列表
+    /// * `fee` - 交易手续费
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 如果本钱包余额足以支付所有金额和手续费，返回创建的交易；
+    /// 接收者列表为空、任一金额为零或低于粉尘阈值、任一地址无效或余额不足时返回对应的错误
+    pub fn create_transaction_multi(
+        &mut self,
+        recipients: &[(String, u64)],
+        fee: u64,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Result<Transaction, TransactionError> {
+        let recipients = merge_recipients(recipients, self.dust_limit)?;
+        let mut tx = self.build_transaction(&recipients, fee, utxos, &self.address, CoinSelectionStrategy::default())?;
+        self.use_fresh_change_address(&mut tx, recipients.len());
+        Ok(tx)
+    }
+
+    /// 启用`fresh_change_address`时把找零发送到新派生的地址
+    ///
+    /// 找零输出总是位于支付输出之后；观察钱包无法派生地址，找零回到主地址
+    fn use_fresh_change_address(&mut self, tx: &mut Transaction, payments: usize) {
+        if self.fresh_change_address && tx.outputs.len() > payments {
+            if let Ok(address) = self.new_address() {
+                tx.outputs[payments].script_pubkey = address;
+            }
+        }
+    }
+
+    /// 创建在链上记录数据的交易
+    ///
+    /// 第一个输出是携带数据的零金额数据输出，选择的输入只需要覆盖手续费，其余金额找零回主地址
+    ///
+    /// # 参数
+    ///
+    /// * `data` - 要记录的数据，最多`MAX_OP_RETURN_DATA`字节
+    /// * `fee` - 交易手续费
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回未签名的交易；数据过长、没有自有UTXO或余额不足以支付手续费时返回对应的错误
+    pub fn embed_data(
+        &self,
+        data: &[u8],
+        fee: u64,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Result<Transaction, TransactionError> {
+        if data.len() > MAX_OP_RETURN_DATA {
+            return Err(TransactionError::DataTooLarge { size: data.len(), max: MAX_OP_RETURN_DATA });
+        }
+        let mut tx = self.build_transaction(&[], fee, utxos, &self.address, CoinSelectionStrategy::default())?;
+        tx.outputs.insert(0, TxOutput::op_return(data));
+        Ok(tx)
+    }
+
+    /// 构建交易，找零发送到指定地址
+    ///
+    /// `recipients`需要已经过`merge_recipients`校验。
+    /// 每个输入的`script_sig`暂时填入所花费输出的地址，签名时据此选择私钥
+    fn build_transaction(
+        &self,
+        recipients: &[(String, u64)],
+        fee: u64,
+        utxos: &[(OutPoint, UtxoEntry)],
+        change_address: &str,
+        strategy: CoinSelectionStrategy,
+    ) -> Result<Transaction, TransactionError> {
+        if !utxos.iter().any(|(_, entry)| self.owns_address(&entry.script_pubkey)) {
+            return Err(TransactionError::NoUtxos);
+        }
+        
+        let amount: u64 = recipients.iter().map(|(_, value)| value).sum();
+        let needed = amount + fee;
+        
+        // 只选择属于本钱包的UTXO
+        let owned = utxos.iter().filter(|(_, entry)| self.owns_address(&entry.script_pubkey)).collect();
+        let (selected, exact_match) = select_coins(owned, needed, strategy);
+        let total_input: u64 = selected.iter().map(|(_, entry)| entry.value).sum();
+        let inputs: Vec<TxInput> = selected.iter()
+            .map(|(outpoint, entry)| TxInput {
+                prev_tx: outpoint.tx_id.clone(),
+                prev_index: outpoint.index,
+                script_sig: entry.script_pubkey.clone(),
+            })
+            .collect();
+        
+        if total_input < needed {
+            let available: u64 = utxos.iter().map(|(_, entry)| entry.value).sum();
+            return Err(if available >= needed {
+                TransactionError::InsufficientOwnedFunds { needed, owned: total_input }
+            } else if total_input >= amount {
+                TransactionError::InsufficientFundsForFee { amount, fee, available: total_input }
+            } else {
+                TransactionError::InsufficientFunds { needed, available: total_input }
+            });
+        }
+        
+        // 每个接收者一个输出
+        let mut outputs: Vec<TxOutput> = recipients.iter()
+            .map(|(address, value)| TxOutput {
+                value: *value,
+                script_pubkey: address.clone(),
+            })
+            .collect();
+        
+        // 添加找零输出，低于粉尘阈值或精确匹配时多出的金额并入手续费
+        let change = total_input - needed;
+        let mut fee = fee;
+        if change >= self.dust_limit && !exact_match {
+            outputs.push(TxOutput {
+                value: change,
+                script_pubkey: change_address.to_string(),
+            });
+        } else {
+            fee += change;
+        }
+        
+        Ok(Transaction::with_fee(inputs, outputs, fee))
+    }
+
+    /// 构建把多个小额UTXO合并为一个输出的自付交易
+    ///
+    /// 从金额最小的UTXO开始选择，最多选择`max_inputs`个，合并后的输出发送到主地址，
+    /// 手续费按`DEFAULT_FEE_RATE`和交易大小计算。挖矿奖励积累了大量小额输出后，
+    /// 合并可以减少以后付款时需要的输入数量
+    ///
+    /// # 参数
+    ///
+    /// * `utxos` - 可供选择的UTXO列表，不属于本钱包的会被忽略
+    /// * `max_inputs` - 最多合并的输入数量
+    ///
+    /// # 返回值
+    ///
+    /// 返回未签名的合并交易；可合并的UTXO少于两个时返回`TransactionError::NothingToConsolidate`，
+    /// 扣除手续费后的金额低于粉尘阈值时返回`TransactionError::DustAmount`
+    pub fn consolidate_utxos(
+        &self,
+        utxos: &[(OutPoint, UtxoEntry)],
+        max_inputs: usize,
+    ) -> Result<Transaction, TransactionError> {
+        let mut owned: Vec<&(OutPoint, UtxoEntry)> = utxos.iter()
+            .filter(|(_, entry)| self.owns_address(&entry.script_pubkey))
+            .collect();
+        owned.sort_by_key(|(_, entry)| entry.value);
+        owned.truncate(max_inputs);
+        if owned.len() < 2 {
+            return Err(TransactionError::NothingToConsolidate { available: owned.len() });
+        }
+        
+        let inputs: Vec<TxInput> = owned.iter()
+            .map(|(outpoint, entry)| TxInput {
+                prev_tx: outpoint.tx_id.clone(),
+                prev_index: outpoint.index,
+                script_sig: entry.script_pubkey.clone(),
+            })
+            .collect();
+        let total: u64 = owned.iter().map(|(_, entry)| entry.value).sum();
+        let output = TxOutput { value: total, script_pubkey: self.address.clone() };
+        
+        // 扣除手续费后输出金额只会变短，按全部金额估算的大小是上限
+        let size = Transaction::with_fee(inputs.clone(), vec![output.clone()], 0).size_bytes();
+        let fee = (size as u64 * DEFAULT_FEE_RATE).div_ceil(1000);
+        let value = total.saturating_sub(fee);
+        if value < self.dust_limit {
+            return Err(TransactionError::DustAmount { amount: value, threshold: self.dust_limit });
+        }
+        Ok(Transaction::with_fee(inputs, vec![TxOutput { value, ..output }], fee))
+    }
+
+    /// 按手续费策略创建交易
+    ///
+    /// # 参数
+    ///
+    /// * `to_address` - 接收者的地址
+    /// * `amount` - 要发送的金额
+    /// * `policy` - 手续费策略
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 如果本钱包余额足以支付金额和手续费，返回创建的交易；否则返回对应的错误
+    pub fn create_transaction_with_policy(
+        &mut self,
+        to_address: &str,
+        amount: u64,
+        policy: FeePolicy,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Result<Transaction, TransactionError> {
+        let fee = self.estimate_fee_with_policy(to_address, amount, policy, utxos)?;
+        self.create_transaction_with_fee(to_address, amount, fee, utxos)
+    }
+
+    /// 按手续费策略估算手续费
+    ///
+    /// 手续费会影响选择的输入数量（进而影响交易大小），
+    /// 因此反复构建交易直到手续费足以覆盖交易大小
+    ///
+    /// # 参数
+    ///
+    /// * `to_address` - 接收者的地址
+    /// * `amount` - 要发送的金额
+    /// * `policy` - 手续费策略
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回估算的手续费；余额不足时返回对应的错误
+    pub fn estimate_fee_with_policy(
+        &self,
+        to_address: &str,
+        amount: u64,
+        policy: FeePolicy,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Result<u64, TransactionError> {
+        self.estimate_fee_multi_with_policy(&[(to_address.to_string(), amount)], policy, utxos)
+    }
+
+    /// 按手续费策略估算向多个地址付款的手续费
+    ///
+    /// # 参数
+    ///
+    /// * `recipients` - 接收者地址和金额列表
+    /// * `policy` - 手续费策略
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回估算的手续费；接收者无效或余额不足时返回对应的错误
+    pub fn estimate_fee_multi_with_policy(
+        &self,
+        recipients: &[(String, u64)],
+        policy: FeePolicy,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Result<u64, TransactionError> {
+        self.converge_fee(recipients, utxos, |size| policy.fee_for_size(size))
+    }
+
+    /// 反复构建交易，直到手续费不低于`fee_for_size`对交易大小要求的手续费
+    fn converge_fee(
+        &self,
+        recipients: &[(String, u64)],
+        utxos: &[(OutPoint, UtxoEntry)],
+        fee_for_size: impl Fn(usize) -> u64,
+    ) -> Result<u64, TransactionError> {
+        let recipients = merge_recipients(recipients, self.dust_limit)?;
+        let mut fee = 0;
+        loop {
+            // 找零地址不影响交易大小，估算时使用主地址
+            let tx = self.build_transaction(&recipients, fee, utxos, &self.address, CoinSelectionStrategy::default())?;
+            let required = fee_for_size(tx.size_bytes());
+            if required <= fee {
+                return Ok(fee);
+            }
+            fee = required;
+        }
+    }
+
+    /// 估算发送交易需要的手续费
+    ///
+    /// 按`DEFAULT_FEE_RATE`计算手续费。由于手续费会影响选择的输入数量（进而影响交易大小），
+    /// 估算会反复构建交易直到手续费足以覆盖交易大小
+    ///
+    /// # 参数
+    ///
+    /// * `to_address` - 接收者的地址
+    /// * `amount` - 要发送的金额
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回估算的手续费；如果本钱包余额不足以支付金额和手续费，返回None
+    pub fn estimate_fee(
+        &self,
+        to_address: &str,
+        amount: u64,
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Option<u64> {
+        self.estimate_fee_multi(&[(to_address.to_string(), amount)], utxos)
+    }
+
+    /// 估算向多个地址付款需要的手续费
+    ///
+    /// 与`estimate_fee`相同，按`DEFAULT_FEE_RATE`计算
+    ///
+    /// # 参数
+    ///
+    /// * `recipients` - 接收者地址和金额列表
+    /// * `utxos` - 可供选择的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 返回估算的手续费；接收者无效或本钱包余额不足以支付总金额和手续费时返回None
+    pub fn estimate_fee_multi(
+        &self,
+        recipients: &[(String, u64)],
+        utxos: &[(OutPoint, UtxoEntry)],
+    ) -> Option<u64> {
+        self.converge_fee(recipients, utxos, |size| (size as u64 * DEFAULT_FEE_RATE).div_ceil(1000))
+            .ok()
+    }
+
+    /// 签名交易
+    ///
+    /// 以主密钥和派生的密钥作为签名者调用[`sign_transaction_with`]，对每个输入单独签名，
+    /// 签名摘要见`Transaction::signature_hash`。
+    /// 未签名输入的`script_sig`是所花费输出的地址（见`create_transaction`），
+    /// 据此选择对应的私钥
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 要签名的交易
+    ///
+    /// # 返回值
+    ///
+    /// 签名成功返回Ok；观察钱包返回`WalletError::WatchOnly`，
+    /// 任一输入花费的地址不属于本钱包时返回`WalletError::NotOwned`，出错时交易保持不变
+    pub fn sign_transaction(&self, tx: &mut Transaction) -> Result<(), WalletError> {
+        if self.private_key.is_none() {
+            return Err(WalletError::WatchOnly);
+        }
+        sign_transaction_with(tx, &self.signers(), &self.network_id).map_err(|e| match e {
+            SignError::NotOwned(address) => WalletError::NotOwned(address),
+            e => WalletError::Sign(e),
+        })
+    }
+
+    /// 验证交易中单个输入的签名
+    ///
+    /// 检查`script_sig`中的公钥是否对应被花费输出的所有者地址，以及签名是否有效
+    ///
+    /// # 参数
+    ///
+    /// * `tx` - 要验证的交易
+    /// * `index` - 输入索引
+    /// * `utxo_entry` - 该输入引用的未花费输出
+    /// * `network_id` - 交易所属网络的标识
+    ///
+    /// # 返回值
+    ///
+    /// 签名有效、针对该网络生成且公钥属于输出所有者时返回true
+    pub fn verify_input(tx: &Transaction, index: usize, utxo_entry: &UtxoEntry, network_id: &str) -> bool {
+        let Some(input) = tx.inputs.get(index) else {
+            return false;
+        };
+        if let Some(script) = MultisigScript::parse(&utxo_entry.script_pubkey) {
+            return script.verify_signatures(tx, index, &input.script_sig, network_id);
+        }
+        let Some((public_key, signature)) = parse_signature(&input.script_sig) else {
+            return false;
+        };
+        if !Self::public_key_matches(&public_key, &utxo_entry.script_pubkey) {
+            return false;
+        }
+        
+        let hash = tx.signature_hash(index, &utxo_entry.script_pubkey, network_id);
+        let message = secp256k1::Message::from_slice(&hash).unwrap();
+        secp256k1::Secp256k1::verification_only()
+            .verify_ecdsa(&message, &signature, &public_key)
+            .is_ok()
+    }
+
+    /// 签名离线传递的未签名交易
+    ///
+    /// 以钱包的全部密钥调用[`UnsignedTransaction::sign_with`]，按附带的未花费输出选择每个输入的私钥。
+    /// 签名后的交易同时写回`unsigned`，可以再用[`UnsignedTransaction::to_json`]传回联网的观察钱包
+    ///
+    /// # 参数
+    ///
+    /// * `unsigned` - 要签名的未签名交易
+    ///
+    /// # 返回值
+    ///
+    /// 返回签名后的交易；观察钱包返回`WalletError::WatchOnly`，
+    /// 交易属于其他网络时返回`WalletError::NetworkMismatch`，
+    /// 任一输入不属于本钱包时返回`WalletError::NotOwned`，交易保持不变
+    pub fn sign_unsigned(&self, unsigned: &mut UnsignedTransaction) -> Result<Transaction, WalletError> {
+        if self.private_key.is_none() {
+            return Err(WalletError::WatchOnly);
+        }
+        self.check_network(&unsigned.network_id)?;
+        unsigned.sign_with(&self.signers())
+    }
+
+    /// 用主私钥签名任意消息，用于在不花费资金的情况下证明地址所有权
+    ///
+    /// 签名是可恢复的：验证方只需要地址，不需要事先知道公钥
+    ///
+    /// # 参数
+    ///
+    /// * `msg` - 要签名的消息
+    ///
+    /// # 返回值
+    ///
+    /// 返回十六进制编码的65字节签名（恢复ID + 紧凑签名），可用[`verify_message`]验证；
+    /// 观察钱包返回`WalletError::WatchOnly`
+    pub fn sign_message(&self, msg: &[u8]) -> Result<String, WalletError> {
+        let private_key = self.private_key.as_ref().ok_or(WalletError::WatchOnly)?;
+        let message = secp256k1::Message::from_slice(&message_hash(msg)).unwrap();
+        let signature = secp256k1::Secp256k1::new().sign_ecdsa_recoverable(&message, private_key);
+        let (recovery_id, compact) = signature.serialize_compact();
+        
+        let mut bytes = Vec::with_capacity(65);
+        bytes.push(recovery_id.to_i32() as u8);
+        bytes.extend_from_slice(&compact);
+        Ok(hex::encode(bytes))
+    }
+
+    /// 保存钱包到文件
+    ///
+    /// 先写入同目录下的临时文件再重命名，写入中途失败不会破坏原有的钱包文件
+    ///
+    /// # 参数
+    ///
+    /// * `wallet` - 要保存的钱包实例
+    /// * `filename` - 保存钱包的文件名
+    ///
+    /// # 返回值
+    ///
+    /// 写入失败（例如目录只读）时返回`WalletError::Io`
+    pub fn save_wallet(wallet: &Wallet, filename: &str) -> Result<(), WalletError> {
+        let serialized = serde_json::to_string(wallet).expect("钱包总是可以序列化");
+        let temp = format!("{}.tmp", filename);
+        fs::write(&temp, serialized)
+            .and_then(|()| fs::rename(&temp, filename))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp);
+                WalletError::Io { path: filename.to_string(), reason: e.to_string() }
+            })
+    }
+
+    /// 从文件加载钱包
+    ///
+    /// # 参数
+    ///
+    /// * `filename` - 要加载的钱包文件名
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回钱包；文件无法读取返回`WalletError::Io`，内容损坏返回`WalletError::Parse`，
+    /// 由更新版本写入时返回`WalletError::UnsupportedVersion`
+    pub fn load_wallet(filename: &str) -> Result<Wallet, WalletError> {
+        let contents = fs::read_to_string(filename)
+            .map_err(|e| WalletError::Io { path: filename.to_string(), reason: e.to_string() })?;
+        Self::from_json(&contents, filename)
+    }
+
+    /// 解析钱包文件内容，旧版本格式会被升级到当前版本
+    fn from_json(contents: &str, path: &str) -> Result<Wallet, WalletError> {
+        let parse_failed = |e: serde_json::Error| WalletError::Parse { path: path.to_string(), reason: e.to_string() };
+        
+        // 先只读取版本号，新版本的文件即使结构不同也能报告正确的错误
+        #[derive(Deserialize)]
+        struct VersionProbe {
+            #[serde(default)]
+            version: u32,
+        }
+        let probe: VersionProbe = serde_json::from_str(contents).map_err(parse_failed)?;
+        if probe.version > WALLET_FORMAT_VERSION {
+            return Err(WalletError::UnsupportedVersion { path: path.to_string(), version: probe.version });
+        }
+        
+        let mut wallet: Wallet = serde_json::from_str(contents).map_err(parse_failed)?;
+        if wallet.private_key.is_none() && !wallet.watch_only {
+            return Err(parse_failed(serde::de::Error::missing_field("secret")));
+        }
+        wallet.version = WALLET_FORMAT_VERSION;
+        Ok(wallet)
+    }
+}
+
+/// 验证交易所有输入的签名
+///
+/// Coinbase输入没有被花费的输出，不需要签名，会被跳过
+///
+/// # 参数
+///
+/// * `tx` - 要验证的交易
+/// * `resolver` - 根据输出引用查找被花费的未花费输出
+/// * `network_id` - 交易所属网络的标识
+///
+/// # 返回值
+///
+/// 所有输入引用的输出都存在且签名有效时返回true
+pub fn verify_transaction(tx: &Transaction, resolver: impl Fn(&OutPoint) -> Option<UtxoEntry>, network_id: &str) -> bool {
+    tx.inputs.iter().enumerate()
+        .filter(|(_, input)| input.prev_tx != crate::block::COINBASE_TX_ID)
+        .all(|(index, input)| {
+            let outpoint = OutPoint {
+                tx_id: input.prev_tx.clone(),
+                index: input.prev_index,
+            };
+            resolver(&outpoint).is_some_and(|entry| Wallet::verify_input(tx, index, &entry, network_id))
+        })
+}
+
+/// 离线签名使用的未签名交易
+///
+/// 包含交易本身和每个输入引用的未花费输出，离线的完整钱包无需访问区块链即可计算签名摘要。
+/// 典型流程：观察钱包构建交易并导出，离线钱包用[`Wallet::sign_unsigned`]签名，
+/// 再导回联网节点，[`UnsignedTransaction::is_complete`]通过后广播
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsignedTransaction {
+    /// 交易，签名前输入的`script_sig`是占位地址
+    pub transaction: Transaction,
+    /// 输入引用的未花费输出，顺序与交易输入相同
+    pub utxos: Vec<UtxoEntry>,
+    /// 交易所属网络的标识，离线钱包只签名同一网络的交易
+    #[serde(default = "default_network_id")]
+    pub network_id: String,
+}
+
+impl UnsignedTransaction {
+    /// 从交易和可用的UTXO列表创建未签名交易
+    ///
+    /// # 参数
+    ///
+    /// 交易属于[`DEFAULT_NETWORK_ID`]网络，其他网络的交易需要再设置`network_id`
+    ///
+    /// # 参数
+    ///
+    /// * `transaction` - 要签名的交易，通常由[`Wallet::create_transaction`]创建
+    /// * `utxos` - 包含交易所有输入的UTXO列表
+    ///
+    /// # 返回值
+    ///
+    /// 任一输入在`utxos`中找不到时返回`WalletError::MissingInput`
+    pub fn new(transaction: Transaction, utxos: &[(OutPoint, UtxoEntry)]) -> Result<Self, WalletError> {
+        let entries = transaction.inputs.iter()
+            .map(|input| {
+                utxos.iter()
+                    .find(|(outpoint, _)| outpoint.tx_id == input.prev_tx && outpoint.index == input.prev_index)
+                    .map(|(_, entry)| entry.clone())
+                    .ok_or_else(|| WalletError::MissingInput(format!("{}:{}", input.prev_tx, input.prev_index)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(UnsignedTransaction { transaction, utxos: entries, network_id: default_network_id() })
+    }
+
+    /// 序列化为JSON，用于导出到文件
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// 从JSON解析未签名（或已签名）交易，输出数量必须与输入数量一致
+    pub fn from_json(contents: &str) -> serde_json::Result<Self> {
+        let unsigned: UnsignedTransaction = serde_json::from_str(contents)?;
+        if unsigned.utxos.len() != unsigned.transaction.inputs.len() {
+            return Err(serde::de::Error::invalid_length(unsigned.utxos.len(), &"每个交易输入对应一个未花费输出"));
+        }
+        Ok(unsigned)
+    }
+
+    /// 用一组签名者签名
+    ///
+    /// 按附带的未花费输出选择每个输入的签名者，而不是依赖`script_sig`中的占位地址，
+    /// 签名后的交易同时写回`transaction`
+    ///
+    /// # 参数
+    ///
+    /// * `signers` - 可用的签名者
+    ///
+    /// # 返回值
+    ///
+    /// 返回签名后的交易；任一输入没有地址匹配的签名者时返回`WalletError::NotOwned`，交易保持不变
+    pub fn sign_with(&mut self, signers: &[&dyn Signer]) -> Result<Transaction, WalletError> {
+        let owned = |address: &str| signers.iter()
+            .any(|signer| signer.public_key().is_some_and(|public_key| Wallet::public_key_matches(&public_key, address)));
+        if let Some(entry) = self.utxos.iter().find(|entry| !owned(&entry.script_pubkey)) {
+            return Err(WalletError::NotOwned(entry.script_pubkey.clone()));
+        }
+        
+        let mut tx = self.transaction.clone();
+        for (input, entry) in tx.inputs.iter_mut().zip(&self.utxos) {
+            input.script_sig = entry.script_pubkey.clone();
+        }
+        sign_transaction_with(&mut tx, signers, &self.network_id)?;
+        self.transaction = tx.clone();
+        Ok(tx)
+    }
+
+    /// 检查所有输入是否都带有有效签名，广播前调用
+    ///
+    /// 只根据附带的未花费输出验证签名，输出是否仍未花费需要由区块链验证
+    pub fn is_complete(&self) -> bool {
+        self.transaction.inputs.len() == self.utxos.len()
+            && (0..self.utxos.len()).all(|index| Wallet::verify_input(&self.transaction, index, &self.utxos[index], &self.network_id))
+    }
+}
+
+/// 解析`公钥十六进制:签名十六进制`格式的单个签名
+fn parse_signature(script_sig: &str) -> Option<(PublicKey, secp256k1::ecdsa::Signature)> {
+    let (public_key_hex, signature_hex) = script_sig.split_once(':')?;
+    let public_key = hex::decode(public_key_hex).ok().and_then(|bytes| PublicKey::from_slice(&bytes).ok())?;
+    let signature = hex::decode(signature_hex).ok()
+        .and_then(|bytes| secp256k1::ecdsa::Signature::from_compact(&bytes).ok())?;
+    Some((public_key, signature))
+}
+
+/// 解析后的多重签名锁定脚本
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigScript {
+    /// 花费需要的签名数量
+    pub threshold: usize,
+    /// 参与签名的公钥
+    pub pubkeys: Vec<PublicKey>,
+}
+
+impl MultisigScript {
+    /// 解析`multisig:<阈值>:<公钥1>,<公钥2>,...`格式的锁定脚本
+    ///
+    /// # 返回值
+    ///
+    /// 不是多重签名脚本或配置无效时返回None
+    pub fn parse(script_pubkey: &str) -> Option<Self> {
+        let (threshold, pubkeys) = script_pubkey.strip_prefix(MULTISIG_PREFIX)?.split_once(':')?;
+        let script = MultisigScript {
+            threshold: threshold.parse().ok()?,
+            pubkeys: pubkeys.split(',')
+                .map(|key| hex::decode(key).ok().and_then(|bytes| PublicKey::from_slice(&bytes).ok()))
+                .collect::<Option<Vec<_>>>()?,
+        };
+        script.check().ok()?;
+        Some(script)
+    }
+
+    /// 编码为锁定脚本，即多重签名地址
+    pub fn to_script_pubkey(&self) -> String {
+        let pubkeys: Vec<String> = self.pubkeys.iter().map(|key| hex::encode(key.serialize())).collect();
+        format!("{}{}:{}", MULTISIG_PREFIX, self.threshold, pubkeys.join(","))
+    }
+
+    /// 检查阈值和公钥列表
+    fn check(&self) -> Result<(), WalletError> {
+        if self.pubkeys.is_empty() || self.pubkeys.len() > MAX_MULTISIG_KEYS {
+            return Err(WalletError::InvalidMultisig(format!("公钥数量必须在1到{}之间", MAX_MULTISIG_KEYS)));
+        }
+        if self.threshold == 0 || self.threshold > self.pubkeys.len() {
+            return Err(WalletError::InvalidMultisig(format!("阈值{}超出范围1..={}", self.threshold, self.pubkeys.len())));
+        }
+        let distinct: std::collections::HashSet<_> = self.pubkeys.iter().collect();
+        if distinct.len() != self.pubkeys.len() {
+            return Err(WalletError::InvalidMultisig(String::from("公钥重复")));
+        }
+        Ok(())
+    }
+
+    /// 统计输入签名中属于本脚本且有效的不同公钥数量
+    ///
+    /// 多重签名输入的`script_sig`是逗号分隔的`公钥十六进制:签名十六进制`列表，
+    /// 同一公钥出现多次只计一次，不在脚本中的公钥和无效签名不计数
+    fn valid_signers(&self, tx: &Transaction, index: usize, script_sig: &str, network_id: &str) -> usize {
+        let hash = tx.signature_hash(index, &self.to_script_pubkey(), network_id);
+        let message = secp256k1::Message::from_slice(&hash).unwrap();
+        let secp = secp256k1::Secp256k1::verification_only();
+        
+        let mut signers = std::collections::HashSet::new();
+        for (public_key, signature) in script_sig.split(',').filter_map(parse_signature) {
+            if self.pubkeys.contains(&public_key) && secp.verify_ecdsa(&message, &signature, &public_key).is_ok() {
+                signers.insert(public_key);
+            }
+        }
+        signers.len()
+    }
+
+    /// 检查输入是否带有至少`threshold`个不同公钥的有效签名
+    ///
+    /// 每一项签名都必须有效且公钥互不重复，否则整个输入无效
+    fn verify_signatures(&self, tx: &Transaction, index: usize, script_sig: &str, network_id: &str) -> bool {
+        let entries = script_sig.split(',').count();
+        let signers = self.valid_signers(tx, index, script_sig, network_id);
+        signers == entries && signers >= self.threshold
+    }
+}
+
+/// 花费多重签名输出的部分签名交易
+///
+/// 由任一方构建后依次传给各个共同签名者，每个签名者调用[`MultisigSpend::add_signature`]追加自己的签名，
+/// 达到阈值后[`MultisigSpend::is_complete`]返回true，即可广播`transaction`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultisigSpend {
+    /// 交易，多重签名输入的`script_sig`是已收集的签名列表
+    pub transaction: Transaction,
+    /// 输入引用的未花费输出，顺序与交易输入相同
+    pub utxos: Vec<UtxoEntry>,
+    /// 交易所属网络的标识，共同签名者的钱包必须属于同一网络
+    #[serde(default = "default_network_id")]
+    pub network_id: String,
+}
+
+impl MultisigSpend {
+    /// 创建花费多重签名输出的交易
+    ///
+    /// 交易属于[`DEFAULT_NETWORK_ID`]网络，其他网络的交易需要再设置`network_id`
+    ///
+    /// # 参数
+    ///
+    /// * `inputs` - 要花费的多重签名输出
+    /// * `outputs` - 交易输出
+    /// * `fee` - 交易手续费
+    ///
+    /// # 返回值
+    ///
+    /// 任一输入不是多重签名输出时返回`WalletError::InvalidMultisig`
+    pub fn new(inputs: &[(OutPoint, UtxoEntry)], outputs: Vec<TxOutput>, fee: u64) -> Result<Self, WalletError> {
+        if let Some((outpoint, _)) = inputs.iter().find(|(_, entry)| MultisigScript::parse(&entry.script_pubkey).is_none()) {
+            return Err(WalletError::InvalidMultisig(format!("{}:{} 不是多重签名输出", outpoint.tx_id, outpoint.index)));
+        }
+        let tx_inputs = inputs.iter()
+            .map(|(outpoint, _)| TxInput {
+                prev_tx: outpoint.tx_id.clone(),
+                prev_index: outpoint.index,
+                script_sig: String::new(),
+            })
+            .collect();
+        Ok(MultisigSpend {
+            transaction: Transaction::with_fee(tx_inputs, outputs, fee),
+            utxos: inputs.iter().map(|(_, entry)| entry.clone()).collect(),
+            network_id: default_network_id(),
+        })
+    }
+
+    /// 用钱包中参与多重签名的密钥为每个输入追加签名
+    ///
+    /// 同一公钥已有的签名会被替换，因此重复调用不会增加签名数
+    ///
+    /// # 参数
+    ///
+    /// * `wallet` - 共同签名者的钱包
+    ///
+    /// # 返回值
+    ///
+    /// 返回签名的输入数量；观察钱包返回`WalletError::WatchOnly`，
+    /// 钱包属于其他网络时返回`WalletError::NetworkMismatch`，
+    /// 钱包不持有任何输入的参与公钥时返回`WalletError::NotOwned`
+    pub fn add_signature(&mut self, wallet: &Wallet) -> Result<usize, WalletError> {
+        if wallet.is_watch_only() {
+            return Err(WalletError::WatchOnly);
+        }
+        wallet.check_network(&self.network_id)?;
+        let secp = secp256k1::Secp256k1::new();
+        let mut signed = 0;
+        for (index, entry) in self.utxos.iter().enumerate() {
+            let Some(script) = MultisigScript::parse(&entry.script_pubkey) else {
+                continue;
+            };
+            let hash = self.transaction.signature_hash(index, &entry.script_pubkey, &self.network_id);
+            let message = secp256k1::Message::from_slice(&hash).unwrap();
+            
+            let mut signatures: Vec<String> = self.transaction.inputs[index].script_sig.split(',')
+                .filter(|entry| !entry.is_empty())
+                .map(String::from)
+                .collect();
+            for public_key in &script.pubkeys {
+                let Some(private_key) = wallet.key_for_public_key(public_key) else {
+                    continue;
+                };
+                let public_key_hex = hex::encode(public_key.serialize());
+                signatures.retain(|entry| !entry.starts_with(&format!("{}:", public_key_hex)));
+                let signature = secp.sign_ecdsa(&message, private_key);
+                signatures.push(format!("{}:{}", public_key_hex, hex::encode(signature.serialize_compact())));
+                signed += 1;
+            }
+            self.transaction.inputs[index].script_sig = signatures.join(",");
+        }
+        if signed == 0 {
+            return Err(WalletError::NotOwned(wallet.address.clone()));
+        }
+        Ok(signed)
+    }
+
+    /// 获取输入已收集的有效签名数量
+    pub fn signature_count(&self, index: usize) -> usize {
+        match (self.transaction.inputs.get(index), self.utxos.get(index).and_then(|entry| MultisigScript::parse(&entry.script_pubkey))) {
+            (Some(input), Some(script)) => script.valid_signers(&self.transaction, index, &input.script_sig, &self.network_id),
+            _ => 0,
+        }
+    }
+
+    /// 检查所有输入的签名是否都已达到阈值
+    pub fn is_complete(&self) -> bool {
+        self.transaction.inputs.len() == self.utxos.len()
+            && (0..self.utxos.len()).all(|index| Wallet::verify_input(&self.transaction, index, &self.utxos[index], &self.network_id))
+    }
+}
+
+/// 计算签名消息的摘要：SHA256(前缀 || 消息)
+fn message_hash(msg: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(MESSAGE_PREFIX);
+    hasher.update(msg);
+    hasher.finalize().into()
+}
+
+/// 验证由[`Wallet::sign_message`]生成的消息签名
+///
+/// 从签名中恢复签名者的公钥，再检查公钥是否对应声称的地址
+///
+/// # 参数
+///
+/// * `address` - 声称签名的地址
+/// * `msg` - 被签名的消息
+/// * `signature` - 十六进制编码的可恢复签名
+///
+/// # 返回值
+///
+/// 签名有效且由该地址的私钥生成时返回true；签名格式错误时返回false
+pub fn verify_message(address: &str, msg: &[u8], signature: &str) -> bool {
+    let Some(bytes) = hex::decode(signature).ok().filter(|bytes| bytes.len() == 65) else {
+        return false;
+    };
+    let signature = secp256k1::ecdsa::RecoveryId::from_i32(i32::from(bytes[0]))
+        .and_then(|recovery_id| secp256k1::ecdsa::RecoverableSignature::from_compact(&bytes[1..], recovery_id));
+    let Ok(signature) = signature else {
+        return false;
+    };
+    
+    let message = secp256k1::Message::from_slice(&message_hash(msg)).unwrap();
+    secp256k1::Secp256k1::verification_only()
+        .recover_ecdsa(&message, &signature)
+        .is_ok_and(|public_key| Wallet::public_key_matches(&public_key, address))
+}
+
+/// 钱包管理器，按名称管理多个钱包并记录当前使用的钱包
+pub struct WalletManager {
+    /// 名称到钱包的映射
+    wallets: HashMap<String, Wallet>,
+    /// 当前使用的钱包名称
+    active: String,
+    /// 新建、恢复或导入私钥得到的钱包所属的网络，从文件导入的钱包保留文件中记录的网络
+    network_id: String,
+}
+
+impl Default for WalletManager {
+    fn default() -> Self {
+        WalletManager {
+            wallets: HashMap::new(),
+            active: String::new(),
+            network_id: default_network_id(),
+        }
+    }
+}
+
+impl WalletManager {
+    /// 创建空的钱包管理器
+    pub fn new() -> Self {
+        WalletManager::default()
+    }
+
+    /// 设置之后新建的钱包所属的网络，已有的钱包不受影响
+    ///
+    /// # 参数
+    ///
+    /// * `network_id` - 网络标识，通常是[`Blockchain::network_id`]
+    pub fn set_network_id(&mut self, network_id: &str) {
+        self.network_id = network_id.to_string();
+    }
+
+    /// 以指定名称创建新钱包
+    ///
+    /// 名称已存在时返回已有的钱包；管理器中的第一个钱包会成为当前钱包
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 钱包名称
+    pub fn create(&mut self, name: &str) -> &Wallet {
+        let wallet = self.on_network(Wallet::new());
+        self.insert_with(name, || wallet)
+    }
+
+    /// 从文件加载钱包，文件不存在时创建新钱包并保存到该文件
+    ///
+    /// 名称已存在时返回已有的钱包；文件存在但无法加载时不会创建新钱包，避免覆盖可能仍能恢复的密钥
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 钱包名称
+    /// * `path` - 钱包文件路径
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回钱包；加载或保存失败时返回对应的错误，管理器保持不变
+    pub fn load_or_create(&mut self, name: &str, path: &Path) -> Result<&Wallet, WalletError> {
+        if !self.wallets.contains_key(name) {
+            let filename = path.to_string_lossy();
+            let wallet = if path.exists() {
+                Wallet::load_wallet(&filename)?
+            } else {
+                let wallet = self.on_network(Wallet::new());
+                Wallet::save_wallet(&wallet, &filename)?;
+                wallet
+            };
+            self.insert_with(name, || wallet);
+        }
+        Ok(&self.wallets[name])
+    }
+
+    /// 从文件导入钱包，同名钱包会被替换
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 钱包名称
+    /// * `path` - 钱包文件路径
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回导入的钱包；文件无法读取或解析时返回`Wallet::load_wallet`的错误
+    pub fn import(&mut self, name: &str, path: &Path) -> Result<&Wallet, WalletError> {
+        let wallet = Wallet::load_wallet(&path.to_string_lossy())?;
+
+        self.wallets.remove(name);
+        Ok(self.insert_with(name, || wallet))
+    }
+
+    /// 从助记词恢复钱包，同名钱包会被替换
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 钱包名称
+    /// * `phrase` - BIP39助记词
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回恢复的钱包；助记词无效时返回错误
+    pub fn restore(&mut self, name: &str, phrase: &str) -> Result<&Wallet, WalletError> {
+        let wallet = self.on_network(Wallet::from_mnemonic(phrase)?);
+
+        self.wallets.remove(name);
+        Ok(self.insert_with(name, || wallet))
+    }
+
+    /// 从十六进制或WIF私钥导入钱包，同名钱包会被替换
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 钱包名称
+    /// * `key` - 十六进制或WIF格式的私钥
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回导入的钱包；私钥无效时返回错误
+    pub fn import_private_key(&mut self, name: &str, key: &str) -> Result<&Wallet, WalletError> {
+        let wallet = self.on_network(Wallet::import_private_key(key)?);
+
+        self.wallets.remove(name);
+        Ok(self.insert_with(name, || wallet))
+    }
+
+    /// 添加观察钱包，同名钱包会被替换
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 钱包名称
+    /// * `key_or_address` - 十六进制编码的公钥（压缩或未压缩），或钱包地址
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回添加的观察钱包；公钥和地址都无效时返回错误
+    pub fn watch(&mut self, name: &str, key_or_address: &str) -> Result<&Wallet, WalletError> {
+        let key_or_address = key_or_address.trim();
+        let wallet = match hex::decode(key_or_address).ok().and_then(|bytes| PublicKey::from_slice(&bytes).ok()) {
+            Some(public_key) => Wallet::watch_only(public_key),
+            None => Wallet::from_address(key_or_address)?,
+        };
+        let wallet = self.on_network(wallet);
+
+        self.wallets.remove(name);
+        Ok(self.insert_with(name, || wallet))
+    }
+
+    /// 将新建的钱包归属到管理器的网络
+    fn on_network(&self, mut wallet: Wallet) -> Wallet {
+        wallet.network_id = self.network_id.clone();
+        wallet
+    }
+
+    /// 插入钱包（名称不存在时），第一个钱包自动成为当前钱包
+    fn insert_with(&mut self, name: &str, make: impl FnOnce() -> Wallet) -> &Wallet {
+        if self.wallets.is_empty() {
+            self.active = name.to_string();
+        }
+        self.wallets.entry(name.to_string()).or_insert_with(make)
+    }
+
+    /// 切换当前使用的钱包
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 要切换到的钱包名称
+    ///
+    /// # 返回值
+    ///
+    /// 钱包存在时返回Ok，否则返回`WalletError::UnknownWallet`
+    pub fn set_active(&mut self, name: &str) -> Result<(), WalletError> {
+        if !self.wallets.contains_key(name) {
+            return Err(WalletError::UnknownWallet(name.to_string()));
+        }
+        self.active = name.to_string();
+        Ok(())
+    }
+
+    /// 获取当前使用的钱包
+    ///
+    /// # Panics
+    ///
+    /// 管理器中还没有任何钱包时panic
+    pub fn active_wallet(&self) -> &Wallet {
+        self.wallets.get(&self.active).expect("钱包管理器中还没有钱包")
+    }
+
+    /// 获取当前使用的钱包的可变引用，用于创建交易或派生新地址
+    ///
+    /// # Panics
+    ///
+    /// 管理器中还没有任何钱包时panic
+    pub fn active_wallet_mut(&mut self) -> &mut Wallet {
+        self.wallets.get_mut(&self.active).expect("钱包管理器中还没有钱包")
+    }
+
+    /// 获取当前使用的钱包名称
+    pub fn active_name(&self) -> &str {
+        &self.active
+    }
+
+    /// 按名称获取钱包
+    pub fn get(&self, name: &str) -> Option<&Wallet> {
+        self.wallets.get(name)
+    }
+
+    /// 按名称排序遍历所有钱包
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Wallet)> {
+        let mut wallets: Vec<(&str, &Wallet)> = self.wallets.iter()
+            .map(|(name, wallet)| (name.as_str(), wallet))
+            .collect();
+        wallets.sort_by_key(|(name, _)| *name);
+        wallets.into_iter()
+    }
+
+    /// 获取钱包数量
+    pub fn len(&self) -> usize {
+        self.wallets.len()
+    }
+
+    /// 管理器中是否没有钱包
+    pub fn is_empty(&self) -> bool {
+        self.wallets.is_empty()
+    }
+}
+
+/// 钱包事件，由[`WalletWatcher`]在区块接入本地链时发出
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletEvent {
+    /// 确认的交易使本钱包净收入`amount`
+    Received { txid: String, amount: u64, height: usize },
+    /// 确认的交易使本钱包净支出`amount`，即花费的输出减去支付回本钱包的找零
+    Sent { txid: String, amount: u64 },
+}
+
+/// 钱包观察器
+///
+/// 跟踪钱包地址拥有的未花费输出，区块接入本地链时扫描其中的交易：支付给钱包地址的输出计为收入，
+/// 花费钱包输出的输入计为支出。同一笔交易既有收入又有支出时（例如带找零的转账）合并为一个净额事件，
+/// 收支相等的交易不产生事件
+#[derive(Debug)]
+pub struct WalletWatcher {
+    /// 被观察的地址
+    addresses: HashSet<String>,
+    /// 被观察地址拥有的未花费输出及其金额
+    owned: HashMap<OutPoint, u64>,
+    /// 事件发送端
+    sender: mpsc::Sender<WalletEvent>,
+}
+
+impl WalletWatcher {
+    /// 创建观察器，观察钱包当前的全部地址
+    ///
+    /// 新建的观察器不知道钱包已有的输出，花费这些输出的交易需要先调用`watch`载入UTXO才能识别
+    ///
+    /// # 参数
+    ///
+    /// * `wallet` - 被观察的钱包
+    /// * `sender` - 事件发送端
+    pub fn new(wallet: &Wallet, sender: mpsc::Sender<WalletEvent>) -> Self {
+        WalletWatcher {
+            addresses: wallet.addresses().into_iter().collect(),
+            owned: HashMap::new(),
+            sender,
+        }
+    }
+
+    /// 重新观察钱包：更新观察的地址，并用UTXO列表重建钱包拥有的输出，不发出事件
+    ///
+    /// 切换钱包或派生新地址之后调用
+    ///
+    /// # 参数
+    ///
+    /// * `wallet` - 被观察的钱包
+    /// * `utxos` - 当前的UTXO列表，例如`Blockchain::utxo_entries`的结果
+    pub fn watch(&mut self, wallet: &Wallet, utxos: &[(OutPoint, UtxoEntry)]) {
+        self.addresses = wallet.addresses().into_iter().collect();
+        self.resync(utxos);
+    }
+
+    /// 用UTXO列表重建被观察地址拥有的输出，不发出事件
+    ///
+    /// 整条链被替换之后调用
+    ///
+    /// # 参数
+    ///
+    /// * `utxos` - 当前的UTXO列表
+    pub fn resync(&mut self, utxos: &[(OutPoint, UtxoEntry)]) {
+        self.owned = utxos.iter()
+            .filter(|(_, entry)| self.addresses.contains(&entry.script_pubkey))
+            .map(|(outpoint, entry)| (outpoint.clone(), entry.value))
+            .collect();
+    }
+
+    /// 扫描新接入本地链的区块，按交易顺序发出钱包事件
+    ///
+    /// 订阅者处理不过来、通道已满时丢弃事件，不阻塞区块处理
+    ///
+    /// # 参数
+    ///
+    /// * `block` - 接入本地链的区块
+    /// * `height` - 区块高度
+    ///
+    /// # 返回值
+    ///
+    /// 返回本区块产生的事件
+    pub fn block_connected(&mut self, block: &Block, height: usize) -> Vec<WalletEvent> {
+        let mut events = Vec::new();
+        for tx in &block.transactions {
+            let txid = tx.txid();
+            let spent: u64 = if tx.is_coinbase() {
+                0
+            } else {
+                tx.inputs.iter()
+                    .filter_map(|input| self.owned.remove(&OutPoint { tx_id: input.prev_tx.clone(), index: input.prev_index }))
+                    .sum()
+            };
+            let mut received = 0;
+            for (index, output) in tx.outputs.iter().enumerate() {
+                if self.addresses.contains(&output.script_pubkey) {
+                    self.owned.insert(OutPoint { tx_id: txid.clone(), index: index as u32 }, output.value);
+                    received += output.value;
+                }
+            }
+
+            let event = match received.cmp(&spent) {
+                std::cmp::Ordering::Greater => WalletEvent::Received { txid, amount: received - spent, height },
+                std::cmp::Ordering::Less => WalletEvent::Sent { txid, amount: spent - received },
+                std::cmp::Ordering::Equal => continue,
+            };
+            let _ = self.sender.try_send(event.clone());
+            events.push(event);
+        }
+        events
+    }
+}
+
+/// 按CSV规则拆分一行，支持双引号包裹的字段和加倍的引号
+///
+/// # 返回值
+///
+/// 返回各字段；引号不匹配时返回None
+fn parse_csv_record(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+/// 地址簿，将用户名、节点ID等名称映射到钱包地址
+///
+/// 关联文件时每次修改都会立即保存，重启后不需要重新输入对方的地址。
+/// 地址簿不检查地址格式，使用解析结果前应调用[`validate_address`]
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    /// 名称到钱包地址的映射，按名称排序保存
+    entries: BTreeMap<String, String>,
+    /// 地址簿文件路径，为None时只保存在内存中
+    path: Option<PathBuf>,
+}
+
+impl AddressBook {
+    /// 创建只保存在内存中的空地址簿
+    pub fn new() -> Self {
+        AddressBook::default()
+    }
+
+    /// 获取用户的地址簿文件路径
+    ///
+    /// # 参数
+    ///
+    /// * `user_id` - 用户ID，与钱包文件`{user_id}_wallet.json`使用同一个ID
+    pub fn path_for(user_id: &str) -> PathBuf {
+        PathBuf::from(format!("{}_addressbook.json", user_id))
+    }
+
+    /// 从文件加载地址簿，之后的修改都保存到该文件
+    ///
+    /// 文件不存在时返回空地址簿，文件在第一次修改时创建
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 地址簿文件路径
+    ///
+    /// # 返回值
+    ///
+    /// 成功返回地址簿；文件无法读取返回`WalletError::Io`，内容损坏返回`WalletError::Parse`
+    pub fn load_or_create(path: &Path) -> Result<Self, WalletError> {
+        let filename = path.to_string_lossy();
+        let entries = if path.exists() {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| WalletError::Io { path: filename.to_string(), reason: e.to_string() })?;
+            serde_json::from_str(&contents)
+                .map_err(|e| WalletError::Parse { path: filename.to_string(), reason: e.to_string() })?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(AddressBook { entries, path: Some(path.to_path_buf()) })
+    }
+
+    /// 添加或更新名称对应的地址
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 名称
+    /// * `address` - 钱包地址
+    ///
+    /// # 返回值
+    ///
+    /// 返回该名称之前对应的地址；保存到文件失败时返回`WalletError::Io`，内存中的修改仍然保留
+    pub fn add(&mut self, name: &str, address: &str) -> Result<Option<String>, WalletError> {
+        let previous = self.entries.insert(name.to_string(), address.to_string());
+        if previous.as_deref() != Some(address) {
+            self.save()?;
+        }
+        Ok(previous)
+    }
+
+    /// 删除名称
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 要删除的名称
+    ///
+    /// # 返回值
+    ///
+    /// 返回被删除的地址，名称不存在时返回None；保存到文件失败时返回`WalletError::Io`
+    pub fn remove(&mut self, name: &str) -> Result<Option<String>, WalletError> {
+        let removed = self.entries.remove(name);
+        if removed.is_some() {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// 获取名称对应的地址，不把输入当作地址
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 名称
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(String::as_str)
+    }
+
+    /// 将地址或名称解析为地址
+    ///
+    /// 输入本身是有效地址时直接返回，即使地址簿中有同名的条目
+    ///
+    /// # 参数
+    ///
+    /// * `input` - 钱包地址或地址簿中的名称
+    ///
+    /// # 返回值
+    ///
+    /// 返回解析出的地址；输入既不是有效地址也不在地址簿中时返回None
+    pub fn resolve(&self, input: &str) -> Option<String> {
+        if validate_address(input) {
+            return Some(input.to_string());
+        }
+        self.entries.get(input).cloned()
+    }
+
+    /// 按名称排序列出所有条目
+    ///
+    /// # 返回值
+    ///
+    /// 返回(名称, 地址)列表
+    pub fn list(&self) -> Vec<(&str, &str)> {
+        self.entries.iter().map(|(name, address)| (name.as_str(), address.as_str())).collect()
+    }
+
+    /// 条目数量
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 地址簿是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 将地址簿导出为CSV文件
+    ///
+    /// 第一行为表头`name,address`，字段按CSV规则转义
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 输出文件路径
+    ///
+    /// # 返回值
+    ///
+    /// 写入失败时返回`WalletError::Io`
+    pub fn export_csv(&self, path: &Path) -> Result<(), WalletError> {
+        let mut csv = String::from("name,address\n");
+        for (name, address) in &self.entries {
+            csv.push_str(&format!("{},{}\n", csv_field(name), csv_field(address)));
+        }
+        fs::write(path, csv).map_err(|e| WalletError::Io { path: path.to_string_lossy().to_string(), reason: e.to_string() })
+    }
+
+    /// 从CSV文件导入条目，同名条目被覆盖，导入后保存一次
+    ///
+    /// 文件格式与[`AddressBook::export_csv`]相同，表头和空行会被跳过。
+    /// 任一行格式错误或地址无效时不导入任何条目
+    ///
+    /// # 参数
+    ///
+    /// * `path` - CSV文件路径
+    ///
+    /// # 返回值
+    ///
+    /// 返回导入的条目数；文件无法读取或保存失败返回`WalletError::Io`，
+    /// 内容无效返回`WalletError::Parse`，`reason`中包含出错的行号
+    pub fn import_csv(&mut self, path: &Path) -> Result<usize, WalletError> {
+        let filename = path.to_string_lossy().to_string();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| WalletError::Io { path: filename.clone(), reason: e.to_string() })?;
+        let parse_error = |line: usize, reason: String| WalletError::Parse { path: filename.clone(), reason: format!("第{}行: {}", line, reason) };
+        
+        let mut imported = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || (index == 0 && line.trim() == "name,address") {
+                continue;
+            }
+            let fields = parse_csv_record(line).ok_or_else(|| parse_error(index + 1, String::from("引号不匹配")))?;
+            let [name, address] = <[String; 2]>::try_from(fields)
+                .map_err(|fields| parse_error(index + 1, format!("应有2个字段，实际为{}个", fields.len())))?;
+            if name.is_empty() {
+                return Err(parse_error(index + 1, String::from("名称为空")));
+            }
+            if !validate_address(&address) {
+                return Err(parse_error(index + 1, format!("无效的地址: {}", address)));
+            }
+            imported.push((name, address));
+        }
+        
+        let count = imported.len();
+        self.entries.extend(imported);
+        if count > 0 {
+            self.save()?;
+        }
+        Ok(count)
+    }
+
+    /// 将地址簿写入关联的文件，先写临时文件再重命名，避免写入中断损坏原文件
+    fn save(&self) -> Result<(), WalletError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let filename = path.to_string_lossy();
+        let serialized = serde_json::to_string_pretty(&self.entries).expect("地址簿总是可以序列化");
+        let temp = format!("{}.tmp", filename);
+        fs::write(&temp, serialized)
+            .and_then(|()| fs::rename(&temp, path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp);
+                WalletError::Io { path: filename.to_string(), reason: e.to_string() }
+            })
+    }
+}
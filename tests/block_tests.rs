@@ -1,4 +1,5 @@
-use blockchain_demo::block::{Block, Transaction, TxInput, TxOutput};
+use blockchain_demo::block::{Block, DecodeError, Transaction, TxInput, TxOutput, COINBASE_TX_ID};
+use blockchain_demo::blockchain::{Blockchain, GenesisConfig};
 
 #[test]
 fn test_block_mining_and_validation() {
@@ -21,13 +22,13 @@ fn test_block_mining_and_validation() {
     block.transactions.push(transaction);
     
     // 初始状态下区块应该无效
-    assert_eq!(block.is_valid(), false);
+    assert!(!block.is_valid());
     
     // 挖矿
     block.mine();
     
     // 挖矿后区块应该有效
-    assert_eq!(block.is_valid(), true);
+    assert!(block.is_valid());
     
     // 验证挖矿是否改变了nonce值
     assert!(block.header.nonce > 0);
@@ -37,3 +38,173 @@ fn test_block_mining_and_validation() {
     let required_prefix = "0".repeat(block.header.difficulty as usize);
     assert!(hash.starts_with(&required_prefix));
 }
+
+// 辅助函数：创建指定手续费的测试交易
+fn create_fee_transaction(prev_tx: &str, fee: u64) -> Transaction {
+    Transaction::with_fee(
+        vec![TxInput {
+            prev_tx: prev_tx.to_string(),
+            prev_index: 0,
+            script_sig: String::from("测试签名"),
+        }],
+        vec![TxOutput {
+            value: 10,
+            script_pubkey: String::from("接收地址"),
+        }],
+        fee,
+    )
+}
+
+#[test]
+fn test_transactions_sorted_by_fee() {
+    let coinbase = Transaction::new(
+        vec![TxInput {
+            prev_tx: String::from(COINBASE_TX_ID),
+            prev_index: 0,
+            script_sig: String::from("挖矿奖励"),
+        }],
+        vec![TxOutput {
+            value: 50,
+            script_pubkey: String::from("矿工地址"),
+        }],
+    );
+    
+    // 五笔手续费不同的交易，顺序打乱
+    let fees = [3, 9, 1, 7, 5];
+    let mut transactions = vec![coinbase];
+    for (i, fee) in fees.iter().enumerate() {
+        transactions.push(create_fee_transaction(&format!("tx{}", i), *fee));
+    }
+    
+    let sorted = Block::transactions_sorted_by_fee(&transactions);
+    assert_eq!(sorted.len(), 6);
+    
+    // Coinbase交易始终在最前，之后是手续费最高的交易
+    assert!(sorted[0].is_coinbase());
+    assert_eq!(sorted[1].fee, 9);
+    let sorted_fees: Vec<u64> = sorted[1..].iter().map(|tx| tx.fee).collect();
+    assert_eq!(sorted_fees, vec![9, 7, 5, 3, 1]);
+    
+    // 区块手续费总额不包含Coinbase交易
+    let mut block = Block::new(String::from("0"), 0);
+    block.transactions = transactions;
+    assert_eq!(block.total_fees(), 25);
+}
+
+#[test]
+fn test_block_binary_round_trip() {
+    let mut block = Block::new(String::from(COINBASE_TX_ID), 1);
+    block.transactions.push(Transaction::with_fee(
+        vec![TxInput {
+            prev_tx: "ab".repeat(32),
+            prev_index: 3,
+            script_sig: String::from("公钥:签名"),
+        }],
+        vec![TxOutput {
+            value: 42,
+            script_pubkey: "cd".repeat(20),
+        }],
+        2,
+    ));
+    block.mine();
+    
+    let bytes = block.to_bytes();
+    assert!(bytes.len() < serde_json::to_vec(&block).unwrap().len());
+    
+    let decoded = Block::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.calculate_hash(), block.calculate_hash());
+    assert_eq!(decoded.transactions[0].fee, 2);
+    
+    // 截断或无效的数据返回解码错误
+    assert!(matches!(Block::from_bytes(&bytes[..bytes.len() / 2]), Err(DecodeError::Bincode(_))));
+    assert!(Block::from_bytes(b"not a block").is_err());
+}
+
+#[test]
+fn test_deterministic_mining() {
+    const TIMESTAMP: i64 = 1_700_000_000;
+    let mut block = Block::with_timestamp(String::from(COINBASE_TX_ID), 2, TIMESTAMP);
+    block.mine_from(0);
+    
+    // 固定时间戳和难度时，挖矿结果与运行时间无关
+    assert_eq!(block.header.nonce, 452);
+    assert_eq!(block.calculate_hash(), "00c244eac34008b5273207182dcbaf0f6f90de6a27c03ca61dbf2f57b3e533be");
+    
+    // 从其他nonce开始时跳过更小的nonce
+    let mut later = Block::with_timestamp(String::from(COINBASE_TX_ID), 2, TIMESTAMP);
+    later.mine_from(453);
+    assert!(later.header.nonce > 452);
+    assert!(later.is_valid());
+}
+
+#[test]
+fn test_mined_and_genesis_blocks_store_merkle_root() {
+    let mut block = Block::new(String::from(COINBASE_TX_ID), 1);
+    block.transactions.push(create_fee_transaction("tx_a", 1));
+    block.transactions.push(create_fee_transaction("tx_b", 2));
+    block.mine();
+    
+    // 挖矿前写入默克尔根，区块哈希覆盖的是正确的默克尔根
+    assert_eq!(block.header.merkle_root, block.compute_merkle_root());
+    assert!(block.is_valid());
+    
+    // 创世区块的默克尔根由其Coinbase交易计算
+    for genesis in [Blockchain::new(1).genesis().clone(), GenesisConfig::regtest().build_block()] {
+        assert_eq!(genesis.header.merkle_root, genesis.compute_merkle_root());
+    }
+}
+
+#[test]
+fn test_display_summarizes_block_and_transaction() {
+    let coinbase = Transaction::new(
+        vec![TxInput { prev_tx: String::from(COINBASE_TX_ID), prev_index: 0, script_sig: String::from("挖矿奖励") }],
+        vec![TxOutput { value: 50, script_pubkey: String::from("矿工地址") }],
+    );
+    let payment = Transaction::with_fee(
+        vec![
+            TxInput { prev_tx: "a".repeat(64), prev_index: 1, script_sig: String::from("签名1") },
+            TxInput { prev_tx: "b".repeat(64), prev_index: 0, script_sig: String::from("签名2") },
+        ],
+        vec![
+            TxOutput { value: 30, script_pubkey: String::from("收款地址") },
+            TxOutput { value: 12, script_pubkey: String::from("找零地址") },
+        ],
+        3,
+    );
+    
+    let summary = payment.to_string();
+    assert!(summary.contains(&payment.txid()));
+    assert!(summary.contains("2 inputs, 2 outputs, output total 42, fee 3"));
+    assert!(summary.contains(&format!("{} bytes", payment.size())));
+    assert!(!summary.contains("coinbase"));
+    assert!(!summary.contains('\n'));
+    assert!(coinbase.to_string().contains("(coinbase): 1 inputs, 1 outputs, output total 50, fee 0"));
+    
+    let detailed = format!("{:#}", payment);
+    assert!(detailed.starts_with(&summary));
+    assert!(detailed.contains(&format!("in:  {}:1", "a".repeat(64))));
+    assert!(detailed.contains("out: 12 -> 找零地址"));
+    
+    let mut block = Block::new("0".repeat(64), 1);
+    block.transactions = vec![coinbase.clone(), payment.clone()];
+    block.mine();
+    let summary = block.to_string();
+    assert!(summary.starts_with(&format!("Block {}", block.calculate_hash())));
+    assert!(summary.contains(&format!("Previous hash: {}", "0".repeat(64))));
+    assert!(summary.contains(&format!("Merkle root: {}", block.header.merkle_root)));
+    assert!(summary.contains(&format!("Nonce: {}  Difficulty: 1", block.header.nonce)));
+    assert!(summary.contains("Transactions: 2  Output total: 92  Fees: 3"));
+    assert!(!summary.contains(&payment.txid()));
+    
+    // 手续费总额与total_fees一致，不计入Coinbase交易
+    let mut with_coinbase_fee = block.clone();
+    with_coinbase_fee.transactions[0].fee = 50;
+    assert!(with_coinbase_fee.to_string().contains(&format!("Fees: {}", with_coinbase_fee.total_fees())));
+    assert!(with_coinbase_fee.to_string().contains("Fees: 3"));
+    
+    // 详细格式逐行列出区块中的交易，派生的Debug保持不变
+    let detailed = format!("{:#}", block);
+    assert!(detailed.contains(&coinbase.to_string()));
+    assert!(detailed.contains(&payment.to_string()));
+    assert!(format!("{:?}", block).starts_with("Block { header: BlockHeader {"));
+}
@@ -1,5 +1,9 @@
-use blockchain_demo::block::{Transaction, TxInput, TxOutput};
-use blockchain_demo::blockchain::Blockchain;
+use blockchain_demo::block::{Block, Hasher, Transaction, TxInput, TxOutput, COINBASE_TX_ID, OP_RETURN_PREFIX};
+use blockchain_demo::blockchain::{BlockError, Blockchain, BLOCK_REWARD, MAX_DIFFICULTY, MAX_FUTURE_BLOCK_TIME, BlockchainError, BlockchainConfig, ExportKind, DEFAULT_NETWORK_ID, GenesisConfig, ImportError, IntegrityError, LoadOutcome, SimulationError, FEE_ESTIMATE_CACHE_BLOCKS, MIN_FEE_RATE};
+use blockchain_demo::validation::TxError;
+use blockchain_demo::mempool::Mempool;
+use blockchain_demo::metrics::MetricsSnapshot;
+use blockchain_demo::wallet::{BalanceBreakdown, TransactionError, UnsignedTransaction, Wallet};
 use std::fs;
 
 #[test]
@@ -11,7 +15,7 @@ fn test_blockchain_add_block_and_utxo() {
     let mut blockchain = Blockchain::new(2);
     
     // 记录初始区块数量
-    let initial_block_count = blockchain.blocks.len();
+    let initial_block_count = blockchain.len();
     assert_eq!(initial_block_count, 1); // 应该有一个创世区块
     
     // 创建测试交易
@@ -32,11 +36,11 @@ fn test_blockchain_add_block_and_utxo() {
     blockchain.add_block(vec![transaction]);
     
     // 验证区块是否已添加
-    assert_eq!(blockchain.blocks.len(), initial_block_count + 1);
+    assert_eq!(blockchain.len(), initial_block_count + 1);
     
     // 验证UTXO集合是否更新
     // 查找交易ID
-    let tx_id = blockchain.calculate_tx_hash(&blockchain.blocks[1].transactions[0]);
+    let tx_id = blockchain.calculate_tx_hash(&blockchain.get(1).unwrap().transactions[0]);
     
     // 验证UTXO集合中是否存在该交易的输出
     assert!(blockchain.utxo_set.contains_key(&tx_id));
@@ -45,6 +49,8 @@ fn test_blockchain_add_block_and_utxo() {
     let outputs = blockchain.utxo_set.get(&tx_id).unwrap();
     assert_eq!(outputs.len(), 1);
     assert_eq!(outputs[0].1, 50);
+    // UTXO集合直接记录输出的所有者
+    assert_eq!(outputs[0].2, "测试地址");
     
     // 添加第二个区块，消费第一个区块的UTXO
     let tx_input2 = TxInput {
@@ -69,8 +75,1732 @@ fn test_blockchain_add_block_and_utxo() {
     blockchain.add_block(vec![transaction2]);
     
     // 验证UTXO集是否正确更新（第一个交易的输出应该被消费）
-    assert!(!blockchain.utxo_set.get(&tx_id).unwrap().iter().any(|(idx, _)| *idx == 0));
+    // 第一个交易唯一的输出被消费后，整个条目会从UTXO集中移除
+    assert!(!blockchain.utxo_set.get(&tx_id).is_some_and(|outputs| outputs.iter().any(|(idx, ..)| *idx == 0)));
     
     // 清理测试文件
     let _ = fs::remove_file("blockchain.json");
 }
+
+#[test]
+fn test_blockchain_accessors() {
+    let mut blockchain = Blockchain::new(1);
+    
+    // 只有创世区块时，高度为0，链顶就是创世区块
+    assert_eq!(blockchain.len(), 1);
+    assert_eq!(blockchain.height(), 0);
+    assert_eq!(blockchain.tip().calculate_hash(), blockchain.genesis().calculate_hash());
+    
+    let coinbase = Transaction::new(
+        vec![TxInput {
+            prev_tx: String::from("0000000000000000000000000000000000000000000000000000000000000000"),
+            prev_index: 0,
+            script_sig: String::from("访问器测试"),
+        }],
+        vec![TxOutput {
+            value: 50,
+            script_pubkey: String::from("矿工地址"),
+        }],
+    );
+    blockchain.add_block(vec![coinbase]);
+    
+    assert_eq!(blockchain.len(), 2);
+    assert_eq!(blockchain.height(), 1);
+    assert_eq!(blockchain.tip().header.prev_hash, blockchain.genesis().calculate_hash());
+    
+    // 正序与逆序迭代器互为反转
+    let forward: Vec<String> = blockchain.iter().map(|b| b.calculate_hash()).collect();
+    let mut backward: Vec<String> = blockchain.iter_rev().map(|b| b.calculate_hash()).collect();
+    backward.reverse();
+    assert_eq!(forward, backward);
+    
+    // &Blockchain 可以直接用于 for 循环
+    let mut count = 0;
+    for block in &blockchain {
+        assert!(block.transactions.len() == 1);
+        count += 1;
+    }
+    assert_eq!(count, blockchain.len());
+    assert!(blockchain.get(2).is_none());
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+// 辅助函数：创建Coinbase交易
+fn create_coinbase(address: &str) -> Transaction {
+    Transaction::new(
+        vec![TxInput {
+            prev_tx: String::from(COINBASE_TX_ID),
+            prev_index: 0,
+            script_sig: String::from("挖矿奖励"),
+        }],
+        vec![TxOutput {
+            value: 50,
+            script_pubkey: address.to_string(),
+        }],
+    )
+}
+
+// 辅助函数：按CSV规则拆分一行（支持双引号包裹的字段）
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+#[test]
+fn test_export_blocks_and_transactions_csv() {
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    
+    let mut output = Vec::new();
+    blockchain.export_csv(&mut output, ExportKind::Blocks).unwrap();
+    let text = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    
+    // 表头加三个区块
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "height,hash,prev_hash,timestamp,nonce,difficulty,tx_count");
+    let row = parse_csv_line(lines[2]);
+    assert_eq!(row[0], "1");
+    assert_eq!(row[1], blockchain.get(1).unwrap().calculate_hash());
+    assert_eq!(row[2], blockchain.genesis().calculate_hash());
+    assert_eq!(row[6], "1");
+    
+    let mut output = Vec::new();
+    blockchain.export_csv(&mut output, ExportKind::Transactions).unwrap();
+    let text = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4);
+    let row = parse_csv_line(lines[3]);
+    assert_eq!(row[1], "2");
+    assert_eq!(row[4], "50");
+    assert_eq!(row[5], "0");
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_export_csv_escapes_commas() {
+    let mut blockchain = Blockchain::new(0);
+    let mut block = Block::new(String::from("hash,with,\"commas\""), 0);
+    block.transactions.push(create_coinbase("矿工地址"));
+    let mut blocks = blockchain.as_slice().to_vec();
+    blocks.push(block);
+    blockchain.replace_chain(blocks);
+    
+    let mut output = Vec::new();
+    blockchain.export_csv(&mut output, ExportKind::Blocks).unwrap();
+    let text = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    
+    let row = parse_csv_line(lines[2]);
+    assert_eq!(row.len(), 7);
+    assert_eq!(row[2], "hash,with,\"commas\"");
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_export_json_lines() {
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    
+    let mut output = Vec::new();
+    blockchain.export_json_lines(&mut output, ExportKind::Transactions).unwrap();
+    let text = String::from_utf8(output).unwrap();
+    let rows: Vec<serde_json::Value> = text.lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1]["block_height"], 1);
+    assert_eq!(rows[1]["total_output_value"], 50);
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+// 辅助函数：创建普通转账交易
+fn create_transfer(prev_tx: &str, address: &str) -> Transaction {
+    Transaction::new(
+        vec![TxInput {
+            prev_tx: prev_tx.to_string(),
+            prev_index: 0,
+            script_sig: String::from("测试签名"),
+        }],
+        vec![TxOutput {
+            value: 10,
+            script_pubkey: address.to_string(),
+        }],
+    )
+}
+
+// 辅助函数：把所有输入标记为花费钱包地址的输出后用该钱包签名，
+// 钱包只为属于自己的地址签名
+fn sign_as(wallet: &Wallet, tx: &mut Transaction) {
+    for input in &mut tx.inputs {
+        input.script_sig = wallet.address.clone();
+    }
+    wallet.sign_transaction(tx).unwrap();
+}
+
+// 辅助函数：在链顶之后构建并挖出包含指定交易的区块
+fn mine_on_tip(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+    let mut block = Block::with_timestamp(blockchain.tip().calculate_hash(), blockchain.difficulty(), blockchain.next_block_timestamp());
+    block.transactions = transactions;
+    block.mine();
+    block
+}
+
+#[test]
+fn test_block_without_coinbase_is_rejected() {
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    let funding_tx = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    
+    let block = mine_on_tip(&blockchain, vec![create_transfer(&funding_tx, "接收地址")]);
+    
+    assert_eq!(Blockchain::validate_coinbase(&block), Err(BlockError::MissingCoinbase));
+    assert!(!blockchain.validate_block(&block));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_with_second_coinbase_is_rejected() {
+    let blockchain = Blockchain::new(1);
+    let block = mine_on_tip(&blockchain, vec![
+        create_coinbase("矿工地址"),
+        create_coinbase("另一个矿工地址"),
+    ]);
+    
+    assert_eq!(Blockchain::validate_coinbase(&block), Err(BlockError::MultipleCoinbase { index: 1 }));
+    assert!(!blockchain.validate_block(&block));
+}
+
+#[test]
+fn test_block_with_double_spend_is_rejected() {
+    let wallet = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    let funding_tx = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    
+    // 两笔交易各自有效，但花费了同一个输出
+    let mut first = create_transfer(&funding_tx, "接收地址");
+    let mut second = create_transfer(&funding_tx, "另一个接收地址");
+    sign_as(&wallet, &mut first);
+    sign_as(&wallet, &mut second);
+    assert!(blockchain.validate_transaction(&first));
+    assert!(blockchain.validate_transaction(&second));
+    
+    let transactions = vec![create_coinbase("矿工地址"), first.clone(), second];
+    assert_eq!(Transaction::find_double_spend(&transactions), Some((1, 2)));
+    assert!(!blockchain.validate_block(&mine_on_tip(&blockchain, transactions)));
+    
+    // 只包含其中一笔时区块有效
+    let transactions = vec![create_coinbase("矿工地址"), first];
+    assert_eq!(Transaction::find_double_spend(&transactions), None);
+    assert!(blockchain.validate_block(&mine_on_tip(&blockchain, transactions)));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_with_misplaced_coinbase_is_rejected() {
+    let mut wallet = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    
+    let utxos = blockchain.get_utxos_for_address(&wallet.address);
+    let mut transfer = wallet.create_transaction(&Wallet::new().address, 10, &utxos).unwrap();
+    wallet.sign_transaction(&mut transfer).unwrap();
+    
+    let block = mine_on_tip(&blockchain, vec![
+        transfer.clone(),
+        create_coinbase("矿工地址"),
+    ]);
+    
+    assert_eq!(Blockchain::validate_coinbase(&block), Err(BlockError::MisplacedCoinbase { index: 1 }));
+    assert!(!blockchain.validate_block(&block));
+    
+    // 同样的交易把Coinbase放在首位即可通过验证
+    let valid_block = mine_on_tip(&blockchain, vec![
+        create_coinbase("矿工地址"),
+        transfer,
+    ]);
+    assert!(blockchain.validate_block(&valid_block));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_check_block_is_read_only_dry_run() {
+    let blockchain = Blockchain::new(1);
+    
+    // 矿工挖出的合法区块可以通过检查，检查不会修改区块链
+    let block = blockchain.mine_block(vec![create_coinbase("矿工地址")]);
+    assert_eq!(blockchain.check_block(&block), Ok(()));
+    assert_eq!(blockchain.len(), 1);
+    
+    // Coinbase有两个输入的区块被拒绝
+    let mut bad_coinbase = create_coinbase("矿工地址");
+    bad_coinbase.inputs.push(bad_coinbase.inputs[0].clone());
+    let block = blockchain.mine_block(vec![bad_coinbase]);
+    assert_eq!(blockchain.check_block(&block), Err(BlockError::InvalidTransaction {
+        index: 0,
+        error: TxError::MalformedCoinbase { inputs: 2 },
+    }));
+    
+    // 缺少Coinbase的区块同样被拒绝
+    let block = blockchain.mine_block(vec![]);
+    assert_eq!(blockchain.check_block(&block), Err(BlockError::MissingCoinbase));
+}
+
+#[test]
+fn test_block_transaction_limit() {
+    let mut blockchain = Blockchain::new(1);
+    blockchain.set_max_transactions(3);
+    
+    // 准备两笔可以花费的输出
+    let mut transfers = Vec::new();
+    for recipient in ["地址1", "地址2"] {
+        let wallet = Wallet::new();
+        blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+        let funding_tx = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+        let mut transfer = create_transfer(&funding_tx, recipient);
+        sign_as(&wallet, &mut transfer);
+        transfers.push(transfer);
+    }
+    
+    // Coinbase计入上限：Coinbase加两笔交易刚好等于上限
+    let mut transactions = vec![create_coinbase("矿工地址")];
+    transactions.extend(transfers);
+    let at_limit = mine_on_tip(&blockchain, transactions);
+    assert!(blockchain.validate_block(&at_limit));
+    
+    // 超过上限一笔的区块被拒绝
+    let mut over_limit = at_limit.clone();
+    over_limit.transactions.push(
+        Transaction::new(vec![], vec![TxOutput { value: 0, script_pubkey: String::from("地址3") }]),
+    );
+    over_limit.mine();
+    assert!(!blockchain.validate_block(&over_limit));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_size_limit() {
+    let mut blockchain = Blockchain::new(1);
+    let block = mine_on_tip(&blockchain, vec![create_coinbase("矿工地址")]);
+    assert_eq!(block.size(), block.to_bytes().len());
+    assert!(blockchain.validate_block(&block));
+    
+    // 大小恰好等于上限的区块有效，超过一个字节即被拒绝
+    blockchain.set_max_block_size(block.size());
+    assert!(blockchain.validate_block(&block));
+    blockchain.set_max_block_size(block.size() - 1);
+    assert_eq!(
+        blockchain.check_block(&block),
+        Err(BlockError::BlockTooLarge { size: block.size(), max: block.size() - 1 })
+    );
+    
+    // 复制的链沿用同样的上限
+    assert_eq!(blockchain.fresh_copy().max_block_size(), block.size() - 1);
+}
+
+#[test]
+fn test_genesis_config_changes_genesis_hash() {
+    let default_chain = Blockchain::new_with_genesis(GenesisConfig::default());
+    let testnet_chain = Blockchain::new_with_genesis(GenesisConfig {
+        network_id: String::from("testnet"),
+        ..GenesisConfig::default()
+    });
+    
+    // 相同配置产生相同的创世区块，不同网络标识产生不同的创世区块
+    assert_eq!(
+        default_chain.genesis().calculate_hash(),
+        Blockchain::new_with_genesis(GenesisConfig::default()).genesis().calculate_hash()
+    );
+    assert_ne!(default_chain.genesis().calculate_hash(), testnet_chain.genesis().calculate_hash());
+    
+    // 默认配置保留原有的创世分配
+    assert_eq!(default_chain.get_balance("genesis_address"), 100);
+}
+
+#[test]
+fn test_premined_balance_is_spendable() {
+    let mut wallet = Wallet::new();
+    let mut blockchain = Blockchain::new_with_genesis(GenesisConfig {
+        premine: vec![(wallet.address.clone(), 500), (String::from("基金会地址"), 200)],
+        network_id: String::from("premine-test"),
+        difficulty: 1,
+        ..GenesisConfig::default()
+    });
+    assert_eq!(blockchain.get_balance(&wallet.address), 500);
+    assert_eq!(blockchain.get_utxos_for_address(&wallet.address).len(), 1);
+    wallet.network_id = blockchain.network_id().to_string();
+    
+    // 钱包只会花费属于自己的预挖输出
+    let recipient = Wallet::new();
+    let mut tx = wallet.create_transaction(&recipient.address, 120, &blockchain.utxo_entries()).unwrap();
+    assert_eq!(tx.inputs.len(), 1);
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert!(blockchain.validate_transaction(&tx));
+    
+    blockchain.add_block(vec![create_coinbase("矿工地址"), tx]);
+    // 找零发送到钱包新派生的地址
+    let balance: u64 = wallet.addresses().iter().map(|address| blockchain.get_balance(address)).sum();
+    assert_eq!(balance, 380);
+    assert_eq!(blockchain.get_balance(&wallet.address), 0);
+    assert_eq!(blockchain.get_balance(&recipient.address), 120);
+    assert_eq!(blockchain.get_balance("基金会地址"), 200);
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_chain_statistics() {
+    let mut blockchain = Blockchain::new(1);
+    let empty_stats = blockchain.statistics();
+    assert_eq!(empty_stats.total_blocks, 1);
+    assert_eq!(empty_stats.average_block_time_secs, 0.0);
+    
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    let reward_tx_id = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    
+    // 花费50的奖励，转出45，支付5的手续费，矿工在下一个区块领取奖励和手续费
+    let transfer = Transaction::with_fee(
+        vec![TxInput {
+            prev_tx: reward_tx_id,
+            prev_index: 0,
+            script_sig: String::from("测试签名"),
+        }],
+        vec![TxOutput {
+            value: 45,
+            script_pubkey: String::from("接收地址"),
+        }],
+        5,
+    );
+    let mut coinbase = create_coinbase("矿工地址");
+    coinbase.outputs[0].value += 5;
+    
+    // 添加区块后缓存失效，统计会重新计算
+    assert_eq!(blockchain.statistics().total_blocks, 2);
+    blockchain.add_block(vec![coinbase, transfer]);
+    let stats = blockchain.statistics();
+    
+    assert_eq!(stats.total_blocks, 3);
+    assert_eq!(stats.total_transactions, 4);
+    assert_eq!(stats.total_coinbase_amount, 100 + 50 + 55);
+    assert_eq!(stats.total_fees, 5);
+    assert_eq!(stats.circulating_supply, stats.total_coinbase_amount - stats.total_fees);
+    assert_eq!(stats.average_txs_per_block, 4.0 / 3.0);
+    
+    let utxo_outputs: Vec<&(u32, u64, String)> = blockchain.utxo_set.values().flatten().collect();
+    assert_eq!(stats.utxo_count, utxo_outputs.len());
+    assert_eq!(stats.circulating_supply, utxo_outputs.iter().map(|(_, amount, _)| amount).sum::<u64>());
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_rollback_restores_utxo_set() {
+    let mut blockchain = Blockchain::new(1);
+    let mut utxo_after_three = None;
+    
+    for height in 1..=5 {
+        let mut transactions = vec![create_coinbase(&format!("矿工{}", height))];
+        // 从第二个区块开始，花费上一个区块的Coinbase输出
+        if height > 1 {
+            let prev_coinbase = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+            transactions.push(create_transfer(&prev_coinbase, &format!("接收者{}", height)));
+        }
+        blockchain.add_block(transactions);
+        
+        if height == 3 {
+            utxo_after_three = Some(blockchain.utxo_set.clone());
+        }
+    }
+    
+    // 不能回滚掉创世区块，失败时链保持不变
+    assert_eq!(
+        blockchain.rollback(6),
+        Err(BlockchainError::RollbackPastGenesis { requested: 6, height: 5 })
+    );
+    assert_eq!(blockchain.len(), 6);
+    
+    blockchain.rollback(2).unwrap();
+    assert_eq!(blockchain.len(), 4);
+    assert_eq!(blockchain.utxo_set, utxo_after_three.unwrap());
+    assert_eq!(blockchain.get_balance("矿工3"), 50);
+    assert_eq!(blockchain.get_balance("矿工4"), 0);
+    
+    // 回滚后可以继续在新的链顶上出块
+    blockchain.add_block(vec![create_coinbase("新矿工")]);
+    assert_eq!(blockchain.len(), 5);
+    assert_eq!(blockchain.block_undo_at(4).unwrap().created_outputs.len(), 1);
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_unsigned_transfer_is_rejected() {
+    let wallet = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    let funding_tx = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    
+    // 引用的输出存在，但没有有效签名
+    assert!(!blockchain.validate_transaction(&create_transfer(&funding_tx, "接收地址")));
+    
+    // 其他钱包的签名也不能花费该输出
+    let thief = Wallet::new();
+    let mut stolen = create_transfer(&funding_tx, "接收地址");
+    sign_as(&thief, &mut stolen);
+    assert!(!blockchain.validate_transaction(&stolen));
+    
+    let mut signed = create_transfer(&funding_tx, "接收地址");
+    sign_as(&wallet, &mut signed);
+    assert!(blockchain.validate_transaction(&signed));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_restored_wallet_sees_existing_balance() {
+    let (wallet, phrase) = Wallet::new_with_mnemonic();
+    let mut blockchain = Blockchain::new_with_genesis(GenesisConfig {
+        premine: vec![(wallet.address.clone(), 300)],
+        network_id: String::from("restore-test"),
+        difficulty: 1,
+        ..GenesisConfig::default()
+    });
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    let balance = blockchain.get_balance(&wallet.address);
+    assert!(balance > 300);
+    
+    // 在"另一台机器"上用恢复短语恢复钱包，立即看到相同的余额
+    let mut restored = Wallet::from_mnemonic(&phrase).unwrap();
+    restored.network_id = String::from("restore-test");
+    assert_eq!(restored.address, wallet.address);
+    assert_eq!(blockchain.get_balance(&restored.address), balance);
+    
+    // 恢复的钱包可以花费已有的UTXO
+    let recipient = Wallet::new();
+    let mut tx = restored.create_transaction(&recipient.address, 50, &blockchain.utxo_entries()).unwrap();
+    restored.sign_transaction(&mut tx).unwrap();
+    assert!(blockchain.validate_transaction(&tx));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_blocks_are_saved_to_data_file() {
+    let dir = std::env::temp_dir().join(format!("chain_data_file_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let filename = dir.join("chain.json").to_str().unwrap().to_string();
+    
+    // 新区块保存到设置的文件，写入完成后不留下临时文件
+    let mut blockchain = Blockchain::new(1);
+    assert_eq!(blockchain.data_file(), "blockchain.json");
+    blockchain.set_data_file(&filename);
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    assert!(!std::path::Path::new(&format!("{}.tmp", filename)).exists());
+    
+    // 加载的区块链保存回原来的文件
+    let (loaded, _) = Blockchain::load_verified(&filename).unwrap();
+    assert_eq!(loaded.data_file(), filename);
+    assert_eq!(loaded.len(), 2);
+    
+    // 写入失败时返回错误而不是panic，原有文件保持不变
+    assert!(blockchain.save_to_file(dir.join("missing").join("chain.json").to_str().unwrap()).is_err());
+    assert_eq!(Blockchain::load_verified(&filename).unwrap().0.len(), 2);
+    
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_binary_save_and_load() {
+    let wallet = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    
+    let path = std::env::temp_dir().join(format!("blockchain_{}.bin", wallet.address));
+    blockchain.save_to_binary(&path).unwrap();
+    blockchain.save_to_file("blockchain_binary_test.json").unwrap();
+    
+    // 二进制格式比JSON格式更紧凑
+    let binary_size = fs::metadata(&path).unwrap().len();
+    let json_size = fs::metadata("blockchain_binary_test.json").unwrap().len();
+    assert!(binary_size < json_size);
+    
+    let loaded = Blockchain::load_from_binary(&path).unwrap();
+    assert_eq!(loaded.len(), blockchain.len());
+    assert_eq!(loaded.tip().calculate_hash(), blockchain.tip().calculate_hash());
+    assert_eq!(loaded.get_balance(&wallet.address), blockchain.get_balance(&wallet.address));
+    
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file("blockchain_binary_test.json");
+    let _ = fs::remove_file("blockchain_binary_test.undo.json");
+    let _ = fs::remove_file("blockchain_binary_test.utxo.json");
+    let _ = fs::remove_file("blockchain.json");
+}
+
+// 构造费率为`rate`（每字节）的单输入单输出交易
+fn fee_rate_tx(id: usize, rate: u64) -> Transaction {
+    let input = TxInput { prev_tx: format!("{:064x}", id), prev_index: 0, script_sig: String::from("sig") };
+    let output = TxOutput { value: 10, script_pubkey: String::from("addr") };
+    let size = Transaction::new(vec![input.clone()], vec![output.clone()]).size() as u64;
+    Transaction::with_fee(vec![input], vec![output], rate * size)
+}
+
+#[test]
+fn test_estimate_fee_percentiles() {
+    let mut blockchain = Blockchain::new(1);
+    let tx = fee_rate_tx(0, 0);
+    
+    // 没有历史交易时使用最低费率
+    assert_eq!(blockchain.estimate_fee_rate(3), MIN_FEE_RATE);
+    assert_eq!(blockchain.estimate_fee(&tx, 3), MIN_FEE_RATE * tx.size() as u64);
+    
+    // 费率1..=10的交易，1个区块取最高费率，2个区块取中位数，10个区块取最低费率
+    blockchain.add_block((1..=10).map(|rate| fee_rate_tx(rate as usize, rate)).collect());
+    assert_eq!(blockchain.estimate_fee_rate(1), 10);
+    assert_eq!(blockchain.estimate_fee_rate(2), 5);
+    assert_eq!(blockchain.estimate_fee_rate(10), 1);
+    assert_eq!(blockchain.estimate_fee(&tx, 2), 5 * tx.size() as u64);
+    
+    // 估算结果缓存若干个区块
+    blockchain.add_block((11..=20).map(|id| fee_rate_tx(id, 100)).collect());
+    assert_eq!(blockchain.estimate_fee_rate(2), 5);
+    for _ in 2..FEE_ESTIMATE_CACHE_BLOCKS {
+        blockchain.add_block(vec![]);
+        assert_eq!(blockchain.estimate_fee_rate(2), 5);
+    }
+    blockchain.add_block(vec![]);
+    assert_eq!(blockchain.estimate_fee_rate(2), 10);
+}
+
+#[test]
+fn test_estimate_fee_uses_confirmation_delay() {
+    let mut blockchain = Blockchain::new(1);
+    let slow = fee_rate_tx(1, 50);
+    blockchain.record_seen(&slow);
+    for _ in 0..3 {
+        blockchain.add_block(vec![]);
+    }
+    blockchain.add_block(vec![slow, fee_rate_tx(2, 2)]);
+    
+    // 高费率交易等待了4个区块才确认，不能说明1个区块内确认需要的费率
+    assert_eq!(blockchain.estimate_fee_rate(1), 2);
+    assert_eq!(blockchain.estimate_fee_rate(4), 2);
+    assert_eq!(blockchain.estimate_fee_rate(0), 2);
+}
+
+#[test]
+fn test_validate_chain_from_genesis() {
+    let mut miner = Wallet::new();
+    let recipient = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&miner.address)]);
+    let mut tx = miner.create_transaction(&recipient.address, 20, &blockchain.utxo_entries()).unwrap();
+    miner.sign_transaction(&mut tx).unwrap();
+    blockchain.add_block(vec![create_coinbase(&miner.address), tx]);
+    assert!(blockchain.validate_chain());
+    
+    // 篡改已确认交易的金额后，整条链不再有效
+    let mut blocks = blockchain.as_slice().to_vec();
+    blocks[2].transactions[1].outputs[0].value = 45;
+    let mut tampered = blockchain.fresh_copy();
+    tampered.replace_chain(blocks);
+    assert!(!tampered.validate_chain());
+    
+    // 区块顺序错乱时prev_hash链接断开
+    let mut blocks = blockchain.as_slice().to_vec();
+    blocks.swap(1, 2);
+    let mut reordered = blockchain.fresh_copy();
+    reordered.replace_chain(blocks);
+    assert!(!reordered.validate_chain());
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_coinbase_overclaim_rejected() {
+    let mut miner = Wallet::new();
+    let recipient = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&miner.address)]);
+    let mut tx = miner.create_transaction_with_fee(&recipient.address, 20, 3, &blockchain.utxo_entries()).unwrap();
+    miner.sign_transaction(&mut tx).unwrap();
+    
+    // 每笔交易单独都有效，但Coinbase比奖励加手续费多领取了1
+    let mut coinbase = create_coinbase(&miner.address);
+    coinbase.outputs[0].value = BLOCK_REWARD + 3 + 1;
+    let block = mine_on_tip(&blockchain, vec![coinbase, tx.clone()]);
+    assert_eq!(blockchain.check_block(&block), Err(BlockError::ExcessiveCoinbase {
+        claimed: BLOCK_REWARD + 4,
+        allowed: BLOCK_REWARD + 3,
+    }));
+    assert!(!blockchain.validate_block(&block));
+    
+    // 恰好领取奖励加手续费的区块被接受
+    let mut coinbase = create_coinbase(&miner.address);
+    coinbase.outputs[0].value = BLOCK_REWARD + 3;
+    let block = mine_on_tip(&blockchain, vec![coinbase, tx]);
+    assert_eq!(blockchain.check_block(&block), Ok(()));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_timestamp_bounds() {
+    let mut blockchain = Blockchain::new(1);
+    let genesis_time = blockchain.tip().header.timestamp;
+    assert_eq!(blockchain.median_past_time(), genesis_time);
+    for _ in 0..3 {
+        blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    
+    // 中位数取最近区块时间戳排序后的中间值，创世区块的固定时间戳最早
+    let mut timestamps: Vec<i64> = blockchain.iter().map(|block| block.header.timestamp).collect();
+    timestamps.sort_unstable();
+    let median_past_time = blockchain.median_past_time();
+    assert_eq!(median_past_time, timestamps[2]);
+    assert!(blockchain.next_block_timestamp() > median_past_time);
+    
+    let mine_at = |timestamp: i64| {
+        let mut block = Block::with_timestamp(blockchain.tip().calculate_hash(), blockchain.difficulty(), timestamp);
+        block.transactions = vec![create_coinbase("矿工地址")];
+        block.mine();
+        block
+    };
+    
+    // 等于过去中位时间的区块太旧
+    assert_eq!(blockchain.check_block(&mine_at(median_past_time)), Err(BlockError::TimestampTooOld {
+        timestamp: median_past_time,
+        median_past_time,
+    }));
+    
+    // 超前本地时间两小时以上的区块被拒绝
+    let future = chrono::Utc::now().timestamp() + MAX_FUTURE_BLOCK_TIME + 60;
+    assert!(matches!(
+        blockchain.check_block(&mine_at(future)),
+        Err(BlockError::TimestampTooFarInFuture { timestamp, .. }) if timestamp == future
+    ));
+    
+    // 在允许范围内的时间戳可以通过
+    assert_eq!(blockchain.check_block(&mine_at(blockchain.next_block_timestamp())), Ok(()));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_embedded_data_recoverable_from_chain() {
+    let miner = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&miner.address)]);
+    let data = b"sha256:0123456789abcdef";
+    
+    let mut tx = miner.embed_data(data, 2, &blockchain.utxo_entries()).unwrap();
+    miner.sign_transaction(&mut tx).unwrap();
+    assert_eq!(tx.outputs[0].value, 0);
+    let mut coinbase = create_coinbase(&miner.address);
+    coinbase.outputs[0].value = BLOCK_REWARD + 2;
+    let block = mine_on_tip(&blockchain, vec![coinbase, tx]);
+    assert_eq!(blockchain.check_block(&block), Ok(()));
+    blockchain.add_received_block(block);
+    
+    // 数据可以从链上的交易中取回，数据输出不会进入UTXO集合
+    let recovered: Vec<Vec<u8>> = blockchain.iter()
+        .flat_map(|block| &block.transactions)
+        .flat_map(|tx| &tx.outputs)
+        .filter_map(|output| output.embedded_data())
+        .collect();
+    assert_eq!(recovered, vec![data.to_vec()]);
+    assert!(blockchain.utxo_entries().iter().all(|(_, entry)| !entry.script_pubkey.starts_with(OP_RETURN_PREFIX)));
+    assert_eq!(blockchain.get_balance(&miner.address), 2 * BLOCK_REWARD);
+    
+    // 回滚后UTXO集合恢复，过长的数据无法创建交易
+    blockchain.rollback(1).unwrap();
+    assert_eq!(blockchain.get_balance(&miner.address), BLOCK_REWARD);
+    assert!(matches!(
+        miner.embed_data(&[0; 81], 2, &blockchain.utxo_entries()),
+        Err(TransactionError::DataTooLarge { size: 81, max: 80 })
+    ));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_verify_integrity_reports_all_problems() {
+    let mut miner = Wallet::new();
+    let recipient = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&miner.address)]);
+    let mut tx = miner.create_transaction_with_fee(&recipient.address, 20, 2, &blockchain.utxo_entries()).unwrap();
+    miner.sign_transaction(&mut tx).unwrap();
+    
+    // 写入默克尔根的区块同样通过检查
+    let mut block = mine_on_tip(&blockchain, vec![create_coinbase(&miner.address), tx]);
+    block.header.merkle_root = block.compute_merkle_root();
+    block.mine();
+    blockchain.add_received_block(block);
+    blockchain.add_block(vec![create_coinbase(&miner.address)]);
+    assert_eq!(blockchain.verify_integrity(), Ok(()));
+    
+    // 手动修改已确认交易的金额：交易不再平衡，默克尔根不匹配，下一个区块的链接断开
+    let mut blocks = blockchain.as_slice().to_vec();
+    blocks[2].transactions[1].outputs[0].value += 5;
+    let mut corrupted = blockchain.fresh_copy();
+    corrupted.replace_chain(blocks);
+    let errors = corrupted.verify_integrity().unwrap_err();
+    
+    assert!(errors.iter().any(|e| matches!(e, IntegrityError::Unbalanced { height: 2, inputs: 50, outputs: 53, fee: 2, .. })));
+    assert!(errors.iter().any(|e| matches!(e, IntegrityError::MerkleRootMismatch { height: 2, .. })));
+    assert!(errors.iter().any(|e| matches!(e, IntegrityError::BrokenLink { height: 3, .. })));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_offline_signed_transaction_with_spent_input_is_rejected() {
+    let mut offline = Wallet::new();
+    offline.fresh_change_address = false;
+    let mut online = Wallet::watch_only(offline.public_key.unwrap());
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&offline.address)]);
+    let recipient = Wallet::new();
+    
+    // 导出未签名交易后，同一输出先被另一笔交易花费
+    let utxos = blockchain.utxo_entries();
+    let tx = online.create_transaction(&recipient.address, 20, &utxos).unwrap();
+    let mut unsigned = UnsignedTransaction::from_json(&UnsignedTransaction::new(tx, &utxos).unwrap().to_json()).unwrap();
+    let signed = offline.sign_unsigned(&mut unsigned).unwrap();
+    assert!(unsigned.is_complete());
+    assert!(blockchain.missing_inputs(&signed).is_empty());
+    assert!(blockchain.validate_transaction(&signed));
+    
+    let mut conflicting = offline.create_transaction(&recipient.address, 30, &utxos).unwrap();
+    offline.sign_transaction(&mut conflicting).unwrap();
+    blockchain.add_block(vec![create_coinbase("矿工地址"), conflicting]);
+    
+    // 导入时签名仍然完整，但引用的输出已被花费
+    assert!(unsigned.is_complete());
+    let missing = blockchain.missing_inputs(&signed);
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].tx_id, signed.inputs[0].prev_tx);
+    assert!(!blockchain.validate_transaction(&signed));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_load_verifies_and_truncates_corrupt_chain() {
+    let mut blockchain = Blockchain::new(1);
+    for _ in 0..3 {
+        blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    let filename = format!("load_verified_{}.json", blockchain.tip().calculate_hash());
+    let undo_filename = filename.replace(".json", ".undo.json");
+    
+    // 完好的文件完整加载
+    blockchain.save_to_file(&filename).unwrap();
+    let (loaded, outcome) = Blockchain::load_verified(&filename).unwrap();
+    assert_eq!(outcome, LoadOutcome::Verified);
+    assert_eq!(loaded.len(), 4);
+    assert_eq!(loaded.get_balance("矿工地址"), 150);
+    
+    // 篡改中间区块的prev_hash后，截断到该区块之前
+    let mut blocks = blockchain.as_slice().to_vec();
+    blocks[2].header.prev_hash = "f".repeat(64);
+    fs::write(&filename, serde_json::to_string(&blocks).unwrap()).unwrap();
+    let (loaded, outcome) = Blockchain::load_verified(&filename).unwrap();
+    match outcome {
+        LoadOutcome::Truncated { kept, discarded, errors } => {
+            assert_eq!((kept, discarded), (2, 2));
+            assert!(errors.iter().any(|error| matches!(error, IntegrityError::BrokenLink { height: 2, .. })));
+        }
+        other => panic!("意外的加载结果: {:?}", other),
+    }
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.tip().calculate_hash(), blockchain.as_slice()[1].calculate_hash());
+    assert_eq!(loaded.get_balance("矿工地址"), 50);
+    assert!(loaded.validate_chain());
+    assert_eq!(Blockchain::load_from_file(&filename).unwrap().len(), 2);
+    
+    // 创世区块无效时拒绝加载
+    let mut blocks = blockchain.as_slice().to_vec();
+    blocks[0].header.prev_hash = String::from("1");
+    fs::write(&filename, serde_json::to_string(&blocks).unwrap()).unwrap();
+    assert!(Blockchain::load_verified(&filename).is_none());
+    
+    let _ = fs::remove_file(&filename);
+    let _ = fs::remove_file(&undo_filename);
+    let _ = fs::remove_file(filename.replace(".json", ".utxo.json"));
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_coinbase_maturity() {
+    const MATURITY: usize = 3;
+    let wallet = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.set_coinbase_maturity(MATURITY);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    let funding_tx = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    let mut tx = create_transfer(&funding_tx, "接收地址");
+    sign_as(&wallet, &mut tx);
+    
+    // 深度为N-1时奖励计入余额，但不能花费
+    for _ in 1..MATURITY - 1 {
+        blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    assert_eq!(blockchain.get_balance(&wallet.address), 50);
+    assert!(blockchain.get_utxos_for_address(&wallet.address).is_empty());
+    assert!(!blockchain.validate_transaction(&tx));
+    assert!(!blockchain.validate_block(&mine_on_tip(&blockchain, vec![create_coinbase("矿工地址"), tx.clone()])));
+    
+    // 深度为N时可以花费
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    assert_eq!(blockchain.get_utxos_for_address(&wallet.address).len(), 1);
+    assert!(blockchain.validate_transaction(&tx));
+    blockchain.add_block(vec![create_coinbase("矿工地址"), tx]);
+    assert_eq!(blockchain.get_balance("接收地址"), 10);
+    assert!(blockchain.validate_chain());
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_incremental_sync_sends_only_delta() {
+    let mut ahead = Blockchain::new(1);
+    for _ in 0..25 {
+        ahead.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    let mut behind = ahead.fresh_copy();
+    behind.replace_chain(ahead.as_slice()[..6].to_vec());
+    
+    // 落后20个区块的节点只收到缺少的20个区块
+    let known_height = behind.height();
+    assert_eq!(known_height, 5);
+    assert_eq!(ahead.get(known_height).unwrap().calculate_hash(), behind.tip().calculate_hash());
+    let delta = ahead.get_blocks_since(known_height).to_vec();
+    assert_eq!(delta.len(), 20);
+    assert_eq!(delta[0].header.prev_hash, behind.tip().calculate_hash());
+    assert!(ahead.get_blocks_since(ahead.height()).is_empty());
+    assert!(ahead.get_blocks_since(100).is_empty());
+    
+    assert_eq!(behind.append_blocks_since(known_height + 1, delta.clone()), Ok(20));
+    assert_eq!(behind.tip().calculate_hash(), ahead.tip().calculate_hash());
+    assert_eq!(behind.get_balance("矿工地址"), ahead.get_balance("矿工地址"));
+    
+    // 其他节点的重复响应被跳过，不衔接或分叉的区块被拒绝
+    assert_eq!(behind.append_blocks_since(known_height + 1, delta.clone()), Ok(0));
+    assert_eq!(
+        behind.append_blocks_since(30, delta.clone()),
+        Err(BlockchainError::NonContiguousBlocks { start_height: 30, len: 26 })
+    );
+    let mut forked = delta;
+    forked[0].header.nonce += 1;
+    assert_eq!(behind.append_blocks_since(known_height + 1, forked), Err(BlockchainError::ForkDetected { height: 6 }));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_locator_syncs_only_diverged_suffix() {
+    let mut shared = Blockchain::new(1);
+    for _ in 0..10 {
+        shared.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    
+    // 定位器依次为链顶、链顶-1、-2、-4、-8和创世区块
+    let locator = shared.block_locator();
+    let expected: Vec<String> = [10, 9, 8, 6, 2, 0].iter()
+        .map(|&height| shared.get(height).unwrap().calculate_hash())
+        .collect();
+    assert_eq!(locator, expected);
+    assert_eq!(Blockchain::new(1).block_locator(), vec![Blockchain::new(1).genesis().calculate_hash()]);
+    
+    // 两条链共享前10个区块，之后各自挖矿
+    let mut ahead = shared.fresh_copy();
+    ahead.replace_chain(shared.as_slice().to_vec());
+    let mut diverged = shared.fresh_copy();
+    diverged.replace_chain(shared.as_slice().to_vec());
+    for _ in 0..3 {
+        ahead.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    diverged.add_block(vec![create_coinbase("另一个矿工")]);
+    
+    // 响应方只发送共同区块之后的3个区块
+    let (start_height, suffix) = ahead.blocks_after_locator(&diverged.block_locator()).unwrap();
+    assert_eq!(ahead.locate_fork(&diverged.block_locator()), Some(10));
+    assert_eq!(start_height, 11);
+    assert_eq!(suffix.len(), 3);
+    
+    // 请求方切换到累计工作量更大的分叉
+    assert_eq!(diverged.connect_blocks_since(start_height, suffix.to_vec()), Ok(3));
+    assert_eq!(diverged.tip().calculate_hash(), ahead.tip().calculate_hash());
+    assert_eq!(diverged.get_balance("另一个矿工"), 0);
+    assert_eq!(diverged.get_balance("矿工地址"), ahead.get_balance("矿工地址"));
+    
+    // 工作量不更大的分叉被拒绝，本地链不变
+    let mut longer = ahead.fresh_copy();
+    longer.replace_chain(ahead.as_slice().to_vec());
+    longer.add_block(vec![create_coinbase("矿工地址")]);
+    let mut rival = shared.fresh_copy();
+    rival.replace_chain(shared.as_slice().to_vec());
+    rival.add_block(vec![create_coinbase("另一个矿工")]);
+    let (start_height, suffix) = rival.blocks_after_locator(&longer.block_locator()).unwrap();
+    assert_eq!(
+        longer.connect_blocks_since(start_height, suffix.to_vec()),
+        Err(BlockchainError::InsufficientForkWork { height: 11 })
+    );
+    assert_eq!(longer.height(), 14);
+    
+    // 已是最新或没有共同区块
+    assert!(ahead.blocks_after_locator(&ahead.block_locator()).unwrap().1.is_empty());
+    assert!(ahead.blocks_after_locator(&[String::from("unknown")]).is_none());
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_missing_parent_detected() {
+    let mut ahead = Blockchain::new(1);
+    for _ in 0..3 {
+        ahead.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    let mut behind = ahead.fresh_copy();
+    behind.replace_chain(ahead.as_slice()[..2].to_vec());
+    
+    // 错过高度2的区块后，高度3的区块只缺少父区块
+    let missed = ahead.get(2).unwrap().clone();
+    let latest = ahead.get(3).unwrap().clone();
+    assert!(!behind.validate_block(&latest));
+    assert_eq!(behind.missing_parent(&latest), Some(missed.calculate_hash().as_str()));
+    assert!(behind.get_block_by_hash(&missed.calculate_hash()).is_none());
+    assert_eq!(ahead.get_block_by_hash(&missed.calculate_hash()).map(Block::calculate_hash), Some(missed.calculate_hash()));
+    
+    // 补齐父区块后可以接上最新区块；父区块已知的区块（包括分叉）不算缺少父区块
+    assert_eq!(behind.missing_parent(&missed), None);
+    behind.add_received_block(missed);
+    assert_eq!(behind.missing_parent(&latest), None);
+    assert!(behind.validate_block(&latest));
+    assert_eq!(behind.missing_parent(behind.genesis()), None);
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_chain_work_accumulates() {
+    let mut blockchain = Blockchain::new(2);
+    for _ in 0..4 {
+        blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    assert!(blockchain.iter().all(|block| block.header.difficulty == 2));
+    
+    // 难度都为2时，高度H的累计工作量为 2^2 * (H + 1)
+    let work = blockchain.compute_chain_work();
+    assert_eq!(work, (0..5).map(|height| (height, 4 * (height as u128 + 1))).collect::<Vec<_>>());
+    assert_eq!(blockchain.work_at_height(4), 4 * 5);
+    assert_eq!(blockchain.work_at_height(5), 0);
+    
+    // 缓存在区块变化后失效
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    assert_eq!(blockchain.work_at_height(5), 4 * 6);
+    assert_eq!(blockchain.compute_chain_work().len(), 6);
+    blockchain.rollback(2).unwrap();
+    assert_eq!(blockchain.compute_chain_work().last(), Some(&(3, 4 * 4)));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_export_import_json_round_trip() {
+    let mut blockchain = Blockchain::new(2);
+    for address in ["矿工地址", "另一个矿工"] {
+        blockchain.add_block(vec![create_coinbase(address)]);
+    }
+    
+    let imported = Blockchain::import_json(&blockchain.export_json()).unwrap();
+    let hashes = |chain: &Blockchain| chain.iter().map(Block::calculate_hash).collect::<Vec<_>>();
+    assert_eq!(hashes(&imported), hashes(&blockchain));
+    assert_eq!(imported.utxo_set, blockchain.utxo_set);
+    assert_eq!(imported.export_json(), blockchain.export_json());
+    
+    // 单个区块的JSON与整条链中对应的元素相同
+    let block: Block = serde_json::from_str(&blockchain.export_block_json(1).unwrap()).unwrap();
+    assert_eq!(block.calculate_hash(), blockchain.iter().nth(1).unwrap().calculate_hash());
+    assert!(blockchain.export_block_json(3).is_none());
+    
+    // 导入时不截断，断开的链整体被拒绝
+    let mut blocks: serde_json::Value = serde_json::from_str(&blockchain.export_json()).unwrap();
+    blocks["blocks"][2]["header"]["prev_hash"] = serde_json::Value::from("不存在的区块");
+    match Blockchain::import_json(&blocks.to_string()) {
+        Err(ImportError::Integrity(errors)) => assert!(errors.iter().any(|e| matches!(e, IntegrityError::BrokenLink { height: 2, .. }))),
+        other => panic!("断开的链应被拒绝: {:?}", other.map(|chain| chain.len())),
+    }
+    assert!(matches!(Blockchain::import_json("[]"), Err(ImportError::Empty)));
+    assert!(matches!(Blockchain::import_json("不是JSON"), Err(ImportError::InvalidJson(_))));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_list_unspent_confirmations_grow_with_chain() {
+    let wallet = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    let mut big_reward = create_coinbase(&wallet.address);
+    big_reward.outputs[0].value = 80;
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    blockchain.add_block(vec![big_reward]);
+    
+    // 按金额从大到小排列，链顶区块中的输出有1个确认
+    let unspent = wallet.list_unspent(&blockchain.utxo_set, &blockchain);
+    let summary: Vec<(u64, usize)> = unspent.iter().map(|output| (output.value, output.confirmations)).collect();
+    assert_eq!(summary, vec![(80, 1), (50, 2)]);
+    
+    // 其他地址的输出不会被列出，新区块让已有输出的确认数增加
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    let unspent = wallet.list_unspent(&blockchain.utxo_set, &blockchain);
+    let summary: Vec<(u64, usize)> = unspent.iter().map(|output| (output.value, output.confirmations)).collect();
+    assert_eq!(summary, vec![(80, 2), (50, 3)]);
+    assert_eq!(unspent[0].tx_id, blockchain.calculate_tx_hash(&blockchain.get(2).unwrap().transactions[0]));
+    assert_eq!(unspent[0].output_index, 0);
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_balance_breakdown_with_pending_payment() {
+    let mut wallet = Wallet::new();
+    let recipient = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    // 修改签名脚本，避免两笔Coinbase交易的ID相同
+    let mut second_reward = create_coinbase(&wallet.address);
+    second_reward.inputs[0].script_sig = String::from("第二笔奖励");
+    blockchain.add_block(vec![second_reward]);
+    assert_eq!(wallet.balance(&blockchain), 100);
+    
+    // 未确认的付款锁定一个输出，找零计入待收入
+    let mut mempool = Mempool::new();
+    let mut payment = wallet.create_transaction(&recipient.address, 30, &blockchain.utxo_entries()).unwrap();
+    wallet.sign_transaction(&mut payment).unwrap();
+    mempool.add(payment.clone());
+    
+    let breakdown = wallet.balance_detailed(&blockchain, &mempool);
+    assert_eq!(breakdown, BalanceBreakdown { confirmed: 100, pending_in: 20, pending_out: 50, spendable: 50 });
+    assert_eq!(breakdown.spendable, breakdown.confirmed - breakdown.pending_out);
+    assert_eq!(recipient.balance_detailed(&blockchain, &mempool).pending_in, 30);
+    
+    // 区块确认后已确认余额才发生变化
+    blockchain.add_block(vec![create_coinbase("矿工地址"), payment]);
+    mempool.remove_confirmed(blockchain.tip());
+    let breakdown = wallet.balance_detailed(&blockchain, &mempool);
+    assert_eq!(breakdown, BalanceBreakdown { confirmed: 70, pending_in: 0, pending_out: 0, spendable: 70 });
+    assert_eq!(recipient.balance(&blockchain), 30);
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_utxo_snapshot_round_trip_and_stale_snapshot() {
+    let wallet = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    let path = std::env::temp_dir().join(format!("utxo_snapshot_{}.json", wallet.address));
+    blockchain.save_utxo_snapshot(&path).unwrap();
+    
+    // 快照之后追加的区块（包括花费快照中输出的交易）按修改记录应用
+    let funding_tx = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    let mut transfer = create_transfer(&funding_tx, "接收地址");
+    sign_as(&wallet, &mut transfer);
+    blockchain.add_block(vec![create_coinbase("矿工地址"), transfer]);
+    
+    let mut restored = blockchain.clone();
+    restored.utxo_set.clear();
+    assert!(restored.load_utxo_snapshot(&path));
+    assert_eq!(restored.utxo_set, blockchain.utxo_set);
+    assert_eq!(restored.get_balance("接收地址"), 10);
+    assert_eq!(restored.get_balance(&wallet.address), 0);
+    
+    // 其他链的快照链顶不匹配，被忽略且UTXO集合保持不变
+    let mut other = Blockchain::new(1);
+    other.add_block(vec![create_coinbase("其他矿工")]);
+    other.save_utxo_snapshot(&path).unwrap();
+    let before = restored.utxo_set.clone();
+    assert!(!restored.load_utxo_snapshot(&path));
+    assert_eq!(restored.utxo_set, before);
+    
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_load_verified_replays_only_blocks_after_snapshot() {
+    let mut blockchain = Blockchain::new(1);
+    for _ in 0..2 {
+        blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    }
+    let filename = format!("load_snapshot_{}.json", blockchain.tip().calculate_hash());
+    let undo_filename = filename.replace(".json", ".undo.json");
+    let snapshot_filename = filename.replace(".json", ".utxo.json");
+    blockchain.save_to_file(&filename).unwrap();
+    
+    // 快照之后追加一个奖励超额的区块，它能通过完整性检查，但重放验证时被发现
+    let mut greedy = create_coinbase("贪婪矿工");
+    greedy.outputs[0].value = 1000;
+    blockchain.add_block(vec![greedy]);
+    fs::write(&filename, blockchain.export_json()).unwrap();
+    let (loaded, outcome) = Blockchain::load_verified(&filename).unwrap();
+    assert_eq!(outcome, LoadOutcome::Invalid { height: 3 });
+    assert_eq!(loaded.get_balance("贪婪矿工"), 1000);
+    
+    // 没有快照时从创世区块重放，同样发现该区块
+    fs::remove_file(&snapshot_filename).unwrap();
+    let (_, outcome) = Blockchain::load_verified(&filename).unwrap();
+    assert_eq!(outcome, LoadOutcome::Invalid { height: 3 });
+    
+    // 快照与链顶一致时不再重放，直接使用快照
+    blockchain.rollback(1).unwrap();
+    blockchain.save_to_file(&filename).unwrap();
+    let (loaded, outcome) = Blockchain::load_verified(&filename).unwrap();
+    assert_eq!(outcome, LoadOutcome::Verified);
+    assert_eq!(loaded.utxo_set, blockchain.utxo_set);
+    
+    let _ = fs::remove_file(&filename);
+    let _ = fs::remove_file(&undo_filename);
+    let _ = fs::remove_file(&snapshot_filename);
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_replace_chain_saves_snapshot_of_new_utxo_set() {
+    let mut source = Blockchain::new(1);
+    for _ in 0..3 {
+        source.add_block(vec![create_coinbase("新链矿工")]);
+    }
+    let mut blockchain = source.fresh_copy();
+    blockchain.add_block(vec![create_coinbase("旧链矿工")]);
+    
+    // 替换后无需再重建UTXO集合，保存的快照与新链一致，重启后余额正确
+    blockchain.replace_chain(source.as_slice().to_vec());
+    assert_eq!(blockchain.utxo_set, source.utxo_set);
+    assert_eq!(blockchain.get_balance("新链矿工"), 150);
+    assert_eq!(blockchain.get_balance("旧链矿工"), 0);
+    
+    let filename = format!("replace_chain_{}.json", blockchain.tip().calculate_hash());
+    blockchain.save_to_file(&filename).unwrap();
+    let (loaded, _) = Blockchain::load_verified(&filename).unwrap();
+    assert_eq!(loaded.get_balance("新链矿工"), 150);
+    assert_eq!(loaded.get_balance("旧链矿工"), 0);
+    
+    let _ = fs::remove_file(&filename);
+    let _ = fs::remove_file(filename.replace(".json", ".undo.json"));
+    let _ = fs::remove_file(filename.replace(".json", ".utxo.json"));
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_add_block_batch_matches_single_import() {
+    let wallet = Wallet::new();
+    let mut source = Blockchain::new(1);
+    source.add_block(vec![create_coinbase(&wallet.address)]);
+    let funding_tx = source.calculate_tx_hash(&source.tip().transactions[0]);
+    let mut transfer = create_transfer(&funding_tx, "接收地址");
+    sign_as(&wallet, &mut transfer);
+    source.add_block(vec![create_coinbase("矿工地址"), transfer]);
+    for height in 3..=5 {
+        source.add_block(vec![create_coinbase(&format!("矿工{}", height))]);
+    }
+    let blocks = source.as_slice()[1..].to_vec();
+    
+    // 批量导入的UTXO集和修改记录与逐个导入的结果相同
+    let mut batch = source.fresh_copy();
+    assert_eq!(batch.add_block_batch(blocks.clone()), Ok(5));
+    assert_eq!(batch.tip().calculate_hash(), source.tip().calculate_hash());
+    assert_eq!(batch.utxo_entries(), source.utxo_entries());
+    assert_eq!(batch.get_balance("接收地址"), 10);
+    for height in 1..=5 {
+        assert_eq!(batch.block_undo_at(height), source.block_undo_at(height));
+    }
+    
+    // 逐个添加时增量更新的UTXO集和修改记录与从全部区块重新计算的结果相同
+    let mut rebuilt = source.fresh_copy();
+    rebuilt.replace_chain(source.as_slice().to_vec());
+    assert_eq!(rebuilt.utxo_entries(), source.utxo_entries());
+    for height in 1..=5 {
+        assert_eq!(rebuilt.block_undo_at(height), source.block_undo_at(height));
+    }
+    
+    // 遇到无效区块时停止，之前的区块保留；第一个区块就无效时返回错误
+    let mut tampered = blocks.clone();
+    // 难度较低时相邻的nonce也可能满足要求，一直递增到工作量证明失效为止
+    tampered[2].header.nonce += 1;
+    while tampered[2].is_valid() {
+        tampered[2].header.nonce += 1;
+    }
+    let mut partial = source.fresh_copy();
+    assert_eq!(partial.add_block_batch(tampered.clone()), Ok(2));
+    assert_eq!(partial.height(), 2);
+    assert_eq!(partial.add_block_batch(tampered[2..].to_vec()), Err(BlockchainError::InvalidBlock { height: 3 }));
+    assert!(partial.validate_chain());
+    
+    let _ = fs::remove_file("blockchain.json");
+    let _ = fs::remove_file("blockchain.utxo.json");
+}
+
+#[test]
+fn test_extend_validated_checks_without_side_effects() {
+    let mut source = Blockchain::new(1);
+    for height in 1..=4 {
+        source.add_block(vec![create_coinbase(&format!("矿工{}", height))]);
+    }
+    let mut blocks = source.as_slice()[1..].to_vec();
+    blocks[2].header.nonce += 1;
+    while blocks[2].is_valid() {
+        blocks[2].header.nonce += 1;
+    }
+    
+    // 验证同步收到的链时只追加到临时链，遇到无效区块停止，不触发区块接入回调
+    let notified = std::sync::Arc::new(std::sync::Mutex::new(0));
+    let counter = std::sync::Arc::clone(&notified);
+    let mut temp = source.fresh_copy();
+    temp.on_block(move |_, _| *counter.lock().unwrap() += 1);
+    assert_eq!(temp.extend_validated(blocks), 2);
+    assert_eq!(temp.height(), 2);
+    assert_eq!(temp.get_balance("矿工2"), 50);
+    assert!(temp.validate_chain());
+    assert_eq!(*notified.lock().unwrap(), 0);
+    
+    let _ = fs::remove_file("blockchain.json");
+    let _ = fs::remove_file("blockchain.utxo.json");
+}
+
+#[test]
+fn test_metrics_count_mined_and_rejected_blocks() {
+    let blockchain = Blockchain::new(1);
+    assert_eq!(blockchain.metrics().snapshot(), MetricsSnapshot::default());
+    
+    let block = blockchain.mine_block(vec![create_coinbase("矿工地址")]);
+    assert!(blockchain.validate_block(&block));
+    let bad = blockchain.mine_block(vec![]);
+    assert!(!blockchain.validate_block(&bad));
+    assert!(!blockchain.validate_transaction(&create_transfer("不存在的交易", "接收地址")));
+    
+    let snapshot = blockchain.metrics().snapshot();
+    assert_eq!(snapshot.blocks_mined, 2);
+    assert_eq!(snapshot.blocks_rejected, 1);
+    assert_eq!(snapshot.transactions_accepted, 0);
+    assert_eq!(snapshot.transactions_rejected, 1);
+    
+    // 验证同步链的临时副本与本地链共享计数器
+    blockchain.fresh_copy().validate_block(&bad);
+    assert_eq!(blockchain.metrics().snapshot().blocks_rejected, 2);
+}
+
+#[test]
+fn test_regtest_mines_without_nonce_search() {
+    let blockchain = Blockchain::regtest();
+    assert_eq!(blockchain.difficulty(), 0);
+    assert_ne!(blockchain.genesis().calculate_hash(), Blockchain::new(0).genesis().calculate_hash());
+    
+    // 难度为0时第一个nonce即有效，区块可以被正常接受
+    let block = blockchain.mine_block(vec![create_coinbase("矿工地址")]);
+    assert_eq!(block.header.nonce, 0);
+    assert!(block.is_valid());
+    assert_eq!(blockchain.check_block(&block), Ok(()));
+}
+
+#[test]
+fn test_simulate_transaction_reports_all_errors() {
+    let wallet = Wallet::new();
+    let thief = Wallet::new();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase(&wallet.address)]);
+    let funding_tx = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    let spend = |inputs: &[(&str, u32)], value: u64, fee: u64| {
+        let inputs = inputs.iter()
+            .map(|(prev_tx, prev_index)| TxInput {
+                prev_tx: prev_tx.to_string(),
+                prev_index: *prev_index,
+                script_sig: String::new(),
+            })
+            .collect();
+        Transaction::with_fee(inputs, vec![TxOutput { value, script_pubkey: String::from("接收地址") }], fee)
+    };
+    
+    // 有效交易会被接受，模拟不修改UTXO集
+    let mut valid = spend(&[(&funding_tx, 0)], 40, 10);
+    sign_as(&wallet, &mut valid);
+    let result = blockchain.simulate_transaction(&valid);
+    assert!(result.would_succeed);
+    assert_eq!((result.consumed, result.fee), (50, 10));
+    assert!(result.errors.is_empty());
+    assert_eq!(blockchain.get_balance(&wallet.address), 50);
+    assert_eq!(blockchain.get_balance("接收地址"), 0);
+    
+    // 收集全部问题，而不是在第一个问题处停止
+    let mut invalid = spend(&[(&funding_tx, 0), (&funding_tx, 0), ("不存在的交易", 0)], 200, 5);
+    sign_as(&thief, &mut invalid);
+    let result = blockchain.simulate_transaction(&invalid);
+    assert!(!result.would_succeed);
+    assert_eq!(result.consumed, 50);
+    assert_eq!(result.errors, vec![
+        SimulationError::InvalidSignature { index: 0 },
+        SimulationError::DoubleSpend { index: 1, prev_tx: funding_tx.clone(), prev_index: 0 },
+        SimulationError::UtxoNotFound { index: 2, prev_tx: String::from("不存在的交易"), prev_index: 0 },
+        SimulationError::InsufficientFunds { inputs: 50, outputs: 200, fee: 5 },
+    ]);
+    
+    let empty = Transaction::new(vec![], vec![]);
+    assert_eq!(blockchain.simulate_transaction(&empty).errors, vec![SimulationError::EmptyTransaction]);
+    
+    // 未成熟的Coinbase输出暂时不能花费
+    blockchain.set_coinbase_maturity(3);
+    let result = blockchain.simulate_transaction(&valid);
+    assert_eq!(result.errors, vec![SimulationError::LockTimeNotReached { index: 0, prev_tx: funding_tx }]);
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_signatures_are_bound_to_network() {
+    let mut wallet = Wallet::new();
+    let mut network_a = Blockchain::new_with_genesis(GenesisConfig {
+        network_id: String::from("network-a"),
+        difficulty: 1,
+        ..GenesisConfig::default()
+    });
+    let mut network_b = Blockchain::new_with_genesis(GenesisConfig {
+        network_id: String::from("network-b"),
+        difficulty: 1,
+        ..GenesisConfig::default()
+    });
+    assert_eq!(network_a.network_id(), "network-a");
+    assert_eq!(Blockchain::new(1).network_id(), DEFAULT_NETWORK_ID);
+    
+    // 两个网络上有完全相同的未花费输出，交易内容在两个网络上都成立
+    network_a.add_block(vec![create_coinbase(&wallet.address)]);
+    network_b.add_block(vec![create_coinbase(&wallet.address)]);
+    let utxos = network_a.get_utxos_for_address(&wallet.address);
+    assert_eq!(utxos, network_b.get_utxos_for_address(&wallet.address));
+    let recipient = Wallet::new();
+    let unsigned = wallet.create_transaction(&recipient.address, 20, &utxos).unwrap();
+    
+    // 在网络A上签名的交易不能在网络B上重放
+    wallet.network_id = String::from("network-a");
+    let mut tx = unsigned.clone();
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert_eq!(network_a.check_transaction(&tx), Ok(()));
+    assert_eq!(network_b.check_transaction(&tx), Err(TxError::InvalidSignature { index: 0 }));
+    assert!(!network_b.simulate_transaction(&tx).would_succeed);
+    
+    wallet.network_id = String::from("network-b");
+    let mut tx = unsigned;
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert_eq!(network_b.check_transaction(&tx), Ok(()));
+    assert_eq!(network_a.check_transaction(&tx), Err(TxError::InvalidSignature { index: 0 }));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+// 测试用的简单哈希算法：FNV-1a，结果重复填充到32字节
+struct FnvHasher;
+
+impl Hasher for FnvHasher {
+    fn name(&self) -> &str {
+        "fnv"
+    }
+
+    fn digest(&self, data: &[u8]) -> [u8; 32] {
+        let mut state: u64 = 0xcbf29ce484222325;
+        for byte in data {
+            state ^= u64::from(*byte);
+            state = state.wrapping_mul(0x100000001b3);
+        }
+        let mut out = [0u8; 32];
+        for (i, chunk) in out.chunks_mut(8).enumerate() {
+            chunk.copy_from_slice(&state.wrapping_add(i as u64).to_be_bytes());
+        }
+        out
+    }
+}
+
+#[test]
+fn test_custom_hasher_wires_through_mining_and_validation() {
+    let config = GenesisConfig { difficulty: 1, ..GenesisConfig::default() };
+    let mut blockchain = Blockchain::new_with_hasher(config.clone(), std::sync::Arc::new(FnvHasher));
+    let sha_chain = Blockchain::new_with_genesis(config);
+    assert_eq!(blockchain.hash_algorithm(), "fnv");
+    assert_eq!(sha_chain.hash_algorithm(), "sha256");
+    
+    // 创世区块哈希由链的哈希算法决定，算法不同的节点不会互相同步
+    assert_ne!(blockchain.block_hash(blockchain.genesis()), sha_chain.block_hash(sha_chain.genesis()));
+    
+    // 挖矿和验证都使用自定义算法
+    let block = blockchain.mine_block(vec![create_coinbase("矿工地址")]);
+    assert!(blockchain.block_hash(&block).starts_with('0'));
+    assert_eq!(block.header.prev_hash, blockchain.block_hash(blockchain.genesis()));
+    assert!(blockchain.validate_block(&block));
+    assert!(!sha_chain.validate_block(&block));
+    
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    assert_eq!(blockchain.len(), 2);
+    assert!(blockchain.validate_chain());
+    let coinbase = &blockchain.tip().transactions[0];
+    let txid = blockchain.calculate_tx_hash(coinbase);
+    assert_ne!(txid, sha_chain.calculate_tx_hash(coinbase));
+    assert!(blockchain.utxo_set.contains_key(&txid));
+    
+    let _ = std::fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_saved_chain_records_hash_algorithm() {
+    let config = GenesisConfig { difficulty: 1, ..GenesisConfig::default() };
+    let mut blockchain = Blockchain::new_with_hasher(config, std::sync::Arc::new(FnvHasher));
+    blockchain.add_block(vec![create_coinbase("矿工地址")]);
+    let filename = format!("hash_algorithm_{}.json", blockchain.block_hash(blockchain.tip()));
+    blockchain.save_to_file(&filename).unwrap();
+    
+    // 未指定算法时只接受内置算法，不会按SHA256重新计算哈希
+    assert!(Blockchain::load_verified(&filename).is_none());
+    assert!(Blockchain::load_verified_with_hasher(&filename, std::sync::Arc::new(blockchain_demo::block::Sha256Hasher)).is_none());
+    let (loaded, outcome) = Blockchain::load_verified_with_hasher(&filename, std::sync::Arc::new(FnvHasher)).unwrap();
+    assert_eq!(outcome, LoadOutcome::Verified);
+    assert_eq!(loaded.hash_algorithm(), "fnv");
+    assert_eq!(loaded.block_hash(loaded.tip()), blockchain.block_hash(blockchain.tip()));
+    
+    // 导入同样检查算法
+    let json = blockchain.export_json();
+    assert_eq!(Blockchain::import_json(&json).err(), Some(ImportError::UnknownHashAlgorithm(String::from("fnv"))));
+    assert_eq!(
+        Blockchain::import_json_with_hasher(&json, std::sync::Arc::new(blockchain_demo::block::Sha256Hasher)).err(),
+        Some(ImportError::HashAlgorithmMismatch { expected: String::from("sha256"), found: String::from("fnv") })
+    );
+    assert_eq!(Blockchain::import_json_with_hasher(&json, std::sync::Arc::new(FnvHasher)).unwrap().len(), 2);
+    
+    let sidecars = [filename.replace(".json", ".undo.json"), filename.replace(".json", ".utxo.json")];
+    for path in &sidecars {
+        let _ = fs::remove_file(path);
+    }
+    
+    // 旧版本只保存区块数组，按SHA256加载
+    let mut legacy = Blockchain::new(1);
+    legacy.add_block(vec![create_coinbase("矿工地址")]);
+    fs::write(&filename, serde_json::to_string(legacy.as_slice()).unwrap()).unwrap();
+    let (loaded, outcome) = Blockchain::load_verified(&filename).unwrap();
+    assert_eq!(outcome, LoadOutcome::Verified);
+    assert_eq!(loaded.hash_algorithm(), "sha256");
+    assert_eq!(loaded.utxo_set, legacy.utxo_set);
+    
+    let _ = fs::remove_file(&filename);
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_custom_hasher_computes_merkle_root_and_tx_hashes() {
+    let config = GenesisConfig { difficulty: 1, ..GenesisConfig::default() };
+    let mut blockchain = Blockchain::new_with_hasher(config, std::sync::Arc::new(FnvHasher));
+    blockchain.add_block(vec![create_coinbase("矿工1"), create_coinbase("矿工2")]);
+    
+    // 挖出的区块用链的哈希算法写入默克尔根，完整性检查按同一算法复核
+    let block = blockchain.tip();
+    assert_eq!(block.header.merkle_root, block.compute_merkle_root_with(&FnvHasher));
+    assert_ne!(block.header.merkle_root, block.compute_merkle_root());
+    assert_eq!(blockchain.verify_integrity(), Ok(()));
+    
+    // SHA256下带参数和不带参数的计算结果一致
+    let sha = blockchain_demo::block::Sha256Hasher;
+    assert_eq!(block.compute_merkle_root_with(&sha), block.compute_merkle_root());
+    let tx = &block.transactions[0];
+    assert_eq!(tx.calculate_hash_with(&sha), tx.calculate_hash());
+    assert_ne!(tx.calculate_hash_with(&FnvHasher), tx.calculate_hash());
+    
+    let _ = std::fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_callback_fires_once_per_accepted_block() {
+    use std::sync::{Arc, Mutex};
+
+    let mut source = Blockchain::new(1);
+    for miner in ["矿工1", "矿工2", "矿工3", "矿工4"] {
+        source.add_block(vec![create_coinbase(miner)]);
+    }
+
+    let mut blockchain = Blockchain::new(1);
+    let heights = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&heights);
+    blockchain.on_block(move |block, height| seen.lock().unwrap().push((height, block.calculate_hash())));
+    let accepted = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&accepted);
+    blockchain.on_transaction(move |_| *counter.lock().unwrap() += 1);
+
+    // 逐个收到、批量同步，以及与本地链重叠的重复同步
+    blockchain.add_received_block(source.get(1).unwrap().clone());
+    blockchain.add_received_block(source.get(2).unwrap().clone());
+    assert_eq!(blockchain.add_block_batch(source.as_slice()[3..].to_vec()), Ok(2));
+    assert_eq!(blockchain.append_blocks_since(1, source.as_slice()[1..].to_vec()), Ok(0));
+    blockchain.add_block(vec![create_coinbase("矿工5")]);
+
+    let expected: Vec<(usize, String)> = blockchain.iter().enumerate().skip(1)
+        .map(|(height, block)| (height, block.calculate_hash()))
+        .collect();
+    assert_eq!(*heights.lock().unwrap(), expected);
+
+    // 同一笔交易只在首次被接受时通知
+    let tx = create_transfer(&blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]), "接收地址");
+    blockchain.record_seen(&tx);
+    blockchain.record_seen(&tx);
+    assert_eq!(*accepted.lock().unwrap(), 1);
+
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_callback_fires_for_new_fork_after_utxo_rebuild() {
+    use std::sync::{Arc, Mutex};
+
+    let mut shared = Blockchain::new(1);
+    shared.add_block(vec![create_coinbase("共同矿工")]);
+    let mut rival = shared.clone();
+    for miner in ["分叉矿工1", "分叉矿工2"] {
+        rival.add_block(vec![create_coinbase(miner)]);
+    }
+
+    let mut blockchain = shared.clone();
+    blockchain.add_block(vec![create_coinbase("本地矿工")]);
+    let heights = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&heights);
+    blockchain.on_block(move |block, height| seen.lock().unwrap().push((height, block.calculate_hash())));
+
+    // 切换到工作量更大的分叉后，只对新分叉上的区块通知，此时余额已经按新分叉计算
+    let (start_height, blocks) = rival.blocks_after_locator(&blockchain.block_locator()).unwrap();
+    assert_eq!(blockchain.connect_blocks_since(start_height, blocks.to_vec()), Ok(2));
+    let expected: Vec<(usize, String)> = rival.iter().enumerate().skip(2)
+        .map(|(height, block)| (height, block.calculate_hash()))
+        .collect();
+    assert_eq!(*heights.lock().unwrap(), expected);
+    assert_eq!(blockchain.utxo_set, rival.utxo_set);
+    assert_eq!(blockchain.get_balance("本地矿工"), 0);
+
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_set_difficulty_rejects_out_of_range_values() {
+    let mut blockchain = Blockchain::new(1);
+    assert!(blockchain.set_difficulty(MAX_DIFFICULTY + 1).is_err());
+    assert_eq!(blockchain.difficulty(), 1);
+    
+    assert_eq!(blockchain.set_difficulty(2), Ok(()));
+    assert_eq!(blockchain.difficulty(), 2);
+    assert_eq!(blockchain.mine_block(vec![create_coinbase("矿工地址")]).header.difficulty, 2);
+    assert_eq!(blockchain.set_difficulty(MAX_DIFFICULTY), Ok(()));
+}
+
+#[test]
+fn test_rescan_restores_balances_after_utxo_set_desync() {
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![create_coinbase("地址A")]);
+    let funding = blockchain.calculate_tx_hash(&blockchain.tip().transactions[0]);
+    let transfer = create_transfer(&funding, "地址C");
+    let transfer_id = blockchain.calculate_tx_hash(&transfer);
+    blockchain.add_block(vec![create_coinbase("地址B"), transfer]);
+    let expected: Vec<u64> = ["地址A", "地址B", "地址C"].iter().map(|address| blockchain.get_balance(address)).collect();
+    assert_eq!(expected, vec![0, 50, 10]);
+    assert!(blockchain.rescan().is_clean());
+    
+    // 手动破坏UTXO集合：丢失一个输出、伪造一个输出
+    blockchain.utxo_set.remove(&transfer_id);
+    blockchain.utxo_set.insert(String::from("伪造交易"), vec![(0, 100, String::from("地址A"))]);
+    assert_eq!(blockchain.get_balance("地址A"), 100);
+    assert_eq!(blockchain.get_balance("地址C"), 0);
+    
+    let report = blockchain.rescan();
+    assert!(!report.is_clean());
+    assert!(report.consistent);
+    assert_eq!(report.added.len(), 1);
+    assert_eq!(report.added[0].0.tx_id, transfer_id);
+    assert_eq!(report.removed.len(), 1);
+    assert_eq!(report.removed[0].0.tx_id, "伪造交易");
+    assert_eq!(report.balance_changes, vec![
+        (String::from("地址A"), 100, 0),
+        (String::from("地址C"), 0, 10),
+    ]);
+    
+    let restored: Vec<u64> = ["地址A", "地址B", "地址C"].iter().map(|address| blockchain.get_balance(address)).collect();
+    assert_eq!(restored, expected);
+    assert!(blockchain.rescan().is_clean());
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_block_reward_halves_at_interval() {
+    let mut blockchain = Blockchain::new_with_config(BlockchainConfig { halving_interval: 3, ..BlockchainConfig::testnet() });
+    let rewards: Vec<u64> = (0..10).map(|height| blockchain.block_reward_at_height(height)).collect();
+    assert_eq!(rewards, vec![50, 50, 50, 25, 25, 25, 12, 12, 12, 6]);
+    assert_eq!(blockchain.block_reward_at_height(3 * 64), 0);
+    assert_eq!(blockchain.block_reward_at_height(usize::MAX), 0);
+    assert_eq!(blockchain.next_halving_height(), Some(3));
+    
+    // 高度3的区块是第一个减半后的区块
+    for (miner, value) in [("矿工1", 50), ("矿工2", 50), ("矿工3", 25)] {
+        assert_eq!(blockchain.block_reward_at_height(blockchain.len()), value);
+        let coinbase = Transaction::new(
+            vec![TxInput { prev_tx: String::from(COINBASE_TX_ID), prev_index: 0, script_sig: format!("{}的奖励", miner) }],
+            vec![TxOutput { value, script_pubkey: miner.to_string() }],
+        );
+        let block = blockchain.mine_block(vec![coinbase]);
+        assert_eq!(blockchain.check_block(&block), Ok(()));
+        blockchain.add_received_block(block);
+    }
+    assert_eq!(blockchain.height(), 3);
+    assert_eq!(blockchain.next_halving_height(), Some(6));
+    assert_eq!(blockchain.get_coinbase_transaction(2).unwrap().outputs[0].script_pubkey, "矿工2");
+    assert!(blockchain.get_coinbase_transaction(4).is_none());
+    
+    // 高度4的区块只能领取减半后的奖励
+    let claim = |value: u64| Transaction::new(
+        vec![TxInput { prev_tx: String::from(COINBASE_TX_ID), prev_index: 0, script_sig: format!("领取{}", value) }],
+        vec![TxOutput { value, script_pubkey: String::from("矿工4") }],
+    );
+    let block = blockchain.mine_block(vec![claim(50)]);
+    assert_eq!(blockchain.check_block(&block), Err(BlockError::ExcessiveCoinbase { claimed: 50, allowed: 25 }));
+    let block = blockchain.mine_block(vec![claim(25)]);
+    assert!(blockchain.validate_block(&block));
+    
+    let _ = fs::remove_file("blockchain.json");
+}
+
+#[test]
+fn test_zero_halving_interval_never_halves() {
+    let blockchain = Blockchain::new_with_config(BlockchainConfig { halving_interval: 0, ..BlockchainConfig::testnet() });
+    for height in [0, 1, 210_000, usize::MAX] {
+        assert_eq!(blockchain.block_reward_at_height(height), 50);
+    }
+    assert_eq!(blockchain.next_halving_height(), None);
+}
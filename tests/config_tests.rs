@@ -0,0 +1,71 @@
+use blockchain_demo::blockchain::{Blockchain, BlockchainConfig};
+use blockchain_demo::config::{ConfigError, NodeConfig};
+use blockchain_demo::network::NetworkConfig;
+use std::fs;
+use std::path::PathBuf;
+
+/// 在临时目录中写入配置文件，返回文件路径
+fn write_config(name: &str, content: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("blockchain_config_test_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("blockchain.toml");
+    fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn test_config_file_fills_missing_fields_with_defaults() {
+    let path = write_config("partial", r#"
+[blockchain]
+difficulty = 1
+initial_coinbase = 25
+
+[network]
+max_connections = 3
+bootstrap_peers = ["/ip4/127.0.0.1/tcp/40000"]
+"#);
+    let config = NodeConfig::load(&path).unwrap();
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert_eq!(config.blockchain, BlockchainConfig {
+        difficulty: 1,
+        initial_coinbase: 25,
+        ..BlockchainConfig::mainnet()
+    });
+    assert_eq!(config.network, NetworkConfig {
+        max_connections: 3,
+        bootstrap_peers: vec!["/ip4/127.0.0.1/tcp/40000".to_string()],
+        ..NetworkConfig::default()
+    });
+
+    // 无效的引导节点地址和为0的间隔在读取时被拒绝
+    let path = write_config("invalid", "[network]\nbootstrap_peers = [\"not an address\"]\n");
+    let result = NodeConfig::load(&path);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    assert!(matches!(result, Err(ConfigError::Invalid { .. })));
+
+    let path = write_config("zero", "[blockchain]\nretarget_interval = 0\n");
+    let result = NodeConfig::load(&path);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    assert!(matches!(result, Err(ConfigError::Invalid { reason, .. }) if reason.contains("retarget_interval")));
+
+    let path = write_config("syntax", "[blockchain\n");
+    let result = NodeConfig::load(&path);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    assert!(matches!(result, Err(ConfigError::Parse { .. })));
+}
+
+#[test]
+fn test_new_with_config_applies_consensus_parameters() {
+    let testnet = BlockchainConfig::testnet();
+    assert!(testnet.difficulty < BlockchainConfig::mainnet().difficulty);
+    assert!(testnet.target_block_time_secs < BlockchainConfig::mainnet().target_block_time_secs);
+
+    let config = BlockchainConfig { max_block_size: 4096, initial_coinbase: 10, ..testnet };
+    let blockchain = Blockchain::new_with_config(config.clone());
+    assert_eq!(blockchain.config(), config);
+    assert_eq!(blockchain.difficulty(), 1);
+    assert_eq!(blockchain.max_block_size(), 4096);
+    assert_eq!(blockchain.block_reward(), 10);
+    assert!(blockchain.validate_chain());
+}
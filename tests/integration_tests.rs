@@ -1,18 +1,12 @@
-use blockchain_demo::block::{Block, Transaction, TxInput, TxOutput};
+use blockchain_demo::block::{Transaction, TxInput, TxOutput};
 use blockchain_demo::blockchain::Blockchain;
 use blockchain_demo::wallet::Wallet;
 use blockchain_demo::network::Network;
-use std::collections::HashMap;
-use sha2::{Sha256, Digest};
-use hex;
 use tokio::sync::mpsc;
 
-// 辅助函数：计算交易哈希
+// 辅助函数：计算交易ID，UTXO集合以它为键
 fn calculate_tx_hash(tx: &Transaction) -> String {
-    let mut hasher = Sha256::new();
-    let serialized = serde_json::to_string(tx).unwrap();
-    hasher.update(serialized.as_bytes());
-    hex::encode(hasher.finalize())
+    tx.txid()
 }
 
 // 主流程测试，展示整个区块链系统如何工作
@@ -20,15 +14,15 @@ fn calculate_tx_hash(tx: &Transaction) -> String {
 async fn test_blockchain_workflow() {
     println!("=== 区块链完整工作流程展示 ===");
     
-    // 第1步：创建一个区块链，初始难度为1
+    // 第1步：创建一个回归测试区块链，难度为0，挖矿立即完成
     println!("\n步骤1: 创建区块链");
-    let mut blockchain = Blockchain::new(1);
-    println!("  创建了区块链，初始难度为1");
-    println!("  创世区块已创建，哈希值: {}", blockchain.blocks[0].calculate_hash());
+    let mut blockchain = Blockchain::regtest();
+    println!("  创建了回归测试区块链，难度为0");
+    println!("  创世区块已创建，哈希值: {}", blockchain.genesis().calculate_hash());
     
     // 第2步：创建两个钱包（矿工和用户）
     println!("\n步骤2: 创建钱包");
-    let miner_wallet = Wallet::new();
+    let mut miner_wallet = Wallet::new();
     let user_wallet = Wallet::new();
     println!("  矿工钱包地址: {}", miner_wallet.address);
     println!("  用户钱包地址: {}", user_wallet.address);
@@ -55,31 +49,32 @@ async fn test_blockchain_workflow() {
     println!("\n步骤4: 挖掘第一个区块");
     blockchain.add_block(vec![coinbase_tx]);
     println!("  成功挖掘了第一个区块");
-    println!("  区块哈希: {}", blockchain.blocks[1].calculate_hash());
-    println!("  区块中的交易数量: {}", blockchain.blocks[1].transactions.len());
+    println!("  区块哈希: {}", blockchain.get(1).unwrap().calculate_hash());
+    println!("  区块中的交易数量: {}", blockchain.get(1).unwrap().transactions.len());
     
     // 第5步：验证区块链状态和UTXO集合
     println!("\n步骤5: 验证区块链状态");
-    assert_eq!(blockchain.blocks.len(), 2);
+    assert_eq!(blockchain.len(), 2);
     assert!(blockchain.utxo_set.contains_key(&coinbase_tx_id));
     let _miner_balance = blockchain.get_balance(&miner_wallet.address);
-    println!("  区块链现在有{}个区块", blockchain.blocks.len());
+    println!("  区块链现在有{}个区块", blockchain.len());
     println!("  矿工余额: {}", _miner_balance);
     assert_eq!(_miner_balance, 50);
     
     // 第6步：矿工向用户转账
     println!("\n步骤6: 矿工向用户转账20个代币");
     
-    // 模拟矿工创建交易
+    // 模拟矿工创建交易，找零回到矿工主地址以便后面按地址统计余额
+    miner_wallet.fresh_change_address = false;
     let tx_from_miner = miner_wallet.create_transaction(
         &user_wallet.address,
         20,
-        &blockchain.utxo_set,
+        &blockchain.get_utxos_for_address(&miner_wallet.address),
     ).unwrap();
     
     // 签名交易
     let mut signed_tx = tx_from_miner.clone();
-    miner_wallet.sign_transaction(&mut signed_tx);
+    miner_wallet.sign_transaction(&mut signed_tx).unwrap();
     println!("  矿工创建并签名了转账交易");
     
     let tx_id = calculate_tx_hash(&signed_tx);
@@ -89,39 +84,21 @@ async fn test_blockchain_workflow() {
     println!("\n步骤7: 挖掘第二个区块（包含转账交易）");
     blockchain.add_block(vec![signed_tx]);
     println!("  成功挖掘了第二个区块");
-    println!("  区块哈希: {}", blockchain.blocks[2].calculate_hash());
+    println!("  区块哈希: {}", blockchain.get(2).unwrap().calculate_hash());
     
     // 第8步：检查余额
     println!("\n步骤8: 检查交易后的余额");
     let _total_balance = blockchain.get_balance("any_address"); // 现有的get_balance实际上返回总余额
     
-    // 由于get_balance实现的限制，它返回的是所有UTXO的总和，而不是特定地址的余额
-    // 我们需要手动计算每个地址的余额
+    // 直接从UTXO集合中按锁定脚本统计每个地址的余额
     let mut manual_miner_balance = 0;
     let mut manual_user_balance = 0;
     
-    for (tx_id, outputs) in &blockchain.utxo_set {
-        for (_, (output_idx, utxo_value)) in outputs.iter().enumerate() {
-            // 找到这个交易ID对应的区块
-            let mut found_tx = None;
-            'outer: for block in &blockchain.blocks {
-                for tx in &block.transactions {
-                    if calculate_tx_hash(tx) == *tx_id {
-                        found_tx = Some(tx);
-                        break 'outer;
-                    }
-                }
-            }
-            
-            if let Some(transaction) = found_tx {
-                if let Some(output) = transaction.outputs.get(*output_idx as usize) {
-                    if output.script_pubkey == miner_wallet.address {
-                        manual_miner_balance += utxo_value;
-                    } else if output.script_pubkey == user_wallet.address {
-                        manual_user_balance += utxo_value;
-                    }
-                }
-            }
+    for (_, utxo_value, script_pubkey) in blockchain.utxo_set.values().flatten() {
+        if *script_pubkey == miner_wallet.address {
+            manual_miner_balance += utxo_value;
+        } else if *script_pubkey == user_wallet.address {
+            manual_user_balance += utxo_value;
         }
     }
     
@@ -130,9 +107,9 @@ async fn test_blockchain_workflow() {
     
     // 第9步：检查区块链完整性
     println!("\n步骤9: 验证区块链的完整性");
-    for (i, block) in blockchain.blocks.iter().enumerate() {
+    for (i, block) in blockchain.iter().enumerate() {
         if i > 0 {
-            let prev_block = &blockchain.blocks[i-1];
+            let prev_block = blockchain.get(i - 1).unwrap();
             let prev_hash = prev_block.calculate_hash();
             assert_eq!(block.header.prev_hash, prev_hash);
             println!("  区块 #{} 正确引用了前一个区块", i);
@@ -145,7 +122,7 @@ async fn test_blockchain_workflow() {
     // 第10步：模拟P2P网络广播
     println!("\n步骤10: 模拟网络广播");
     // 创建网络实例
-    let _network = Network::new().await;
+    let _network = Network::new(std::sync::Arc::clone(blockchain.hasher())).await;
     
     // 创建通道以接收事件
     let (tx, mut rx) = mpsc::channel(10);
@@ -155,11 +132,11 @@ async fn test_blockchain_workflow() {
         let mut event_count = 0;
         while let Some(event) = rx.recv().await {
             match event {
-                blockchain_demo::network::NetworkEvent::NewBlock(_) => {
+                blockchain_demo::network::NetworkCommand::BroadcastBlock { .. } => {
                     println!("  收到新区块广播");
                     event_count += 1;
                 }
-                blockchain_demo::network::NetworkEvent::NewTransaction(_) => {
+                blockchain_demo::network::NetworkCommand::BroadcastTransaction(_) => {
                     println!("  收到新交易广播");
                     event_count += 1;
                 }
@@ -199,20 +176,20 @@ async fn test_blockchain_workflow() {
     );
     
     // 广播区块和交易
-    tx.send(blockchain_demo::network::NetworkEvent::NewBlock(blockchain.blocks[2].clone())).await.unwrap();
-    tx.send(blockchain_demo::network::NetworkEvent::NewTransaction(new_tx)).await.unwrap();
+    tx.send(blockchain_demo::network::NetworkCommand::BroadcastBlock { block: blockchain.get(2).unwrap().clone(), height: 2 }).await.unwrap();
+    tx.send(blockchain_demo::network::NetworkCommand::BroadcastTransaction(new_tx)).await.unwrap();
     
     // 等待监听任务完成
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     
     // 总结
     println!("\n=== 区块链演示完成 ===");
-    println!("区块链现在有{}个区块", blockchain.blocks.len());
+    println!("区块链现在有{}个区块", blockchain.len());
     println!("矿工最终余额: {}", manual_miner_balance);
     println!("用户最终余额: {}", manual_user_balance);
     println!("UTXO集合大小: {}", blockchain.utxo_set.len());
     
-    assert_eq!(blockchain.blocks.len(), 3);
+    assert_eq!(blockchain.len(), 3);
     assert!(manual_miner_balance > 0);
     assert!(manual_user_balance > 0);
 } 
\ No newline at end of file
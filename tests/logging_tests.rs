@@ -0,0 +1,38 @@
+use blockchain_demo::blockchain::Blockchain;
+use log::{Level, Log, Metadata, Record};
+use std::sync::Mutex;
+
+// 记录所有日志的测试用logger，每个测试二进制只能安装一次
+struct CapturingLogger {
+    records: Mutex<Vec<(Level, String, String)>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records.lock().unwrap().push((record.level(), record.target().to_string(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger { records: Mutex::new(Vec::new()) };
+
+#[test]
+fn test_rejected_block_emits_warning() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let blockchain = Blockchain::new(1);
+    let block = blockchain.mine_block(vec![]);
+    assert!(!blockchain.validate_block(&block));
+
+    let records = LOGGER.records.lock().unwrap();
+    let warning = records.iter()
+        .find(|(level, target, _)| *level == Level::Warn && target == "blockchain_demo::blockchain")
+        .expect("被拒绝的区块应当输出warn日志");
+    assert!(warning.2.contains(&block.calculate_hash()));
+}
@@ -0,0 +1,233 @@
+use blockchain_demo::block::{Block, OutPoint, Transaction, TxInput, TxOutput, UtxoEntry, COINBASE_TX_ID};
+use blockchain_demo::blockchain::Blockchain;
+use blockchain_demo::mempool::{Mempool, REBROADCAST_INTERVAL};
+use blockchain_demo::wallet::{TransactionError, Wallet};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// 辅助函数：创建指定手续费的测试交易
+fn create_fee_transaction(prev_tx: &str, fee: u64) -> Transaction {
+    Transaction::with_fee(
+        vec![TxInput {
+            prev_tx: prev_tx.to_string(),
+            prev_index: 0,
+            script_sig: String::from("测试签名"),
+        }],
+        vec![TxOutput {
+            value: 10,
+            script_pubkey: String::from("接收地址"),
+        }],
+        fee,
+    )
+}
+
+#[test]
+fn test_mempool_rejects_duplicates() {
+    let mut mempool = Mempool::new();
+    let tx = create_fee_transaction("tx1", 1);
+    
+    assert!(mempool.add(tx.clone()));
+    assert!(!mempool.add(tx));
+    assert_eq!(mempool.len(), 1);
+}
+
+#[test]
+fn test_mempool_dedups_resigned_copies_by_txid() {
+    let blockchain = Blockchain::new(1);
+    let mut mempool = Mempool::with_hasher(blockchain.hasher().clone());
+    let tx = create_fee_transaction("tx1", 1);
+    assert!(mempool.add(tx.clone()));
+    assert!(mempool.contains(&blockchain.calculate_tx_hash(&tx)));
+    
+    // 签名不同的副本交易ID相同，不会被当作新交易加入，两者不会同时锁定同一个输出
+    let mut resigned = tx.clone();
+    resigned.inputs[0].script_sig = String::from("另一个签名");
+    assert_ne!(resigned.calculate_hash(), tx.calculate_hash());
+    assert!(!mempool.add(resigned.clone()));
+    assert_eq!(mempool.len(), 1);
+    
+    // 区块确认的是签名不同的副本时，交易池中的原交易同样被移除
+    let mut block = Block::new(blockchain.tip().calculate_hash(), 1);
+    block.transactions.push(resigned);
+    assert_eq!(mempool.remove_confirmed(&block), 1);
+    assert!(mempool.is_empty());
+}
+
+#[test]
+fn test_block_template_places_highest_fee_first() {
+    let mut mempool = Mempool::new();
+    for (i, fee) in [2, 8, 4, 10, 6].iter().enumerate() {
+        mempool.add(create_fee_transaction(&format!("tx{}", i), *fee));
+    }
+    
+    // 按照挖矿流程构建区块模板：Coinbase在前，其后为交易池挑选的交易
+    let coinbase = Transaction::new(
+        vec![TxInput {
+            prev_tx: String::from(COINBASE_TX_ID),
+            prev_index: 0,
+            script_sig: String::from("挖矿奖励"),
+        }],
+        vec![TxOutput {
+            value: 50,
+            script_pubkey: String::from("矿工地址"),
+        }],
+    );
+    let mut template = vec![coinbase];
+    template.extend(mempool.select_transactions(3));
+    
+    assert_eq!(template.len(), 4);
+    assert!(template[0].is_coinbase());
+    assert_eq!(template[1].fee, 10);
+    assert_eq!(template[2].fee, 8);
+    assert_eq!(template[3].fee, 6);
+    
+    // 挑选不会移除交易，区块确认后才清理
+    assert_eq!(mempool.len(), 5);
+    let mut block = Block::new(String::from("0"), 0);
+    block.transactions = template;
+    assert_eq!(mempool.remove_confirmed(&block), 3);
+    assert_eq!(mempool.len(), 2);
+}
+
+#[test]
+fn test_mempool_evicts_expired_transactions() {
+    let mut mempool = Mempool::new();
+    let now = chrono::Utc::now().timestamp();
+    
+    mempool.add_at(create_fee_transaction("old_tx", 1), now - 2 * 3600);
+    mempool.add(create_fee_transaction("new_tx", 1));
+    
+    assert_eq!(mempool.evict_expired(Duration::from_secs(3600)), 1);
+    assert_eq!(mempool.len(), 1);
+    assert_eq!(mempool.iter().next().unwrap().inputs[0].prev_tx, "new_tx");
+}
+
+#[test]
+fn test_mempool_drops_transactions_with_spent_inputs() {
+    let mut mempool = Mempool::new();
+    mempool.add(create_fee_transaction("unspent_tx", 1));
+    mempool.add(create_fee_transaction("spent_tx", 1));
+    
+    // spent_tx的输出已在其他区块中被花费，不再出现在UTXO集合中
+    let mut utxo_set = HashMap::new();
+    utxo_set.insert(String::from("unspent_tx"), vec![(0, 20, String::from("地址"))]);
+    
+    assert_eq!(mempool.revalidate(&utxo_set), 1);
+    assert_eq!(mempool.len(), 1);
+    assert_eq!(mempool.iter().next().unwrap().inputs[0].prev_tx, "unspent_tx");
+}
+
+#[test]
+fn test_pending_transaction_locks_its_inputs() {
+    let mut wallet = Wallet::new();
+    let recipient = Wallet::new().address.clone();
+    let funded = vec![(
+        OutPoint { tx_id: String::from("资金交易"), index: 0 },
+        UtxoEntry { value: 50, script_pubkey: wallet.address.clone() },
+    )];
+    let mut mempool = Mempool::new();
+    
+    // 第一笔交易很久以前加入交易池
+    let first = wallet.create_transaction(&recipient, 20, &mempool.unlocked_utxos(&funded)).unwrap();
+    mempool.add_at(first, 0);
+    assert!(mempool.is_locked(&funded[0].0));
+    
+    // 唯一的UTXO已被第一笔待处理交易花费，第二笔交易无法再选中它
+    assert!(mempool.unlocked_utxos(&funded).is_empty());
+    assert_eq!(
+        wallet.create_transaction(&recipient, 20, &mempool.unlocked_utxos(&funded)).unwrap_err(),
+        TransactionError::NoUtxos,
+    );
+    
+    // 交易被驱逐后锁定随之释放
+    assert_eq!(mempool.evict_expired(Duration::from_secs(60)), 1);
+    assert!(!mempool.is_locked(&funded[0].0));
+    assert!(wallet.create_transaction(&recipient, 20, &mempool.unlocked_utxos(&funded)).is_ok());
+}
+
+#[test]
+fn test_selection_respects_size_budget() {
+    let mut mempool = Mempool::new();
+    let large = Transaction::with_fee(
+        (0..10)
+            .map(|i| TxInput { prev_tx: format!("大交易{}", i), prev_index: 0, script_sig: String::from("测试签名") })
+            .collect(),
+        vec![TxOutput { value: 10, script_pubkey: String::from("接收地址") }],
+        100,
+    );
+    let small_high = create_fee_transaction("tx1", 8);
+    let small_low = create_fee_transaction("tx2", 2);
+    for tx in [&large, &small_high, &small_low] {
+        mempool.add(tx.clone());
+    }
+    assert!(large.size() > small_high.size());
+    assert_eq!(mempool.total_size(), large.size() + small_high.size() + small_low.size());
+    
+    // 不限大小时全部挑选；大交易放不下时被跳过，之后的小交易仍被选中
+    assert_eq!(mempool.select_transactions_within(10, usize::MAX).len(), 3);
+    let budget = small_high.size() + small_low.size();
+    let selected = mempool.select_transactions_within(10, budget);
+    assert_eq!(selected.len(), 2);
+    assert_eq!(selected[0].fee, 8);
+    assert_eq!(selected[1].fee, 2);
+    
+    // 预算只够一笔小交易时选手续费率更高的那笔
+    let selected = mempool.select_transactions_within(10, budget - 1);
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].fee, 8);
+    assert!(mempool.select_transactions_within(10, 0).is_empty());
+}
+
+#[test]
+fn test_new_transaction_included_in_rebroadcast() {
+    let mut mempool = Mempool::new();
+    let now = chrono::Utc::now().timestamp();
+    let tx = create_fee_transaction("pending_tx", 1);
+    mempool.add(tx.clone());
+    
+    // 新加入的交易会被选中，间隔内再次连接节点时不重复发送
+    let selected = mempool.pending_for_rebroadcast_at(now);
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].calculate_hash(), tx.calculate_hash());
+    assert!(mempool.pending_for_rebroadcast_at(now + 1).is_empty());
+    
+    // 间隔过后仍未确认的交易再次被选中，之后加入的交易也被选中
+    mempool.add(create_fee_transaction("later_tx", 1));
+    let later = now + REBROADCAST_INTERVAL.as_secs() as i64;
+    assert_eq!(mempool.pending_for_rebroadcast_at(later).len(), 2);
+}
+
+#[test]
+fn test_double_spend_evicted_when_conflicting_transaction_confirmed() {
+    let coinbase = |miner: &str| Transaction::new(
+        vec![TxInput { prev_tx: String::from(COINBASE_TX_ID), prev_index: 0, script_sig: format!("{}的奖励", miner) }],
+        vec![TxOutput { value: 50, script_pubkey: miner.to_string() }],
+    );
+    let spend = |prev_tx: &str, recipient: &str, signature: &str| Transaction::new(
+        vec![TxInput { prev_tx: prev_tx.to_string(), prev_index: 0, script_sig: signature.to_string() }],
+        vec![TxOutput { value: 50, script_pubkey: recipient.to_string() }],
+    );
+    
+    let mut blockchain = Blockchain::new(1);
+    blockchain.add_block(vec![coinbase("矿工A"), coinbase("矿工B")]);
+    let funding_a = blockchain.calculate_tx_hash(&coinbase("矿工A"));
+    let funding_b = blockchain.calculate_tx_hash(&coinbase("矿工B"));
+    
+    // 交易池中：将被确认的支付（签名不同的版本）、与它双花的支付、花费双花交易输出的后代交易，以及无关的支付
+    let confirmed = spend(&funding_a, "商户", "签名1");
+    let double_spend = spend(&funding_a, "攻击者", "签名2");
+    let descendant = spend(&double_spend.txid(), "同伙", "签名3");
+    let unrelated = spend(&funding_b, "朋友", "签名4");
+    let mut mempool = Mempool::new();
+    for tx in [spend(&funding_a, "商户", "另一个签名"), double_spend, descendant, unrelated.clone()] {
+        assert!(mempool.add(tx));
+    }
+    
+    blockchain.add_block(vec![coinbase("矿工C"), confirmed]);
+    let block = blockchain.tip().clone();
+    assert_eq!(mempool.revalidate_against_new_block(&block, &blockchain), 3);
+    assert_eq!(mempool.len(), 1);
+    assert!(mempool.contains(&blockchain.calculate_tx_hash(&unrelated)));
+    
+    let _ = std::fs::remove_file("blockchain.json");
+}
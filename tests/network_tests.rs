@@ -1,11 +1,33 @@
-use blockchain_demo::network::{Network, NetworkEvent};
-use blockchain_demo::block::{Block, Transaction, TxInput, TxOutput};
-use blockchain_demo::blockchain::Blockchain;
+use blockchain_demo::network::{
+    apply_address_announce, compress_payload, BlockQuery, NetworkError, decode_message, encode_message, relay_circuit_addr, AnnounceRateLimiter, CodecError,
+    NatStatus, Network, NetworkCommand, NetworkEvent, NetworkMessage, PeerStore, RateLimiter, ReconnectBackoff, WireFormat, ANNOUNCE_MIN_INTERVAL_SECS, COMPRESSION_THRESHOLD,
+    DEFAULT_OUTBOUND_RESERVE, DEFAULT_RATE_LIMIT_CAPACITY, FEATURE_BINARY_BLOCKS, FEATURE_BLOCK_LOCATOR, FEATURE_INCREMENTAL_SYNC, LEGACY_PROTOCOL_VERSION, MAX_RECONNECT_ATTEMPTS, MISBEHAVIOR_BAN_THRESHOLD, MIN_SUPPORTED_VERSION, PEER_EXPIRY_SECS, PROTOCOL_VERSION,
+    RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY, WIRE_FORMAT_VERSION,
+};
+use blockchain_demo::wallet::AddressBook;
+use blockchain_demo::block::{Block, Hasher, Sha256Hasher, Transaction, TxInput, TxOutput, COINBASE_TX_ID};
+use blockchain_demo::blockchain::{Blockchain, GenesisConfig};
+use blockchain_demo::metrics::Metrics;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
+// 辅助函数：测试节点的区块链使用的哈希算法
+fn chain_hasher() -> Arc<dyn Hasher> {
+    Arc::new(Sha256Hasher)
+}
+
+// 辅助函数：创建保存到独立临时目录的区块链，返回区块链和该目录，测试结束时删除目录
+fn temp_chain(name: &str) -> (Blockchain, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut blockchain = Blockchain::new(1);
+    blockchain.set_data_file(dir.join("blockchain.json").to_str().unwrap());
+    (blockchain, dir)
+}
+
 // 辅助函数：创建测试区块
 fn create_test_block() -> Block {
     let mut block = Block::new(String::from("0000000000000000000000000000000000000000000000000000000000000000"), 1);
@@ -47,10 +69,11 @@ fn create_test_transaction() -> Transaction {
 #[tokio::test]
 async fn test_network_creation() {
     // 创建网络实例
-    let network = Network::new().await;
+    let network = Network::new(chain_hasher()).await;
     
-    // 由于peer_id是私有字段，我们不能直接访问，所以这里只验证网络实例创建成功
-    assert!(true);
+    // 新建的网络尚未建立任何连接
+    assert_eq!(network.connected_peer_count(), 0);
+    assert_eq!(network.discovered_peer_count(), 0);
 }
 
 #[tokio::test]
@@ -60,18 +83,15 @@ async fn test_broadcast_block() {
     
     // 创建监听任务，接收广播的区块
     let listen_handle = tokio::spawn(async move {
-        if let Some(event) = rx.recv().await {
-            match event {
-                NetworkEvent::NewBlock(block) => {
-                    // 验证收到的区块
-                    assert_eq!(block.transactions.len(), 1);
-                    assert_eq!(block.transactions[0].outputs[0].value, 50);
-                    return true;
-                }
-                _ => return false,
+        match rx.recv().await {
+            Some(NetworkCommand::BroadcastBlock { block, height }) => {
+                assert_eq!(height, 1);
+                // 验证收到的区块
+                assert_eq!(block.transactions.len(), 1);
+                assert_eq!(block.transactions[0].outputs[0].value, 50);
+                true
             }
-        } else {
-            return false;
+            _ => false,
         }
     });
     
@@ -79,7 +99,7 @@ async fn test_broadcast_block() {
     let test_block = create_test_block();
     
     // 发送区块到通道
-    tx.send(NetworkEvent::NewBlock(test_block)).await.unwrap();
+    tx.send(NetworkCommand::BroadcastBlock { block: test_block, height: 1 }).await.unwrap();
     
     // 等待接收结果
     let result = timeout(Duration::from_secs(1), listen_handle).await.unwrap().unwrap();
@@ -93,20 +113,16 @@ async fn test_broadcast_transaction() {
     
     // 创建监听任务，接收广播的交易
     let listen_handle = tokio::spawn(async move {
-        if let Some(event) = rx.recv().await {
-            match event {
-                NetworkEvent::NewTransaction(transaction) => {
-                    // 验证收到的交易
-                    assert_eq!(transaction.inputs.len(), 1);
-                    assert_eq!(transaction.outputs.len(), 1);
-                    assert_eq!(transaction.outputs[0].value, 50);
-                    assert_eq!(transaction.outputs[0].script_pubkey, "接收地址");
-                    return true;
-                }
-                _ => return false,
+        match rx.recv().await {
+            Some(NetworkCommand::BroadcastTransaction(transaction)) => {
+                // 验证收到的交易
+                assert_eq!(transaction.inputs.len(), 1);
+                assert_eq!(transaction.outputs.len(), 1);
+                assert_eq!(transaction.outputs[0].value, 50);
+                assert_eq!(transaction.outputs[0].script_pubkey, "接收地址");
+                true
             }
-        } else {
-            return false;
+            _ => false,
         }
     });
     
@@ -114,7 +130,7 @@ async fn test_broadcast_transaction() {
     let test_transaction = create_test_transaction();
     
     // 发送交易到通道
-    tx.send(NetworkEvent::NewTransaction(test_transaction)).await.unwrap();
+    tx.send(NetworkCommand::BroadcastTransaction(test_transaction)).await.unwrap();
     
     // 等待接收结果
     let result = timeout(Duration::from_secs(1), listen_handle).await.unwrap().unwrap();
@@ -128,20 +144,11 @@ async fn test_request_blocks() {
     
     // 创建监听任务，接收区块请求
     let listen_handle = tokio::spawn(async move {
-        if let Some(event) = rx.recv().await {
-            match event {
-                NetworkEvent::RequestBlocks => {
-                    return true;
-                }
-                _ => return false,
-            }
-        } else {
-            return false;
-        }
+        matches!(rx.recv().await, Some(NetworkCommand::RequestBlocks { from_height: 0, .. }))
     });
     
     // 发送区块请求
-    tx.send(NetworkEvent::RequestBlocks).await.unwrap();
+    tx.send(NetworkCommand::RequestBlocks { from_height: 0, locator: Vec::new() }).await.unwrap();
     
     // 等待接收结果
     let result = timeout(Duration::from_secs(1), listen_handle).await.unwrap().unwrap();
@@ -155,19 +162,15 @@ async fn test_send_blocks() {
     
     // 创建监听任务，接收多个区块
     let listen_handle = tokio::spawn(async move {
-        if let Some(event) = rx.recv().await {
-            match event {
-                NetworkEvent::SendBlocks(blocks) => {
-                    // 验证收到的区块列表
-                    assert_eq!(blocks.len(), 2);
-                    assert_eq!(blocks[0].transactions.len(), 1);
-                    assert_eq!(blocks[1].transactions.len(), 1);
-                    return true;
-                }
-                _ => return false,
+        match rx.recv().await {
+            Some(NetworkCommand::SendBlocksTo(_, blocks)) => {
+                // 验证收到的区块列表
+                assert_eq!(blocks.len(), 2);
+                assert_eq!(blocks[0].transactions.len(), 1);
+                assert_eq!(blocks[1].transactions.len(), 1);
+                true
             }
-        } else {
-            return false;
+            _ => false,
         }
     });
     
@@ -176,7 +179,7 @@ async fn test_send_blocks() {
     let test_block2 = create_test_block();
     
     // 发送区块列表
-    tx.send(NetworkEvent::SendBlocks(vec![test_block1, test_block2])).await.unwrap();
+    tx.send(NetworkCommand::SendBlocksTo(random_peer_id(), vec![test_block1, test_block2])).await.unwrap();
     
     // 等待接收结果
     let result = timeout(Duration::from_secs(1), listen_handle).await.unwrap().unwrap();
@@ -190,7 +193,7 @@ async fn test_network_broadcast_methods() {
     let blockchain = Blockchain::new(1);
     
     // 创建网络实例
-    let network = Network::new().await;
+    let network = Network::new(chain_hasher()).await;
     
     // 创建一个接收器，拦截 network 内部的 event_sender 发送的消息
     // 因为 network 内部的 event_receiver 是私有的，我们不能直接访问
@@ -198,25 +201,24 @@ async fn test_network_broadcast_methods() {
     
     // 测试广播区块
     let test_block = create_test_block();
-    let broadcast_result = network.broadcast_block(test_block).await;
+    network.broadcast_block(test_block, 1).await;
     
     // 测试广播交易
     let test_transaction = create_test_transaction();
-    let transaction_result = network.broadcast_transaction(test_transaction).await;
+    network.broadcast_transaction(test_transaction).await;
     
     // 测试同步链
-    let sync_result = network.sync_chain(&blockchain).await;
+    network.sync_chain(&blockchain).await;
     
     // 这里我们只是测试方法调用不会崩溃
     // 由于 Network 结构的设计，我们无法在测试中直接验证内部通道的事件
-    assert!(true);
 }
 
 #[tokio::test]
 async fn test_network_connection() {
     // 创建两个网络节点
-    let mut node1 = Network::new().await;
-    let mut node2 = Network::new().await;
+    let mut node1 = Network::new(chain_hasher()).await;
+    let mut node2 = Network::new(chain_hasher()).await;
     
     println!("创建了两个网络节点");
     println!("节点1 ID: {}", node1.peer_id());
@@ -260,14 +262,14 @@ async fn test_network_connection() {
 #[tokio::test]
 async fn test_message_broadcast() {
     // 创建两个网络节点和消息通道
-    let (tx1, mut rx1) = mpsc::channel(100);
-    let (tx2, mut rx2) = mpsc::channel(100);
+    let (tx1, _rx1) = mpsc::channel(100);
+    let (tx2, _rx2) = mpsc::channel(100);
     
-    // 创建一个独立的发送通道用于向节点1发送消息
-    let node1_tx = tx1.clone();
+    let mut node1 = Network::new_with_channel(chain_hasher(), tx1).await;
+    let mut node2 = Network::new_with_channel(chain_hasher(), tx2).await;
     
-    let mut node1 = Network::new_with_channel(tx1).await;
-    let mut node2 = Network::new_with_channel(tx2).await;
+    // 节点1的命令发送器，用于让节点1广播消息
+    let node1_tx = node1.get_event_sender();
     
     println!("创建了两个网络节点");
     println!("节点1 ID: {}", node1.peer_id());
@@ -305,7 +307,7 @@ async fn test_message_broadcast() {
     let test_block = create_test_block();
     
     // 节点1广播区块
-    if let Err(e) = node1_tx.send(NetworkEvent::NewBlock(test_block.clone())).await {
+    if let Err(e) = node1_tx.send(NetworkCommand::BroadcastBlock { block: test_block.clone(), height: 1 }).await {
         eprintln!("广播区块失败: {}", e);
     }
     
@@ -313,5 +315,1141 @@ async fn test_message_broadcast() {
     
     // 由于我们使用的是模拟实现，实际上并没有真正的网络连接
     // 所以这里我们直接断言测试成功，实际应用中需要更完善的测试
+    node1_handle.abort();
+    node2_handle.abort();
     println!("消息广播测试完成");
-} 
\ No newline at end of file
+} 
+// 辅助函数：生成随机节点ID
+fn random_peer_id() -> libp2p::PeerId {
+    libp2p::identity::Keypair::generate_ed25519().public().to_peer_id()
+}
+
+#[test]
+fn test_peer_store_round_trip() {
+    let path = std::env::temp_dir().join(format!("peers_round_trip_{}.json", std::process::id()));
+    let now = chrono::Utc::now().timestamp() as u64;
+    
+    let peer_a = random_peer_id();
+    let peer_b = random_peer_id();
+    let addr_a: libp2p::Multiaddr = "/ip4/127.0.0.1/tcp/40000".parse().unwrap();
+    let addr_b: libp2p::Multiaddr = "/ip4/192.168.1.2/tcp/40001".parse().unwrap();
+    
+    let mut store = PeerStore::load(&path);
+    assert!(store.is_empty());
+    store.record(peer_a, addr_a.clone(), now - 100);
+    store.record(peer_b, addr_b.clone(), now);
+    store.save().unwrap();
+    
+    let loaded = PeerStore::load(&path);
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.last_seen(&peer_a), Some(now - 100));
+    assert_eq!(loaded.best_peers(2), vec![(peer_b, addr_b.clone()), (peer_a, addr_a)]);
+    assert_eq!(loaded.best_peers(1), vec![(peer_b, addr_b)]);
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_network_loads_peer_store_from_given_file() {
+    let path = std::env::temp_dir().join(format!("peers_network_{}.json", std::process::id()));
+    let mut store = PeerStore::load(&path);
+    store.record(random_peer_id(), "/ip4/127.0.0.1/tcp/40000".parse().unwrap(), chrono::Utc::now().timestamp() as u64);
+    store.save().unwrap();
+    
+    // 未指定文件时节点存储只在内存中，不读取工作目录下的文件
+    let (tx, _rx) = mpsc::channel(10);
+    assert!(Network::new_with_channel(chain_hasher(), tx.clone()).await.peer_store().is_empty());
+    let network = Network::new_with_channel(chain_hasher(), tx).await.with_peer_store_file(&path);
+    assert_eq!(network.peer_store().len(), 1);
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_peer_store_prunes_stale_peers() {
+    let now = chrono::Utc::now().timestamp() as u64;
+    let fresh_peer = random_peer_id();
+    let stale_peer = random_peer_id();
+    let addr: libp2p::Multiaddr = "/ip4/127.0.0.1/tcp/40000".parse().unwrap();
+    
+    let mut store = PeerStore::in_memory();
+    store.record(fresh_peer, addr.clone(), now);
+    store.record(stale_peer, addr.clone(), now - PEER_EXPIRY_SECS - 1);
+    
+    // 断开连接时更新最后见到的时间，未知节点不会被加入
+    assert!(store.touch(&fresh_peer, now + 10));
+    assert!(!store.touch(&random_peer_id(), now));
+    assert_eq!(store.last_seen(&fresh_peer), Some(now + 10));
+    
+    assert_eq!(store.prune(now), 1);
+    assert_eq!(store.best_peers(10), vec![(fresh_peer, addr)]);
+}
+
+#[test]
+fn test_address_announce_exchange() {
+    let alice_peer = random_peer_id();
+    let bob_peer = random_peer_id();
+    let alice_address = "a".repeat(40);
+    let bob_address = "b".repeat(40);
+    
+    let mut alice_mapping = AddressBook::new();
+    alice_mapping.add("alice", &alice_address).unwrap();
+    alice_mapping.add("me", &alice_address).unwrap();
+    let mut bob_mapping = AddressBook::new();
+    bob_mapping.add("bob", &bob_address).unwrap();
+    bob_mapping.add("me", &bob_address).unwrap();
+    let mut alice_limiter = AnnounceRateLimiter::default();
+    let mut bob_limiter = AnnounceRateLimiter::default();
+    
+    // 模拟双方在连接建立后交换公告消息
+    let deliver = |message: &NetworkMessage| {
+        let data = serde_json::to_vec(message).unwrap();
+        match serde_json::from_slice(&data).unwrap() {
+            NetworkMessage::AddressAnnounce { wallet_address, nickname } => (wallet_address, nickname),
+            other => panic!("意外的消息: {:?}", other),
+        }
+    };
+    let now = 1_700_000_000;
+    
+    let (address, nickname) = deliver(&NetworkMessage::AddressAnnounce {
+        wallet_address: bob_address.clone(),
+        nickname: "bob".to_string(),
+    });
+    assert!(alice_limiter.allow(bob_peer, now));
+    assert!(apply_address_announce(&mut alice_mapping, &bob_peer, &address, &nickname));
+    
+    let (address, nickname) = deliver(&NetworkMessage::AddressAnnounce {
+        wallet_address: alice_address.clone(),
+        nickname: "alice".to_string(),
+    });
+    assert!(bob_limiter.allow(alice_peer, now));
+    assert!(apply_address_announce(&mut bob_mapping, &alice_peer, &address, &nickname));
+    
+    assert_eq!(alice_mapping.get(&bob_peer.to_string()), Some(bob_address.as_str()));
+    assert_eq!(alice_mapping.get("bob"), Some(bob_address.as_str()));
+    assert_eq!(bob_mapping.get(&alice_peer.to_string()), Some(alice_address.as_str()));
+    assert_eq!(bob_mapping.get("alice"), Some(alice_address.as_str()));
+    
+    // 远程节点不能覆盖本地用户的映射
+    assert!(!apply_address_announce(&mut alice_mapping, &bob_peer, &bob_address, "alice"));
+    assert!(!apply_address_announce(&mut alice_mapping, &bob_peer, &bob_address, "me"));
+    assert_eq!(alice_mapping.get("alice"), Some(alice_address.as_str()));
+    assert_eq!(alice_mapping.get("me"), Some(alice_address.as_str()));
+}
+
+#[test]
+fn test_address_announce_rate_limit() {
+    let peer = random_peer_id();
+    let other = random_peer_id();
+    let mut limiter = AnnounceRateLimiter::default();
+    let now = 1_700_000_000;
+    
+    assert!(limiter.allow(peer, now));
+    assert!(!limiter.allow(peer, now + 1));
+    assert!(!limiter.allow(peer, now + ANNOUNCE_MIN_INTERVAL_SECS - 1));
+    // 其他节点不受影响
+    assert!(limiter.allow(other, now + 1));
+    assert!(limiter.allow(peer, now + ANNOUNCE_MIN_INTERVAL_SECS));
+}
+
+#[test]
+fn test_network_message_binary_and_json_encoding() {
+    let block = create_test_block();
+    
+    // JSON和二进制编码的消息都能被自动识别并解码
+    let json = encode_message(&NetworkMessage::Block(block.clone()), WireFormat::Json);
+    let binary = encode_message(&NetworkMessage::BlockBinary(block.to_bytes()), WireFormat::Binary);
+    assert!(binary.len() < json.len());
+    
+    match decode_message(&json).unwrap() {
+        NetworkMessage::Block(decoded) => assert_eq!(decoded.calculate_hash(), block.calculate_hash()),
+        other => panic!("意外的消息: {:?}", other),
+    }
+    match decode_message(&binary).unwrap() {
+        NetworkMessage::BlockBinary(data) => {
+            assert_eq!(Block::from_bytes(&data).unwrap().calculate_hash(), block.calculate_hash());
+        }
+        other => panic!("意外的消息: {:?}", other),
+    }
+    
+    // 旧节点直接用serde_json编码的消息仍然可以解码
+    let legacy = serde_json::to_vec(&NetworkMessage::BlockRequest).unwrap();
+    assert!(matches!(decode_message(&legacy).unwrap(), NetworkMessage::BlockRequest));
+}
+
+#[test]
+fn test_binary_codec_round_trips_every_variant() {
+    let block = create_test_block();
+    let messages = vec![
+        NetworkMessage::Block(block.clone()),
+        NetworkMessage::Transaction(create_test_transaction()),
+        NetworkMessage::BlockRequest,
+        NetworkMessage::BlockResponse(vec![block.clone(), block.clone()]),
+        NetworkMessage::AddressAnnounce {
+            wallet_address: "a".repeat(40),
+            nickname: String::from("alice"),
+        },
+        NetworkMessage::BlockBinary(block.to_bytes()),
+        NetworkMessage::BlockResponseBinary(vec![block.to_bytes()]),
+        NetworkMessage::Capabilities { binary_blocks: true, compression: true },
+        NetworkMessage::RelayServerAnnounce("/ip4/203.0.113.7/tcp/4001".parse().unwrap()),
+        NetworkMessage::BlockRequestSince { known_height: 5, known_hash: block.calculate_hash() },
+        NetworkMessage::BlockResponseSince { start_height: 6, blocks: vec![block.clone()] },
+        NetworkMessage::Handshake { protocol_version: PROTOCOL_VERSION, supported_features: vec![String::from(FEATURE_INCREMENTAL_SYNC)] },
+        NetworkMessage::GetBlock(block.calculate_hash()),
+        NetworkMessage::GetBlockByHeight(7),
+        NetworkMessage::BlockNotFound,
+        NetworkMessage::GenesisHandshake(block.calculate_hash()),
+        NetworkMessage::BlockRequestLocator { known_height: 5, locator: vec![block.calculate_hash()] },
+        NetworkMessage::GetBlocks { from_height: 3, limit: 100 },
+        NetworkMessage::Blocks(vec![block.clone()]),
+        NetworkMessage::GetBlocksAfter { known_height: 20, locator: vec![block.calculate_hash()], limit: 100 },
+        NetworkMessage::BlocksPage { start_height: 21, blocks: vec![block.clone()], more: false },
+        NetworkMessage::BlockAnnounce { hash: block.calculate_hash(), height: 22 },
+    ];
+    
+    for message in messages {
+        let encoded = encode_message(&message, WireFormat::Binary);
+        assert_eq!(encoded[1], WIRE_FORMAT_VERSION);
+        let decoded = decode_message(&encoded).unwrap();
+        // 消息没有实现PartialEq，比较两种编码的结果
+        assert_eq!(serde_json::to_vec(&decoded).unwrap(), serde_json::to_vec(&message).unwrap());
+    }
+    
+    // 空消息、未知版本和损坏的消息
+    assert_eq!(decode_message(b"").unwrap_err(), CodecError::Empty);
+    let mut future = encode_message(&NetworkMessage::BlockRequest, WireFormat::Binary);
+    future[1] = WIRE_FORMAT_VERSION + 1;
+    assert_eq!(decode_message(&future).unwrap_err(), CodecError::UnsupportedVersion(WIRE_FORMAT_VERSION + 1));
+    let truncated = encode_message(&NetworkMessage::Block(block), WireFormat::Binary);
+    assert!(matches!(decode_message(&truncated[..truncated.len() / 2]), Err(CodecError::Malformed(_))));
+}
+
+#[tokio::test]
+async fn test_duplicate_block_forwarded_once() {
+    let (app_tx, mut app_rx) = mpsc::channel(10);
+    let mut network = Network::new_with_channel(chain_hasher(), app_tx).await;
+    let block = create_test_block();
+    let json = encode_message(&NetworkMessage::Block(block.clone()), WireFormat::Json);
+    let binary = encode_message(&NetworkMessage::BlockBinary(block.to_bytes()), WireFormat::Binary);
+    
+    // 同一区块从多个节点到达（包括二进制编码的副本），只转发一次
+    for _ in 0..5 {
+        network.handle_gossip_message(Some(random_peer_id()), &json).await;
+    }
+    network.handle_gossip_message(Some(random_peer_id()), &binary).await;
+    
+    match app_rx.try_recv() {
+        Ok(NetworkEvent::NewBlock(received)) => assert_eq!(received.calculate_hash(), block.calculate_hash()),
+        other => panic!("意外的事件: {:?}", other),
+    }
+    assert!(app_rx.try_recv().is_err());
+    
+    // 控制消息不去重，每个同步请求都会被转发
+    let request = encode_message(&NetworkMessage::BlockRequest, WireFormat::Binary);
+    network.handle_gossip_message(Some(random_peer_id()), &request).await;
+    network.handle_gossip_message(Some(random_peer_id()), &request).await;
+    assert!(matches!(app_rx.try_recv(), Ok(NetworkEvent::BlocksRequested(_))));
+    assert!(matches!(app_rx.try_recv(), Ok(NetworkEvent::BlocksRequested(_))));
+}
+
+#[tokio::test]
+async fn test_sync_request_names_requester_and_shutdown_stops_network() {
+    let (app_tx, mut app_rx) = mpsc::channel(10);
+    let mut network = Network::new_with_channel(chain_hasher(), app_tx).await;
+    
+    // 收到的同步请求携带请求方，应用层用SendBlocksTo回复给它
+    let requester = random_peer_id();
+    let request = encode_message(&NetworkMessage::BlockRequest, WireFormat::Json);
+    network.handle_gossip_message(Some(requester), &request).await;
+    assert!(matches!(app_rx.try_recv(), Ok(NetworkEvent::BlocksRequested(peer)) if peer == requester));
+    
+    // 来源未知的请求无法回复，不转发到应用层
+    network.handle_gossip_message(None, &request).await;
+    assert!(app_rx.try_recv().is_err());
+    
+    // 命令走独立的通道，停止命令让网络任务正常返回
+    let commands = network.get_event_sender();
+    let handle = tokio::spawn(async move { network.start().await.is_ok() });
+    commands.send(NetworkCommand::Shutdown).await.unwrap();
+    assert!(timeout(Duration::from_secs(10), handle).await.expect("网络任务没有停止").unwrap());
+}
+
+#[test]
+fn test_large_block_response_compressed() {
+    // 足够长的链使区块响应超过压缩阈值
+    let blocks = vec![create_test_block(); 200];
+    let message = NetworkMessage::BlockResponse(blocks);
+    let encoded = encode_message(&message, WireFormat::Json);
+    assert!(encoded.len() > COMPRESSION_THRESHOLD);
+    
+    let compressed = compress_payload(encoded.clone());
+    assert!(compressed.len() < encoded.len());
+    let decoded = decode_message(&compressed).unwrap();
+    assert_eq!(serde_json::to_vec(&decoded).unwrap(), encoded);
+    
+    // 小消息保持原样
+    let small = encode_message(&NetworkMessage::BlockResponse(vec![create_test_block()]), WireFormat::Json);
+    assert_eq!(compress_payload(small.clone()), small);
+    
+    // 旧节点的能力声明没有压缩字段
+    let legacy = br#"{"Capabilities":{"binary_blocks":true}}"#;
+    assert!(matches!(
+        decode_message(legacy).unwrap(),
+        NetworkMessage::Capabilities { binary_blocks: true, compression: false }
+    ));
+}
+
+#[tokio::test]
+async fn test_private_node_requests_relay_registration() {
+    let relay: libp2p::Multiaddr = format!("/ip4/203.0.113.7/tcp/4001/p2p/{}", random_peer_id()).parse().unwrap();
+    let circuit = relay_circuit_addr(&relay);
+    assert!(circuit.to_string().ends_with("/p2p-circuit"));
+    
+    // 没有中继服务器时无法注册
+    let (app_tx, _app_rx) = mpsc::channel(10);
+    let mut network = Network::new_with_channel(chain_hasher(), app_tx).await;
+    assert_eq!(network.handle_nat_status(NatStatus::Private), None);
+    
+    // 收到中继公告后，位于NAT之后的节点在中继上注册，且只注册一次
+    let announce = encode_message(&NetworkMessage::RelayServerAnnounce(relay.clone()), WireFormat::Json);
+    network.handle_gossip_message(Some(random_peer_id()), &announce).await;
+    assert_eq!(network.handle_nat_status(NatStatus::Private), Some(circuit.clone()));
+    assert_eq!(network.nat_status(), NatStatus::Private);
+    assert_eq!(network.handle_nat_status(NatStatus::Private), None);
+    
+    // 可以直接拨入的节点不使用中继
+    let mut public = Network::new(chain_hasher()).await;
+    public.set_relay_server(relay);
+    assert_eq!(public.handle_nat_status(NatStatus::Public), None);
+    assert_eq!(public.handle_nat_status(NatStatus::Private), Some(circuit));
+}
+
+#[tokio::test]
+async fn test_private_node_reserves_circuit_on_announced_relay() {
+    // 中继节点监听固定的本地端口，并把它作为外部地址公告
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let relay_addr: libp2p::Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap();
+    let (relay_tx, mut relay_rx) = mpsc::channel(100);
+    let mut relay = Network::new_with_channel(chain_hasher(), relay_tx).await;
+    relay.enable_relay_service();
+    relay.set_listen_addr(relay_addr.clone());
+    relay.set_external_addr(relay_addr.clone());
+    let relay_handle = tokio::spawn(async move {
+        let _ = relay.start().await;
+    });
+    timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(NetworkEvent::Listening { .. }) = relay_rx.recv().await {
+                return;
+            }
+        }
+    }).await.expect("等待中继节点监听超时");
+    
+    // 位于NAT之后的节点连接中继节点，收到中继公告后通过中继传输监听电路地址
+    let (node_tx, mut node_rx) = mpsc::channel(100);
+    let mut node = Network::new_with_channel(chain_hasher(), node_tx).await;
+    let node_sender = node.get_event_sender();
+    node.dial(relay_addr).await.unwrap();
+    let node_handle = tokio::spawn(async move {
+        let _ = node.start().await;
+    });
+    node_sender.send(NetworkCommand::UpdateNatStatus(NatStatus::Private)).await.unwrap();
+    
+    let reserved = timeout(Duration::from_secs(20), async {
+        loop {
+            node_sender.send(NetworkCommand::QueryPeers).await.unwrap();
+            while let Ok(Some(event)) = timeout(Duration::from_millis(500), node_rx.recv()).await {
+                if let NetworkEvent::ConnectionInfo { listen_addrs, .. } = event {
+                    let circuit = listen_addrs.into_iter()
+                        .find(|addr| addr.iter().any(|protocol| protocol == libp2p::multiaddr::Protocol::P2pCircuit));
+                    if let Some(circuit) = circuit {
+                        return circuit;
+                    }
+                }
+            }
+        }
+    }).await.expect("节点没有在中继上预留电路地址");
+    assert!(reserved.to_string().starts_with(&format!("/ip4/127.0.0.1/tcp/{}/p2p/", port)));
+    
+    relay_handle.abort();
+    node_handle.abort();
+}
+
+#[tokio::test]
+async fn test_old_version_peer_degrades_gracefully() {
+    let mut network = Network::new(chain_hasher()).await;
+    let hash = "a".repeat(64);
+    
+    // 只发送能力声明的旧节点按旧协议版本处理，同步请求回退到完整同步
+    let old_peer = random_peer_id();
+    let capabilities = encode_message(&NetworkMessage::Capabilities { binary_blocks: true, compression: false }, WireFormat::Json);
+    network.handle_gossip_message(Some(old_peer), &capabilities).await;
+    assert_eq!(network.peer_version(&old_peer), Some(LEGACY_PROTOCOL_VERSION));
+    assert!(!network.all_peers_support(FEATURE_INCREMENTAL_SYNC));
+    assert!(matches!(network.sync_request_message(3, hash.clone()), NetworkMessage::BlockRequest));
+    
+    // 旧节点升级后发送握手，握手中的声明优先于能力声明
+    let handshake = NetworkMessage::Handshake {
+        protocol_version: PROTOCOL_VERSION,
+        supported_features: vec![String::from(FEATURE_INCREMENTAL_SYNC)],
+    };
+    network.handle_gossip_message(Some(old_peer), &encode_message(&handshake, WireFormat::Json)).await;
+    network.handle_gossip_message(Some(old_peer), &capabilities).await;
+    assert_eq!(network.peer_version(&old_peer), Some(PROTOCOL_VERSION));
+    assert!(!network.all_peers_support(FEATURE_BINARY_BLOCKS));
+    assert!(matches!(
+        network.sync_request_message(3, hash.clone()),
+        NetworkMessage::BlockRequestSince { known_height: 3, .. }
+    ));
+    
+    // 来自更新版本的节点按本节点的版本通信
+    let newer_peer = random_peer_id();
+    assert!(network.handle_handshake(newer_peer, PROTOCOL_VERSION + 1, vec![String::from(FEATURE_INCREMENTAL_SYNC)]));
+    assert_eq!(network.peer_version(&newer_peer), Some(PROTOCOL_VERSION));
+    
+    // 低于最低支持版本的节点被拒绝
+    let ancient_peer = random_peer_id();
+    assert!(!network.handle_handshake(ancient_peer, MIN_SUPPORTED_VERSION - 1, vec![]));
+    assert_eq!(network.peer_version(&ancient_peer), None);
+}
+
+#[tokio::test]
+async fn test_request_specific_block() {
+    let (app_tx, mut app_rx) = mpsc::channel(10);
+    let mut network = Network::new_with_channel(chain_hasher(), app_tx).await;
+    
+    // 定向请求只能发给已连接的节点
+    let peer = random_peer_id();
+    assert_eq!(network.request_specific_block(peer, "a".repeat(64)), Err(NetworkError::PeerNotConnected(peer)));
+    
+    // 定向请求消息不经过gossipsub，广播收到时被忽略
+    for message in [NetworkMessage::GetBlock("a".repeat(64)), NetworkMessage::GetBlockByHeight(1), NetworkMessage::BlockNotFound] {
+        network.handle_gossip_message(Some(peer), &encode_message(&message, WireFormat::Json)).await;
+    }
+    assert!(app_rx.try_recv().is_err());
+    
+    // 应用层按哈希或高度查找请求的区块，区块链保存在临时目录中
+    let (mut blockchain, dir) = temp_chain("block_query");
+    blockchain.add_block(vec![create_test_block().transactions.remove(0)]);
+    let tip_hash = blockchain.tip().calculate_hash();
+    assert_eq!(BlockQuery::Hash(tip_hash.clone()).find(&blockchain).map(Block::calculate_hash), Some(tip_hash.clone()));
+    assert_eq!(BlockQuery::Height(1).find(&blockchain).map(Block::calculate_hash), Some(tip_hash));
+    assert!(BlockQuery::Hash("b".repeat(64)).find(&blockchain).is_none());
+    assert!(BlockQuery::Height(2).find(&blockchain).is_none());
+    assert!(matches!(NetworkMessage::from(BlockQuery::Height(1)), NetworkMessage::GetBlockByHeight(1)));
+    assert!(dir.join("blockchain.json").exists());
+    
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_inbound_connections_limited() {
+    let mut network = Network::new(chain_hasher()).await;
+    network.set_max_connections(5);
+    assert_eq!(DEFAULT_OUTBOUND_RESERVE, 2);
+    
+    // 入站连接只能占用未保留的槽位，第N+1个入站连接被拒绝
+    let inbound_slots = 5 - DEFAULT_OUTBOUND_RESERVE;
+    for _ in 0..inbound_slots {
+        assert!(network.admit_connection(random_peer_id(), true));
+    }
+    let rejected = random_peer_id();
+    assert!(!network.admit_connection(rejected, true));
+    assert_eq!(network.connected_peer_count(), inbound_slots);
+    assert_eq!(network.inbound_peer_count(), inbound_slots);
+    assert!(network.get_connected_peers_info().iter().all(|(peer, _)| *peer != rejected));
+    
+    // 保留的槽位仍可用于主动连接，直到达到最大连接数
+    assert!(network.admit_connection(random_peer_id(), false));
+    assert!(network.admit_connection(random_peer_id(), false));
+    assert!(!network.admit_connection(random_peer_id(), false));
+    assert_eq!(network.connected_peer_count(), 5);
+    
+    // 不保留槽位时入站连接可以用满最大连接数
+    let mut open = Network::new(chain_hasher()).await;
+    open.set_max_connections(2);
+    open.set_outbound_reserve(0);
+    assert!(open.admit_connection(random_peer_id(), true));
+    assert!(open.admit_connection(random_peer_id(), true));
+    assert!(!open.admit_connection(random_peer_id(), true));
+}
+
+#[tokio::test]
+async fn test_peer_id_stable_after_start() {
+    let (app_tx, mut app_rx) = mpsc::channel(10);
+    let mut network = Network::new_with_channel(chain_hasher(), app_tx).await;
+    let peer_id = network.peer_id();
+    
+    let handle = tokio::spawn(async move {
+        let _ = network.start().await;
+    });
+    
+    // 启动后swarm使用的本地节点ID与启动前公布的节点ID一致
+    let listening = timeout(Duration::from_secs(10), async {
+        loop {
+            match app_rx.recv().await {
+                Some(NetworkEvent::Listening { peer_id, .. }) => return Some(peer_id),
+                Some(_) => continue,
+                None => return None,
+            }
+        }
+    }).await.expect("等待监听事件超时");
+    handle.abort();
+    
+    assert_eq!(listening, Some(peer_id));
+}
+
+#[tokio::test]
+async fn test_message_rate_limit_drops_excess() {
+    let mut network = Network::new(chain_hasher()).await;
+    let spammer = random_peer_id();
+    
+    // 默认配置下1秒内的第101条消息被丢弃，并计入不当行为分
+    let accepted = (0..=DEFAULT_RATE_LIMIT_CAPACITY)
+        .filter(|_| network.message_rate_limiter(spammer))
+        .count();
+    assert_eq!(accepted, DEFAULT_RATE_LIMIT_CAPACITY as usize);
+    assert_eq!(network.misbehavior_score(&spammer), 1);
+    
+    // 限速按节点独立计算
+    let other = random_peer_id();
+    assert!(network.message_rate_limiter(other));
+    assert_eq!(network.misbehavior_score(&other), 0);
+    
+    // 重新配置后按新的容量计数
+    network.set_rate_limit(1, 1);
+    assert!(network.message_rate_limiter(spammer));
+    assert!(!network.message_rate_limiter(spammer));
+    assert_eq!(network.misbehavior_score(&spammer), 2);
+    assert!(!network.is_banned(&spammer));
+    
+    // 计分达到阈值后拒绝该节点的连接，其他节点不受影响
+    while network.misbehavior_score(&spammer) < MISBEHAVIOR_BAN_THRESHOLD {
+        assert!(!network.message_rate_limiter(spammer));
+    }
+    assert!(network.is_banned(&spammer));
+    assert!(!network.admit_connection(spammer, false));
+    assert!(network.admit_connection(other, false));
+}
+
+#[test]
+fn test_rate_limiter_refills_over_time() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(2, 10);
+    assert!(limiter.try_acquire_at(start));
+    assert!(limiter.try_acquire_at(start));
+    assert!(!limiter.try_acquire_at(start));
+    
+    // 每秒补充10个令牌，不足一个令牌的时间不补充但也不丢失
+    assert!(!limiter.try_acquire_at(start + Duration::from_millis(60)));
+    assert!(limiter.try_acquire_at(start + Duration::from_millis(120)));
+    assert_eq!(limiter.available(), 0);
+    
+    // 补充的令牌不超过容量
+    assert!(limiter.try_acquire_at(start + Duration::from_secs(60)));
+    assert_eq!(limiter.available(), 1);
+}
+
+#[tokio::test]
+async fn test_different_genesis_refuses_to_peer() {
+    let mainnet = Blockchain::new(1);
+    let regtest = Blockchain::new_with_genesis(GenesisConfig::regtest());
+    assert!(mainnet.matches_genesis(Blockchain::new(1).genesis()));
+    assert!(!mainnet.matches_genesis(regtest.genesis()));
+    assert_eq!(mainnet.export_genesis_block(), serde_json::to_value(mainnet.genesis()).unwrap());
+    
+    let mut network = Network::new(chain_hasher()).await;
+    network.set_genesis_hash(mainnet.genesis().calculate_hash());
+    
+    // 创世区块相同的节点保持连接
+    let same = random_peer_id();
+    assert!(network.admit_connection(same, true));
+    let handshake = NetworkMessage::GenesisHandshake(Blockchain::new(1).genesis().calculate_hash());
+    network.handle_gossip_message(Some(same), &encode_message(&handshake, WireFormat::Json)).await;
+    assert!(network.admit_connection(same, true));
+    
+    // 创世区块不同的节点被断开，之后的连接也被拒绝
+    let other = random_peer_id();
+    assert!(!network.handle_genesis_handshake(other, &regtest.genesis().calculate_hash()));
+    assert!(!network.admit_connection(other, true));
+    assert!(!network.admit_connection(other, false));
+    assert_eq!(network.connected_peer_count(), 1);
+}
+
+#[test]
+fn test_reconnect_backoff_increases_delay() {
+    let mut backoff = ReconnectBackoff::default();
+    let peer = random_peer_id();
+    
+    // 连续失败时等待时间翻倍，直到上限
+    let delays: Vec<_> = std::iter::from_fn(|| backoff.next_delay(peer)).collect();
+    assert_eq!(delays.len(), MAX_RECONNECT_ATTEMPTS as usize);
+    assert_eq!(delays[0], RECONNECT_BASE_DELAY);
+    assert_eq!(delays[1], RECONNECT_BASE_DELAY * 2);
+    assert!(delays.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert_eq!(*delays.last().unwrap(), RECONNECT_MAX_DELAY);
+    
+    // 重连次数用完后放弃，状态被清除
+    assert!(!backoff.is_pending(&peer));
+    
+    // 连接成功后重新从初始等待时间开始
+    assert_eq!(backoff.next_delay(peer), Some(RECONNECT_BASE_DELAY));
+    assert_eq!(backoff.next_delay(peer), Some(RECONNECT_BASE_DELAY * 2));
+    backoff.reset(&peer);
+    assert!(!backoff.is_pending(&peer));
+    assert_eq!(backoff.next_delay(peer), Some(RECONNECT_BASE_DELAY));
+}
+
+#[tokio::test]
+async fn test_identity_file_keeps_peer_id() {
+    let path = std::env::temp_dir().join(format!("node_key_{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    
+    // 第一次运行生成并保存密钥，重新创建后节点ID不变
+    let first = Network::new(chain_hasher()).await.with_identity_file(&path).unwrap().peer_id();
+    let second = Network::new(chain_hasher()).await.with_identity_file(&path).unwrap().peer_id();
+    assert_eq!(first, second);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    
+    // 损坏的密钥文件返回错误而不是崩溃，也不会被覆盖
+    std::fs::write(&path, b"not a key").unwrap();
+    assert!(matches!(
+        Network::new(chain_hasher()).await.with_identity_file(&path),
+        Err(NetworkError::InvalidIdentity { .. })
+    ));
+    assert_eq!(std::fs::read(&path).unwrap(), b"not a key");
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_connection_info_lists_connected_peer() {
+    let (tx1, mut rx1) = mpsc::channel(100);
+    let mut node1 = Network::new_with_channel(chain_hasher(), tx1).await;
+    let node1_id = node1.peer_id();
+    let (tx2, mut rx2) = mpsc::channel(100);
+    let mut node2 = Network::new_with_channel(chain_hasher(), tx2).await;
+    let node2_sender = node2.get_event_sender();
+    
+    let node1_handle = tokio::spawn(async move {
+        let _ = node1.start().await;
+    });
+    let node1_addr = timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(NetworkEvent::Listening { address, .. }) = rx1.recv().await {
+                return address;
+            }
+        }
+    }).await.expect("等待节点1监听超时");
+    
+    node2.dial(node1_addr).await.unwrap();
+    let node2_handle = tokio::spawn(async move {
+        let _ = node2.start().await;
+    });
+    
+    // 连接建立后，节点2的连接信息中节点1显示为已连接，并包含节点2自己的监听地址
+    let connected = timeout(Duration::from_secs(15), async {
+        loop {
+            node2_sender.send(NetworkCommand::QueryPeers).await.unwrap();
+            while let Ok(Some(event)) = timeout(Duration::from_millis(500), rx2.recv()).await {
+                if let NetworkEvent::ConnectionInfo { connected_peers, all_peers, listen_addrs } = event {
+                    if connected_peers.iter().any(|(peer, _)| *peer == node1_id) {
+                        return (all_peers, listen_addrs);
+                    }
+                }
+            }
+        }
+    }).await;
+    node1_handle.abort();
+    node2_handle.abort();
+    
+    let (all_peers, listen_addrs) = connected.expect("节点2没有连接到节点1");
+    assert!(!listen_addrs.is_empty());
+    assert!(all_peers.iter().all(|(peer, _, is_connected)| *peer != node1_id || *is_connected));
+}
+
+#[tokio::test]
+async fn test_locator_request_falls_back_for_old_peers() {
+    let mut network = Network::new(chain_hasher()).await;
+    let locator = vec!["a".repeat(64), "b".repeat(64)];
+    
+    // 节点只支持增量同步时以定位器中的链顶哈希请求增量同步
+    let peer = random_peer_id();
+    assert!(network.handle_handshake(peer, PROTOCOL_VERSION - 1, vec![String::from(FEATURE_INCREMENTAL_SYNC)]));
+    assert!(matches!(
+        network.locator_request_message(7, locator.clone()),
+        NetworkMessage::BlockRequestSince { known_height: 7, ref known_hash } if *known_hash == "a".repeat(64)
+    ));
+    
+    // 所有节点都支持定位器同步时发送完整定位器
+    assert!(network.handle_handshake(peer, PROTOCOL_VERSION, vec![String::from(FEATURE_INCREMENTAL_SYNC), String::from(FEATURE_BLOCK_LOCATOR)]));
+    assert!(matches!(
+        network.locator_request_message(7, locator.clone()),
+        NetworkMessage::BlockRequestLocator { known_height: 7, locator: ref sent } if *sent == locator
+    ));
+}
+
+#[tokio::test]
+async fn test_paged_sync_converges_without_gossip_responses() {
+    // 节点B有10个区块，节点A只有相同的创世区块
+    let (mut chain_b, dir) = temp_chain("paged_sync");
+    for height in 1..=10 {
+        let coinbase = Transaction::new(
+            vec![TxInput { prev_tx: String::from(COINBASE_TX_ID), prev_index: 0, script_sig: format!("区块{}奖励", height) }],
+            vec![TxOutput { value: 50, script_pubkey: String::from("矿工地址") }],
+        );
+        chain_b.add_block(vec![coinbase]);
+    }
+    let mut chain_a = chain_b.fresh_copy();
+    let tip_b = chain_b.tip().calculate_hash();
+    
+    let (tx_a, mut rx_a) = mpsc::channel(100);
+    let mut node_a = Network::new_with_channel(chain_hasher(), tx_a).await;
+    // 每页4个区块，10个区块需要3页，第4页为空表示已经同步到最新
+    node_a.set_sync_page_size(4);
+    let commands_a = node_a.get_event_sender();
+    let (tx_b, mut rx_b) = mpsc::channel(100);
+    let mut node_b = Network::new_with_channel(chain_hasher(), tx_b).await;
+    let commands_b = node_b.get_event_sender();
+    
+    let node_b_handle = tokio::spawn(async move {
+        let _ = node_b.start().await;
+    });
+    let node_b_addr = timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(NetworkEvent::Listening { address, .. }) = rx_b.recv().await {
+                return address;
+            }
+        }
+    }).await.expect("等待节点B监听超时");
+    
+    // 节点B的应用层只回复分页请求，从不发送区块响应广播
+    let responder = tokio::spawn(async move {
+        while let Some(event) = rx_b.recv().await {
+            if let NetworkEvent::BlocksPageRequested { request_id, from_height, limit, .. } = event {
+                let blocks = chain_b.blocks_from(from_height as usize, limit as usize).to_vec();
+                let _ = commands_b.send(NetworkCommand::SendBlocksPage { request_id, blocks }).await;
+            }
+        }
+    });
+    
+    node_a.dial(node_b_addr).await.unwrap();
+    let node_a_handle = tokio::spawn(async move {
+        let _ = node_a.start().await;
+    });
+    
+    // 节点A连接后向节点B逐页请求，直到收到空页
+    let synced = timeout(Duration::from_secs(20), async {
+        let mut pages = 0;
+        let mut gossip_responses = 0;
+        let mut started = false;
+        while let Some(event) = rx_a.recv().await {
+            match event {
+                NetworkEvent::PeerConnected(peer) if !started => {
+                    started = true;
+                    commands_a.send(NetworkCommand::RequestBlocksFrom(peer, chain_a.height() as u64 + 1)).await.unwrap();
+                }
+                NetworkEvent::BlocksPageReceived { peer_id, from_height, blocks, .. } => {
+                    if blocks.is_empty() {
+                        return (chain_a, pages, gossip_responses);
+                    }
+                    pages += 1;
+                    chain_a.connect_blocks_since(from_height as usize, blocks).unwrap();
+                    commands_a.send(NetworkCommand::RequestBlocksFrom(peer_id, chain_a.height() as u64 + 1)).await.unwrap();
+                }
+                NetworkEvent::BlocksReceived(_) | NetworkEvent::BlocksSinceReceived { .. } => gossip_responses += 1,
+                NetworkEvent::BlocksPageFailed { .. } => panic!("分页请求失败"),
+                _ => {}
+            }
+        }
+        panic!("节点A的事件通道已关闭");
+    }).await;
+    node_a_handle.abort();
+    node_b_handle.abort();
+    responder.abort();
+    
+    let (chain_a, pages, gossip_responses) = synced.expect("节点A没有同步到节点B的链顶");
+    assert_eq!(chain_a.height(), 10);
+    assert_eq!(chain_a.tip().calculate_hash(), tip_b);
+    assert_eq!(pages, 3);
+    assert_eq!(gossip_responses, 0);
+    
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_locator_paged_sync_transfers_only_diverged_blocks() {
+    fn mine(chain: &mut Blockchain, label: &str, count: usize) {
+        for _ in 0..count {
+            let coinbase = Transaction::new(
+                vec![TxInput { prev_tx: String::from(COINBASE_TX_ID), prev_index: 0, script_sig: format!("{}区块{}奖励", label, chain.height() + 1) }],
+                vec![TxOutput { value: 50, script_pubkey: String::from("矿工地址") }],
+            );
+            chain.add_block(vec![coinbase]);
+        }
+    }
+    
+    // 两个节点共享20个区块，之后节点A挖出1个、节点B挖出3个，节点B的分叉工作量更大
+    let (mut chain_a, dir) = temp_chain("locator_sync");
+    mine(&mut chain_a, "共同", 20);
+    let mut chain_b = chain_a.clone();
+    mine(&mut chain_a, "A", 1);
+    mine(&mut chain_b, "B", 3);
+    let tip_b = chain_b.tip().calculate_hash();
+    
+    let (tx_a, mut rx_a) = mpsc::channel(100);
+    let mut node_a = Network::new_with_channel(chain_hasher(), tx_a).await;
+    node_a.set_metrics(chain_a.metrics().clone());
+    let metrics_a = chain_a.metrics().clone();
+    let commands_a = node_a.get_event_sender();
+    let (tx_b, mut rx_b) = mpsc::channel(100);
+    let mut node_b = Network::new_with_channel(chain_hasher(), tx_b).await;
+    let commands_b = node_b.get_event_sender();
+    
+    let node_b_handle = tokio::spawn(async move {
+        let _ = node_b.start().await;
+    });
+    let node_b_addr = timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(NetworkEvent::Listening { address, .. }) = rx_b.recv().await {
+                return address;
+            }
+        }
+    }).await.expect("等待节点B监听超时");
+    
+    // 节点B按定位器找到共同区块，只回复之后的区块
+    let responder = tokio::spawn(async move {
+        while let Some(event) = rx_b.recv().await {
+            if let NetworkEvent::BlocksPageAfterRequested { request_id, locator, limit, .. } = event {
+                let (start_height, blocks, more) = chain_b.page_after_locator(&locator, limit as usize).unwrap();
+                let command = NetworkCommand::SendBlocksPageAfter { request_id, start_height: start_height as u64, blocks: blocks.to_vec(), more };
+                let _ = commands_b.send(command).await;
+            }
+        }
+    });
+    
+    node_a.dial(node_b_addr).await.unwrap();
+    let node_a_handle = tokio::spawn(async move {
+        let _ = node_a.start().await;
+    });
+    
+    let synced = timeout(Duration::from_secs(20), async {
+        let mut started = false;
+        while let Some(event) = rx_a.recv().await {
+            match event {
+                NetworkEvent::PeerConnected(peer_id) if !started => {
+                    started = true;
+                    let command = NetworkCommand::RequestBlocksAfter { peer_id, known_height: chain_a.height() as u64, locator: chain_a.block_locator() };
+                    commands_a.send(command).await.unwrap();
+                }
+                NetworkEvent::BlocksPageReceived { peer_id, from_height, blocks, more } => {
+                    if !blocks.is_empty() {
+                        chain_a.connect_blocks_since(from_height as usize, blocks).unwrap();
+                    }
+                    if !more {
+                        return chain_a;
+                    }
+                    let command = NetworkCommand::RequestBlocksAfter { peer_id, known_height: chain_a.height() as u64, locator: chain_a.block_locator() };
+                    commands_a.send(command).await.unwrap();
+                }
+                NetworkEvent::BlocksPageFailed { .. } => panic!("分页请求失败"),
+                _ => {}
+            }
+        }
+        panic!("节点A的事件通道已关闭");
+    }).await;
+    node_a_handle.abort();
+    node_b_handle.abort();
+    responder.abort();
+    
+    // 共同区块低于节点A的链顶，节点A切换到节点B的分叉，只有分叉后的3个区块经过网络
+    let chain_a = synced.expect("节点A没有同步到节点B的链顶");
+    assert_eq!(chain_a.height(), 23);
+    assert_eq!(chain_a.tip().calculate_hash(), tip_b);
+    assert_eq!(metrics_a.snapshot().blocks_synced, 3);
+    
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// 区块公告测试中节点应用层上报的事件
+#[derive(Debug, PartialEq)]
+enum AnnounceReport {
+    Connected,
+    Announced(String),
+    Fetched(String),
+    FullBlock,
+}
+
+/// 模拟节点的应用层：缺少公告的区块时向公告方请求，回复其他节点的区块请求
+fn spawn_announce_app(
+    name: &'static str,
+    mut chain: Blockchain,
+    mut events: mpsc::Receiver<NetworkEvent>,
+    commands: mpsc::Sender<NetworkCommand>,
+    reports: mpsc::Sender<(&'static str, AnnounceReport)>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let report = match event {
+                NetworkEvent::PeerConnected(_) => AnnounceReport::Connected,
+                NetworkEvent::BlockAnnounced { peer_id, hash, .. } => {
+                    if chain.get_block_by_hash(&hash).is_none() {
+                        let _ = commands.send(NetworkCommand::RequestBlock { peer_id, query: BlockQuery::Hash(hash.clone()) }).await;
+                    }
+                    AnnounceReport::Announced(hash)
+                }
+                NetworkEvent::BlockRequested { request_id, query, .. } => {
+                    let block = query.find(&chain).cloned();
+                    let _ = commands.send(NetworkCommand::SendBlockTo { request_id, block }).await;
+                    continue;
+                }
+                NetworkEvent::BlockFetched { block, .. } => {
+                    assert!(chain.validate_block(&block));
+                    chain.add_received_block(block.clone());
+                    AnnounceReport::Fetched(block.calculate_hash())
+                }
+                NetworkEvent::NewBlock(_) => AnnounceReport::FullBlock,
+                _ => continue,
+            };
+            let _ = reports.send((name, report)).await;
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_block_announce_fetches_each_block_once() {
+    // 三个节点共享创世区块，节点A挖出一个新区块
+    let (mut chain_a, dir) = temp_chain("block_announce");
+    let chain_b = chain_a.fresh_copy();
+    let chain_c = chain_a.fresh_copy();
+    chain_a.add_block(vec![create_test_block().transactions.remove(0)]);
+    let block = chain_a.tip().clone();
+    let hash = block.calculate_hash();
+    
+    let (reports_tx, mut reports) = mpsc::channel(100);
+    let mut handles = Vec::new();
+    let mut commands = Vec::new();
+    let mut addresses: Vec<libp2p::Multiaddr> = Vec::new();
+    let mut metrics = Vec::new();
+    for (name, chain) in [("A", chain_a), ("B", chain_b), ("C", chain_c)] {
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut node = Network::new_with_channel(chain_hasher(), tx).await;
+        let node_metrics = Arc::new(Metrics::new());
+        node.set_metrics(node_metrics.clone());
+        let node_commands = node.get_event_sender();
+        // 后启动的节点连接所有先启动的节点
+        for address in &addresses {
+            node.dial(address.clone()).await.unwrap();
+        }
+        handles.push(tokio::spawn(async move {
+            let _ = node.start().await;
+        }));
+        let address = timeout(Duration::from_secs(10), async {
+            loop {
+                if let Some(NetworkEvent::Listening { address, .. }) = rx.recv().await {
+                    return address;
+                }
+            }
+        }).await.expect("等待节点监听超时");
+        addresses.push(address);
+        handles.push(spawn_announce_app(name, chain, rx, node_commands.clone(), reports_tx.clone()));
+        commands.push(node_commands);
+        metrics.push(node_metrics);
+    }
+    
+    // 等待节点A连上另外两个节点，并完成订阅和握手
+    let mut connected = 0;
+    timeout(Duration::from_secs(20), async {
+        while connected < 2 {
+            if let Some(("A", AnnounceReport::Connected)) = reports.recv().await {
+                connected += 1;
+            }
+        }
+    }).await.expect("等待节点连接超时");
+    sleep(Duration::from_secs(3)).await;
+    
+    // 节点A公告区块后，节点B和C各自请求一次区块内容
+    commands[0].send(NetworkCommand::BroadcastBlock { block: block.clone(), height: 1 }).await.unwrap();
+    let mut received = Vec::new();
+    timeout(Duration::from_secs(20), async {
+        while received.len() < 2 {
+            match reports.recv().await {
+                Some((name, AnnounceReport::Fetched(fetched))) => {
+                    assert_eq!(fetched, hash);
+                    received.push(name);
+                }
+                Some((name, AnnounceReport::FullBlock)) => panic!("节点{}收到了完整区块广播", name),
+                Some(_) => {}
+                None => panic!("上报通道已关闭"),
+            }
+        }
+    }).await.expect("节点没有获取到公告的区块");
+    received.sort();
+    assert_eq!(received, vec!["B", "C"]);
+    
+    // 节点A重复公告、节点B再次公告同一区块，都被忽略
+    commands[0].send(NetworkCommand::BroadcastBlock { block: block.clone(), height: 1 }).await.unwrap();
+    commands[1].send(NetworkCommand::BroadcastBlock { block, height: 1 }).await.unwrap();
+    sleep(Duration::from_secs(3)).await;
+    if let Ok((name, report)) = reports.try_recv() {
+        panic!("节点{}处理了重复的区块公告: {:?}", name, report);
+    }
+    
+    for handle in handles {
+        handle.abort();
+    }
+    let received: Vec<u64> = metrics.iter().map(|metrics| metrics.snapshot().blocks_received).collect();
+    assert_eq!(received, vec![0, 1, 1]);
+    
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_seen_cache_delivers_each_transaction_once() {
+    // 有效期过后同一交易视为新消息
+    let (app_tx, mut app_rx) = mpsc::channel(10);
+    let mut network = Network::new_with_channel(chain_hasher(), app_tx).await;
+    network.set_seen_cache(10, Duration::from_millis(50));
+    let single_metrics = Arc::new(Metrics::new());
+    network.set_metrics(single_metrics.clone());
+    let message = encode_message(&NetworkMessage::Transaction(create_test_transaction()), WireFormat::Json);
+    network.handle_gossip_message(Some(random_peer_id()), &message).await;
+    network.handle_gossip_message(Some(random_peer_id()), &message).await;
+    sleep(Duration::from_millis(60)).await;
+    network.handle_gossip_message(Some(random_peer_id()), &message).await;
+    assert!(matches!(app_rx.try_recv(), Ok(NetworkEvent::NewTransaction(_))));
+    assert!(matches!(app_rx.try_recv(), Ok(NetworkEvent::NewTransaction(_))));
+    assert!(app_rx.try_recv().is_err());
+    let snapshot = single_metrics.snapshot();
+    assert_eq!((snapshot.seen_cache_hits, snapshot.seen_cache_misses), (1, 2));
+    
+    // 三个互相连接的节点，每个节点的应用层收到交易后都尝试重新广播
+    let (reports_tx, mut reports) = mpsc::channel(100);
+    let mut handles = Vec::new();
+    let mut commands = Vec::new();
+    let mut addresses: Vec<libp2p::Multiaddr> = Vec::new();
+    let mut metrics = Vec::new();
+    for name in ["A", "B", "C"] {
+        let (tx, mut rx) = mpsc::channel(100);
+        let mut node = Network::new_with_channel(chain_hasher(), tx).await;
+        let node_metrics = Arc::new(Metrics::new());
+        node.set_metrics(node_metrics.clone());
+        let node_commands = node.get_event_sender();
+        for address in &addresses {
+            node.dial(address.clone()).await.unwrap();
+        }
+        handles.push(tokio::spawn(async move {
+            let _ = node.start().await;
+        }));
+        let address = timeout(Duration::from_secs(10), async {
+            loop {
+                if let Some(NetworkEvent::Listening { address, .. }) = rx.recv().await {
+                    return address;
+                }
+            }
+        }).await.expect("等待节点监听超时");
+        addresses.push(address);
+        
+        let app_commands = node_commands.clone();
+        let reports = reports_tx.clone();
+        handles.push(tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    NetworkEvent::PeerConnected(_) => {
+                        let _ = reports.send((name, None)).await;
+                    }
+                    NetworkEvent::NewTransaction(tx) => {
+                        let _ = reports.send((name, Some(tx.calculate_hash()))).await;
+                        let _ = app_commands.send(NetworkCommand::BroadcastTransaction(tx)).await;
+                    }
+                    _ => {}
+                }
+            }
+        }));
+        commands.push(node_commands);
+        metrics.push(node_metrics);
+    }
+    
+    // 每个节点各连上另外两个节点后再等待订阅完成
+    let mut connections = 0;
+    timeout(Duration::from_secs(20), async {
+        while connections < 6 {
+            if let Some((_, None)) = reports.recv().await {
+                connections += 1;
+            }
+        }
+    }).await.expect("等待节点连接超时");
+    sleep(Duration::from_secs(3)).await;
+    
+    // 节点A和节点B各发出一笔交易
+    let tx_a = create_test_transaction();
+    let mut tx_b = create_test_transaction();
+    tx_b.outputs[0].value += 1;
+    commands[0].send(NetworkCommand::BroadcastTransaction(tx_a.clone())).await.unwrap();
+    commands[1].send(NetworkCommand::BroadcastTransaction(tx_b.clone())).await.unwrap();
+    sleep(Duration::from_secs(4)).await;
+    
+    let mut seen = Vec::new();
+    while let Ok((name, Some(hash))) = reports.try_recv() {
+        seen.push((name, hash));
+    }
+    seen.sort();
+    let mut expected = vec![
+        ("A", tx_b.calculate_hash()),
+        ("B", tx_a.calculate_hash()),
+        ("C", tx_a.calculate_hash()),
+        ("C", tx_b.calculate_hash()),
+    ];
+    expected.sort();
+    assert_eq!(seen, expected);
+    
+    // 只统计收到的消息：每笔收到的交易记一次未命中，节点拒绝重新广播不计入命中
+    for (node, received) in metrics.iter().zip([1, 1, 2]) {
+        let snapshot = node.snapshot();
+        assert_eq!(snapshot.seen_cache_hits, 0, "{:?}", snapshot);
+        assert_eq!(snapshot.seen_cache_misses, received, "{:?}", snapshot);
+    }
+    
+    for handle in handles {
+        handle.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_listen_addr_binds_configured_port_or_fails() {
+    // 先占用一个空闲端口再释放，得到一个可用的指定端口
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let configured: libp2p::Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap();
+
+    let (tx, mut rx) = mpsc::channel(100);
+    let mut node = Network::new_with_channel(chain_hasher(), tx).await;
+    node.set_listen_addr(configured.clone());
+    node.set_external_addr("/ip4/203.0.113.8/tcp/4001".parse().unwrap());
+    let handle = tokio::spawn(async move {
+        let _ = node.start().await;
+    });
+    let address = timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(NetworkEvent::Listening { address, .. }) = rx.recv().await {
+                return address;
+            }
+        }
+    }).await.expect("等待监听超时");
+    assert_eq!(address, configured, "节点应该监听指定的地址");
+    handle.abort();
+
+    // 指定的端口已被占用时，start立即返回错误，而不是换用其他端口
+    let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let busy_port = occupied.local_addr().unwrap().port();
+    let (tx, _rx) = mpsc::channel(100);
+    let mut node = Network::new_with_channel(chain_hasher(), tx).await;
+    node.set_listen_addr(format!("/ip4/127.0.0.1/tcp/{}", busy_port).parse().unwrap());
+    let result = timeout(Duration::from_secs(5), node.start()).await.expect("绑定失败时应该立即返回");
+    assert!(result.is_err(), "端口被占用时应该返回错误");
+    drop(occupied);
+}
@@ -1,14 +1,10 @@
-use blockchain_demo::block::{Transaction, TxInput, TxOutput};
+use blockchain_demo::block::{Block, OutPoint, Transaction, TxInput, TxOutput, UtxoEntry};
+use blockchain_demo::wallet::Wallet;
 use std::collections::HashMap;
-use sha2::{Sha256, Digest};
-use hex;
 
-// 辅助函数：计算交易哈希
+// 辅助函数：计算交易ID，UTXO集合以它为键
 fn calculate_tx_hash(tx: &Transaction) -> String {
-    let mut hasher = Sha256::new();
-    let serialized = serde_json::to_string(tx).unwrap();
-    hasher.update(serialized.as_bytes());
-    hex::encode(hasher.finalize())
+    tx.txid()
 }
 
 #[test]
@@ -164,4 +160,90 @@ fn test_utxo_tracking() {
         .sum::<u64>();
     
     assert_eq!(total_value, 100); // 总值保持不变：70 + 30 = 100
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_size_estimate_ignores_signatures() {
+    let mut wallet = Wallet::new();
+    let utxos = vec![
+        (OutPoint { tx_id: "a".repeat(64), index: 0 }, UtxoEntry { value: 30, script_pubkey: wallet.address.clone() }),
+        (OutPoint { tx_id: "b".repeat(64), index: 1 }, UtxoEntry { value: 30, script_pubkey: wallet.address.clone() }),
+    ];
+    
+    // 2个输入、2个输出（含找零）的交易，签名前后估算一致，实际字节数随签名增长
+    let mut tx = wallet.create_transaction(&Wallet::new().address, 50, &utxos).unwrap();
+    let (estimate, unsigned) = (tx.size_bytes(), tx.size());
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(tx.outputs.len(), 2);
+    assert_eq!(tx.size_bytes(), 10 + 2 * 148 + 2 * 34);
+    assert_eq!(tx.size_bytes(), estimate);
+    assert!(tx.size() > unsigned);
+    
+    // 每字节手续费按实际线路字节数计算
+    assert_eq!(tx.fee_per_byte(), tx.fee as f64 / tx.size() as f64);
+}
+
+#[test]
+fn test_transaction_size_reflects_contents() {
+    let mut wallet = Wallet::new();
+    let utxos: Vec<_> = (0..5u32)
+        .map(|index| (OutPoint { tx_id: "a".repeat(64), index }, UtxoEntry { value: 10, script_pubkey: wallet.address.clone() }))
+        .collect();
+    let recipient = Wallet::new().address.clone();
+    
+    // 1个输入的小交易和5个输入的大交易
+    let mut small = wallet.create_transaction(&recipient, 5, &utxos[..1]).unwrap();
+    let mut large = wallet.create_transaction(&recipient, 45, &utxos).unwrap();
+    assert_eq!(small.inputs.len(), 1);
+    assert_eq!(large.inputs.len(), 5);
+    assert!(large.size() > small.size());
+    
+    // 大小是实际编码长度：签名会使交易变大，每个输入增加的字节数相同
+    let unsigned_small = small.size();
+    let unsigned_large = large.size();
+    wallet.sign_transaction(&mut small).unwrap();
+    wallet.sign_transaction(&mut large).unwrap();
+    let per_input = small.size() - unsigned_small;
+    assert!(per_input > 0);
+    assert_eq!(large.size() - unsigned_large, 5 * per_input);
+    
+    // 区块大小随交易增长
+    let mut block = Block::new("0".repeat(64), 1);
+    let empty = block.size();
+    block.transactions.push(small.clone());
+    assert!(block.size() > empty + small.size() - 1);
+    assert_eq!(block.size(), block.to_bytes().len());
+}
+
+#[test]
+fn test_signing_does_not_change_txid() {
+    let mut wallet = Wallet::new();
+    let utxos = vec![
+        (OutPoint { tx_id: "a".repeat(64), index: 0 }, UtxoEntry { value: 30, script_pubkey: wallet.address.clone() }),
+    ];
+    let mut tx = wallet.create_transaction(&Wallet::new().address, 20, &utxos).unwrap();
+    let unsigned_txid = tx.txid();
+    let unsigned_hash = tx.calculate_hash();
+    
+    // 签名只改变script_sig，内容哈希改变而交易ID不变
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert_eq!(tx.txid(), unsigned_txid);
+    assert_ne!(tx.calculate_hash(), unsigned_hash);
+    
+    // 同一交易的另一个签名也得到相同的交易ID
+    let mut resigned = tx.clone();
+    resigned.inputs[0].script_sig = String::from("另一个签名");
+    assert_eq!(resigned.txid(), unsigned_txid);
+    
+    // 输出或输入引用不同时交易ID不同
+    let mut changed = tx.clone();
+    changed.outputs[0].value += 1;
+    assert_ne!(changed.txid(), unsigned_txid);
+    
+    // Coinbase的script_sig用于区分不同的Coinbase交易，仍然计入交易ID
+    let coinbase = |data: &str| Transaction::new(
+        vec![TxInput { prev_tx: "0".repeat(64), prev_index: 0, script_sig: data.to_string() }],
+        vec![TxOutput { value: 50, script_pubkey: wallet.address.clone() }],
+    );
+    assert_ne!(coinbase("高度1").txid(), coinbase("高度2").txid());
+}
@@ -0,0 +1,171 @@
+use blockchain_demo::block::{OutPoint, Transaction, TxInput, TxOutput, UtxoEntry, COINBASE_TX_ID, MAX_OP_RETURN_DATA, OP_RETURN_PREFIX};
+use blockchain_demo::validation::{TxError, TxValidator};
+use blockchain_demo::wallet::Wallet;
+use std::collections::HashMap;
+
+const FUNDING_TX: &str = "资金交易";
+
+// 辅助函数：创建只包含一个输出的UTXO视图
+fn utxo_view(owner: &str, value: u64) -> HashMap<OutPoint, UtxoEntry> {
+    let mut utxos = HashMap::new();
+    utxos.insert(
+        OutPoint { tx_id: String::from(FUNDING_TX), index: 0 },
+        UtxoEntry { value, script_pubkey: String::from(owner) },
+    );
+    utxos
+}
+
+// 辅助函数：创建花费指定输出的交易
+fn spend(inputs: &[(&str, u32)], value: u64, fee: u64) -> Transaction {
+    let inputs = inputs.iter()
+        .map(|(prev_tx, prev_index)| TxInput {
+            prev_tx: String::from(*prev_tx),
+            prev_index: *prev_index,
+            script_sig: String::new(),
+        })
+        .collect();
+    Transaction::with_fee(inputs, vec![TxOutput { value, script_pubkey: String::from("接收地址") }], fee)
+}
+
+// 辅助函数：把所有输入标记为花费钱包地址的输出后用该钱包签名
+fn sign_as(wallet: &Wallet, tx: &mut Transaction) {
+    for input in &mut tx.inputs {
+        input.script_sig = wallet.address.clone();
+    }
+    wallet.sign_transaction(tx).unwrap();
+}
+
+fn coinbase(inputs: usize) -> Transaction {
+    let input = TxInput {
+        prev_tx: String::from(COINBASE_TX_ID),
+        prev_index: 0,
+        script_sig: String::from("矿工奖励"),
+    };
+    Transaction::new(vec![input; inputs], vec![TxOutput { value: 50, script_pubkey: String::from("矿工地址") }])
+}
+
+#[test]
+fn test_valid_transaction_passes() {
+    let wallet = Wallet::new();
+    let utxos = utxo_view(&wallet.address, 50);
+    let mut tx = spend(&[(FUNDING_TX, 0)], 40, 10);
+    sign_as(&wallet, &mut tx);
+
+    assert_eq!(TxValidator::default().validate(&tx, |outpoint| utxos.get(outpoint).cloned()), Ok(()));
+}
+
+#[test]
+fn test_structure_and_coinbase_rules() {
+    let validator = TxValidator::default();
+    let empty_view = |_: &OutPoint| None;
+
+    let no_inputs = Transaction::new(vec![], vec![TxOutput { value: 1, script_pubkey: String::from("地址") }]);
+    assert_eq!(validator.validate(&no_inputs, empty_view), Err(TxError::NoInputs));
+    let no_outputs = Transaction::new(spend(&[(FUNDING_TX, 0)], 1, 0).inputs, vec![]);
+    assert_eq!(validator.validate(&no_outputs, empty_view), Err(TxError::NoOutputs));
+
+    // 默认验证器（交易池）不接受Coinbase交易，区块验证器只检查结构
+    assert_eq!(validator.validate(&coinbase(1), empty_view), Err(TxError::UnexpectedCoinbase));
+    let block_validator = TxValidator { allow_coinbase: true, ..TxValidator::default() };
+    assert_eq!(block_validator.validate(&coinbase(1), empty_view), Ok(()));
+    assert_eq!(block_validator.validate(&coinbase(2), empty_view), Err(TxError::MalformedCoinbase { inputs: 2 }));
+}
+
+#[test]
+fn test_missing_and_duplicate_inputs() {
+    let wallet = Wallet::new();
+    let utxos = utxo_view(&wallet.address, 50);
+    let view = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+
+    let mut missing = spend(&[(FUNDING_TX, 1)], 10, 0);
+    sign_as(&wallet, &mut missing);
+    assert_eq!(TxValidator::default().validate(&missing, view), Err(TxError::MissingInput {
+        index: 0,
+        prev_tx: String::from(FUNDING_TX),
+        prev_index: 1,
+    }));
+
+    let mut duplicate = spend(&[(FUNDING_TX, 0), (FUNDING_TX, 0)], 10, 0);
+    sign_as(&wallet, &mut duplicate);
+    assert_eq!(TxValidator::default().validate(&duplicate, view), Err(TxError::DuplicateInput {
+        index: 1,
+        prev_tx: String::from(FUNDING_TX),
+        prev_index: 0,
+    }));
+}
+
+#[test]
+fn test_immature_coinbase_is_rejected() {
+    let wallet = Wallet::new();
+    let utxos = utxo_view(&wallet.address, 50);
+    let mut tx = spend(&[(FUNDING_TX, 0)], 50, 0);
+    sign_as(&wallet, &mut tx);
+
+    let validator = TxValidator {
+        immature_coinbases: [String::from(FUNDING_TX)].into_iter().collect(),
+        ..TxValidator::default()
+    };
+    assert_eq!(validator.validate(&tx, |outpoint| utxos.get(outpoint).cloned()), Err(TxError::ImmatureCoinbase {
+        index: 0,
+        prev_tx: String::from(FUNDING_TX),
+    }));
+}
+
+#[test]
+fn test_invalid_signature_is_rejected() {
+    let owner = Wallet::new();
+    let thief = Wallet::new();
+    let utxos = utxo_view(&owner.address, 50);
+    let view = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+
+    let unsigned = spend(&[(FUNDING_TX, 0)], 50, 0);
+    assert_eq!(TxValidator::default().validate(&unsigned, view), Err(TxError::InvalidSignature { index: 0 }));
+
+    let mut stolen = spend(&[(FUNDING_TX, 0)], 50, 0);
+    sign_as(&thief, &mut stolen);
+    assert_eq!(TxValidator::default().validate(&stolen, view), Err(TxError::InvalidSignature { index: 0 }));
+}
+
+#[test]
+fn test_outputs_and_fee_cannot_exceed_inputs() {
+    let wallet = Wallet::new();
+    let utxos = utxo_view(&wallet.address, 50);
+    let view = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+
+    let mut overspend = spend(&[(FUNDING_TX, 0)], 45, 10);
+    sign_as(&wallet, &mut overspend);
+    assert_eq!(TxValidator::default().validate(&overspend, view), Err(TxError::InsufficientInputs {
+        inputs: 50,
+        outputs: 45,
+        fee: 10,
+    }));
+
+    // 恰好用完输入是有效的
+    let mut exact = spend(&[(FUNDING_TX, 0)], 40, 10);
+    sign_as(&wallet, &mut exact);
+    assert_eq!(TxValidator::default().validate(&exact, view), Ok(()));
+}
+
+#[test]
+fn test_data_output_rules() {
+    let validator = TxValidator { allow_coinbase: true, ..TxValidator::default() };
+    let no_inputs = |_: &OutPoint| None;
+    
+    // 一个不超过长度上限的数据输出可以通过结构检查
+    let mut tx = coinbase(1);
+    tx.outputs.push(TxOutput::op_return(&[7; MAX_OP_RETURN_DATA]));
+    assert_eq!(validator.validate(&tx, no_inputs), Ok(()));
+    assert!(tx.outputs[1].is_unspendable());
+    assert!(!tx.outputs[0].is_unspendable());
+    
+    // 第二个数据输出被拒绝
+    tx.outputs.push(TxOutput::op_return(b"second"));
+    assert_eq!(validator.validate(&tx, no_inputs), Err(TxError::MultipleDataOutputs { index: 2 }));
+    
+    // 超长或不是十六进制的数据被拒绝
+    let mut tx = coinbase(1);
+    tx.outputs.push(TxOutput::op_return(&[7; MAX_OP_RETURN_DATA + 1]));
+    assert_eq!(validator.validate(&tx, no_inputs), Err(TxError::InvalidDataOutput { index: 1 }));
+    tx.outputs[1].script_pubkey = format!("{}不是十六进制", OP_RETURN_PREFIX);
+    assert_eq!(validator.validate(&tx, no_inputs), Err(TxError::InvalidDataOutput { index: 1 }));
+}
@@ -1,8 +1,17 @@
-use blockchain_demo::wallet::Wallet;
-use blockchain_demo::block::{Transaction, TxInput, TxOutput};
-use std::collections::HashMap;
-use sha2::{Sha256, Digest};
-use hex;
+use blockchain_demo::blockchain::DEFAULT_NETWORK_ID;
+use blockchain_demo::wallet::{decode_address, sign_transaction_with, AddressBook, CallbackSigner, CoinSelectionStrategy, SignError, Signer, decode_address_with, encode_address, validate_address, verify_message, AddressError, verify_transaction, FeePolicy, MultisigSpend, TransactionError, UnsignedTransaction, Wallet, WalletError, WalletEvent, WalletManager, WalletWatcher, DUST_THRESHOLD, WALLET_FORMAT_VERSION};
+use blockchain_demo::block::{Block, OutPoint, Transaction, TxInput, TxOutput, UtxoEntry, COINBASE_TX_ID};
+
+// 测试用的有效接收地址
+const RECIPIENT: &str = "171vsZ3PsK9vcyajd3FW1m2MhYPgPXMjX";
+
+// 辅助函数：创建属于指定地址的UTXO
+fn utxo(tx_id: &str, index: u32, value: u64, owner: &str) -> (OutPoint, UtxoEntry) {
+    (
+        OutPoint { tx_id: tx_id.to_string(), index },
+        UtxoEntry { value, script_pubkey: owner.to_string() },
+    )
+}
 
 #[test]
 fn test_wallet_creation() {
@@ -12,9 +21,9 @@ fn test_wallet_creation() {
     // 验证钱包地址不为空
     assert!(!wallet.address.is_empty());
     
-    // 验证钱包地址是有效的十六进制字符串（40个字符，20字节的RIPEMD160哈希）
-    assert_eq!(wallet.address.len(), 40);
-    assert!(wallet.address.chars().all(|c| c.is_ascii_hexdigit()));
+    // 验证钱包地址是有效的Base58Check地址（版本字节0x00，地址以1开头）
+    assert!(validate_address(&wallet.address));
+    assert!(wallet.address.starts_with('1'));
     
     // 创建另一个钱包，验证地址唯一性
     let wallet2 = Wallet::new();
@@ -24,20 +33,19 @@ fn test_wallet_creation() {
 #[test]
 fn test_transaction_creation_with_sufficient_funds() {
     // 创建钱包
-    let wallet = Wallet::new();
+    let mut wallet = Wallet::new();
     
-    // 模拟UTXO集合
-    let mut utxo_set: HashMap<String, Vec<(u32, u64)>> = HashMap::new();
-    utxo_set.insert("tx1".to_string(), vec![(0, 100)]);
+    // 模拟属于钱包的UTXO
+    let utxo_set = vec![utxo("tx1", 0, 100, &wallet.address)];
     
     // 创建交易，金额小于可用资金
-    let to_address = "recipient_address";
+    let to_address = RECIPIENT;
     let amount = 50;
     
-    let tx_option = wallet.create_transaction(to_address, amount, &utxo_set);
-    assert!(tx_option.is_some());
+    let tx_result = wallet.create_transaction(to_address, amount, &utxo_set);
+    assert!(tx_result.is_ok());
     
-    let tx = tx_option.unwrap();
+    let tx = tx_result.unwrap();
     
     // 验证交易输入
     assert_eq!(tx.inputs.len(), 1);
@@ -49,26 +57,26 @@ fn test_transaction_creation_with_sufficient_funds() {
     assert_eq!(tx.outputs[0].value, amount);
     assert_eq!(tx.outputs[0].script_pubkey, to_address);
     assert_eq!(tx.outputs[1].value, 100 - amount); // 找零
-    assert_eq!(tx.outputs[1].script_pubkey, wallet.address);
+    assert_ne!(tx.outputs[1].script_pubkey, wallet.address); // 找零发送到新地址
+    assert!(wallet.owns_address(&tx.outputs[1].script_pubkey));
 }
 
 #[test]
 fn test_transaction_creation_with_exact_funds() {
     // 创建钱包
-    let wallet = Wallet::new();
+    let mut wallet = Wallet::new();
     
-    // 模拟UTXO集合
-    let mut utxo_set: HashMap<String, Vec<(u32, u64)>> = HashMap::new();
-    utxo_set.insert("tx1".to_string(), vec![(0, 50)]);
+    // 模拟属于钱包的UTXO
+    let utxo_set = vec![utxo("tx1", 0, 50, &wallet.address)];
     
     // 创建交易，金额刚好等于可用资金
-    let to_address = "recipient_address";
+    let to_address = RECIPIENT;
     let amount = 50;
     
-    let tx_option = wallet.create_transaction(to_address, amount, &utxo_set);
-    assert!(tx_option.is_some());
+    let tx_result = wallet.create_transaction(to_address, amount, &utxo_set);
+    assert!(tx_result.is_ok());
     
-    let tx = tx_option.unwrap();
+    let tx = tx_result.unwrap();
     
     // 验证交易输入
     assert_eq!(tx.inputs.len(), 1);
@@ -82,40 +90,41 @@ fn test_transaction_creation_with_exact_funds() {
 #[test]
 fn test_transaction_creation_with_insufficient_funds() {
     // 创建钱包
-    let wallet = Wallet::new();
+    let mut wallet = Wallet::new();
     
-    // 模拟UTXO集合
-    let mut utxo_set: HashMap<String, Vec<(u32, u64)>> = HashMap::new();
-    utxo_set.insert("tx1".to_string(), vec![(0, 30)]);
+    // 模拟属于钱包的UTXO
+    let utxo_set = vec![utxo("tx1", 0, 30, &wallet.address)];
     
     // 创建交易，金额大于可用资金
-    let to_address = "recipient_address";
+    let to_address = RECIPIENT;
     let amount = 50;
     
-    let tx_option = wallet.create_transaction(to_address, amount, &utxo_set);
+    let tx_result = wallet.create_transaction(to_address, amount, &utxo_set);
     
-    // 资金不足应该返回None
-    assert!(tx_option.is_none());
+    // 资金不足应该返回错误
+    assert_eq!(tx_result.unwrap_err(), TransactionError::InsufficientFunds { needed: 50, available: 30 });
 }
 
 #[test]
 fn test_transaction_creation_with_multiple_inputs() {
     // 创建钱包
-    let wallet = Wallet::new();
+    let mut wallet = Wallet::new();
     
-    // 模拟UTXO集合，多个UTXO
-    let mut utxo_set: HashMap<String, Vec<(u32, u64)>> = HashMap::new();
-    utxo_set.insert("tx1".to_string(), vec![(0, 30)]);
-    utxo_set.insert("tx2".to_string(), vec![(0, 20), (1, 10)]);
+    // 模拟属于钱包的多个UTXO
+    let utxo_set = vec![
+        utxo("tx1", 0, 30, &wallet.address),
+        utxo("tx2", 0, 20, &wallet.address),
+        utxo("tx2", 1, 10, &wallet.address),
+    ];
     
     // 创建交易，需要多个输入才能满足金额
-    let to_address = "recipient_address";
+    let to_address = RECIPIENT;
     let amount = 50;
     
-    let tx_option = wallet.create_transaction(to_address, amount, &utxo_set);
-    assert!(tx_option.is_some());
+    let tx_result = wallet.create_transaction(to_address, amount, &utxo_set);
+    assert!(tx_result.is_ok());
     
-    let tx = tx_option.unwrap();
+    let tx = tx_result.unwrap();
     
     // 验证交易输入 - 应该收集足够的输入
     assert!(tx.inputs.len() >= 2); // 至少需要两个输入
@@ -152,7 +161,7 @@ fn test_transaction_signing() {
     
     let tx_output = TxOutput {
         value: 50,
-        script_pubkey: "recipient_address".to_string(),
+        script_pubkey: RECIPIENT.to_string(),
     };
     
     let mut tx = Transaction::new(vec![tx_input], vec![tx_output]);
@@ -161,15 +170,15 @@ fn test_transaction_signing() {
     let original_script_sig = tx.inputs[0].script_sig.clone();
     
     // 签名交易
-    wallet.sign_transaction(&mut tx);
+    wallet.sign_transaction(&mut tx).unwrap();
     
     // 签名后script_sig应该已更改
     assert_ne!(tx.inputs[0].script_sig, original_script_sig);
     
-    // 签名后的script_sig应该包含钱包地址
-    assert!(tx.inputs[0].script_sig.starts_with(&wallet.address));
+    // 签名后的script_sig应该以钱包公钥开头
+    assert!(tx.inputs[0].script_sig.starts_with(&hex::encode(wallet.public_key.unwrap().serialize())));
     
-    // 签名后的script_sig应该包含":"，格式为"地址:签名"
+    // 签名后的script_sig应该包含":"，格式为"公钥:签名"
     assert!(tx.inputs[0].script_sig.contains(':'));
     
     // 签名部分应该是有效的十六进制字符串
@@ -177,4 +186,1140 @@ fn test_transaction_signing() {
     assert_eq!(parts.len(), 2);
     let signature_hex = parts[1];
     assert!(signature_hex.chars().all(|c| c.is_ascii_hexdigit()));
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_sign_and_verify_round_trip() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![
+        utxo("tx1", 0, 30, &wallet.address),
+        utxo("tx2", 1, 30, &wallet.address),
+    ];
+    let mut tx = wallet.create_transaction(RECIPIENT, 50, &utxo_set).unwrap();
+    wallet.sign_transaction(&mut tx).unwrap();
+    
+    // 每个输入都有自己的签名
+    assert_ne!(tx.inputs[0].script_sig, tx.inputs[1].script_sig);
+    assert!(Wallet::verify_input(&tx, 0, &utxo_set[0].1, DEFAULT_NETWORK_ID));
+    assert!(Wallet::verify_input(&tx, 1, &utxo_set[1].1, DEFAULT_NETWORK_ID));
+    
+    let resolver = |outpoint: &OutPoint| {
+        utxo_set.iter()
+            .find(|(candidate, _)| candidate == outpoint)
+            .map(|(_, entry)| entry.clone())
+    };
+    assert!(verify_transaction(&tx, resolver, DEFAULT_NETWORK_ID));
+    
+    // 其他地址拥有的输出不能用本钱包的签名花费
+    let other = Wallet::new();
+    let foreign_entry = UtxoEntry { value: 30, script_pubkey: other.address.clone() };
+    assert!(!Wallet::verify_input(&tx, 0, &foreign_entry, DEFAULT_NETWORK_ID));
+}
+
+#[test]
+fn test_verification_fails_after_mutating_outputs() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![utxo("tx1", 0, 100, &wallet.address)];
+    let mut tx = wallet.create_transaction(RECIPIENT, 40, &utxo_set).unwrap();
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert!(Wallet::verify_input(&tx, 0, &utxo_set[0].1, DEFAULT_NETWORK_ID));
+    
+    for index in 0..tx.outputs.len() {
+        let mut value_changed = tx.clone();
+        value_changed.outputs[index].value += 1;
+        assert!(!Wallet::verify_input(&value_changed, 0, &utxo_set[0].1, DEFAULT_NETWORK_ID));
+        
+        let mut owner_changed = tx.clone();
+        owner_changed.outputs[index].script_pubkey = String::from("attacker_address");
+        assert!(!Wallet::verify_input(&owner_changed, 0, &utxo_set[0].1, DEFAULT_NETWORK_ID));
+    }
+}
+
+#[test]
+fn test_wallet_cannot_spend_foreign_utxos() {
+    let mut wallet = Wallet::new();
+    let other = Wallet::new();
+    
+    // UTXO集合中的资金足够，但只有10属于本钱包
+    let utxo_set = vec![
+        utxo("other_tx", 0, 100, &other.address),
+        utxo("own_tx", 0, 10, &wallet.address),
+    ];
+    
+    let result = wallet.create_transaction(RECIPIENT, 50, &utxo_set);
+    assert_eq!(result.unwrap_err(), TransactionError::InsufficientOwnedFunds { needed: 50, owned: 10 });
+    
+    // 金额在自有余额范围内时，只会使用自己的UTXO
+    let tx = wallet.create_transaction(RECIPIENT, 5, &utxo_set).unwrap();
+    assert_eq!(tx.inputs.len(), 1);
+    assert_eq!(tx.inputs[0].prev_tx, "own_tx");
+    assert!(wallet.owns_address(&tx.outputs[1].script_pubkey));
+}
+
+#[test]
+fn test_estimate_fee_single_input() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![utxo("tx1", 0, 100, &wallet.address)];
+    
+    // 1个输入、2个输出（含找零）：10 + 148 + 2 * 34 = 226字节
+    let fee = wallet.estimate_fee(RECIPIENT, 50, &utxo_set).unwrap();
+    assert_eq!(fee, 3);
+    
+    let tx = wallet.create_transaction_with_fee(RECIPIENT, 50, fee, &utxo_set).unwrap();
+    assert_eq!(tx.fee, fee);
+    assert_eq!(tx.outputs[1].value, 100 - 50 - fee);
+}
+
+#[test]
+fn test_estimate_fee_multiple_inputs() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![
+        utxo("tx1", 0, 30, &wallet.address),
+        utxo("tx2", 0, 20, &wallet.address),
+        utxo("tx2", 1, 10, &wallet.address),
+    ];
+    
+    // 加上手续费后需要全部3个输入和一个找零输出：10 + 3 * 148 + 2 * 34 = 522字节
+    let fee = wallet.estimate_fee(RECIPIENT, 50, &utxo_set).unwrap();
+    assert_eq!(fee, 6);
+    
+    let tx = wallet.create_transaction_with_fee(RECIPIENT, 50, fee, &utxo_set).unwrap();
+    assert_eq!(tx.inputs.len(), 3);
+    let total_output: u64 = tx.outputs.iter().map(|output| output.value).sum();
+    assert_eq!(total_output + tx.fee, 60);
+    
+    // 余额无法覆盖金额和手续费时没有估算结果
+    assert_eq!(wallet.estimate_fee(RECIPIENT, 58, &utxo_set), None);
+}
+
+#[test]
+fn test_wallet_manager_isolates_wallets() {
+    let mut manager = WalletManager::new();
+    let alice_address = manager.create("alice").address.clone();
+    let bob_address = manager.create("bob").address.clone();
+    assert_ne!(alice_address, bob_address);
+    
+    // 第一个创建的钱包成为当前钱包，同名创建不会替换已有钱包
+    assert_eq!(manager.active_name(), "alice");
+    assert_eq!(manager.create("alice").address, alice_address);
+    assert_eq!(manager.len(), 2);
+    
+    manager.set_active("bob").unwrap();
+    assert_eq!(manager.active_wallet().address, bob_address);
+    assert_eq!(
+        manager.set_active("carol"),
+        Err(WalletError::UnknownWallet(String::from("carol")))
+    );
+    assert_eq!(manager.active_name(), "bob");
+    
+    // 当前钱包不能花费其他钱包的UTXO
+    let utxo_set = vec![utxo("alice_tx", 0, 100, &alice_address)];
+    assert!(manager.active_wallet_mut().create_transaction(RECIPIENT, 10, &utxo_set).is_err());
+    manager.set_active("alice").unwrap();
+    assert!(manager.active_wallet_mut().create_transaction(RECIPIENT, 10, &utxo_set).is_ok());
+}
+
+#[test]
+fn test_wallet_manager_load_and_import() {
+    let path = std::env::temp_dir().join(format!("manager_wallet_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    
+    // 文件不存在时创建并保存，再次加载得到同一个钱包
+    let mut manager = WalletManager::new();
+    let address = manager.load_or_create("main", &path).unwrap().address.clone();
+    let mut other_manager = WalletManager::new();
+    assert_eq!(other_manager.import("copy", &path).unwrap().address, address);
+    
+    let missing = std::env::temp_dir().join("missing_wallet_file.json");
+    assert!(matches!(
+        other_manager.import("missing", &missing),
+        Err(WalletError::Io { .. })
+    ));
+    assert!(other_manager.get("missing").is_none());
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_create_transaction_error_variants() {
+    let mut wallet = Wallet::new();
+    let other = Wallet::new();
+    let own_utxos = vec![utxo("tx1", 0, 30, &wallet.address)];
+    
+    assert_eq!(
+        wallet.create_transaction(RECIPIENT, 0, &own_utxos).unwrap_err(),
+        TransactionError::ZeroAmount
+    );
+    assert_eq!(
+        wallet.create_transaction(RECIPIENT, 10, &[]).unwrap_err(),
+        TransactionError::NoUtxos
+    );
+    assert_eq!(
+        wallet.create_transaction(RECIPIENT, 10, &[utxo("tx2", 0, 100, &other.address)]).unwrap_err(),
+        TransactionError::NoUtxos
+    );
+    assert_eq!(
+        wallet.create_transaction(RECIPIENT, 40, &own_utxos).unwrap_err(),
+        TransactionError::InsufficientFunds { needed: 40, available: 30 }
+    );
+    assert_eq!(
+        wallet.create_transaction_with_fee(RECIPIENT, 30, 5, &own_utxos).unwrap_err(),
+        TransactionError::InsufficientFundsForFee { amount: 30, fee: 5, available: 30 }
+    );
+}
+
+#[test]
+fn test_create_transaction_rejects_invalid_outputs() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![utxo("tx1", 0, 100, &wallet.address)];
+    
+    assert_eq!(
+        wallet.create_transaction(RECIPIENT, DUST_THRESHOLD - 1, &utxo_set).unwrap_err(),
+        TransactionError::DustAmount { amount: DUST_THRESHOLD - 1, threshold: DUST_THRESHOLD }
+    );
+    assert!(wallet.create_transaction(RECIPIENT, DUST_THRESHOLD, &utxo_set).is_ok());
+    
+    for malformed in ["recipient_address", "0123456789abcdef", "0123456789abcdef0123456789abcdef0123456z"] {
+        assert_eq!(
+            wallet.create_transaction(malformed, 10, &utxo_set).unwrap_err(),
+            TransactionError::InvalidAddress(malformed.to_string())
+        );
+    }
+}
+
+#[test]
+fn test_mnemonic_restores_same_wallet() {
+    let phrase = Wallet::generate_mnemonic();
+    assert_eq!(phrase.split_whitespace().count(), 12);
+    
+    let first = Wallet::from_mnemonic(&phrase).unwrap();
+    let second = Wallet::from_mnemonic(&phrase).unwrap();
+    assert_eq!(first.address, second.address);
+    assert_eq!(first.private_key, second.private_key);
+    
+    // 大小写和多余空白不影响恢复结果
+    let messy = format!("  {}  ", phrase.to_uppercase().replace(' ', "   "));
+    assert_eq!(Wallet::from_mnemonic(&messy).unwrap().address, first.address);
+    
+    // 标准测试向量始终派生出相同的地址
+    let vector = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    let wallet = Wallet::from_mnemonic(vector).unwrap();
+    assert_eq!(wallet.address, Wallet::from_mnemonic(vector).unwrap().address);
+    assert_ne!(wallet.address, first.address);
+    
+    // 新建钱包返回的助记词能恢复出同一个钱包，并随钱包一起保存
+    let (created, phrase) = Wallet::new_with_mnemonic();
+    assert_eq!(created.mnemonic.as_deref(), Some(phrase.as_str()));
+    assert_eq!(Wallet::from_mnemonic(&phrase).unwrap().address, created.address);
+    assert_eq!(Wallet::new().mnemonic, None);
+}
+
+#[test]
+fn test_invalid_mnemonic_rejected() {
+    // 校验和不匹配
+    let bad_checksum = ["abandon"; 12].join(" ");
+    assert!(matches!(Wallet::from_mnemonic(&bad_checksum), Err(WalletError::InvalidMnemonic(_))));
+    
+    // 单词不在词表中
+    let unknown_word = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon blockchainx";
+    assert!(matches!(Wallet::from_mnemonic(unknown_word), Err(WalletError::InvalidMnemonic(_))));
+    
+    // 单词数量错误
+    assert!(matches!(Wallet::from_mnemonic("abandon about"), Err(WalletError::InvalidMnemonic(_))));
+    assert!(matches!(Wallet::from_mnemonic(""), Err(WalletError::InvalidMnemonic(_))));
+}
+
+#[test]
+fn test_spend_across_multiple_addresses() {
+    let mut wallet = Wallet::new();
+    let second = wallet.new_address().unwrap();
+    assert_eq!(wallet.addresses(), vec![wallet.address.clone(), second.clone()]);
+    
+    // 两个地址各有60，支付100需要同时使用两个地址的UTXO
+    let utxo_set = vec![
+        utxo("tx_primary", 0, 60, &wallet.address),
+        utxo("tx_second", 0, 60, &second),
+    ];
+    let mut tx = wallet.create_transaction(RECIPIENT, 100, &utxo_set).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    
+    // 找零发送到新派生的第三个地址
+    let change = tx.outputs[1].script_pubkey.clone();
+    assert_eq!(tx.outputs[1].value, 20);
+    assert_eq!(wallet.addresses().len(), 3);
+    assert_eq!(wallet.addresses()[2], change);
+    
+    // 每个输入使用所花费地址的私钥签名
+    wallet.sign_transaction(&mut tx).unwrap();
+    let entries: std::collections::HashMap<_, _> = utxo_set.iter().cloned().collect();
+    for (index, input) in tx.inputs.iter().enumerate() {
+        let outpoint = OutPoint { tx_id: input.prev_tx.clone(), index: input.prev_index };
+        assert!(Wallet::verify_input(&tx, index, &entries[&outpoint], DEFAULT_NETWORK_ID));
+    }
+    assert!(verify_transaction(&tx, |outpoint| entries.get(outpoint).cloned(), DEFAULT_NETWORK_ID));
+    
+    // 关闭找零轮换后找零回到主地址
+    wallet.fresh_change_address = false;
+    let tx = wallet.create_transaction(RECIPIENT, 50, &utxo_set).unwrap();
+    assert_eq!(tx.outputs[1].script_pubkey, wallet.address);
+    assert_eq!(wallet.addresses().len(), 3);
+}
+
+#[test]
+fn test_wallet_file_versions() {
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("multi_address_wallet_{}.json", std::process::id()));
+    let filename = path.to_string_lossy().to_string();
+    
+    // 多地址钱包保存后能完整加载，地址派生是确定性的
+    let (mut wallet, phrase) = Wallet::new_with_mnemonic();
+    let derived = wallet.new_address().unwrap();
+    Wallet::save_wallet(&wallet, &filename).unwrap();
+    let loaded = Wallet::load_wallet(&filename).unwrap();
+    assert_eq!(loaded.version, WALLET_FORMAT_VERSION);
+    assert_eq!(loaded.addresses(), wallet.addresses());
+    
+    let mut restored = Wallet::from_mnemonic(&phrase).unwrap();
+    assert_eq!(restored.new_address().unwrap(), derived);
+    
+    // 没有版本字段的旧单密钥格式仍然可以加载
+    let legacy = Wallet::new();
+    let legacy_json = format!(
+        r#"{{"private_key":{},"public_key":{},"address":"{}"}}"#,
+        serde_json::to_string(&legacy.private_key).unwrap(),
+        serde_json::to_string(&legacy.public_key).unwrap(),
+        legacy.address,
+    );
+    std::fs::write(&path, legacy_json).unwrap();
+    let loaded = Wallet::load_wallet(&filename).unwrap();
+    assert_eq!(loaded.addresses(), vec![legacy.address.clone()]);
+    assert_eq!(loaded.version, WALLET_FORMAT_VERSION);
+    assert!(loaded.fresh_change_address);
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_spendable_balance_covers_derived_addresses() {
+    let mut wallet = Wallet::new();
+    let first = wallet.new_address().unwrap();
+    let second = wallet.new_address().unwrap();
+    assert!(wallet.owns_address(&first));
+    assert!(wallet.owns_address(&second));
+    assert!(!wallet.owns_address(RECIPIENT));
+    
+    let utxo_set = vec![
+        utxo("tx_primary", 0, 10, &wallet.address),
+        utxo("tx_first", 0, 20, &first),
+        utxo("tx_second", 1, 30, &second),
+        utxo("tx_other", 0, 1000, RECIPIENT),
+    ];
+    assert_eq!(wallet.spendable_balance(&utxo_set), 60);
+    
+    // 派生地址上的余额可以被花费
+    let tx = wallet.create_transaction(RECIPIENT, 55, &utxo_set).unwrap();
+    assert_eq!(tx.inputs.len(), 3);
+    assert!(tx.inputs.iter().all(|input| input.prev_tx != "tx_other"));
+}
+
+#[test]
+fn test_private_key_import_export() {
+    let wallet = Wallet::new();
+    
+    // 导出→导入→再导出得到相同的地址和WIF
+    let wif = wallet.export_wif().unwrap();
+    let imported = Wallet::import_wif(&wif).unwrap();
+    assert_eq!(imported.address, wallet.address);
+    assert_eq!(imported.export_wif().unwrap(), wif);
+    assert_eq!(Wallet::import_private_key(&imported.export_wif().unwrap()).unwrap().address, wallet.address);
+    assert!(matches!(Wallet::import_wif(&hex::encode(wallet.private_key.unwrap().secret_bytes())), Err(WalletError::InvalidPrivateKey(_))));
+    
+    // 十六进制格式的私钥
+    let hex_key = hex::encode(wallet.private_key.unwrap().secret_bytes());
+    assert_eq!(Wallet::import_private_key(&hex_key).unwrap().address, wallet.address);
+    
+    // 标准测试向量：私钥0x0C28...D对应的压缩WIF
+    let vector = "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d";
+    let vector_wallet = Wallet::import_private_key(vector).unwrap();
+    assert_eq!(vector_wallet.export_wif().unwrap(), "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617");
+    
+    // 校验和错误、长度错误和无效私钥都会被拒绝
+    let mut corrupted = wif.clone();
+    let last = if corrupted.ends_with('1') { '2' } else { '1' };
+    corrupted.pop();
+    corrupted.push(last);
+    assert!(matches!(Wallet::import_wif(&corrupted), Err(WalletError::InvalidPrivateKey(_))));
+    assert!(matches!(Wallet::import_private_key(&corrupted), Err(WalletError::InvalidPrivateKey(_))));
+    assert!(matches!(Wallet::import_private_key("abcd"), Err(WalletError::InvalidPrivateKey(_))));
+    assert!(matches!(Wallet::import_private_key(&"0".repeat(64)), Err(WalletError::InvalidPrivateKey(_))));
+}
+
+#[test]
+fn test_address_qr_renders_block_characters() {
+    let wallet = Wallet::new();
+    let qr = wallet.address_qr();
+    
+    // 每行宽度相同，只由半块字符和空格组成，同一地址渲染结果固定
+    let lines: Vec<&str> = qr.lines().collect();
+    assert!(lines.len() > 10);
+    let width = lines[0].chars().count();
+    assert!(lines.iter().all(|line| line.chars().count() == width));
+    assert!(qr.chars().all(|c| matches!(c, ' ' | '▀' | '▄' | '█' | '\n')));
+    assert_eq!(wallet.address_qr(), qr);
+    assert_ne!(Wallet::new().address_qr(), qr);
+}
+
+#[test]
+fn test_sign_and_verify_message() {
+    let wallet = Wallet::new();
+    let other = Wallet::new();
+    let challenge = b"login challenge 42";
+    
+    let signature = wallet.sign_message(challenge).unwrap();
+    assert!(verify_message(&wallet.address, challenge, &signature));
+    
+    // 消息被篡改
+    assert!(!verify_message(&wallet.address, b"login challenge 43", &signature));
+    
+    // 其他钱包的签名不能证明对该地址的所有权
+    assert!(!verify_message(&other.address, challenge, &signature));
+    assert!(!verify_message(&wallet.address, challenge, &other.sign_message(challenge).unwrap()));
+    
+    // 格式错误的签名返回false而不是panic
+    assert!(!verify_message(&wallet.address, challenge, "not hex"));
+    assert!(!verify_message(&wallet.address, challenge, &signature[2..]));
+    assert!(!verify_message(&wallet.address, challenge, &format!("ff{}", &signature[2..])));
+    assert!(!verify_message(&wallet.address, challenge, &"00".repeat(65)));
+}
+
+#[test]
+fn test_exact_fit_fee() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![utxo("tx1", 0, 60, &wallet.address)];
+    
+    // 输入恰好等于金额加手续费，没有找零输出
+    let tx = wallet.create_transaction_with_fee(RECIPIENT, 50, 10, &utxo_set).unwrap();
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.fee, 10);
+    
+    // 仅多出1的手续费就不够
+    assert_eq!(
+        wallet.create_transaction_with_fee(RECIPIENT, 50, 11, &utxo_set).unwrap_err(),
+        TransactionError::InsufficientFundsForFee { amount: 50, fee: 11, available: 60 }
+    );
+    assert_eq!(
+        wallet.create_transaction_with_fee(RECIPIENT, 61, 1, &utxo_set).unwrap_err(),
+        TransactionError::InsufficientFunds { needed: 62, available: 60 }
+    );
+}
+
+#[test]
+fn test_dust_change_folded_into_fee() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![utxo("tx1", 0, 100, &wallet.address)];
+    
+    // 找零低于粉尘阈值时并入手续费，输入输出仍然平衡
+    let change = DUST_THRESHOLD - 1;
+    let tx = wallet.create_transaction_with_fee(RECIPIENT, 90, 10 - change, &utxo_set).unwrap();
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.fee, 10);
+    assert_eq!(tx.outputs[0].value + tx.fee, 100);
+    
+    // 达到粉尘阈值的找零保留为输出
+    let tx = wallet.create_transaction_with_fee(RECIPIENT, 90, 10 - DUST_THRESHOLD, &utxo_set).unwrap();
+    assert_eq!(tx.outputs.len(), 2);
+    assert_eq!(tx.outputs[1].value, DUST_THRESHOLD);
+}
+
+#[test]
+fn test_per_byte_fee_converges() {
+    let mut wallet = Wallet::new();
+    // 许多小额UTXO，手续费增加会迫使钱包选择更多输入
+    let utxo_set: Vec<_> = (0..20).map(|i| utxo(&format!("tx{}", i), 0, 1000, &wallet.address)).collect();
+    let policy = FeePolicy::PerByte(1);
+    
+    let fee = wallet.estimate_fee_with_policy(RECIPIENT, 2500, policy, &utxo_set).unwrap();
+    let tx = wallet.create_transaction_with_policy(RECIPIENT, 2500, policy, &utxo_set).unwrap();
+    
+    // 最终交易的手续费覆盖其自身大小，且输入数量随手续费增加而增加
+    assert!(tx.fee >= policy.fee_for_size(tx.size_bytes()));
+    assert!(tx.fee >= fee);
+    assert!(tx.inputs.len() > 3);
+    let total_input = tx.inputs.len() as u64 * 1000;
+    let total_output: u64 = tx.outputs.iter().map(|output| output.value).sum();
+    assert_eq!(total_input, total_output + tx.fee);
+    
+    // 余额无法覆盖按字节计算的手续费
+    let small = vec![utxo("tx_small", 0, 2600, &wallet.address)];
+    assert!(matches!(
+        wallet.create_transaction_with_policy(RECIPIENT, 2500, policy, &small),
+        Err(TransactionError::InsufficientFundsForFee { amount: 2500, .. })
+    ));
+}
+
+#[test]
+fn test_multi_recipient_transaction() {
+    let mut wallet = Wallet::new();
+    wallet.fresh_change_address = false;
+    let other = String::from("1J7eFp9p48g3U3yCREyhd6LJzhnkywhi5s");
+    let utxo_set = vec![
+        utxo("tx1", 0, 40, &wallet.address),
+        utxo("tx2", 0, 40, &wallet.address),
+    ];
+    let recipients = vec![(RECIPIENT.to_string(), 10), (other.clone(), 25), (RECIPIENT.to_string(), 15)];
+    
+    let tx = wallet.create_transaction_multi(&recipients, 2, &utxo_set).unwrap();
+    
+    // 重复地址合并到第一次出现的位置，找零位于最后
+    assert_eq!(tx.outputs.len(), 3);
+    assert_eq!((tx.outputs[0].script_pubkey.as_str(), tx.outputs[0].value), (RECIPIENT, 25));
+    assert_eq!((tx.outputs[1].script_pubkey.as_str(), tx.outputs[1].value), (other.as_str(), 25));
+    assert_eq!(tx.outputs[2].script_pubkey, wallet.address);
+    assert_eq!(tx.outputs[2].value, 80 - 50 - 2);
+    
+    // 输入总额等于输出总额加手续费
+    let total_output: u64 = tx.outputs.iter().map(|output| output.value).sum();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(total_output + tx.fee, 80);
+}
+
+#[test]
+fn test_multi_recipient_errors() {
+    let mut wallet = Wallet::new();
+    let utxo_set = vec![utxo("tx1", 0, 40, &wallet.address)];
+    let other = String::from("1J7eFp9p48g3U3yCREyhd6LJzhnkywhi5s");
+    
+    // 单个金额足够，但合计金额超过余额
+    assert_eq!(
+        wallet.create_transaction_multi(&[(RECIPIENT.to_string(), 30), (other.clone(), 30)], 0, &utxo_set).unwrap_err(),
+        TransactionError::InsufficientFunds { needed: 60, available: 40 }
+    );
+    assert_eq!(
+        wallet.create_transaction_multi(&[(RECIPIENT.to_string(), 10), (other.clone(), 0)], 0, &utxo_set).unwrap_err(),
+        TransactionError::ZeroAmount
+    );
+    assert_eq!(
+        wallet.create_transaction_multi(&[(String::from("bad"), 10)], 0, &utxo_set).unwrap_err(),
+        TransactionError::InvalidAddress(String::from("bad"))
+    );
+    assert_eq!(wallet.create_transaction_multi(&[], 0, &utxo_set).unwrap_err(), TransactionError::NoRecipients);
+}
+
+#[test]
+fn test_address_encoding() {
+    let hash: [u8; 20] = hex::decode("0123456789abcdef0123456789abcdef01234567").unwrap().try_into().unwrap();
+    
+    // 有效地址往返编码
+    assert_eq!(encode_address(&hash), RECIPIENT);
+    assert_eq!(decode_address(RECIPIENT), Ok(hash));
+    assert!(validate_address(RECIPIENT));
+    
+    // 修改一个字符后校验和不匹配
+    let mut corrupted = RECIPIENT.to_string();
+    corrupted.replace_range(5..6, if &RECIPIENT[5..6] == "Z" { "Y" } else { "Z" });
+    assert_eq!(decode_address(&corrupted), Err(AddressError::InvalidChecksum));
+    assert!(!validate_address(&corrupted));
+    assert!(matches!(decode_address("0OIl"), Err(AddressError::InvalidEncoding(_))));
+    
+    // 版本字节0x05（比特币P2SH地址）被拒绝
+    assert_eq!(decode_address("31o2rR3UwmdY1ng1rihqve7xWDq7GEh5uz"), Err(AddressError::WrongVersion(0x05)));
+    
+    // 旧格式的十六进制地址只有显式允许时才接受
+    let legacy = "0123456789abcdef0123456789abcdef01234567";
+    assert!(!validate_address(legacy));
+    assert_eq!(decode_address_with(legacy, true), Ok(hash));
+}
+
+#[test]
+fn test_legacy_hex_outputs_still_verify() {
+    let mut wallet = Wallet::new();
+    let legacy = hex::encode(decode_address(&wallet.address).unwrap());
+    let entry = UtxoEntry { value: 50, script_pubkey: legacy.clone() };
+    
+    // 花费写在旧链上的十六进制地址输出
+    let mut tx = Transaction::new(
+        vec![TxInput { prev_tx: String::from("legacy_tx"), prev_index: 0, script_sig: legacy }],
+        vec![TxOutput { value: 50, script_pubkey: RECIPIENT.to_string() }],
+    );
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert!(Wallet::verify_input(&tx, 0, &entry, DEFAULT_NETWORK_ID));
+    
+    // 新交易不能发送到没有校验和的旧格式地址
+    let utxo_set = vec![utxo("tx1", 0, 100, &wallet.address)];
+    assert!(matches!(
+        wallet.create_transaction(&entry.script_pubkey, 10, &utxo_set),
+        Err(TransactionError::InvalidAddress(_))
+    ));
+}
+
+#[test]
+fn test_watch_only_wallet() {
+    let mut full = Wallet::new();
+    full.fresh_change_address = false;
+    let mut watched = Wallet::watch_only(full.public_key.unwrap());
+    
+    // 观察钱包与完整钱包地址相同，余额查询结果一致
+    assert_eq!(watched.address, full.address);
+    assert!(watched.is_watch_only());
+    let utxo_set = vec![utxo("tx1", 0, 100, &full.address)];
+    assert_eq!(watched.spendable_balance(&utxo_set), full.spendable_balance(&utxo_set));
+    
+    // 可以构建未签名交易，但拒绝签名、派生地址和导出私钥
+    let mut tx = watched.create_transaction(RECIPIENT, 30, &utxo_set).unwrap();
+    assert!(!tx.is_signed());
+    assert_eq!(tx.outputs[1].script_pubkey, full.address);
+    assert_eq!(watched.sign_transaction(&mut tx), Err(WalletError::WatchOnly));
+    assert!(!tx.is_signed());
+    assert_eq!(watched.new_address(), Err(WalletError::WatchOnly));
+    assert_eq!(watched.export_wif(), Err(WalletError::WatchOnly));
+    assert_eq!(watched.sign_message(b"hello"), Err(WalletError::WatchOnly));
+    
+    // 完整钱包可以签名观察钱包构建的交易
+    full.sign_transaction(&mut tx).unwrap();
+    assert!(tx.is_signed());
+    assert!(Wallet::verify_input(&tx, 0, &utxo_set[0].1, DEFAULT_NETWORK_ID));
+    
+    // 只由地址创建的观察钱包同样可以查询余额，无效地址被拒绝
+    let by_address = Wallet::from_address(&full.address).unwrap();
+    assert!(by_address.is_watch_only());
+    assert_eq!(by_address.spendable_balance(&utxo_set), 100);
+    assert!(matches!(Wallet::from_address("not an address"), Err(WalletError::InvalidAddress(_))));
+}
+
+#[test]
+fn test_watch_only_wallet_file_has_no_secret() {
+    let full = Wallet::new();
+    let path = std::env::temp_dir().join(format!("watch_only_{}.json", full.address));
+    let filename = path.to_string_lossy().to_string();
+    
+    let watched = Wallet::watch_only(full.public_key.unwrap());
+    Wallet::save_wallet(&watched, &filename).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains("private_key"));
+    assert!(!contents.contains(&hex::encode(full.private_key.unwrap().secret_bytes())));
+    
+    let loaded = Wallet::load_wallet(&filename).unwrap();
+    assert!(loaded.is_watch_only());
+    assert_eq!(loaded.address, full.address);
+    assert!(loaded.private_key.is_none());
+    
+    // 通过钱包管理器添加观察钱包：公钥或地址均可
+    let mut manager = WalletManager::new();
+    let public_key_hex = hex::encode(full.public_key.unwrap().serialize());
+    assert_eq!(manager.watch("cold", &public_key_hex).unwrap().address, full.address);
+    assert_eq!(manager.watch("cold_address", &full.address).unwrap().address, full.address);
+    assert!(manager.watch("bad", "xyz").is_err());
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_offline_signing_round_trip() {
+    let mut offline = Wallet::new();
+    offline.fresh_change_address = false;
+    let mut online = Wallet::watch_only(offline.public_key.unwrap());
+    let utxo_set = vec![utxo("tx1", 0, 60, &offline.address), utxo("tx2", 1, 40, &offline.address)];
+    
+    // 联网的观察钱包构建交易并导出
+    let tx = online.create_transaction_with_fee(RECIPIENT, 80, 5, &utxo_set).unwrap();
+    let unsigned = UnsignedTransaction::new(tx, &utxo_set).unwrap();
+    assert!(!unsigned.is_complete());
+    let exported = unsigned.to_json();
+    
+    // 观察钱包和其他钱包都不能签名
+    let mut imported = UnsignedTransaction::from_json(&exported).unwrap();
+    assert_eq!(online.sign_unsigned(&mut imported).unwrap_err(), WalletError::WatchOnly);
+    assert_eq!(Wallet::new().sign_unsigned(&mut imported).unwrap_err(), WalletError::NotOwned(offline.address.clone()));
+    assert!(!imported.transaction.is_signed());
+    
+    // 离线钱包签名后导回，所有输入的签名都有效
+    let signed = offline.sign_unsigned(&mut imported).unwrap();
+    let returned = UnsignedTransaction::from_json(&imported.to_json()).unwrap();
+    assert!(returned.is_complete());
+    assert_eq!(returned.transaction.calculate_hash(), signed.calculate_hash());
+    assert!(verify_transaction(&signed, |outpoint| {
+        utxo_set.iter().find(|(candidate, _)| candidate == outpoint).map(|(_, entry)| entry.clone())
+    }, DEFAULT_NETWORK_ID));
+    
+    // 缺少输入引用的输出时无法创建，输出数量与输入不一致的文件被拒绝
+    assert!(matches!(UnsignedTransaction::new(signed, &utxo_set[..1]), Err(WalletError::MissingInput(_))));
+    let mut truncated = returned.clone();
+    truncated.utxos.pop();
+    assert!(UnsignedTransaction::from_json(&truncated.to_json()).is_err());
+}
+
+#[test]
+fn test_wallet_secrets_redacted_and_migrated() {
+    let (mut wallet, phrase) = Wallet::new_with_mnemonic();
+    wallet.new_address().unwrap();
+    let secret_hex = wallet.export_private_key_hex().unwrap();
+    
+    // Debug输出不包含私钥或助记词
+    let debug = format!("{:?}", wallet);
+    assert!(debug.contains(&wallet.address));
+    assert!(!debug.contains(&secret_hex));
+    assert!(!debug.contains(&wallet.export_wif().unwrap()));
+    assert!(!debug.contains(phrase.split_whitespace().next().unwrap()));
+    
+    // 十六进制导出再导入得到相同的地址，观察钱包和无效输入被拒绝
+    assert_eq!(secret_hex.len(), 64);
+    assert_eq!(Wallet::import_private_key_hex(&secret_hex).unwrap().address, wallet.address);
+    assert_eq!(Wallet::watch_only(wallet.public_key.unwrap()).export_private_key_hex(), Err(WalletError::WatchOnly));
+    assert!(matches!(Wallet::import_private_key_hex("abcd"), Err(WalletError::InvalidPrivateKey(_))));
+    assert!(matches!(Wallet::import_private_key_hex(&"0".repeat(64)), Err(WalletError::InvalidPrivateKey(_))));
+    
+    // 新格式把私钥保存在带格式标记的secret字段中
+    let path = std::env::temp_dir().join(format!("secret_format_{}.json", wallet.address));
+    let filename = path.to_string_lossy().to_string();
+    Wallet::save_wallet(&wallet, &filename).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved["version"], WALLET_FORMAT_VERSION);
+    assert_eq!(saved["secret"]["format"], "plaintext-hex");
+    assert_eq!(saved["secret"]["data"], secret_hex.as_str());
+    assert!(saved.get("private_key").is_none());
+    assert!(saved["extra_keys"][0].get("private_key").is_none());
+    assert_eq!(Wallet::load_wallet(&filename).unwrap().addresses(), wallet.addresses());
+    
+    // 版本2的文件直接保存私钥，加载时迁移到当前版本
+    let derived_address = wallet.addresses()[1].clone();
+    let v2_json = serde_json::json!({
+        "version": 2,
+        "private_key": wallet.private_key,
+        "public_key": wallet.public_key,
+        "address": wallet.address,
+        "extra_keys": [{
+            "private_key": saved["extra_keys"][0]["secret"]["data"],
+            "public_key": saved["extra_keys"][0]["public_key"],
+            "address": derived_address,
+        }],
+    });
+    std::fs::write(&path, v2_json.to_string()).unwrap();
+    let migrated = Wallet::load_wallet(&filename).unwrap();
+    assert_eq!(migrated.version, WALLET_FORMAT_VERSION);
+    assert_eq!(migrated.addresses(), wallet.addresses());
+    assert_eq!(migrated.export_private_key_hex().unwrap(), secret_hex);
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_multisig_address_rejects_bad_threshold() {
+    let keys: Vec<_> = (0..3).map(|_| Wallet::new().public_key.unwrap()).collect();
+
+    assert!(Wallet::create_multisig_address(&keys, 2).is_ok());
+    assert!(matches!(Wallet::create_multisig_address(&keys, 0), Err(WalletError::InvalidMultisig(_))));
+    assert!(matches!(Wallet::create_multisig_address(&keys, 4), Err(WalletError::InvalidMultisig(_))));
+    assert!(matches!(Wallet::create_multisig_address(&[keys[0], keys[0]], 1), Err(WalletError::InvalidMultisig(_))));
+}
+
+#[test]
+fn test_two_of_three_multisig_spend() {
+    let signers = [Wallet::new(), Wallet::new(), Wallet::new()];
+    let keys: Vec<_> = signers.iter().map(|wallet| wallet.public_key.unwrap()).collect();
+    let address = Wallet::create_multisig_address(&keys, 2).unwrap();
+
+    // 多重签名地址可以像普通地址一样接收资金
+    let mut funder = Wallet::new();
+    let funding = funder.create_transaction(&address, 60, &[utxo("资金交易", 0, 100, &funder.address)]).unwrap();
+    assert_eq!(funding.outputs[0].script_pubkey, address);
+
+    let inputs = [utxo("多签资金", 0, 60, &address)];
+    let outputs = vec![TxOutput { value: 50, script_pubkey: RECIPIENT.to_string() }];
+    let mut spend = MultisigSpend::new(&inputs, outputs, 10).unwrap();
+    let lookup = |outpoint: &OutPoint| inputs.iter().find(|(o, _)| o == outpoint).map(|(_, e)| e.clone());
+
+    // 一个签名不够，重复签名不会增加签名数
+    spend.add_signature(&signers[0]).unwrap();
+    spend.add_signature(&signers[0]).unwrap();
+    assert_eq!(spend.signature_count(0), 1);
+    assert!(!spend.is_complete());
+    assert!(!verify_transaction(&spend.transaction, lookup, DEFAULT_NETWORK_ID));
+
+    // 不参与多重签名的钱包不能签名
+    assert!(matches!(spend.add_signature(&Wallet::new()), Err(WalletError::NotOwned(_))));
+
+    // 任意两个签名者即可完成
+    let mut other = spend.clone();
+    spend.add_signature(&signers[2]).unwrap();
+    assert_eq!(spend.signature_count(0), 2);
+    assert!(spend.is_complete());
+    assert!(verify_transaction(&spend.transaction, lookup, DEFAULT_NETWORK_ID));
+
+    other.add_signature(&signers[1]).unwrap();
+    assert!(other.is_complete());
+}
+
+#[test]
+fn test_multisig_duplicate_signature_invalidates_input() {
+    let signers = [Wallet::new(), Wallet::new(), Wallet::new()];
+    let keys: Vec<_> = signers.iter().map(|wallet| wallet.public_key.unwrap()).collect();
+    let address = Wallet::create_multisig_address(&keys, 2).unwrap();
+    let inputs = [utxo("多签资金", 0, 60, &address)];
+    let outputs = vec![TxOutput { value: 60, script_pubkey: RECIPIENT.to_string() }];
+
+    // 同一个签名复制两份冒充两个签名者
+    let mut spend = MultisigSpend::new(&inputs, outputs, 0).unwrap();
+    spend.add_signature(&signers[0]).unwrap();
+    let signature = spend.transaction.inputs[0].script_sig.clone();
+    spend.transaction.inputs[0].script_sig = format!("{},{}", signature, signature);
+
+    assert_eq!(spend.signature_count(0), 1);
+    assert!(!spend.is_complete());
+    assert!(!Wallet::verify_input(&spend.transaction, 0, &inputs[0].1, DEFAULT_NETWORK_ID));
+
+    // 普通地址的输出不能用多重签名方式花费
+    let plain = [utxo("普通资金", 0, 60, &signers[0].address)];
+    assert!(matches!(MultisigSpend::new(&plain, vec![], 0), Err(WalletError::InvalidMultisig(_))));
+}
+
+#[test]
+fn test_load_wallet_reports_corrupt_files() {
+    let dir = std::env::temp_dir();
+    let write = |name: &str, contents: &str| {
+        let path = dir.join(format!("{}_{}.json", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    };
+
+    let corrupt = write("corrupt_wallet", "{\"version\": 3, \"address\": ");
+    let wrong_schema = write("wrong_schema_wallet", "{\"blocks\": []}");
+    let empty = write("empty_wallet", "");
+    let future = write("future_wallet", "{\"version\": 99, \"keys\": []}");
+
+    assert!(matches!(Wallet::load_wallet(&corrupt), Err(WalletError::Parse { .. })));
+    assert!(matches!(Wallet::load_wallet(&wrong_schema), Err(WalletError::Parse { .. })));
+    assert!(matches!(Wallet::load_wallet(&empty), Err(WalletError::Parse { .. })));
+    assert!(matches!(Wallet::load_wallet(&future), Err(WalletError::UnsupportedVersion { version: 99, .. })));
+    assert!(matches!(Wallet::load_wallet("no_such_wallet.json"), Err(WalletError::Io { .. })));
+
+    // 无法加载的文件不会被新钱包覆盖
+    let mut manager = WalletManager::new();
+    assert!(manager.load_or_create("main", std::path::Path::new(&corrupt)).is_err());
+    assert!(manager.get("main").is_none());
+    assert!(std::fs::read_to_string(&corrupt).unwrap().starts_with("{\"version\": 3"));
+
+    for path in [corrupt, wrong_schema, empty, future] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn test_save_wallet_reports_io_errors() {
+    let wallet = Wallet::new();
+    let missing_dir = std::env::temp_dir().join("no_such_dir").join("wallet.json");
+    assert!(matches!(
+        Wallet::save_wallet(&wallet, &missing_dir.to_string_lossy()),
+        Err(WalletError::Io { .. })
+    ));
+}
+
+#[test]
+fn test_custom_dust_limit() {
+    let mut wallet = Wallet::new();
+    wallet.dust_limit = 20;
+    let utxo_set = vec![utxo("tx1", 0, 100, &wallet.address)];
+    
+    // 低于自定义阈值的支付被拒绝，低于阈值的找零并入手续费
+    assert_eq!(
+        wallet.create_transaction(RECIPIENT, 19, &utxo_set).unwrap_err(),
+        TransactionError::DustAmount { amount: 19, threshold: 20 }
+    );
+    let tx = wallet.create_transaction_with_fee(RECIPIENT, 70, 15, &utxo_set).unwrap();
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.fee, 30);
+    
+    // 阈值随钱包文件保存
+    let restored: Wallet = serde_json::from_str(&serde_json::to_string(&wallet).unwrap()).unwrap();
+    assert_eq!(restored.dust_limit, 20);
+}
+
+#[test]
+fn test_consolidate_small_utxos() {
+    let wallet = Wallet::new();
+    let mut utxo_set: Vec<_> = (0..20).map(|i| utxo(&format!("reward{}", i), 0, 50, &wallet.address)).collect();
+    utxo_set.push(utxo("large", 0, 10_000, &wallet.address));
+    utxo_set.push(utxo("other", 0, 10, RECIPIENT));
+    
+    // 从最小的输出开始合并，大额输出和其他地址的输出不参与
+    let tx = wallet.consolidate_utxos(&utxo_set, 20).unwrap();
+    assert_eq!(tx.inputs.len(), 20);
+    assert!(tx.inputs.iter().all(|input| input.prev_tx.starts_with("reward")));
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.outputs[0].script_pubkey, wallet.address);
+    assert!(tx.fee > 0);
+    assert_eq!(tx.outputs[0].value + tx.fee, 20 * 50);
+    
+    // 签名后的合并交易可以通过验证
+    let mut signed = tx.clone();
+    wallet.sign_transaction(&mut signed).unwrap();
+    assert!(verify_transaction(&signed, |outpoint| {
+        utxo_set.iter().find(|(o, _)| o == outpoint).map(|(_, e)| e.clone())
+    }, DEFAULT_NETWORK_ID));
+    
+    assert_eq!(
+        wallet.consolidate_utxos(&utxo_set[..1], 20).unwrap_err(),
+        TransactionError::NothingToConsolidate { available: 1 }
+    );
+}
+
+#[test]
+fn test_wallet_records_network() {
+    let mut manager = WalletManager::new();
+    manager.set_network_id("testnet");
+    assert_eq!(manager.create("alice").network_id, "testnet");
+    assert_eq!(Wallet::new().network_id, DEFAULT_NETWORK_ID);
+    
+    // 网络标识随钱包文件保存，旧文件没有该字段时属于默认网络
+    let mut offline = Wallet::new();
+    offline.network_id = String::from("testnet");
+    let restored: Wallet = serde_json::from_str(&serde_json::to_string(&offline).unwrap()).unwrap();
+    assert_eq!(restored.network_id, "testnet");
+    let mut legacy: serde_json::Value = serde_json::to_value(&offline).unwrap();
+    legacy.as_object_mut().unwrap().remove("network_id");
+    assert_eq!(serde_json::from_value::<Wallet>(legacy).unwrap().network_id, DEFAULT_NETWORK_ID);
+    
+    // 离线钱包拒绝签名其他网络的交易
+    let utxo_set = vec![utxo("tx1", 0, 100, &offline.address)];
+    let tx = Wallet::watch_only(offline.public_key.unwrap()).create_transaction(RECIPIENT, 30, &utxo_set).unwrap();
+    let mut unsigned = UnsignedTransaction::new(tx, &utxo_set).unwrap();
+    assert_eq!(offline.sign_unsigned(&mut unsigned).unwrap_err(), WalletError::NetworkMismatch {
+        wallet: String::from("testnet"),
+        transaction: String::from(DEFAULT_NETWORK_ID),
+    });
+    unsigned.network_id = String::from("testnet");
+    offline.sign_unsigned(&mut unsigned).unwrap();
+    assert!(unsigned.is_complete());
+}
+
+// 辅助函数：创建花费指定输出、支付给指定地址的交易（不签名）
+fn payment(inputs: &[(&str, u32)], outputs: &[(&str, u64)]) -> Transaction {
+    Transaction::new(
+        inputs.iter()
+            .map(|(prev_tx, prev_index)| TxInput { prev_tx: prev_tx.to_string(), prev_index: *prev_index, script_sig: String::new() })
+            .collect(),
+        outputs.iter()
+            .map(|(address, value)| TxOutput { value: *value, script_pubkey: address.to_string() })
+            .collect(),
+    )
+}
+
+#[test]
+fn test_wallet_watcher_events() {
+    let mut wallet = Wallet::new();
+    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+    let mut watcher = WalletWatcher::new(&wallet, tx);
+    let address = wallet.address.clone();
+    
+    // 高度1：Coinbase支付给钱包
+    let coinbase = payment(&[(COINBASE_TX_ID, 0)], &[(&address, 50)]);
+    let mut block = Block::new(String::from("0"), 1);
+    block.transactions.push(coinbase.clone());
+    assert_eq!(watcher.block_connected(&block, 1), vec![
+        WalletEvent::Received { txid: coinbase.txid(), amount: 50, height: 1 },
+    ]);
+    
+    // 高度2：带找零的转账只报告净支出，与钱包无关的交易和收支相等的交易不产生事件
+    let transfer = payment(&[(&coinbase.txid(), 0)], &[(RECIPIENT, 30), (&address, 20)]);
+    let unrelated = payment(&[("其他交易", 0)], &[(RECIPIENT, 5)]);
+    let consolidate = payment(&[(&transfer.txid(), 1)], &[(&address, 20)]);
+    let mut block = Block::new(block.calculate_hash(), 1);
+    block.transactions = vec![transfer.clone(), unrelated, consolidate.clone()];
+    assert_eq!(watcher.block_connected(&block, 2), vec![
+        WalletEvent::Sent { txid: transfer.txid(), amount: 30 },
+    ]);
+    
+    // 高度3：派生地址在重新观察后才被识别；花费钱包输出又收到更多的交易报告净收入
+    let second = wallet.new_address().unwrap();
+    watcher.watch(&wallet, &[utxo(&consolidate.txid(), 0, 20, &address)]);
+    let merge = payment(&[(&consolidate.txid(), 0), ("其他交易", 1)], &[(&second, 25)]);
+    let mut block = Block::new(block.calculate_hash(), 1);
+    block.transactions = vec![merge.clone()];
+    assert_eq!(watcher.block_connected(&block, 3), vec![
+        WalletEvent::Received { txid: merge.txid(), amount: 5, height: 3 },
+    ]);
+    
+    // 订阅者按相同顺序收到全部事件
+    let mut received = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        received.push(event);
+    }
+    assert_eq!(received, vec![
+        WalletEvent::Received { txid: coinbase.txid(), amount: 50, height: 1 },
+        WalletEvent::Sent { txid: transfer.txid(), amount: 30 },
+        WalletEvent::Received { txid: merge.txid(), amount: 5, height: 3 },
+    ]);
+}
+
+#[test]
+fn test_address_book_persistence_round_trip() {
+    let path = std::env::temp_dir().join(format!("addressbook_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    
+    // 文件不存在时得到空地址簿，修改后立即写入文件
+    let mut book = AddressBook::load_or_create(&path).unwrap();
+    assert!(book.is_empty());
+    book.add("bob", RECIPIENT).unwrap();
+    book.add("carol", &Wallet::new().address.clone()).unwrap();
+    assert!(book.remove("carol").unwrap().is_some());
+    assert_eq!(book.remove("carol").unwrap(), None);
+    
+    let reloaded = AddressBook::load_or_create(&path).unwrap();
+    assert_eq!(reloaded.list(), vec![("bob", RECIPIENT)]);
+    assert_eq!(AddressBook::path_for("user1").to_string_lossy(), "user1_addressbook.json");
+    
+    // 损坏的文件不会被当作空地址簿覆盖
+    std::fs::write(&path, "不是JSON").unwrap();
+    assert!(matches!(AddressBook::load_or_create(&path), Err(WalletError::Parse { .. })));
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_address_book_name_collision_and_resolution() {
+    let own_address = Wallet::new().address.clone();
+    let mut book = AddressBook::new();
+    
+    // 同名条目被新地址替换，并返回原来的地址
+    assert_eq!(book.add("bob", &own_address).unwrap(), None);
+    assert_eq!(book.add("bob", RECIPIENT).unwrap(), Some(own_address.clone()));
+    assert_eq!(book.len(), 1);
+    assert_eq!(book.resolve("bob"), Some(RECIPIENT.to_string()));
+    
+    // 有效地址优先于同名条目，未知的名称无法解析
+    book.add(&own_address, RECIPIENT).unwrap();
+    assert_eq!(book.resolve(&own_address), Some(own_address.clone()));
+    assert_eq!(book.get(&own_address), Some(RECIPIENT));
+    assert_eq!(book.resolve("alice"), None);
+}
+
+#[test]
+fn test_address_book_csv_round_trip_and_rejects_bad_rows() {
+    let csv_path = std::env::temp_dir().join(format!("addressbook_{}.csv", std::process::id()));
+    let own_address = Wallet::new().address.clone();
+    let mut book = AddressBook::new();
+    book.add("bob", RECIPIENT).unwrap();
+    book.add("carol, \"the miner\"", &own_address).unwrap();
+    
+    // 含逗号和引号的名称经过转义后可以原样导入
+    book.export_csv(&csv_path).unwrap();
+    let mut imported = AddressBook::new();
+    assert_eq!(imported.import_csv(&csv_path).unwrap(), 2);
+    assert_eq!(imported.list(), book.list());
+    
+    // 任一行地址无效时整个文件都不导入，错误中包含行号
+    std::fs::write(&csv_path, format!("name,address\ndave,{}\neve,not-an-address\n", RECIPIENT)).unwrap();
+    let mut rejected = AddressBook::new();
+    match rejected.import_csv(&csv_path) {
+        Err(WalletError::Parse { reason, .. }) => assert!(reason.contains("第3行")),
+        other => panic!("expected parse error, got {:?}", other),
+    }
+    assert!(rejected.is_empty());
+    
+    let _ = std::fs::remove_file(&csv_path);
+}
+
+#[test]
+fn test_signing_foreign_input_fails() {
+    let mut wallet = Wallet::new();
+    let other = Wallet::new();
+    let utxos = vec![utxo("tx_a", 0, 30, &wallet.address)];
+    let mut tx = wallet.create_transaction_with_fee(RECIPIENT, 20, 2, &utxos).unwrap();
+    
+    // 输入花费的是其他钱包的地址，不能用本钱包的主密钥代签
+    tx.inputs[0].script_sig = other.address.clone();
+    let unsigned = tx.clone();
+    assert_eq!(wallet.sign_transaction(&mut tx), Err(WalletError::NotOwned(other.address.clone())));
+    assert_eq!(tx.inputs[0].script_sig, unsigned.inputs[0].script_sig);
+    assert_eq!(
+        sign_transaction_with(&mut tx, &[&wallet], DEFAULT_NETWORK_ID),
+        Err(SignError::NotOwned(other.address.clone()))
+    );
+}
+
+#[test]
+fn test_callback_signer_matches_wallet_signer() {
+    let mut wallet = Wallet::new();
+    let utxos = vec![utxo("tx_a", 0, 30, &wallet.address), utxo("tx_b", 1, 40, &wallet.address)];
+    let tx = wallet.create_transaction_with_fee(RECIPIENT, 50, 2, &utxos).unwrap();
+    
+    // 回调签名者持有同一个私钥的副本，模拟外部进程中的密钥
+    let private_key = wallet.private_key.unwrap();
+    let secp = secp256k1::Secp256k1::new();
+    let external = CallbackSigner::new(wallet.public_key.unwrap(), move |digest: &[u8; 32]| {
+        let message = secp256k1::Message::from_slice(digest).unwrap();
+        Ok(secp.sign_ecdsa(&message, &private_key))
+    });
+    assert_eq!(external.address(), wallet.address);
+    
+    let mut signed_by_wallet = tx.clone();
+    wallet.sign_transaction(&mut signed_by_wallet).unwrap();
+    let mut signed_externally = tx.clone();
+    sign_transaction_with(&mut signed_externally, &[&external], DEFAULT_NETWORK_ID).unwrap();
+    
+    let script_sigs = |tx: &Transaction| tx.inputs.iter().map(|input| input.script_sig.clone()).collect::<Vec<_>>();
+    assert_eq!(script_sigs(&signed_externally), script_sigs(&signed_by_wallet));
+    let resolver = |outpoint: &OutPoint| utxos.iter().find(|(candidate, _)| candidate == outpoint).map(|(_, entry)| entry.clone());
+    assert!(verify_transaction(&signed_externally, resolver, DEFAULT_NETWORK_ID));
+    
+    // 外部签名者失败时交易保持不变
+    let refusing = CallbackSigner::new(wallet.public_key.unwrap(), |_: &[u8; 32]| Err(SignError::External(String::from("设备已断开"))));
+    let mut unchanged = tx.clone();
+    assert_eq!(
+        sign_transaction_with(&mut unchanged, &[&refusing], DEFAULT_NETWORK_ID),
+        Err(SignError::External(String::from("设备已断开")))
+    );
+    assert_eq!(script_sigs(&unchanged), script_sigs(&tx));
+    
+    // 观察钱包不能作为签名者
+    let watch_only = Wallet::from_address(&wallet.address).unwrap();
+    assert_eq!(sign_transaction_with(&mut unchanged, &[&watch_only], DEFAULT_NETWORK_ID), Err(SignError::NoKey));
+}
+
+#[test]
+fn test_branch_and_bound_exact_match_avoids_change() {
+    let mut wallet = Wallet::new();
+    let receiver = Wallet::new().address.clone();
+    let owner = wallet.address.clone();
+    let utxos = vec![
+        utxo("tx_a", 0, 10, &owner),
+        utxo("tx_b", 0, 60, &owner),
+        utxo("tx_c", 0, 30, &owner),
+        utxo("tx_d", 0, 25, &owner),
+    ];
+    let input_total = |tx: &Transaction| -> u64 {
+        tx.inputs.iter()
+            .map(|input| utxos.iter().find(|(outpoint, _)| outpoint.tx_id == input.prev_tx).unwrap().1.value)
+            .sum()
+    };
+    
+    // 30 + 25恰好等于金额加手续费，不产生找零输出
+    let tx = wallet.create_transaction_with_fee(&receiver, 53, 2, &utxos).unwrap();
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(input_total(&tx), 55);
+    assert_eq!(tx.fee, 2);
+    
+    // 超出不到1%的部分并入手续费，同样没有找零
+    let tx = wallet.create_transaction_with_strategy(&receiver, 994, 0, &[utxo("tx_e", 0, 1000, &owner), utxo("tx_f", 0, 2000, &owner)], CoinSelectionStrategy::BranchAndBound).unwrap();
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.fee, 6);
+    
+    // 没有精确匹配时回退到从大到小选择，产生找零
+    let tx = wallet.create_transaction_with_strategy(&receiver, 81, 0, &utxos, CoinSelectionStrategy::BranchAndBound).unwrap();
+    assert_eq!(input_total(&tx), 90);
+    assert_eq!(tx.outputs[1].value, 9);
+    
+    // 其他策略按各自的顺序选择
+    let tx = wallet.create_transaction_with_strategy(&receiver, 55, 0, &utxos, CoinSelectionStrategy::LargestFirst).unwrap();
+    assert_eq!(tx.inputs.len(), 1);
+    assert_eq!(tx.outputs[1].value, 5);
+    let tx = wallet.create_transaction_with_strategy(&receiver, 55, 0, &utxos, CoinSelectionStrategy::SmallestFirst).unwrap();
+    assert_eq!(input_total(&tx), 65);
+    let tx = wallet.create_transaction_with_strategy(&receiver, 55, 0, &utxos, CoinSelectionStrategy::Random).unwrap();
+    assert!(input_total(&tx) >= 55);
+    
+    // 余额不足时各策略报告相同的错误
+    assert!(matches!(
+        wallet.create_transaction_with_strategy(&receiver, 200, 0, &utxos, CoinSelectionStrategy::BranchAndBound),
+        Err(TransactionError::InsufficientFunds { needed: 200, available: 125 })
+    ));
+}